@@ -0,0 +1,54 @@
+//! Custo da conversão para i16 que roda no callback do cpal a cada bloco,
+//! em blocos do tamanho que os drivers costumam entregar e num segundo
+//! inteiro de áudio, para validar mudanças como um ring buffer sem
+//! alocação por bloco.
+//!
+//! `cargo bench -p audio-capture`
+
+use std::hint::black_box;
+
+use audio_capture::samples::{f32_to_i16, u16_to_i16};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+
+/// Estéreo: as amostras chegam intercaladas.
+const CHANNELS: usize = 2;
+/// Frames por bloco: ~10 ms a 48 kHz, o padrão do ALSA/PulseAudio, o
+/// bloco comum do CoreAudio/WASAPI e um segundo inteiro.
+const BLOCK_FRAMES: [usize; 3] = [480, 4096, 48_000];
+
+/// Uma senoide de 440 Hz com um pouco de clipping, para que a saturação
+/// também seja exercitada.
+fn sine(frames: usize) -> Vec<f32> {
+    (0..frames * CHANNELS)
+        .map(|i| {
+            let t = (i / CHANNELS) as f32 / 48_000.0;
+            1.2 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+        })
+        .collect()
+}
+
+fn conversion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("samples");
+    for frames in BLOCK_FRAMES {
+        let float = sine(frames);
+        let unsigned: Vec<u16> = float
+            .iter()
+            .map(|&s| ((s.clamp(-1.0, 1.0) + 1.0) * 32_767.5) as u16)
+            .collect();
+        group.throughput(Throughput::Elements(float.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("f32_to_i16", frames),
+            &float,
+            |b, block| b.iter(|| f32_to_i16(black_box(block))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("u16_to_i16", frames),
+            &unsigned,
+            |b, block| b.iter(|| u16_to_i16(black_box(block))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, conversion);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+//! Áudio com o cpal: o gerador de sinal de teste e a medição de latência
+//! ([`tone`]), a gravação do microfone ([`recorder`]), a conversão das
+//! amostras ([`samples`]) e os destinos do áudio gravado ([`sink`]).
+
+pub mod recorder;
+pub mod samples;
+pub mod sink;
+pub mod tone;
+
+/// Filtro de log dos binários de áudio quando nem `RUST_LOG` nem
+/// `log.level` definem um.
+pub const DEFAULT_LOG_FILTER: &str = "audio_capture=info";
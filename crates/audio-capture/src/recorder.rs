@@ -0,0 +1,588 @@
+//! Gravação do microfone padrão com o cpal: converte os blocos para i16,
+//! aplica o gatilho por nível (`--arm`) e a pausa, e entrega as amostras a
+//! um [`AudioSink`].
+
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::{error, info, warn};
+
+use super::samples;
+use super::sink::{AudioSink, SinkSpec, WavSink, create_sink};
+use playground_common::error::PlaygroundError;
+
+/// O que [`run`] deve gravar, e para onde.
+pub struct Options {
+    /// Duração máxima da gravação.
+    pub duration: Duration,
+    /// Quando presente, a gravação só começa quando o sinal passa do limiar.
+    pub arm: Option<ArmConfig>,
+    /// Nome do sink (`wav`, `flac`, `icecast`, `rtp`, `null` ou outro
+    /// registrado com [`register_sink`](crate::sink::register_sink)).
+    pub format: String,
+    /// Caminho ou URL de saída; padrão `.tmp/meu_audio.<formato>`.
+    pub out: Option<String>,
+    /// WAV existente que deve receber as novas amostras no fim.
+    pub append: Option<PathBuf>,
+    /// Lê o teclado sem esperar Enter: espaço pausa/retoma a gravação.
+    pub interactive: bool,
+    /// Quando ligado por outra thread, encerra a gravação antes do prazo,
+    /// finalizando o arquivo normalmente (o `playground daemon` usa no
+    /// desligamento).
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+/// Parâmetros do modo `--arm` (gravação ativada por som).
+#[derive(Clone, Copy)]
+pub struct ArmConfig {
+    /// Limiar em dBFS (ex.: -40.0). Blocos com pico acima disso contam como som.
+    pub threshold_db: f32,
+    /// Quantos segundos de silêncio contínuo encerram a gravação.
+    pub silence_secs: f32,
+}
+/// Estado do gatilho por nível usado no modo `--arm`.
+enum Gate {
+    /// Aguardando o sinal ultrapassar o limiar; nada é gravado.
+    Armed,
+    /// Gravando; conta as amostras consecutivas em silêncio.
+    Recording { silent_samples: u64 },
+    /// O silêncio durou o suficiente; a gravação terminou.
+    Finished,
+}
+
+/// Quantos segundos de áudio podem se acumular sem a thread principal drenar
+/// antes de começarmos a descartar blocos (overrun do nosso buffer).
+const MAX_PENDING_SECS: u32 = 10;
+
+/// Métricas coletadas a cada callback para diagnosticar gravações com falhas.
+#[derive(Default)]
+struct CaptureStats {
+    callbacks: u64,
+    frames: u64,
+    last_callback: Option<Instant>,
+    /// Timestamp de captura informado pelo backend no callback anterior.
+    last_capture: Option<cpal::StreamInstant>,
+    /// Duração esperada do bloco anterior (frames / taxa).
+    last_block: Duration,
+    min_interval: Option<Duration>,
+    max_interval: Duration,
+    jitter_sum: Duration,
+    max_jitter: Duration,
+    /// Frames que faltaram segundo os timestamps do dispositivo.
+    dropped_frames: u64,
+    /// Blocos descartados porque o buffer compartilhado encheu.
+    overruns: u64,
+}
+
+impl CaptureStats {
+    fn on_callback(&mut self, frames: usize, info: &cpal::InputCallbackInfo, sample_rate: u32) {
+        let now = Instant::now();
+        let capture_ts = info.timestamp().capture;
+
+        if let Some(last) = self.last_callback {
+            let interval = now.duration_since(last);
+            let jitter = interval.abs_diff(self.last_block);
+            self.min_interval = Some(self.min_interval.map_or(interval, |m| m.min(interval)));
+            self.max_interval = self.max_interval.max(interval);
+            self.jitter_sum += jitter;
+            self.max_jitter = self.max_jitter.max(jitter);
+        }
+        // Um salto nos timestamps maior que 1,5 bloco indica frames perdidos
+        // pelo driver (xrun) antes de chegarem ao callback.
+        if let Some(gap) = self
+            .last_capture
+            .and_then(|prev| capture_ts.duration_since(&prev))
+            && gap > self.last_block.mul_f32(1.5)
+        {
+            let missing = (gap - self.last_block).as_secs_f64() * sample_rate as f64;
+            self.dropped_frames += missing as u64;
+        }
+
+        self.callbacks += 1;
+        self.frames += frames as u64;
+        self.last_callback = Some(now);
+        self.last_capture = Some(capture_ts);
+        self.last_block = Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+    }
+
+    fn log(&self, stream_errors: u64, sample_rate: u32) {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let intervals = self.callbacks.saturating_sub(1).max(1) as u32;
+        info!(
+            callbacks = self.callbacks,
+            block_frames = self.frames / self.callbacks.max(1),
+            min_interval_ms = ms(self.min_interval.unwrap_or_default()),
+            max_interval_ms = ms(self.max_interval),
+            jitter_ms = ms(self.jitter_sum / intervals),
+            max_jitter_ms = ms(self.max_jitter),
+            dropped_frames = self.dropped_frames,
+            overruns = self.overruns,
+            stream_errors,
+            captured_secs = self.frames as f64 / sample_rate as f64,
+            "Estatísticas da captura"
+        );
+    }
+}
+
+/// Buffer compartilhado com o callback do cpal. A thread principal drena
+/// `samples` periodicamente e entrega ao sink.
+struct Capture {
+    samples: Vec<i16>,
+    /// Total de amostras aceitas desde o início (inclusive as já drenadas).
+    recorded: u64,
+    gate: Option<Gate>,
+    threshold: i16,
+    silence_limit: u64,
+    max_pending: usize,
+    sample_rate: u32,
+    channels: u16,
+    stats: CaptureStats,
+    /// Alternado por SIGUSR1 ou pela barra de espaço; enquanto `true` nenhuma
+    /// amostra é aceita.
+    paused: Arc<AtomicBool>,
+}
+
+impl Capture {
+    fn new(
+        arm: Option<ArmConfig>,
+        sample_rate: u32,
+        channels: u16,
+        paused: Arc<AtomicBool>,
+    ) -> Self {
+        let (threshold, silence_limit) = match arm {
+            Some(arm) => {
+                let linear = 10f32.powf(arm.threshold_db / 20.0).clamp(0.0, 1.0);
+                let limit = arm.silence_secs.max(0.0) * sample_rate as f32 * channels as f32;
+                ((linear * i16::MAX as f32) as i16, limit as u64)
+            }
+            None => (0, 0),
+        };
+        Self {
+            samples: Vec::new(),
+            recorded: 0,
+            gate: arm.map(|_| Gate::Armed),
+            threshold,
+            silence_limit,
+            max_pending: (MAX_PENDING_SECS * sample_rate) as usize * channels as usize,
+            sample_rate,
+            channels,
+            stats: CaptureStats::default(),
+            paused,
+        }
+    }
+
+    /// Recebe um bloco já convertido para i16 e decide se ele entra no WAV.
+    fn push(&mut self, block: &[i16], info: &cpal::InputCallbackInfo) {
+        let frames = block.len() / self.channels.max(1) as usize;
+        self.stats.on_callback(frames, info, self.sample_rate);
+        if self.paused.load(Ordering::Relaxed) {
+            return;
+        }
+        if self.samples.len() + block.len() > self.max_pending {
+            self.stats.overruns += 1;
+            return;
+        }
+
+        let Some(gate) = &mut self.gate else {
+            self.samples.extend_from_slice(block);
+            self.recorded += block.len() as u64;
+            return;
+        };
+
+        let peak = block.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+        let loud = peak > self.threshold.unsigned_abs();
+
+        match gate {
+            Gate::Armed if loud => {
+                info!("Som detectado, gravando");
+                *gate = Gate::Recording { silent_samples: 0 };
+                self.samples.extend_from_slice(block);
+                self.recorded += block.len() as u64;
+            }
+            Gate::Armed | Gate::Finished => {}
+            Gate::Recording { silent_samples } => {
+                self.samples.extend_from_slice(block);
+                self.recorded += block.len() as u64;
+                if loud {
+                    *silent_samples = 0;
+                } else {
+                    *silent_samples += block.len() as u64;
+                    if *silent_samples >= self.silence_limit {
+                        *gate = Gate::Finished;
+                    }
+                }
+            }
+        }
+    }
+
+    fn finished(&self) -> bool {
+        matches!(self.gate, Some(Gate::Finished))
+    }
+}
+
+/// Formatos de amostra que o callback sabe converter para i16.
+const HANDLED_FORMATS: [cpal::SampleFormat; 3] = [
+    cpal::SampleFormat::F32,
+    cpal::SampleFormat::I16,
+    cpal::SampleFormat::U16,
+];
+
+/// Usa a config padrão do dispositivo quando o formato é suportado; senão
+/// procura entre as configs anunciadas uma com formato conhecido, preferindo
+/// a mesma taxa de amostragem da padrão, e avisa qual foi escolhida.
+fn choose_input_config(device: &cpal::Device) -> Result<cpal::SupportedStreamConfig> {
+    let default = device
+        .default_input_config()
+        .context("Não foi possível obter config de entrada")?;
+    if HANDLED_FORMATS.contains(&default.sample_format()) {
+        return Ok(default);
+    }
+
+    let ranges: Vec<_> = device
+        .supported_input_configs()
+        .context("Não foi possível listar as configs de entrada")?
+        .filter(|range| HANDLED_FORMATS.contains(&range.sample_format()))
+        .collect();
+    let chosen = ranges
+        .iter()
+        .find_map(|range| range.try_with_sample_rate(default.sample_rate()))
+        .or_else(|| ranges.first().map(|range| range.with_max_sample_rate()))
+        .with_context(|| {
+            format!(
+                "Formato de amostra não suportado ({}) e nenhuma alternativa disponível",
+                default.sample_format()
+            )
+        })?;
+
+    warn!(
+        default = %default.sample_format(),
+        format = %chosen.sample_format(),
+        sample_rate = chosen.sample_rate().0,
+        channels = chosen.channels(),
+        "Formato padrão não suportado; usando uma alternativa"
+    );
+    Ok(chosen)
+}
+
+/// Grava do microfone padrão para o sink escolhido até o fim da duração
+/// (ou do silêncio, no modo `--arm`) e registra as estatísticas da captura.
+pub fn run(options: &Options) -> Result<()> {
+    // No modo `--arm` a duração vira o tempo máximo de espera + gravação.
+    let duration = humantime::format_duration(options.duration);
+
+    let out_dir = PathBuf::from(".tmp");
+    std::fs::create_dir_all(&out_dir).context("Erro ao criar diretório de saída")?;
+
+    let out_target = match (&options.out, options.format.as_str()) {
+        (Some(out), _) => out.clone(),
+        (None, "icecast" | "rtp") => {
+            return Err(PlaygroundError::usage(format!(
+                "--format {} exige --out <url>",
+                options.format
+            ))
+            .into());
+        }
+        (None, format) => out_dir
+            .join(format!("meu_audio.{format}"))
+            .display()
+            .to_string(),
+    };
+
+    // 1) Seleciona host e dispositivo de entrada padrão
+    let host = cpal::default_host();
+    let device = host
+        .default_input_device()
+        .context("Nenhum microfone padrão encontrado")
+        .map_err(PlaygroundError::unavailable)?;
+    let supported_config = choose_input_config(&device)?;
+    let sample_format = supported_config.sample_format();
+    let config: cpal::StreamConfig = supported_config.into();
+
+    // 2) Sink de saída e buffer compartilhado para armazenar amostras em i16
+    let spec = SinkSpec {
+        channels: config.channels,
+        sample_rate: config.sample_rate.0,
+    };
+    let mut sink: Box<dyn AudioSink> = match &options.append {
+        Some(path) => Box::new(
+            WavSink::append(path, spec)
+                .with_context(|| format!("Falha ao abrir {} para anexar", path.display()))?,
+        ),
+        None => create_sink(&options.format, &out_target, spec).context("Falha ao criar saída")?,
+    };
+    let paused = Arc::new(AtomicBool::new(false));
+    let capture = Arc::new(Mutex::new(Capture::new(
+        options.arm,
+        config.sample_rate.0,
+        config.channels,
+        Arc::clone(&paused),
+    )));
+    let capture_clone = Arc::clone(&capture);
+
+    let stream_errors = Arc::new(AtomicU64::new(0));
+    let err_fn = {
+        let stream_errors = Arc::clone(&stream_errors);
+        move |err| {
+            stream_errors.fetch_add(1, Ordering::Relaxed);
+            error!(error = %err, "Erro no stream de áudio");
+        }
+    };
+
+    // 3) Cria o stream de entrada conforme o formato do dispositivo
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => {
+            let capture_c = capture_clone;
+            device.build_input_stream(
+                &config,
+                move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                    let block = samples::f32_to_i16(data);
+                    capture_c.lock().unwrap().push(&block, info);
+                },
+                err_fn,
+                None,
+            )?
+        }
+        cpal::SampleFormat::I16 => {
+            let capture_c = capture_clone;
+            device.build_input_stream(
+                &config,
+                move |data: &[i16], info: &cpal::InputCallbackInfo| {
+                    capture_c.lock().unwrap().push(data, info);
+                },
+                err_fn,
+                None,
+            )?
+        }
+        cpal::SampleFormat::U16 => {
+            let capture_c = capture_clone;
+            device.build_input_stream(
+                &config,
+                move |data: &[u16], info: &cpal::InputCallbackInfo| {
+                    let block = samples::u16_to_i16(data);
+                    capture_c.lock().unwrap().push(&block, info);
+                },
+                err_fn,
+                None,
+            )?
+        }
+        _ => anyhow::bail!("Formato de amostra não suportado"),
+    };
+
+    match options.arm {
+        Some(arm) => info!(
+            threshold_db = arm.threshold_db,
+            max = %duration,
+            "Aguardando som acima do limiar"
+        ),
+        None => info!(%duration, "Gravando; fale no microfone"),
+    }
+    let pause_controls = pause_controls::install(&paused, options.interactive)?;
+    stream.play()?;
+    let started = Instant::now();
+    let deadline = started + options.duration;
+    loop {
+        let (block, finished) = {
+            let mut capture = capture.lock().unwrap();
+            (std::mem::take(&mut capture.samples), capture.finished())
+        };
+        sink.write(&block).context("Falha ao escrever amostras")?;
+        let cancelled = pause_controls.interrupted()
+            || options
+                .cancel
+                .as_ref()
+                .is_some_and(|cancel| cancel.load(Ordering::Relaxed));
+        if finished || cancelled || Instant::now() >= deadline {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    drop(stream); // parar a captura
+
+    // 4) Escreve o que sobrou no buffer e finaliza o sink (ex.: cabeçalho WAV)
+    let (rest, recorded) = {
+        let mut capture = capture.lock().unwrap();
+        (std::mem::take(&mut capture.samples), capture.recorded)
+    };
+    sink.write(&rest).context("Falha ao escrever amostras")?;
+    capture
+        .lock()
+        .unwrap()
+        .stats
+        .log(stream_errors.load(Ordering::Relaxed), config.sample_rate.0);
+    let target = sink.describe();
+    if options.arm.is_some() && recorded == 0 {
+        sink.discard().context("Falha ao descartar saída")?;
+        warn!(%target, "Nenhum som acima do limiar; nada foi gravado");
+        return Ok(());
+    }
+    sink.finish().context("Falha ao finalizar saída")?;
+
+    let samples_per_sec = config.sample_rate.0 as f64 * config.channels as f64;
+    info!(
+        %target,
+        recorded_secs = recorded as f64 / samples_per_sec,
+        wall_secs = started.elapsed().as_secs_f64(),
+        "Arquivo salvo"
+    );
+
+    Ok(())
+}
+
+/// Controles de pausa/retomada. Só existem em sistemas unix, onde temos
+/// SIGUSR1 e `termios` para ler teclas sem esperar Enter.
+#[cfg(unix)]
+mod pause_controls {
+    use std::io::Read;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use anyhow::Result;
+    use signal_hook::{
+        consts::{SIGINT, SIGTERM, SIGUSR1},
+        iterator::{Handle, Signals},
+    };
+    use tracing::info;
+
+    use playground_common::error::PlaygroundError;
+
+    /// Restaura o modo original do terminal e para de ouvir os sinais
+    /// quando sai de escopo (o daemon grava várias vezes no mesmo processo).
+    pub struct Guard {
+        signals: Handle,
+        original: Option<libc::termios>,
+        interrupted: Arc<AtomicBool>,
+    }
+
+    impl Guard {
+        /// Se chegou SIGINT (Ctrl+C) ou SIGTERM no modo interativo.
+        pub fn interrupted(&self) -> bool {
+            self.interrupted.load(Ordering::Relaxed)
+        }
+    }
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.signals.close();
+            if let Some(original) = &self.original {
+                // SAFETY: `original` veio de `tcgetattr` no mesmo descritor.
+                unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original) };
+            }
+        }
+    }
+
+    fn toggle(paused: &AtomicBool) {
+        let now_paused = !paused.fetch_xor(true, Ordering::Relaxed);
+        if now_paused {
+            info!("Pausado");
+        } else {
+            info!("Retomando gravação");
+        }
+    }
+
+    /// No modo interativo, SIGINT e SIGTERM também passam por aqui: em vez
+    /// de matar o processo com o terminal ainda sem eco, encerram a gravação
+    /// como o `cancel` das [`Options`](super::Options), e o [`Guard`]
+    /// restaura o terminal na saída. Depois disso esses sinais ficam sem
+    /// efeito no processo (o `signal-hook` não devolve a ação padrão), o que
+    /// não importa para a CLI, que termina logo em seguida.
+    pub fn install(paused: &Arc<AtomicBool>, interactive: bool) -> Result<Guard> {
+        let original = if interactive {
+            // SAFETY: `termios` é uma struct C simples preenchida por
+            // `tcgetattr`.
+            unsafe {
+                let mut original: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                    return Err(
+                        PlaygroundError::usage("--interactive exige um terminal no stdin").into(),
+                    );
+                }
+                Some(original)
+            }
+        } else {
+            None
+        };
+
+        let mut signals = if interactive {
+            Signals::new([SIGUSR1, SIGINT, SIGTERM])?
+        } else {
+            Signals::new([SIGUSR1])?
+        };
+        let handle = signals.handle();
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(paused);
+        let interrupt = Arc::clone(&interrupted);
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                if signal == SIGUSR1 {
+                    toggle(&flag);
+                } else {
+                    info!("Interrompido; finalizando a gravação");
+                    interrupt.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+
+        let Some(original) = original else {
+            return Ok(Guard {
+                signals: handle,
+                original: None,
+                interrupted,
+            });
+        };
+
+        // Desliga só o modo canônico e o eco: as teclas chegam na hora, mas a
+        // saída do terminal continua normal para os logs.
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        // SAFETY: `raw` é uma cópia do que `tcgetattr` preencheu.
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) };
+
+        let flag = Arc::clone(paused);
+        std::thread::spawn(move || {
+            let mut byte = [0u8; 1];
+            let mut stdin = std::io::stdin();
+            while stdin.read_exact(&mut byte).is_ok() {
+                if byte[0] == b' ' {
+                    toggle(&flag);
+                }
+            }
+        });
+        info!("Modo interativo: espaço pausa/retoma");
+
+        Ok(Guard {
+            signals: handle,
+            original: Some(original),
+            interrupted,
+        })
+    }
+}
+
+#[cfg(not(unix))]
+mod pause_controls {
+    use std::sync::{Arc, atomic::AtomicBool};
+
+    use anyhow::Result;
+
+    pub struct Guard;
+
+    impl Guard {
+        pub fn interrupted(&self) -> bool {
+            false
+        }
+    }
+
+    pub fn install(_paused: &Arc<AtomicBool>, interactive: bool) -> Result<Guard> {
+        if interactive {
+            anyhow::bail!("--interactive só é suportado em sistemas unix");
+        }
+        Ok(Guard)
+    }
+}
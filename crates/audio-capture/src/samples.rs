@@ -0,0 +1,16 @@
+//! Conversão dos blocos entregues pelo cpal para o i16 intercalado que os
+//! [sinks](crate::sink) recebem. Roda no callback do áudio a cada bloco.
+
+/// Amostras em ponto flutuante (-1.0 a 1.0), saturando o que passar disso.
+pub fn f32_to_i16(data: &[f32]) -> Vec<i16> {
+    data.iter()
+        .map(|&s| (s * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+        .collect()
+}
+
+/// Amostras sem sinal, centradas em 0.
+pub fn u16_to_i16(data: &[u16]) -> Vec<i16> {
+    data.iter()
+        .map(|&s| (s as i32 - i16::MAX as i32) as i16)
+        .collect()
+}
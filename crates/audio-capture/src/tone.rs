@@ -0,0 +1,471 @@
+//! Gerador de sinal de teste (senoide ou ruído branco) para a saída de
+//! áudio, e medição da latência ida-e-volta com impulsos entre uma saída e
+//! uma entrada ligadas em loopback.
+
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::{
+    f32::consts::TAU,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{error, info};
+
+use playground_common::error::PlaygroundError;
+
+/// Tipo de sinal gerado.
+#[derive(Clone, Copy)]
+pub enum Wave {
+    Sine,
+    Noise,
+}
+/// O que [`run`] deve tocar (ou medir), e em quais dispositivos.
+pub struct Options {
+    pub secs: u64,
+    pub wave: Wave,
+    pub freq: f32,
+    /// Nível de saída em dBFS (0 = escala cheia).
+    pub level_db: f32,
+    /// Trecho do nome do dispositivo de saída; `None` usa o padrão.
+    pub device: Option<String>,
+    pub list_devices: bool,
+    /// Modo de medição de latência ida-e-volta (saída -> entrada).
+    pub latency: bool,
+    /// Trecho do nome do dispositivo de entrada usado no modo `--latency`.
+    pub input: Option<String>,
+    /// Quantos impulsos medir no modo `--latency`.
+    pub runs: usize,
+}
+
+/// Gera as amostras do sinal de teste, uma por frame.
+struct Generator {
+    wave: Wave,
+    amplitude: f32,
+    phase: f32,
+    phase_step: f32,
+    /// Estado do xorshift usado no ruído branco (evita depender de `rand`).
+    rng: u32,
+}
+
+impl Generator {
+    fn new(wave: Wave, freq: f32, level_db: f32, sample_rate: u32) -> Self {
+        Self {
+            wave,
+            amplitude: 10f32.powf(level_db / 20.0).clamp(0.0, 1.0),
+            phase: 0.0,
+            phase_step: TAU * freq / sample_rate as f32,
+            rng: 0x9E37_79B9,
+        }
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        let value = match self.wave {
+            Wave::Sine => {
+                let v = self.phase.sin();
+                self.phase = (self.phase + self.phase_step) % TAU;
+                v
+            }
+            Wave::Noise => {
+                self.rng ^= self.rng << 13;
+                self.rng ^= self.rng >> 17;
+                self.rng ^= self.rng << 5;
+                (self.rng as f32 / u32::MAX as f32) * 2.0 - 1.0
+            }
+        };
+        value * self.amplitude
+    }
+
+    /// Preenche um buffer intercalado repetindo a amostra em todos os canais.
+    fn fill<T>(&mut self, data: &mut [T], channels: usize, convert: impl Fn(f32) -> T)
+    where
+        T: Copy,
+    {
+        for frame in data.chunks_mut(channels) {
+            let sample = convert(self.next_sample());
+            frame.fill(sample);
+        }
+    }
+}
+
+/// Lista os dispositivos, toca o sinal de teste ou mede a latência,
+/// conforme `options`.
+pub fn run(options: &Options) -> Result<()> {
+    let host = cpal::default_host();
+
+    if options.list_devices {
+        for device in host.output_devices()? {
+            println!(
+                "saída:   {}",
+                device.name().unwrap_or_else(|_| "<sem nome>".into())
+            );
+        }
+        for device in host.input_devices()? {
+            println!(
+                "entrada: {}",
+                device.name().unwrap_or_else(|_| "<sem nome>".into())
+            );
+        }
+        return Ok(());
+    }
+
+    // 1) Seleciona o dispositivo de saída (por trecho do nome ou o padrão)
+    let device = match &options.device {
+        Some(wanted) => host
+            .output_devices()?
+            .find(|d| {
+                d.name()
+                    .map(|n| n.contains(wanted.as_str()))
+                    .unwrap_or(false)
+            })
+            .with_context(|| format!("Nenhum dispositivo de saída contém \"{wanted}\""))
+            .map_err(PlaygroundError::unavailable)?,
+        None => host
+            .default_output_device()
+            .context("Nenhum dispositivo de saída padrão encontrado")
+            .map_err(PlaygroundError::unavailable)?,
+    };
+
+    if options.latency {
+        return measure_latency(&host, &device, options);
+    }
+
+    let supported_config = device
+        .default_output_config()
+        .context("Não foi possível obter config de saída")?;
+    let sample_format = supported_config.sample_format();
+    let config: cpal::StreamConfig = supported_config.into();
+    let channels = config.channels as usize;
+
+    // 2) Gerador compartilhado com o callback do cpal
+    let generator = Arc::new(Mutex::new(Generator::new(
+        options.wave,
+        options.freq,
+        options.level_db,
+        config.sample_rate.0,
+    )));
+
+    let err_fn = |err: cpal::StreamError| error!(error = %err, "Erro no stream de áudio");
+
+    // 3) Cria o stream de saída conforme o formato do dispositivo
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => {
+            let generator_c = Arc::clone(&generator);
+            device.build_output_stream(
+                &config,
+                move |data: &mut [f32], _| {
+                    generator_c.lock().unwrap().fill(data, channels, |s| s);
+                },
+                err_fn,
+                None,
+            )?
+        }
+        cpal::SampleFormat::I16 => {
+            let generator_c = Arc::clone(&generator);
+            device.build_output_stream(
+                &config,
+                move |data: &mut [i16], _| {
+                    generator_c
+                        .lock()
+                        .unwrap()
+                        .fill(data, channels, |s| (s * i16::MAX as f32) as i16);
+                },
+                err_fn,
+                None,
+            )?
+        }
+        cpal::SampleFormat::U16 => {
+            let generator_c = Arc::clone(&generator);
+            device.build_output_stream(
+                &config,
+                move |data: &mut [u16], _| {
+                    // Converte para U16 centrando em 32768
+                    generator_c.lock().unwrap().fill(data, channels, |s| {
+                        ((s * i16::MAX as f32) as i32 + 32768).clamp(0, u16::MAX as i32) as u16
+                    });
+                },
+                err_fn,
+                None,
+            )?
+        }
+        _ => anyhow::bail!("Formato de amostra não suportado"),
+    };
+
+    let wave = match options.wave {
+        Wave::Sine => format!("senoide de {} Hz", options.freq),
+        Wave::Noise => "ruído branco".to_string(),
+    };
+    info!(
+        level_db = options.level_db,
+        device = device.name().unwrap_or_default(),
+        secs = options.secs,
+        "Tocando {wave}"
+    );
+    stream.play()?;
+    std::thread::sleep(Duration::from_secs(options.secs));
+    drop(stream);
+
+    info!("Sinal de teste concluído");
+
+    Ok(())
+}
+
+/// Amplitude mínima (escala 0..1) para considerar que o impulso voltou.
+const IMPULSE_DETECT_THRESHOLD: f32 = 0.2;
+/// Duração do impulso em frames; curto o bastante para ser um "clique".
+const IMPULSE_FRAMES: usize = 32;
+/// Tempo máximo esperando o impulso voltar antes de considerá-lo perdido.
+const IMPULSE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Intervalo entre um impulso e o próximo (e antes do primeiro).
+const IMPULSE_GAP: Duration = Duration::from_millis(500);
+
+/// Impulso já escrito na saída, esperando voltar pela entrada.
+struct PendingImpulse {
+    /// Quando foi escrito, no relógio do processo; só para o timeout.
+    sent: Instant,
+    /// Quando o dispositivo vai tocá-lo, segundo o callback de saída.
+    playback: cpal::StreamInstant,
+}
+
+/// Estado compartilhado entre os callbacks de saída e de entrada na medição.
+struct LatencyProbe {
+    runs: usize,
+    pending: Option<PendingImpulse>,
+    /// Frames do impulso pendente que ainda faltam escrever.
+    impulse_left: usize,
+    next_fire: Instant,
+    results: Vec<Duration>,
+    lost: usize,
+}
+
+impl LatencyProbe {
+    fn done(&self) -> bool {
+        self.results.len() + self.lost >= self.runs
+    }
+
+    /// Preenche o buffer de saída com silêncio e, quando for a hora, o
+    /// impulso. `playback` é quando o primeiro frame do buffer vai tocar.
+    fn fill_output(&mut self, frames: usize, playback: cpal::StreamInstant) -> Vec<f32> {
+        let mut block = vec![0.0; frames];
+        let now = Instant::now();
+
+        if let Some(pending) = &self.pending
+            && now.duration_since(pending.sent) > IMPULSE_TIMEOUT
+        {
+            self.pending = None;
+            self.lost += 1;
+            self.next_fire = now + IMPULSE_GAP;
+        }
+
+        if self.pending.is_none() && !self.done() && now >= self.next_fire {
+            // O impulso começa no primeiro frame do buffer.
+            self.pending = Some(PendingImpulse {
+                sent: now,
+                playback,
+            });
+            self.impulse_left = IMPULSE_FRAMES;
+        }
+        for slot in block.iter_mut().take(self.impulse_left) {
+            *slot = 0.9;
+        }
+        self.impulse_left -= self.impulse_left.min(frames);
+        block
+    }
+
+    /// Procura o impulso no bloco capturado e registra a latência medida.
+    /// `capture` é quando o primeiro frame do bloco foi captado.
+    ///
+    /// A latência vem só dos timestamps dos callbacks, que o cpal dá no
+    /// mesmo relógio para os streams de um host, e não da hora em que cada
+    /// callback rodou, que varia com o tamanho dos buffers e o agendamento.
+    fn scan_input(
+        &mut self,
+        block: &[f32],
+        channels: usize,
+        sample_rate: u32,
+        capture: cpal::StreamInstant,
+    ) {
+        let Some(pending) = &self.pending else {
+            return;
+        };
+        let Some(index) = block
+            .iter()
+            .position(|s| s.abs() > IMPULSE_DETECT_THRESHOLD)
+        else {
+            return;
+        };
+
+        let offset = Duration::from_secs_f64((index / channels) as f64 / sample_rate as f64);
+        let latency = capture
+            .add(offset)
+            .and_then(|arrival| arrival.duration_since(&pending.playback));
+        match latency {
+            Some(latency) => self.results.push(latency),
+            // Captado "antes" de tocar: os relógios dos dois streams não
+            // batem, e a medida não vale.
+            None => self.lost += 1,
+        }
+        self.pending = None;
+        self.next_fire = Instant::now() + IMPULSE_GAP;
+    }
+}
+
+/// Toca impulsos no dispositivo de saída, grava pelo de entrada e reporta a
+/// latência ida-e-volta de cada um.
+fn measure_latency(host: &cpal::Host, output: &cpal::Device, options: &Options) -> Result<()> {
+    let input = match &options.input {
+        Some(wanted) => host
+            .input_devices()?
+            .find(|d| {
+                d.name()
+                    .map(|n| n.contains(wanted.as_str()))
+                    .unwrap_or(false)
+            })
+            .with_context(|| format!("Nenhum dispositivo de entrada contém \"{wanted}\""))
+            .map_err(PlaygroundError::unavailable)?,
+        None => host
+            .default_input_device()
+            .context("Nenhum microfone padrão encontrado")
+            .map_err(PlaygroundError::unavailable)?,
+    };
+
+    let out_supported = output
+        .default_output_config()
+        .context("Não foi possível obter config de saída")?;
+    let in_supported = input
+        .default_input_config()
+        .context("Não foi possível obter config de entrada")?;
+    let out_format = out_supported.sample_format();
+    let in_format = in_supported.sample_format();
+    let out_config: cpal::StreamConfig = out_supported.into();
+    let in_config: cpal::StreamConfig = in_supported.into();
+
+    let probe = Arc::new(Mutex::new(LatencyProbe {
+        runs: options.runs.max(1),
+        pending: None,
+        impulse_left: 0,
+        next_fire: Instant::now() + IMPULSE_GAP,
+        results: Vec::new(),
+        lost: 0,
+    }));
+
+    let out_stream = match out_format {
+        cpal::SampleFormat::F32 => build_probe_output::<f32>(output, &out_config, &probe)?,
+        cpal::SampleFormat::I16 => build_probe_output::<i16>(output, &out_config, &probe)?,
+        cpal::SampleFormat::U16 => build_probe_output::<u16>(output, &out_config, &probe)?,
+        _ => anyhow::bail!("Formato de amostra de saída não suportado"),
+    };
+    let in_stream = match in_format {
+        cpal::SampleFormat::F32 => build_probe_input::<f32>(&input, &in_config, &probe)?,
+        cpal::SampleFormat::I16 => build_probe_input::<i16>(&input, &in_config, &probe)?,
+        cpal::SampleFormat::U16 => build_probe_input::<u16>(&input, &in_config, &probe)?,
+        _ => anyhow::bail!("Formato de amostra de entrada não suportado"),
+    };
+
+    info!(
+        output = output.name().unwrap_or_default(),
+        input = input.name().unwrap_or_default(),
+        runs = options.runs.max(1),
+        "Medindo latência"
+    );
+    in_stream.play()?;
+    out_stream.play()?;
+    // Cada impulso leva no máximo o intervalo mais o timeout; passar disso
+    // (com a folga de um impulso) quer dizer que os callbacks pararam.
+    let limit = (IMPULSE_GAP + IMPULSE_TIMEOUT) * (options.runs.max(1) as u32 + 1);
+    let deadline = Instant::now() + limit;
+    while !probe.lock().unwrap().done() {
+        if Instant::now() >= deadline {
+            return Err(PlaygroundError::unavailable(anyhow::anyhow!(
+                "A medição não terminou em {}; o dispositivo parou de processar áudio?",
+                humantime::format_duration(limit)
+            ))
+            .into());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    drop(out_stream);
+    drop(in_stream);
+
+    let probe = probe.lock().unwrap();
+    for (i, latency) in probe.results.iter().enumerate() {
+        info!(
+            run = i + 1,
+            latency_ms = latency.as_secs_f64() * 1000.0,
+            "Impulso recebido"
+        );
+    }
+    if probe.results.is_empty() {
+        anyhow::bail!(
+            "Nenhum impulso voltou pela entrada; confira o loopback entre os dispositivos"
+        );
+    }
+    let total: Duration = probe.results.iter().sum();
+    let avg = total / probe.results.len() as u32;
+    let min = probe.results.iter().min().copied().unwrap_or_default();
+    let max = probe.results.iter().max().copied().unwrap_or_default();
+    info!(
+        avg_ms = avg.as_secs_f64() * 1000.0,
+        min_ms = min.as_secs_f64() * 1000.0,
+        max_ms = max.as_secs_f64() * 1000.0,
+        lost = probe.lost,
+        "Latência ida-e-volta"
+    );
+
+    Ok(())
+}
+
+fn build_probe_output<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    probe: &Arc<Mutex<LatencyProbe>>,
+) -> Result<cpal::Stream>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    let probe = Arc::clone(probe);
+    let channels = config.channels as usize;
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+            let block = probe
+                .lock()
+                .unwrap()
+                .fill_output(data.len() / channels, info.timestamp().playback);
+            for (frame, value) in data.chunks_mut(channels).zip(block) {
+                frame.fill(T::from_sample(value));
+            }
+        },
+        |err| error!(error = %err, "Erro no stream de saída"),
+        None,
+    )?;
+    Ok(stream)
+}
+
+fn build_probe_input<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    probe: &Arc<Mutex<LatencyProbe>>,
+) -> Result<cpal::Stream>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    let probe = Arc::clone(probe);
+    let channels = config.channels as usize;
+    let sample_rate = config.sample_rate.0;
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], info: &cpal::InputCallbackInfo| {
+            let block: Vec<f32> = data.iter().map(|s| s.to_sample::<f32>()).collect();
+            probe.lock().unwrap().scan_input(
+                &block,
+                channels,
+                sample_rate,
+                info.timestamp().capture,
+            );
+        },
+        |err| error!(error = %err, "Erro no stream de entrada"),
+        None,
+    )?;
+    Ok(stream)
+}
@@ -0,0 +1,104 @@
+//! Custo de calcular os checksums de uma pasta de migrações grande, sozinho
+//! ([`checksum`]) e dentro do [`migration_status`] (ler os arquivos, calcular
+//! e comparar com o banco), para validar mudanças como o hashing em
+//! paralelo.
+//!
+//! `cargo bench -p migrate-core`
+
+use std::hint::black_box;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use migrate_core::{AdapterError, AppliedMigration, MigrationBackend, checksum, migration_status};
+
+/// Tamanho aproximado de cada migração; as do projeto ficam entre 0,5 e 4 KB.
+const MIGRATION_BYTES: usize = 2048;
+
+/// Uma migração sintética de ~[`MIGRATION_BYTES`], diferente para cada `i`.
+fn migration(i: usize) -> Vec<u8> {
+    let mut sql = format!("CREATE TABLE table_{i} (\n    id INTEGER PRIMARY KEY");
+    let mut column = 0;
+    while sql.len() < MIGRATION_BYTES {
+        sql.push_str(&format!(",\n    column_{column} TEXT NOT NULL DEFAULT ''"));
+        column += 1;
+    }
+    sql.push_str("\n);\n");
+    sql.into_bytes()
+}
+
+fn checksums(c: &mut Criterion) {
+    let mut group = c.benchmark_group("checksum");
+    for count in [100, 1_000, 10_000] {
+        let migrations: Vec<_> = (0..count).map(migration).collect();
+        let bytes: usize = migrations.iter().map(Vec::len).sum();
+        group.throughput(Throughput::Bytes(bytes as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &migrations, |b, set| {
+            b.iter(|| {
+                set.iter()
+                    .map(|content| checksum(black_box(content)))
+                    .collect::<Vec<_>>()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Banco em memória em que tudo já foi aplicado: o `migration_status`
+/// calcula e compara todos os checksums sem executar nada.
+struct Applied(Vec<AppliedMigration>);
+
+#[async_trait]
+impl MigrationBackend for Applied {
+    async fn ensure_migrations_table(&self, _bootstrap_sql: &str) -> Result<(), AdapterError> {
+        Ok(())
+    }
+
+    async fn fetch_applied_migrations(&self) -> Result<Vec<AppliedMigration>, AdapterError> {
+        Ok(self.0.clone())
+    }
+
+    async fn apply_migration(
+        &self,
+        _name: &str,
+        _sql: &str,
+        _checksum: &str,
+    ) -> Result<(), AdapterError> {
+        Ok(())
+    }
+}
+
+fn status(c: &mut Criterion) {
+    const COUNT: usize = 1_000;
+    // O `migration_status` lê `migrations/` do diretório atual.
+    let dir: PathBuf = std::env::temp_dir().join(format!("migrate-bench-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("migrations")).expect("create bench directory");
+    let mut applied = Vec::with_capacity(COUNT);
+    for i in 0..COUNT {
+        let name = format!("{i:06}_create_table_{i}.sql");
+        let content = migration(i);
+        std::fs::write(dir.join("migrations").join(&name), &content).expect("write migration");
+        applied.push(AppliedMigration {
+            name,
+            checksum: checksum(&content),
+        });
+    }
+    std::env::set_current_dir(&dir).expect("enter bench directory");
+
+    let backend = Applied(applied);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("tokio runtime");
+    c.bench_function("migration_status/1000", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(migration_status(&backend))
+                .expect("status")
+        })
+    });
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, checksums, status);
+criterion_main!(benches);
@@ -4,6 +4,14 @@
 //! validar checksums e registrar o histórico) da implementação de acesso ao
 //! banco. Isso facilita testar e reaproveitar o mesmo fluxo com diferentes
 //! bancos.
+//!
+//! Este crate não depende de nenhum driver: o adaptador libSQL (e o
+//! `migrate_local` usado pelo `migrate-to-latest`, pelo `create-admin` e pelo
+//! servidor) fica no crate `migrate-libsql`, e outro banco só precisa
+//! implementar [`MigrationBackend`] e se registrar no [`registry`] para ser
+//! escolhido pela URL da configuração.
+
+pub mod registry;
 
 // Importamos `async_trait` porque traits no Rust não aceitam métodos async
 // nativamente. Esse macro “embrulha” o trait para que possamos declarar as
@@ -14,12 +22,15 @@ use async_trait::async_trait;
 // que está salvo na tabela de controle do banco.
 use sha2::{Digest, Sha256};
 // `std::fs` e `std::io::Read` são usados para percorrer a pasta de migrações e
-// ler os bytes de cada arquivo `.sql` do disco.
+// ler os bytes de cada arquivo `.sql` do disco; `Path`/`PathBuf` representam
+// os caminhos encontrados.
 use std::fs;
 use std::io::Read;
+use std::path::{Path, PathBuf};
 // `thiserror` reduz a verbosidade na criação de enums de erro que implementam
 // `std::error::Error`, permitindo mensagens mais amigáveis.
 use thiserror::Error;
+use tracing::info;
 
 #[derive(Error, Debug)]
 /// Enum básico com todos os erros que podem acontecer durante uma migração.
@@ -30,6 +41,11 @@ pub enum MigrationError {
     /// erro concreto para `AdapterError` e depois para esta variante.
     #[error("Adapter error: {0}")]
     Adapter(#[from] AdapterError),
+    /// Não foi possível abrir o banco (caminho inexistente, sem permissão,
+    /// arquivo bloqueado); separado de [`MigrationError::Adapter`] porque
+    /// quem chama costuma tratar isso como "banco indisponível".
+    #[error("Failed to open database {0}: {1}")]
+    Open(String, AdapterError),
     /// Falhas em operações básicas de arquivo (abrir, listar, ler bytes, …).
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -41,8 +57,16 @@ pub enum MigrationError {
     /// (por exemplo, caracteres inválidos em UTF-8).
     #[error("Failed to read migration file {0}")]
     ReadFile(String),
+    /// Nenhum adaptador registrado para o esquema da URL (ver
+    /// [`registry`]); a segunda parte lista os esquemas conhecidos.
+    #[error("Unknown migration backend {0}:// (registered: {1})")]
+    UnknownBackend(String, String),
 }
 
+/// Filtro de log do `migrate-to-latest` e do `create-admin` quando nem
+/// `RUST_LOG` nem `log.level` definem um.
+pub const DEFAULT_LOG_FILTER: &str = "migrate_core=info";
+
 #[derive(Debug, Clone)]
 /// Representa uma linha da tabela `__migrations` no banco. Guardamos o nome
 /// do arquivo executado e o checksum correspondente.
@@ -51,11 +75,135 @@ pub struct AppliedMigration {
     pub checksum: String,
 }
 
+// Este SQL garante que a tabela de controle exista. Mesmo se não houver
+// arquivos, precisamos da tabela para registrar futuras execuções.
+const BOOTSTRAP_MIGRATIONS_SQL: &str = r#"
+    CREATE TABLE IF NOT EXISTS __migrations (
+        name TEXT PRIMARY KEY,
+        checksum TEXT NOT NULL,
+        description TEXT,
+        executed_by TEXT,
+        executed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Situação de uma migração comparando a pasta `migrations/` com a tabela
+/// `__migrations`.
+pub enum MigrationState {
+    /// Registrada no banco com o mesmo checksum do arquivo.
+    Applied,
+    /// Existe em disco, mas ainda não foi executada.
+    Pending,
+    /// Registrada no banco, mas o arquivo mudou depois disso (o
+    /// `run_migrations` recusaria continuar).
+    Modified,
+    /// Registrada no banco, mas o arquivo não existe mais em disco.
+    Missing,
+}
+
+#[derive(Debug, Clone)]
+/// Uma linha do relatório devolvido por [`migration_status`].
+pub struct MigrationStatus {
+    pub name: String,
+    /// Checksum do arquivo em disco, ou o registrado no banco quando o
+    /// arquivo sumiu.
+    pub checksum: String,
+    pub state: MigrationState,
+}
+
+/// Pasta lida por [`run_migrations`] e [`migration_status`], relativa ao
+/// diretório atual.
+pub const MIGRATIONS_DIR: &str = "migrations";
+
+/// Lista os arquivos `.sql` de `dir` em ordem alfabética (garantindo que
+/// 0001_... venha antes de 0002_...).
+fn migration_files(dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut migration_files: Vec<_> = fs::read_dir(dir)?
+        .map(|res| res.map(|e| e.path()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?
+        .into_iter()
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "sql"))
+        .collect();
+    migration_files.sort();
+    Ok(migration_files)
+}
+
+/// Checksum SHA-256 (em hexadecimal) do conteúdo de uma migração, o mesmo
+/// valor gravado na coluna `checksum` da `__migrations`.
+pub fn checksum(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Lê o arquivo inteiro e devolve o nome, o conteúdo e o [`checksum`] usado
+/// para comparar com o valor salvo no banco.
+fn read_migration(path: &Path) -> Result<(String, Vec<u8>, String), MigrationError> {
+    let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
+    let mut file = fs::File::open(path)?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    let checksum = checksum(&content);
+    Ok((file_name, content, checksum))
+}
+
+/// Relatório das migrações sem executar nada: quais já foram aplicadas,
+/// quais estão pendentes e quais divergem do que está no banco. Útil para
+/// painéis administrativos e para conferir um deploy antes de migrar.
+pub async fn migration_status<B>(backend: &B) -> Result<Vec<MigrationStatus>, MigrationError>
+where
+    B: MigrationBackend + ?Sized,
+{
+    migration_status_in(backend, Path::new(MIGRATIONS_DIR)).await
+}
+
+/// [`migration_status`] com os arquivos de `dir` em vez de [`MIGRATIONS_DIR`].
+pub async fn migration_status_in<B>(
+    backend: &B,
+    dir: &Path,
+) -> Result<Vec<MigrationStatus>, MigrationError>
+where
+    B: MigrationBackend + ?Sized,
+{
+    // A tabela pode ainda não existir num banco novo; criá-la é inofensivo.
+    backend
+        .ensure_migrations_table(BOOTSTRAP_MIGRATIONS_SQL)
+        .await?;
+    let mut applied = backend.fetch_applied_migrations().await?;
+
+    let mut report = Vec::new();
+    for path in migration_files(dir)? {
+        let (name, _, checksum) = read_migration(&path)?;
+        // Aqui comparamos por nome (e não por posição) para que um arquivo
+        // removido no meio da lista não bagunce o restante do relatório.
+        let state = match applied.iter().position(|m| m.name == name) {
+            Some(i) if applied.remove(i).checksum == checksum => MigrationState::Applied,
+            Some(_) => MigrationState::Modified,
+            None => MigrationState::Pending,
+        };
+        report.push(MigrationStatus {
+            name,
+            checksum,
+            state,
+        });
+    }
+    // O que sobrou no banco não tem mais arquivo correspondente.
+    report.extend(applied.into_iter().map(|m| MigrationStatus {
+        name: m.name,
+        checksum: m.checksum,
+        state: MigrationState::Missing,
+    }));
+    report.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(report)
+}
+
 /// Função principal que orquestra a execução das migrações. Ela recebe um
 /// `backend` genérico que implementa [`MigrationBackend`]. Dessa forma,
 /// podemos reutilizar o mesmo fluxo com qualquer banco ou tecnologia,
 /// contanto que exista um adaptador compatível.
-pub async fn run_migrations<B>(backend: &B) -> Result<(), MigrationError>
+///
+/// Devolve os nomes das migrações executadas nesta chamada (vazio quando o
+/// banco já estava atualizado).
+pub async fn run_migrations<B>(backend: &B) -> Result<Vec<String>, MigrationError>
 where
     // `MigrationBackend + ?Sized` permite aceitar tanto tipos concretos quanto
     // referências trait. O bound `Send + Sync` está definido no trait para que
@@ -64,18 +212,16 @@ where
     // referências para o tipo podem ser compartilhadas por múltiplas threads).
     B: MigrationBackend + ?Sized,
 {
-    // Este SQL garante que a tabela de controle exista. Mesmo se não houver
-    // arquivos, precisamos da tabela para registrar futuras execuções.
-    const BOOTSTRAP_MIGRATIONS_SQL: &str = r#"
-        CREATE TABLE IF NOT EXISTS __migrations (
-            name TEXT PRIMARY KEY,
-            checksum TEXT NOT NULL,
-            description TEXT,
-            executed_by TEXT,
-            executed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-        );
-    "#;
+    run_migrations_in(backend, Path::new(MIGRATIONS_DIR)).await
+}
 
+/// [`run_migrations`] com os arquivos de `dir` em vez de [`MIGRATIONS_DIR`];
+/// útil quando o diretório atual não é a raiz do projeto (testes, deploys
+/// que empacotam as migrações em outro lugar).
+pub async fn run_migrations_in<B>(backend: &B, dir: &Path) -> Result<Vec<String>, MigrationError>
+where
+    B: MigrationBackend + ?Sized,
+{
     // 1. Cria a tabela `__migrations` caso não exista. O adaptador decide
     // como executar o SQL (transação, conexão, etc.).
     backend
@@ -86,15 +232,9 @@ where
     // banco está atualizado.
     let applied_migrations = backend.fetch_applied_migrations().await?;
 
-    // 3. Varre a pasta `migrations/`, pega somente arquivos `.sql`, ordena
+    // 3. Varre a pasta `dir`, pega somente arquivos `.sql`, ordena
     // alfabeticamente (garantindo que 0001_... execute antes de 0002_...).
-    let mut migration_files: Vec<_> = fs::read_dir("migrations")?
-        .map(|res| res.map(|e| e.path()))
-        .collect::<Result<Vec<_>, std::io::Error>>()?
-        .into_iter()
-        .filter(|path| path.is_file() && path.extension().map_or(false, |ext| ext == "sql"))
-        .collect();
-    migration_files.sort();
+    let migration_files = migration_files(dir)?;
 
     // 4. Valida os checksums de tudo que já foi aplicado. Isso protege contra
     // o cenário "alguém editou um arquivo já aplicado".
@@ -102,20 +242,14 @@ where
         if i >= migration_files.len() {
             break;
         }
-        let file_path = &migration_files[i];
-        let file_name = file_path.file_name().unwrap().to_str().unwrap().to_string();
+        // Lemos o arquivo inteiro para gerar o hash e comparar com o valor no
+        // banco.
+        let (file_name, _, checksum) = read_migration(&migration_files[i])?;
 
         if file_name != applied.name {
             continue;
         }
 
-        // Lemos o arquivo inteiro para gerar o hash e comparar com o valor no
-        // banco.
-        let mut file = fs::File::open(file_path)?;
-        let mut content = Vec::new();
-        file.read_to_end(&mut content)?;
-        let checksum = format!("{:x}", Sha256::digest(&content));
-
         if checksum != applied.checksum {
             return Err(MigrationError::ChecksumMismatch(
                 file_name,
@@ -128,13 +262,9 @@ where
     // 5. Executa os arquivos restantes (aqueles que não foram validados no
     // passo anterior). `skip(applied_migrations.len())` garante que aplicamos
     // apenas o que está faltando.
+    let mut executed = Vec::new();
     for file_path in migration_files.iter().skip(applied_migrations.len()) {
-        let file_name = file_path.file_name().unwrap().to_str().unwrap().to_string();
-
-        let mut file = fs::File::open(file_path)?;
-        let mut content = Vec::new();
-        file.read_to_end(&mut content)?;
-        let checksum = format!("{:x}", Sha256::digest(&content));
+        let (file_name, content, checksum) = read_migration(file_path)?;
 
         let sql =
             String::from_utf8(content).map_err(|_| MigrationError::ReadFile(file_name.clone()))?;
@@ -143,10 +273,11 @@ where
             .apply_migration(&file_name, sql.as_str(), &checksum)
             .await?;
 
-        println!("Applied migration: {}", file_name);
+        info!(migration = %file_name, "applied migration");
+        executed.push(file_name);
     }
 
-    Ok(())
+    Ok(executed)
 }
 
 #[async_trait]
@@ -1,8 +1,15 @@
-//! Binário que usa a biblioteca de migrações para atualizar um banco libSQL local.
+//! Adaptador libSQL para o trait [`MigrationBackend`].
 //!
-//! A responsabilidade aqui é conectar na base, implementar o trait
-//! [`MigrationBackend`] usando libSQL como driver e delegar o restante para a
-//! biblioteca compartilhada.
+//! Fica num crate próprio (e não no binário `migrate-to-latest`) para que
+//! qualquer aplicação do projeto — o CLI de migrações ou o servidor HTTP —
+//! consiga aplicar as migrações no mesmo banco sem duplicar código, e para
+//! que o `migrate-core` não precise depender do libSQL.
+//!
+//! [`migrate_local`] junta os dois no fluxo usado pelo `migrate-to-latest`,
+//! pelo `create-admin` e pelo servidor: abrir o banco local e aplicar o que
+//! estiver pendente. [`register`] põe o adaptador no
+//! [`registry`](migrate_core::registry) para o esquema `file`, o que também
+//! cobre caminhos sem esquema.
 
 // `async_trait` novamente permite declarar funções async dentro do trait que
 // implementaremos (MigrationBackend).
@@ -10,25 +17,19 @@ use async_trait::async_trait;
 // Tipos principais do libSQL usados: `Builder` cria/conecta no banco, `Connection`
 // executa comandos e `Transaction` garante atomicidade na aplicação das migrações.
 use libsql::{Builder, Connection, Transaction};
-// Reexportamos da nossa biblioteca as peças necessárias: função que orquestra
-// as migrações, trait que precisamos implementar e tipos auxiliares.
-use rust_test::migrate_to_latest::{
-    AdapterError, AppliedMigration, MigrationBackend, run_migrations,
+
+use migrate_core::{
+    AdapterError, AppliedMigration, MigrationBackend, MigrationError,
+    registry::{self, FILE_SCHEME},
+    run_migrations,
 };
-use std::env;
 
-#[tokio::main]
-/// Função principal. Ela apenas cria o adaptador com base nas variáveis de
-/// ambiente e delega a execução das migrações para a biblioteca.
-async fn main() -> anyhow::Result<()> {
-    // `create_adapter_from_env` lê `LIBSQL_DB_PATH` (ou usa `migrations.db` como
-    // padrão), abre uma conexão libSQL e já retorna o adaptador pronto.
-    let adapter = create_adapter_from_env().await?;
-    // A biblioteca cuida do fluxo completo (listar arquivos, gerar checksum,
-    // chamar o backend). Aqui só precisamos passar uma referência ao adaptador.
-    run_migrations(&adapter).await?;
-    Ok(())
-}
+/// Variável de ambiente com o caminho do banco libSQL local; apelido de
+/// `migrate.db_path` na configuração compartilhada.
+pub const DB_PATH_ENV: &str = "LIBSQL_DB_PATH";
+/// Caminho usado quando nada na configuração diz outro, para facilitar
+/// ambientes locais.
+pub const DEFAULT_DB_PATH: &str = "migrations.db";
 
 #[derive(Clone)]
 /// Adaptador concreto que implementa `MigrationBackend` usando a API do libSQL.
@@ -132,16 +133,38 @@ async fn apply_migration_in_transaction(
     Ok(())
 }
 
-/// Lê variáveis de ambiente necessárias e constrói o `LibSqlAdapter`.
-async fn create_adapter_from_env() -> anyhow::Result<LibSqlAdapter> {
-    // Permite customizar o caminho do arquivo `.db`. Caso a variável não exista,
-    // usamos `migrations.db` como padrão para facilitar ambientes locais.
-    let db_path = env::var("LIBSQL_DB_PATH").unwrap_or_else(|_| "migrations.db".to_string());
+/// Abre (ou cria) um banco libSQL local no caminho informado e devolve a
+/// conexão pronta para uso.
+pub async fn open_local(db_path: &str) -> Result<Connection, libsql::Error> {
     // `Builder::new_local` abre um banco libSQL baseado em arquivo. Poderíamos
     // trocar por outros builders caso queira apontar para um servidor remoto.
     let database = Builder::new_local(db_path).build().await?;
     // `connect` devolve a conexão (`Connection`), que é tudo o que o adaptador
     // precisa para cumprir o contrato do trait.
-    let conn = database.connect()?;
-    Ok(LibSqlAdapter::new(conn))
+    database.connect()
+}
+
+/// Abre o banco local em `db_path` e aplica as migrações pendentes,
+/// devolvendo a conexão e os nomes das migrações executadas.
+pub async fn migrate_local(db_path: &str) -> Result<(Connection, Vec<String>), MigrationError> {
+    let conn = open_local(db_path)
+        .await
+        .map_err(|err| MigrationError::Open(db_path.to_string(), AdapterError::new(err)))?;
+    let applied = run_migrations(&LibSqlAdapter::new(conn.clone())).await?;
+    Ok((conn, applied))
+}
+
+/// Registra o libSQL local para `file://<caminho>` e para caminhos sem
+/// esquema. Os binários chamam isto antes de
+/// [`open_backend`](registry::open_backend).
+pub fn register() {
+    registry::register_backend(FILE_SCHEME, |url| {
+        let db_path = url.strip_prefix("file://").unwrap_or(url).to_string();
+        Box::pin(async move {
+            let conn = open_local(&db_path)
+                .await
+                .map_err(|err| MigrationError::Open(db_path, AdapterError::new(err)))?;
+            Ok(Box::new(LibSqlAdapter::new(conn)) as Box<dyn MigrationBackend>)
+        })
+    });
 }
@@ -0,0 +1,292 @@
+//! Os cenários da suíte. Cada um recebe um banco vazio e usa só SQL que
+//! SQLite, Postgres e MySQL aceitam igual.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use migrate_core::{
+    MigrationBackend, MigrationError, MigrationState, migration_status_in, run_migrations_in,
+};
+use tokio::sync::Barrier;
+
+use crate::{Database, Fault, Faulty, Migrations};
+
+/// Quantas conexões disputam o mesmo banco em
+/// [`concurrent_runs_apply_each_migration_once`].
+const CONCURRENT_RUNNERS: usize = 4;
+
+fn create_table(table: &str) -> String {
+    format!("CREATE TABLE {table} (id INTEGER PRIMARY KEY);\n")
+}
+
+/// Nome e situação de cada migração, em ordem.
+async fn states(
+    backend: &dyn MigrationBackend,
+    migrations: &Migrations,
+) -> Vec<(String, MigrationState)> {
+    migration_status_in(backend, migrations.path())
+        .await
+        .expect("migration status")
+        .into_iter()
+        .map(|status| (status.name, status.state))
+        .collect()
+}
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+pub async fn applies_pending_in_order(db: Database) {
+    let migrations = Migrations::new();
+    migrations
+        .write("0002_posts.sql", &create_table("posts"))
+        .write("0001_users.sql", &create_table("users"))
+        .write("0003_tags.sql", &create_table("tags"))
+        .write("README.md", "não é uma migração");
+    let backend = db.open().await;
+
+    let applied = run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect("run migrations");
+
+    assert_eq!(
+        applied,
+        names(&["0001_users.sql", "0002_posts.sql", "0003_tags.sql"])
+    );
+    assert!(
+        states(&*backend, &migrations)
+            .await
+            .iter()
+            .all(|(_, state)| *state == MigrationState::Applied)
+    );
+}
+
+pub async fn second_run_is_a_no_op(db: Database) {
+    let migrations = Migrations::new();
+    migrations.write("0001_users.sql", &create_table("users"));
+    let backend = db.open().await;
+
+    run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect("first run");
+    let applied = run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect("second run");
+
+    assert!(applied.is_empty(), "second run applied {applied:?}");
+}
+
+pub async fn applies_only_new_files(db: Database) {
+    let migrations = Migrations::new();
+    migrations
+        .write("0001_users.sql", &create_table("users"))
+        .write("0002_posts.sql", &create_table("posts"));
+    let backend = db.open().await;
+    run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect("first run");
+
+    migrations.write("0003_tags.sql", &create_table("tags"));
+    // Outra conexão, para não depender de estado guardado no adaptador.
+    let backend = db.open().await;
+    let applied = run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect("second run");
+
+    assert_eq!(applied, names(&["0003_tags.sql"]));
+}
+
+pub async fn rejects_modified_migration(db: Database) {
+    let migrations = Migrations::new();
+    migrations
+        .write("0001_users.sql", &create_table("users"))
+        .write("0002_posts.sql", &create_table("posts"));
+    let backend = db.open().await;
+    run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect("first run");
+
+    migrations.write("0001_users.sql", &create_table("people"));
+    let err = run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect_err("modified migration must be rejected");
+
+    assert!(
+        matches!(&err, MigrationError::ChecksumMismatch(name, ..) if name == "0001_users.sql"),
+        "unexpected error: {err}"
+    );
+    assert_eq!(
+        states(&*backend, &migrations).await,
+        vec![
+            ("0001_users.sql".to_string(), MigrationState::Modified),
+            ("0002_posts.sql".to_string(), MigrationState::Applied),
+        ]
+    );
+}
+
+pub async fn reports_missing_migration(db: Database) {
+    let migrations = Migrations::new();
+    migrations
+        .write("0001_users.sql", &create_table("users"))
+        .write("0002_posts.sql", &create_table("posts"));
+    let backend = db.open().await;
+    run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect("first run");
+
+    migrations.remove("0002_posts.sql");
+
+    assert_eq!(
+        states(&*backend, &migrations).await,
+        vec![
+            ("0001_users.sql".to_string(), MigrationState::Applied),
+            ("0002_posts.sql".to_string(), MigrationState::Missing),
+        ]
+    );
+}
+
+/// Uma migração que falha no meio não pode deixar metade dela no banco nem
+/// ser registrada: corrigido o arquivo, a próxima execução a aplica do zero.
+/// O `INSERT` (e não um `CREATE TABLE`) é o que prova o rollback, porque o
+/// MySQL faz commit implícito de DDL.
+pub async fn failed_migration_is_rolled_back(db: Database) {
+    let migrations = Migrations::new();
+    migrations
+        .write("0001_users.sql", &create_table("users"))
+        .write(
+            "0002_seed.sql",
+            "INSERT INTO users (id) VALUES (1);\nINSERT INTO no_such_table (id) VALUES (1);\n",
+        );
+    let backend = db.open().await;
+
+    let err = run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect_err("broken migration must fail");
+    assert!(
+        matches!(err, MigrationError::Adapter(_)),
+        "unexpected error: {err}"
+    );
+    assert_eq!(
+        states(&*backend, &migrations).await,
+        vec![
+            ("0001_users.sql".to_string(), MigrationState::Applied),
+            ("0002_seed.sql".to_string(), MigrationState::Pending),
+        ]
+    );
+
+    // Se o primeiro `INSERT` tivesse ficado, este falharia pela chave
+    // primária repetida.
+    migrations.write("0002_seed.sql", "INSERT INTO users (id) VALUES (1);\n");
+    let applied = run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect("fixed migration");
+    assert_eq!(applied, names(&["0002_seed.sql"]));
+}
+
+/// A conexão cai antes da segunda migração: a primeira fica registrada, e a
+/// execução seguinte continua de onde parou.
+pub async fn resumes_after_apply_failure(db: Database) {
+    let migrations = Migrations::new();
+    migrations
+        .write("0001_users.sql", &create_table("users"))
+        .write("0002_posts.sql", &create_table("posts"))
+        .write("0003_tags.sql", &create_table("tags"));
+    let backend = db.open().await;
+
+    let faulty = Faulty::new(&*backend, Fault::Apply("0002_posts.sql"));
+    let err = run_migrations_in(&faulty, migrations.path())
+        .await
+        .expect_err("injected failure must surface");
+    assert!(
+        matches!(err, MigrationError::Adapter(_)),
+        "unexpected error: {err}"
+    );
+    assert_eq!(
+        states(&*backend, &migrations).await,
+        vec![
+            ("0001_users.sql".to_string(), MigrationState::Applied),
+            ("0002_posts.sql".to_string(), MigrationState::Pending),
+            ("0003_tags.sql".to_string(), MigrationState::Pending),
+        ]
+    );
+
+    let applied = run_migrations_in(&*backend, migrations.path())
+        .await
+        .expect("resumed run");
+    assert_eq!(applied, names(&["0002_posts.sql", "0003_tags.sql"]));
+}
+
+/// Sem saber o que já foi aplicado, nada pode ser executado.
+pub async fn fetch_failure_applies_nothing(db: Database) {
+    let migrations = Migrations::new();
+    migrations.write("0001_users.sql", &create_table("users"));
+    let backend = db.open().await;
+
+    let faulty = Faulty::new(&*backend, Fault::Fetch);
+    let err = run_migrations_in(&faulty, migrations.path())
+        .await
+        .expect_err("injected failure must surface");
+    assert!(
+        matches!(err, MigrationError::Adapter(_)),
+        "unexpected error: {err}"
+    );
+    assert_eq!(
+        states(&*backend, &migrations).await,
+        vec![("0001_users.sql".to_string(), MigrationState::Pending)]
+    );
+}
+
+/// Várias instâncias subindo juntas (ex.: réplicas do servidor) disputam o
+/// mesmo banco. Perdedoras podem falhar, mas nenhuma migração é aplicada
+/// duas vezes, e uma execução depois disso termina o que faltar.
+pub async fn concurrent_runs_apply_each_migration_once(db: Database) {
+    let migrations = Migrations::new();
+    for i in 1..=5 {
+        migrations.write(&format!("000{i}_t{i}.sql"), &create_table(&format!("t{i}")));
+    }
+    let barrier = Arc::new(Barrier::new(CONCURRENT_RUNNERS));
+    let mut runners = Vec::new();
+    for _ in 0..CONCURRENT_RUNNERS {
+        let backend = db.open().await;
+        let dir: PathBuf = migrations.path().to_owned();
+        let barrier = Arc::clone(&barrier);
+        runners.push(tokio::spawn(async move {
+            barrier.wait().await;
+            run_migrations_in(&*backend, &dir).await
+        }));
+    }
+
+    let mut executed = Vec::new();
+    for runner in runners {
+        // Erros de trava/conflito são esperados para quem perdeu a corrida.
+        if let Ok(applied) = runner.await.expect("runner panicked") {
+            executed.extend(applied);
+        }
+    }
+    let backend = db.open().await;
+    executed.extend(
+        run_migrations_in(&*backend, migrations.path())
+            .await
+            .expect("run after the race"),
+    );
+
+    let unique: BTreeSet<_> = executed.iter().cloned().collect();
+    assert_eq!(
+        unique.len(),
+        executed.len(),
+        "a migration was applied twice: {executed:?}"
+    );
+    assert_eq!(unique.len(), 5, "missing migrations: {executed:?}");
+    let applied = backend
+        .fetch_applied_migrations()
+        .await
+        .expect("fetch applied");
+    assert_eq!(applied.len(), 5);
+    assert!(
+        states(&*backend, &migrations)
+            .await
+            .iter()
+            .all(|(_, state)| *state == MigrationState::Applied)
+    );
+}
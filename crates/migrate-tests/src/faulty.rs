@@ -0,0 +1,59 @@
+//! Injeção de falhas: um [`MigrationBackend`] que repassa tudo para o
+//! adaptador real, menos a operação escolhida, que falha como se a conexão
+//! tivesse caído.
+
+use async_trait::async_trait;
+use migrate_core::{AdapterError, AppliedMigration, MigrationBackend};
+
+/// Onde o [`Faulty`] falha.
+#[derive(Debug, Clone, Copy)]
+pub enum Fault {
+    /// Na leitura da `__migrations`.
+    Fetch,
+    /// Antes de aplicar a migração com este nome.
+    Apply(&'static str),
+}
+
+pub struct Faulty<'a> {
+    inner: &'a dyn MigrationBackend,
+    fault: Fault,
+}
+
+impl<'a> Faulty<'a> {
+    pub fn new(inner: &'a dyn MigrationBackend, fault: Fault) -> Self {
+        Self { inner, fault }
+    }
+}
+
+fn injected() -> AdapterError {
+    AdapterError::new(std::io::Error::new(
+        std::io::ErrorKind::ConnectionReset,
+        "injected failure",
+    ))
+}
+
+#[async_trait]
+impl MigrationBackend for Faulty<'_> {
+    async fn ensure_migrations_table(&self, bootstrap_sql: &str) -> Result<(), AdapterError> {
+        self.inner.ensure_migrations_table(bootstrap_sql).await
+    }
+
+    async fn fetch_applied_migrations(&self) -> Result<Vec<AppliedMigration>, AdapterError> {
+        match self.fault {
+            Fault::Fetch => Err(injected()),
+            _ => self.inner.fetch_applied_migrations().await,
+        }
+    }
+
+    async fn apply_migration(
+        &self,
+        name: &str,
+        sql: &str,
+        checksum: &str,
+    ) -> Result<(), AdapterError> {
+        match self.fault {
+            Fault::Apply(failing) if failing == name => Err(injected()),
+            _ => self.inner.apply_migration(name, sql, checksum).await,
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Suíte de comportamento compartilhada por todas as implementações de
+//! [`MigrationBackend`].
+//!
+//! Os cenários em [`cases`] só falam com o banco pelo
+//! [`registry`](migrate_core::registry): cada adaptador entra com uma
+//! função que devolve um [`Database`] vazio (a URL e o que precisa ficar
+//! vivo enquanto o teste roda, como um container), e a macro
+//! [`backend_suite!`] gera um `#[tokio::test]` por cenário. Assim um banco
+//! novo passa pela mesma bateria sem copiar teste nenhum:
+//!
+//! - `tests/libsql.rs` roda sempre, com um arquivo temporário;
+//! - `tests/containers.rs` roda o `migrate-postgres` e o `migrate-mysql`
+//!   contra containers do testcontainers. Fica atrás de `#[ignore]` porque
+//!   precisa de Docker (`cargo test -p migrate-tests -- --ignored`), e falha
+//!   se o Docker não estiver disponível.
+
+pub mod cases;
+mod faulty;
+mod migrations;
+
+use std::any::Any;
+
+pub use faulty::{Fault, Faulty};
+pub use migrations::Migrations;
+
+use migrate_core::MigrationBackend;
+
+/// Um banco vazio, só de um cenário.
+pub struct Database {
+    /// A URL passada para [`open_backend`](migrate_core::registry::open_backend).
+    pub url: String,
+    /// Mantém o banco de pé até o fim do cenário (diretório temporário,
+    /// container, …).
+    _guard: Box<dyn Any + Send>,
+}
+
+impl Database {
+    pub fn new(url: impl Into<String>, guard: impl Any + Send) -> Self {
+        Self {
+            url: url.into(),
+            _guard: Box::new(guard),
+        }
+    }
+
+    /// Uma conexão nova com o banco; cada chamada abre outra, como
+    /// processos diferentes fariam.
+    pub async fn open(&self) -> Box<dyn MigrationBackend> {
+        migrate_core::registry::open_backend(&self.url)
+            .await
+            .unwrap_or_else(|err| panic!("failed to open {}: {err}", self.url))
+    }
+}
+
+/// Gera um `#[tokio::test]` para cada cenário de [`cases`]. `$fresh` é uma
+/// função `async fn() -> Database` que também registra o adaptador.
+/// Atributos extras, como `#[ignore = "..."]`, vão para todos os testes
+/// gerados.
+#[macro_export]
+macro_rules! backend_suite {
+    ($fresh:path $(, #[$attr:meta])* $(,)?) => {
+        $crate::backend_suite!(
+            @cases $fresh, [$(#[$attr])*],
+            applies_pending_in_order,
+            second_run_is_a_no_op,
+            applies_only_new_files,
+            rejects_modified_migration,
+            reports_missing_migration,
+            failed_migration_is_rolled_back,
+            resumes_after_apply_failure,
+            fetch_failure_applies_nothing,
+            concurrent_runs_apply_each_migration_once,
+        );
+    };
+    // Um cenário por vez: os atributos não podem se repetir dentro da
+    // repetição dos cenários.
+    (@cases $fresh:path, [$($attrs:tt)*], $case:ident, $($rest:ident,)*) => {
+        #[tokio::test(flavor = "multi_thread")]
+        $($attrs)*
+        async fn $case() {
+            $crate::cases::$case($fresh().await).await;
+        }
+
+        $crate::backend_suite!(@cases $fresh, [$($attrs)*], $($rest,)*);
+    };
+    (@cases $fresh:path, [$($attrs:tt)*],) => {};
+}
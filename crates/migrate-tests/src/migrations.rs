@@ -0,0 +1,39 @@
+//! Uma pasta de migrações temporária por cenário.
+
+use std::fs;
+use std::path::Path;
+
+use tempfile::TempDir;
+
+/// Pasta temporária com arquivos `.sql`, apagada no `Drop`.
+pub struct Migrations {
+    dir: TempDir,
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Self {
+            dir: TempDir::new().expect("create migrations directory"),
+        }
+    }
+
+    /// Cria (ou reescreve) `name` com `sql`.
+    pub fn write(&self, name: &str, sql: &str) -> &Self {
+        fs::write(self.dir.path().join(name), sql).expect("write migration");
+        self
+    }
+
+    pub fn remove(&self, name: &str) {
+        fs::remove_file(self.dir.path().join(name)).expect("remove migration");
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+impl Default for Migrations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
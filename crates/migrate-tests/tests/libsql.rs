@@ -0,0 +1,13 @@
+//! A suíte contra o libSQL local, num arquivo temporário por cenário.
+
+use migrate_tests::{Database, backend_suite};
+use tempfile::TempDir;
+
+async fn libsql() -> Database {
+    migrate_libsql::register();
+    let dir = TempDir::new().expect("create database directory");
+    let url = format!("file://{}", dir.path().join("migrations.db").display());
+    Database::new(url, dir)
+}
+
+backend_suite!(libsql);
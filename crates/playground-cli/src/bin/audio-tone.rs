@@ -0,0 +1,88 @@
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use audio_capture::tone::{self, Options, Wave};
+use playground_cli::config::Config;
+use playground_common::error::{self, PlaygroundError};
+use playground_common::telemetry;
+
+//  cargo run --bin audio-tone -- --wave sine --freq 1000 --level -12 5
+//  cargo run --bin audio-tone -- --wave noise --device BlackHole 10
+//  cargo run --bin audio-tone -- --list-devices
+//  cargo run --bin audio-tone -- --latency --device BlackHole --input BlackHole
+
+/// Lê as opções da linha de comando.
+fn parse_args() -> Result<Options> {
+    let mut parsed = Options {
+        secs: 5,
+        wave: Wave::Sine,
+        freq: 440.0,
+        level_db: -12.0,
+        device: None,
+        list_devices: false,
+        latency: false,
+        input: None,
+        runs: 5,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--wave" => {
+                parsed.wave = match args.next().as_deref() {
+                    Some("sine") => Wave::Sine,
+                    Some("noise") => Wave::Noise,
+                    _ => anyhow::bail!("--wave aceita `sine` ou `noise`"),
+                }
+            }
+            "--freq" => {
+                parsed.freq = args
+                    .next()
+                    .context("--freq precisa de um valor em Hz")?
+                    .parse()
+                    .context("--freq inválido")?;
+            }
+            "--level" => {
+                parsed.level_db = args
+                    .next()
+                    .context("--level precisa de um valor em dBFS")?
+                    .parse()
+                    .context("--level inválido")?;
+            }
+            "--device" => {
+                parsed.device = Some(args.next().context("--device precisa de um nome")?);
+            }
+            "--list-devices" => parsed.list_devices = true,
+            "--latency" => parsed.latency = true,
+            "--input" => {
+                parsed.input = Some(args.next().context("--input precisa de um nome")?);
+            }
+            "--runs" => {
+                parsed.runs = args
+                    .next()
+                    .context("--runs precisa de um número")?
+                    .parse()
+                    .context("--runs inválido")?;
+            }
+            other => parsed.secs = other.parse().unwrap_or(5),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn main() -> ExitCode {
+    error::report(run())
+}
+
+fn run() -> Result<(), PlaygroundError> {
+    let log = Config::load()?.log;
+    let options = parse_args().map_err(PlaygroundError::usage)?;
+    let _telemetry = telemetry::init(
+        "audio-tone",
+        log.format,
+        log.level_or(audio_capture::DEFAULT_LOG_FILTER),
+    )?;
+    tone::run(&options)?;
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! Binário que cria o primeiro administrador num banco libSQL local.
+//!
+//! Uso: `create-admin --email admin@exemplo.com [--name Admin] [--db
+//! <caminho>]`; sem `--db`, o banco é o `migrate.db_path` da configuração.
+//! A senha vem
+//! de `ADMIN_PASSWORD` ou, se a variável não existir, da primeira linha da
+//! entrada padrão (assim ela não aparece no histórico do shell nem em `ps`).
+//! O hash usa os mesmos parâmetros argon2id do servidor (`ARGON2_*`).
+//! Se a entrada padrão fechar sem senha, sai com o código de cancelado
+//! (`130`, ver [`playground_common::error`]).
+
+use std::env;
+use std::io::BufRead;
+use std::process::ExitCode;
+
+use anyhow::Context;
+use migrate_libsql::migrate_local;
+use playground_cli::config::Config;
+use playground_common::error::{self, PlaygroundError};
+use playground_common::telemetry;
+use playground_server::credentials::{Credentials, create_initial_admin};
+
+/// Argumentos de linha de comando.
+struct Args {
+    name: String,
+    email: String,
+    db_path: Option<String>,
+}
+
+impl Args {
+    fn parse() -> anyhow::Result<Self> {
+        let mut name = None;
+        let mut email = None;
+        let mut db_path = None;
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--name" => name = Some(args.next().context("--name precisa de um valor")?),
+                "--email" => email = Some(args.next().context("--email precisa de um valor")?),
+                "--db" => db_path = Some(args.next().context("--db precisa de um caminho")?),
+                other => anyhow::bail!("argumento desconhecido: {other}"),
+            }
+        }
+        Ok(Self {
+            name: name.unwrap_or_else(|| "Admin".to_string()),
+            email: email
+                .context("uso: create-admin --email <email> [--name <nome>] [--db <caminho>]")?,
+            db_path,
+        })
+    }
+}
+
+/// Lê a senha de `ADMIN_PASSWORD` ou da primeira linha da entrada padrão.
+fn read_password() -> Result<String, PlaygroundError> {
+    if let Ok(password) = env::var("ADMIN_PASSWORD") {
+        return Ok(password);
+    }
+    eprintln!("senha do administrador (uma linha na entrada padrão):");
+    let mut line = String::new();
+    let read = std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .context("falha ao ler a senha")?;
+    if read == 0 {
+        return Err(PlaygroundError::Cancelled);
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    error::report(run().await)
+}
+
+async fn run() -> Result<(), PlaygroundError> {
+    let args = Args::parse().map_err(PlaygroundError::usage)?;
+    // Parâmetros inválidos devem falhar antes de pedir a senha.
+    let config = Config::load()?;
+    let db_path = args.db_path.unwrap_or(config.migrate.db_path);
+    let _telemetry = telemetry::init(
+        "create-admin",
+        config.log.format,
+        config.log.level_or(migrate_core::DEFAULT_LOG_FILTER),
+    )?;
+    let credentials = Credentials::from_env()?;
+    let hash = credentials.hash(&read_password()?)?;
+
+    // A tabela `users` precisa existir; as migrações são idempotentes.
+    let (conn, _) = migrate_local(&db_path).await?;
+
+    let id = create_initial_admin(&conn, &args.name, &args.email, &hash).await?;
+    println!("administrador {} criado com id {id}", args.email);
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! Binário que usa a biblioteca de migrações para atualizar o banco da
+//! configuração.
+//!
+//! Todo o fluxo (abrir o banco de `migrate.backend` ou, sem ela, o libSQL
+//! local de `migrate.db_path`, listar os arquivos, conferir checksums e
+//! aplicar o que falta) fica em [`migrate_core`] e nos adaptadores
+//! registrados no [`registry`] (o libSQL sempre; Postgres e MySQL com as
+//! features `postgres` e `mysql`); aqui só ligamos as peças. Aceita
+//! `--db <caminho>` e `--backend <url>` para sobrescrever a configuração. Os
+//! códigos de saída seguem [`playground_common::error`]: `2` para
+//! argumentos ou configuração inválidos (inclusive um esquema sem
+//! adaptador) e `4` quando o banco não abre.
+
+use std::process::ExitCode;
+
+use migrate_core::{registry, run_migrations};
+use playground_cli::config::Config;
+use playground_common::error::{self, PlaygroundError};
+use playground_common::telemetry;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    error::report(run().await)
+}
+
+async fn run() -> Result<(), PlaygroundError> {
+    let config = Config::load()?;
+    let mut db_path = config.migrate.db_path;
+    let mut backend = config.migrate.backend;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                db_path = args
+                    .next()
+                    .ok_or_else(|| PlaygroundError::usage("--db precisa de um caminho"))?;
+            }
+            "--backend" => {
+                backend = Some(
+                    args.next()
+                        .ok_or_else(|| PlaygroundError::usage("--backend precisa de uma URL"))?,
+                );
+            }
+            other => {
+                return Err(PlaygroundError::usage(format!(
+                    "argumento desconhecido: {other}"
+                )));
+            }
+        }
+    }
+    let _telemetry = telemetry::init(
+        "migrate-to-latest",
+        config.log.format,
+        config.log.level_or(migrate_core::DEFAULT_LOG_FILTER),
+    )?;
+    migrate_libsql::register();
+    #[cfg(feature = "postgres")]
+    migrate_postgres::register();
+    #[cfg(feature = "mysql")]
+    migrate_mysql::register();
+    let backend = registry::open_backend(backend.as_deref().unwrap_or(&db_path)).await?;
+    run_migrations(&*backend).await?;
+    Ok(())
+}
@@ -0,0 +1,76 @@
+//! Utilitários do projeto que não pertencem a um binário só.
+//!
+//! Uso: `playground config show [--file <caminho>]` imprime a configuração
+//! compartilhada já resolvida (padrões, arquivo e ambiente), com a origem
+//! de cada valor que não é o padrão. Sem `--file`, o arquivo é o de
+//! `PLAYGROUND_CONFIG` ou `playground.toml`, como nos outros binários.
+//!
+//! `playground daemon [--file <caminho>]` roda o servidor, as capturas
+//! periódicas e as gravações sob demanda juntos, supervisionados; ver
+//! [`playground_cli::daemon`].
+
+use std::{path::PathBuf, process::ExitCode, sync::Arc};
+
+use playground_cli::{config::Config, daemon};
+use playground_common::error::{self, PlaygroundError};
+use playground_common::telemetry;
+
+//  cargo run --bin playground -- config show
+//  PLAYGROUND_SERVER_PORT=8080 cargo run --bin playground -- config show --file deploy/playground.toml
+//  PLAYGROUND_DAEMON_SCREENSHOT_INTERVAL=5m cargo run --bin playground -- daemon
+//  kill -USR2 <pid>   (grava com a seção [recorder])
+
+const USAGE: &str =
+    "uso: playground config show [--file <caminho>]\n     playground daemon [--file <caminho>]";
+
+enum Command {
+    ConfigShow,
+    Daemon,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    error::report(run().await)
+}
+
+async fn run() -> Result<(), PlaygroundError> {
+    let mut args = std::env::args().skip(1);
+    let command = match args.next().as_deref() {
+        Some("config") if args.next().as_deref() == Some("show") => Command::ConfigShow,
+        Some("daemon") => Command::Daemon,
+        _ => return Err(PlaygroundError::usage(USAGE)),
+    };
+    let mut file: Option<PathBuf> = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| PlaygroundError::usage("--file precisa de um caminho"))?;
+                file = Some(path.into());
+            }
+            other => {
+                return Err(PlaygroundError::usage(format!(
+                    "argumento desconhecido: {other}\n{USAGE}"
+                )));
+            }
+        }
+    }
+
+    let config = match &file {
+        Some(path) => Config::load_file(path)?,
+        None => Config::load()?,
+    };
+    match command {
+        Command::ConfigShow => print!("{}", config.to_annotated_toml()),
+        Command::Daemon => {
+            let telemetry = telemetry::init(
+                "playground-daemon",
+                config.log.format,
+                config.log.level_or(daemon::DEFAULT_LOG_FILTER),
+            )?;
+            daemon::run(config, Arc::new(telemetry)).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,569 @@
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use playground_cli::config::{Config, ScreenshotsSection};
+use playground_common::error::{self, PlaygroundError};
+use playground_common::telemetry;
+use screen_capture::{
+    DisplaySelector, annotate, blank, compare, dedup,
+    error::{Result, usage},
+    hotkey,
+    output::{self, OutputFormat},
+    retention, serve,
+    session::{self, ClipboardMode, NameFields, Options, STREAM_INTERVAL, render_name},
+    timelapse, upload, watermark, webhook,
+};
+
+//  cargo run --bin screenshots
+//  cargo run --bin screenshots -- --out-dir capturas --format jpg
+//  cargo run --bin screenshots -- --png-compression best --optimize 4
+//  cargo run --bin screenshots -- --name "{display}-{width}x{height}-{timestamp}.{ext}"
+//  cargo run --bin screenshots -- --list-displays
+//  cargo run --bin screenshots -- --primary
+//  cargo run --bin screenshots -- --delay 5s --window firefox
+//  cargo run --bin screenshots -- --redact 0,0,400x60 --redact 1500,900,420x180 --redact-mode blur
+//  cargo run --bin screenshots -- --stitch --name "desktop-{timestamp}.{ext}"
+//  cargo run --bin screenshots -- --logical --sidecar --name "{display}-{logical_width}x{logical_height}@{scale}x.{ext}"
+//  cargo run --bin screenshots -- --window firefox --cursor
+//  cargo run --bin screenshots -- --region 1800,0,1280x720
+//  cargo run --bin screenshots -- --display primary --clipboard-only
+//  cargo run --bin screenshots -- --display 0 --count 120 --interval 30s --timelapse .tmp/dia.mp4 --fps 12
+//  cargo run --bin screenshots -- --count 480 --interval 15s --min-change 0.5
+//  cargo run --bin screenshots -- --daemon --dedup link --dedup-distance 2
+//  cargo run --bin screenshots -- --on-blank retry
+//  SCREENSHOTS_WEBHOOK_SECRET=... cargo run --bin screenshots -- --daemon --hotkey ctrl+shift+s --webhook http://localhost:9000/hooks
+//  cargo run --bin screenshots -- --count 720 --interval 5s --serve :8080
+//  cargo run --bin screenshots -- --stream :8081 --interval 250ms
+//  cargo run --bin screenshots -- --daemon --keep-last 200 --keep-days 7
+//  cargo run --bin screenshots -- --watch --stitch --sidecar
+//  AWS_ACCESS_KEY_ID=... AWS_SECRET_ACCESS_KEY=... cargo run --bin screenshots -- --daemon --upload s3://capturas/estacao-3 --sidecar
+//  cargo run --bin screenshots -- compare .tmp/antes .tmp/depois --out .tmp/relatorio --threshold 0.1
+//  cargo run --bin screenshots -- --watermark --watermark-position top-left --watermark-size 24
+//  cargo run --bin screenshots -- --annotate "rect:100,100,300,200,red" --annotate "text:20,20,'bug aqui'"
+
+/// Lê as opções da linha de comando por cima da seção `[screenshots]` da
+/// configuração e confere as combinações inválidas.
+fn parse_args(config: ScreenshotsSection) -> Result<Options> {
+    let mut out_dir = config.out_dir;
+    let mut format = OutputFormat::parse(&config.format)?;
+    let mut png_compression = None;
+    let mut optimize = None;
+    let mut name = config.name;
+    let mut display = None;
+    let mut window = None;
+    let mut region = None;
+    let mut list_displays = false;
+    let mut stitch = false;
+    let mut logical = config.logical;
+    let mut sidecar = config.sidecar;
+    let mut upload_target = config.upload;
+    let mut dedup_mode = None;
+    let mut dedup_distance = None;
+    let mut count = 1;
+    let mut interval = config.interval;
+    let mut delay = None;
+    let mut min_change = None;
+    let mut timelapse: Option<PathBuf> = None;
+    let mut fps = 10;
+    let mut clipboard = ClipboardMode::Off;
+    let mut cursor = config.cursor;
+    let mut on_blank: blank::OnBlank = config.on_blank.parse().map_err(usage)?;
+    let mut annotations = Vec::new();
+    let mut redact = Vec::new();
+    let mut redact_mode = None;
+    let mut watermark = false;
+    let mut watermark_position = None;
+    let mut watermark_font: Option<PathBuf> = None;
+    let mut watermark_size = None;
+    let mut daemon = false;
+    let mut watch = false;
+    let mut hotkey = None;
+    let mut webhook_url = config.webhook;
+    let mut serve = None;
+    let mut stream = None;
+    let mut retention = retention::Retention {
+        keep_last: config.keep_last,
+        max_age: config
+            .keep_days
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out-dir" => {
+                out_dir = args
+                    .next()
+                    .ok_or_else(|| usage("--out-dir precisa de um caminho"))?
+                    .into()
+            }
+            "--format" => {
+                format = OutputFormat::parse(
+                    &args
+                        .next()
+                        .ok_or_else(|| usage("--format precisa de um valor"))?,
+                )?
+            }
+            "--png-compression" => {
+                png_compression =
+                    Some(output::parse_compression(&args.next().ok_or_else(
+                        || usage("--png-compression precisa de um nível"),
+                    )?)?);
+            }
+            "--optimize" => {
+                optimize = Some(
+                    args.next()
+                        .ok_or_else(|| usage("--optimize precisa de um nível de 0 a 6"))?
+                        .parse()
+                        .ok()
+                        .filter(|level| *level <= 6)
+                        .ok_or_else(|| usage("--optimize inválido (use de 0 a 6)"))?,
+                );
+            }
+            "--name" => {
+                name = args
+                    .next()
+                    .ok_or_else(|| usage("--name precisa de um modelo"))?
+            }
+            "--display" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage("--display precisa de um id, índice ou `primary`"))?;
+                display = Some(value.parse().map_err(usage)?);
+            }
+            // Atalho para `--display primary`, que já pula os outros
+            // monitores.
+            "--primary" => display = Some(DisplaySelector::Primary),
+            "--window" => {
+                window =
+                    Some(args.next().ok_or_else(|| {
+                        usage("--window precisa de parte do título ou do processo")
+                    })?);
+            }
+            "--region" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage("--region precisa de uma área x,y,LARGURAxALTURA"))?;
+                region = Some(value.parse().map_err(usage)?);
+            }
+            "--list-displays" => list_displays = true,
+            "--stitch" => stitch = true,
+            "--logical" => logical = true,
+            "--sidecar" => sidecar = true,
+            "--dedup" => {
+                dedup_mode = Some(
+                    args.next()
+                        .ok_or_else(|| usage("--dedup precisa de skip ou link"))?
+                        .parse::<dedup::DedupMode>()
+                        .map_err(usage)?,
+                );
+            }
+            "--dedup-distance" => {
+                dedup_distance = Some(
+                    args.next()
+                        .ok_or_else(|| usage("--dedup-distance precisa de um número de bits"))?
+                        .parse()
+                        .ok()
+                        .filter(|bits| *bits <= 64)
+                        .ok_or_else(|| usage("--dedup-distance inválido (use de 0 a 64)"))?,
+                );
+            }
+            "--upload" => {
+                upload_target = Some(args.next().ok_or_else(|| {
+                    usage("--upload precisa de uma URL (ex.: s3://bucket/prefixo)")
+                })?);
+            }
+            "--clipboard" => clipboard = ClipboardMode::Also,
+            "--clipboard-only" => clipboard = ClipboardMode::Only,
+            "--cursor" => cursor = true,
+            "--on-blank" => {
+                on_blank = args
+                    .next()
+                    .ok_or_else(|| usage("--on-blank precisa de warn, retry ou skip"))?
+                    .parse()
+                    .map_err(usage)?;
+            }
+            "--annotate" => {
+                annotations.push(
+                    args.next()
+                        .ok_or_else(|| usage("--annotate precisa de uma anotação"))?
+                        .parse::<annotate::Annotation>()
+                        .map_err(usage)?,
+                );
+            }
+            "--redact" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage("--redact precisa de uma área x,y,LARGURAxALTURA"))?;
+                redact.push(value.parse().map_err(usage)?);
+            }
+            "--redact-mode" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage("--redact-mode precisa de black ou blur"))?;
+                redact_mode = Some(value.parse().map_err(usage)?);
+            }
+            "--watermark" => watermark = true,
+            "--watermark-position" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage("--watermark-position precisa de um canto"))?;
+                watermark_position = Some(value.parse().map_err(usage)?);
+            }
+            "--watermark-font" => {
+                watermark_font = Some(
+                    args.next()
+                        .ok_or_else(|| usage("--watermark-font precisa de um arquivo .ttf"))?
+                        .into(),
+                );
+            }
+            "--watermark-size" => {
+                watermark_size = Some(
+                    args.next()
+                        .ok_or_else(|| usage("--watermark-size precisa de um número"))?
+                        .parse()
+                        .ok()
+                        .filter(|size| (8..=200).contains(size))
+                        .ok_or_else(|| usage("--watermark-size inválido (use de 8 a 200)"))?,
+                );
+            }
+            "--daemon" => daemon = true,
+            "--watch" => watch = true,
+            "--hotkey" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage("--hotkey precisa de um atalho, como ctrl+shift+s"))?;
+                hotkey = Some(value.parse().map_err(usage)?);
+            }
+            "--webhook" => {
+                webhook_url = Some(
+                    args.next()
+                        .ok_or_else(|| usage("--webhook precisa de uma URL"))?,
+                );
+            }
+            "--serve" => {
+                serve = Some(serve::parse_addr(&args.next().ok_or_else(|| {
+                    usage("--serve precisa de um endereço, como :8080")
+                })?)?);
+            }
+            "--stream" => {
+                stream = Some(serve::parse_addr(&args.next().ok_or_else(|| {
+                    usage("--stream precisa de um endereço, como :8081")
+                })?)?);
+            }
+            "--keep-last" => {
+                retention.keep_last = Some(
+                    args.next()
+                        .ok_or_else(|| usage("--keep-last precisa de um número"))?
+                        .parse()
+                        .ok()
+                        .filter(|keep| *keep > 0)
+                        .ok_or_else(|| usage("--keep-last inválido (use um inteiro positivo)"))?,
+                );
+            }
+            "--keep-days" => {
+                let secs = args
+                    .next()
+                    .ok_or_else(|| usage("--keep-days precisa de um número"))?
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|days| *days > 0)
+                    .and_then(|days| days.checked_mul(24 * 60 * 60))
+                    .ok_or_else(|| usage("--keep-days inválido (use um inteiro positivo)"))?;
+                retention.max_age = Some(Duration::from_secs(secs));
+            }
+            "--count" => {
+                count = args
+                    .next()
+                    .ok_or_else(|| usage("--count precisa de um número"))?
+                    .parse()
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or_else(|| usage("--count inválido (use um inteiro positivo)"))?;
+            }
+            "--interval" => {
+                interval = Some(parse_duration(
+                    &args
+                        .next()
+                        .ok_or_else(|| usage("--interval precisa de um valor"))?,
+                )?);
+            }
+            "--delay" => {
+                delay = Some(parse_duration(
+                    &args
+                        .next()
+                        .ok_or_else(|| usage("--delay precisa de um valor"))?,
+                )?)
+                .filter(|delay| !delay.is_zero());
+            }
+            "--min-change" => {
+                min_change = Some(
+                    args.next()
+                        .ok_or_else(|| usage("--min-change precisa de uma porcentagem"))?
+                        .trim_end_matches('%')
+                        .parse()
+                        .ok()
+                        .filter(|percent| (0.0..=100.0).contains(percent))
+                        .ok_or_else(|| usage("--min-change inválido (use de 0 a 100)"))?,
+                );
+            }
+            "--timelapse" => {
+                timelapse = Some(
+                    args.next()
+                        .ok_or_else(|| usage("--timelapse precisa de um arquivo .mp4 ou .gif"))?
+                        .into(),
+                );
+            }
+            "--fps" => {
+                fps = args
+                    .next()
+                    .ok_or_else(|| usage("--fps precisa de um número"))?
+                    .parse()
+                    .ok()
+                    .filter(|fps| (1..=60).contains(fps))
+                    .ok_or_else(|| usage("--fps inválido (use de 1 a 60)"))?;
+            }
+            other => return Err(usage(format!("Argumento desconhecido: {other}"))),
+        }
+    }
+    match &mut format {
+        OutputFormat::Png(png) => {
+            png.compression = png_compression.unwrap_or(png.compression);
+            png.optimize = optimize;
+        }
+        _ if png_compression.is_some() || optimize.is_some() => {
+            return Err(usage(
+                "--png-compression e --optimize só valem para --format png",
+            ));
+        }
+        _ => {}
+    }
+    // Falha antes de capturar se o modelo tiver um campo inválido.
+    render_name(&name, &NameFields::default(), format)?;
+    retention.check_template(&name)?;
+    if daemon && (count > 1 || timelapse.is_some()) {
+        return Err(usage(
+            "--daemon captura até ser interrompido; não use com --count nem --timelapse",
+        ));
+    }
+    if stream.is_some() && (count > 1 || daemon || timelapse.is_some()) {
+        return Err(usage(
+            "--stream captura até ser interrompido; não use com --count, --daemon nem --timelapse",
+        ));
+    }
+    if watch && (count > 1 || daemon || stream.is_some() || timelapse.is_some()) {
+        return Err(usage(
+            "--watch captura até ser interrompido; não use com --count, --daemon, --stream nem --timelapse",
+        ));
+    }
+    if retention.is_enabled() && (clipboard == ClipboardMode::Only || stream.is_some()) {
+        return Err(usage(
+            "--keep-last e --keep-days limpam os arquivos gravados; não use com --clipboard-only nem --stream",
+        ));
+    }
+    if sidecar && (clipboard == ClipboardMode::Only || stream.is_some()) {
+        return Err(usage(
+            "--sidecar acompanha os arquivos gravados; não use com --clipboard-only nem --stream",
+        ));
+    }
+    if dedup_mode.is_some() && (clipboard == ClipboardMode::Only || stream.is_some()) {
+        return Err(usage(
+            "--dedup compara com os arquivos gravados; não use com --clipboard-only nem --stream",
+        ));
+    }
+    if dedup_distance.is_some() && dedup_mode.is_none() {
+        return Err(usage("--dedup-distance precisa de --dedup"));
+    }
+    if upload_target.is_some() && (clipboard == ClipboardMode::Only || stream.is_some()) {
+        return Err(usage(
+            "--upload envia os arquivos gravados; não use com --clipboard-only nem --stream",
+        ));
+    }
+    let interval = interval.unwrap_or(if stream.is_some() {
+        STREAM_INTERVAL
+    } else {
+        Duration::from_secs(5)
+    });
+    if serve.is_some() && count == 1 && !daemon && !watch && stream.is_none() {
+        return Err(usage(
+            "--serve só faz sentido com --count, --daemon, --watch ou --stream; sem eles o programa sai logo após a captura",
+        ));
+    }
+    if hotkey.is_some() && !daemon {
+        return Err(usage("--hotkey só vale com --daemon"));
+    }
+    if (count > 1 || daemon || watch) && !name.contains("{shot}") && !name.contains("{timestamp}") {
+        return Err(usage(
+            "Com --count, --daemon ou --watch o modelo de nome precisa de {shot} ou {timestamp}",
+        ));
+    }
+    if display.is_some() && window.is_some() {
+        return Err(usage("Use --display ou --window, não os dois"));
+    }
+    if region.is_some() && (display.is_some() || window.is_some()) {
+        return Err(usage(
+            "Use --region, --display (ou --primary) ou --window, só um deles",
+        ));
+    }
+    if stitch && (display.is_some() || window.is_some() || region.is_some()) {
+        return Err(usage(
+            "--stitch junta todos os monitores; não use com --display, --primary, --window nem --region",
+        ));
+    }
+    if redact_mode.is_some() && redact.is_empty() {
+        return Err(usage("--redact-mode precisa de pelo menos um --redact"));
+    }
+    let watermark_options =
+        watermark_position.is_some() || watermark_font.is_some() || watermark_size.is_some();
+    if watermark_options && !watermark {
+        return Err(usage(
+            "--watermark-position, --watermark-font e --watermark-size precisam de --watermark",
+        ));
+    }
+    let watermark = watermark
+        .then(|| {
+            watermark::Watermark::new(
+                watermark_font.as_deref(),
+                watermark_size.unwrap_or(watermark::DEFAULT_SIZE),
+                watermark_position.unwrap_or_default(),
+            )
+        })
+        .transpose()?;
+    if let Some(path) = &timelapse {
+        timelapse::Container::from_path(path)?;
+        if clipboard == ClipboardMode::Only {
+            return Err(usage(
+                "--timelapse precisa dos arquivos; use --clipboard em vez de --clipboard-only",
+            ));
+        }
+    }
+
+    let daemon = daemon.then(|| {
+        hotkey.unwrap_or_else(|| {
+            hotkey::DEFAULT_HOTKEY
+                .parse()
+                .expect("DEFAULT_HOTKEY is a valid hotkey")
+        })
+    });
+    let dedup = dedup_mode
+        .map(|mode| {
+            dedup::Dedup::open(
+                &out_dir,
+                mode,
+                dedup_distance.unwrap_or(dedup::DEFAULT_DISTANCE),
+            )
+        })
+        .transpose()?;
+    let upload = upload_target
+        .as_deref()
+        .map(upload::create_uploader)
+        .transpose()?;
+    let webhook = webhook_url
+        .as_deref()
+        .map(webhook::Webhook::new)
+        .transpose()?;
+
+    Ok(Options {
+        out_dir,
+        format,
+        name,
+        display,
+        window,
+        region,
+        stitch,
+        logical,
+        sidecar,
+        upload,
+        dedup,
+        list_displays,
+        count,
+        interval,
+        delay,
+        min_change,
+        timelapse,
+        fps,
+        clipboard,
+        on_blank,
+        cursor,
+        annotate: (!annotations.is_empty()).then(|| annotate::Annotator::new(annotations)),
+        redact,
+        redact_mode: redact_mode.unwrap_or_default(),
+        watermark,
+        daemon,
+        watch,
+        webhook,
+        serve,
+        stream,
+        retention,
+    })
+}
+
+/// Aceita um número puro de segundos (`90`) ou o formato do `humantime`
+/// (`90s`, `1m30s`, `2h`).
+fn parse_duration(value: &str) -> Result<Duration> {
+    if let Ok(secs) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    humantime::parse_duration(value).map_err(|_| {
+        usage(format!(
+            "Duração inválida: {value} (use 90, 90s, 1m30s, 2h...)"
+        ))
+    })
+}
+/// Lê as opções de `compare`, com os argumentos depois do nome do
+/// subcomando.
+fn compare_args(mut args: impl Iterator<Item = String>) -> Result<compare::Options> {
+    let mut dirs = Vec::new();
+    let mut out = PathBuf::from(".tmp/compare");
+    let mut threshold = 0.0;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => {
+                out = args
+                    .next()
+                    .ok_or_else(|| usage("--out precisa de um diretório"))?
+                    .into();
+            }
+            "--threshold" => {
+                threshold = args
+                    .next()
+                    .ok_or_else(|| usage("--threshold precisa de uma porcentagem"))?
+                    .trim_end_matches('%')
+                    .parse()
+                    .ok()
+                    .filter(|percent| (0.0..=100.0).contains(percent))
+                    .ok_or_else(|| usage("--threshold inválido (use de 0 a 100)"))?;
+            }
+            other if other.starts_with("--") => {
+                return Err(usage(format!("Argumento desconhecido: {other}")));
+            }
+            dir => dirs.push(PathBuf::from(dir)),
+        }
+    }
+    let [dir_a, dir_b] = <[PathBuf; 2]>::try_from(dirs)
+        .map_err(|_| usage("Uso: compare <dir-a> <dir-b> [--out <dir>] [--threshold <%>]"))?;
+    Ok(compare::Options {
+        dir_a,
+        dir_b,
+        out,
+        threshold,
+    })
+}
+
+fn main() -> ExitCode {
+    error::report(run())
+}
+
+fn run() -> Result<(), PlaygroundError> {
+    let config = Config::load()?;
+    let _telemetry = telemetry::init(
+        "screenshots",
+        config.log.format,
+        config.log.level_or(screen_capture::DEFAULT_LOG_FILTER),
+    )?;
+    if std::env::args().nth(1).as_deref() == Some("compare") {
+        compare::run(&compare_args(std::env::args().skip(2))?)?;
+    } else {
+        session::run(&parse_args(config.screenshots)?)?;
+    }
+    Ok(())
+}
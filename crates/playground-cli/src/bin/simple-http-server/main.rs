@@ -0,0 +1,31 @@
+//! `simple-http-server`: applies the flags on top of the shared
+//! configuration ([`Config`]: defaults, `playground.toml`, environment) and
+//! hands the result to [`playground_server::run`]. Exit codes follow
+//! [`playground_common::error`]: `2` for bad flags or configuration, `4` when the
+//! port or the database is unavailable.
+
+use std::{env, process::ExitCode};
+
+use playground_cli::{config::Config, server};
+use playground_common::{
+    error::{self, PlaygroundError},
+    telemetry,
+};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    error::report(run().await)
+}
+
+async fn run() -> Result<(), PlaygroundError> {
+    let config = Config::load()?;
+    let log = config.log.clone();
+    let options = server::options(config, env::args().skip(1)).map_err(PlaygroundError::usage)?;
+    let telemetry = telemetry::init(
+        "simple-http-server",
+        log.format,
+        log.level_or(playground_server::DEFAULT_LOG_FILTER),
+    )?;
+    playground_server::run(options, &telemetry).await?;
+    Ok(())
+}
@@ -0,0 +1,486 @@
+//! Configuração em camadas compartilhada pelos binários (servidor,
+//! `migrate-to-latest`, `create-admin`, gravador e `screenshots`), mais a
+//! seção `[log]` que todos eles passam para
+//! [`telemetry::init`](playground_common::telemetry::init).
+//!
+//! Cada valor vem da última camada que o define, nesta ordem:
+//!
+//! 1. os padrões de cada seção;
+//! 2. o arquivo TOML de `PLAYGROUND_CONFIG` ou, sem ela, `playground.toml`
+//!    no diretório atual, se existir;
+//! 3. variáveis de ambiente `PLAYGROUND_<SEÇÃO>_<CHAVE>` (ex.:
+//!    `PLAYGROUND_SERVER_PORT=8080`), com as variáveis antigas de cada
+//!    binário (`PORT`, `LIBSQL_DB_PATH`, …) valendo como apelidos;
+//! 4. as flags do binário, aplicadas por ele sobre a sua seção.
+//!
+//! O valor de uma variável é lido como TOML quando for um valor válido
+//! (número, booleano, `["lista"]`) e como texto caso contrário; aspas
+//! forçam texto (`PLAYGROUND_RECORDER_OUT='"123"'`).
+//!
+//! `playground config show` imprime o resultado das três primeiras camadas,
+//! com a origem de cada valor que não é o padrão.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use playground_common::telemetry::LogFormat;
+use screen_capture::session::DEFAULT_NAME;
+
+pub use playground_common::error::ConfigError;
+
+/// Variável com o caminho do arquivo de configuração.
+pub const CONFIG_ENV: &str = "PLAYGROUND_CONFIG";
+/// Arquivo lido quando [`CONFIG_ENV`] não existe (e só se existir).
+pub const DEFAULT_CONFIG_FILE: &str = "playground.toml";
+/// Prefixo das variáveis de ambiente de cada chave.
+const ENV_PREFIX: &str = "PLAYGROUND_";
+
+/// Variáveis que os binários já liam antes desta configuração existir, e a
+/// chave que cada uma define. Aplicadas antes das `PLAYGROUND_*`, que
+/// vencem quando as duas existem.
+const LEGACY_ENV: [(&str, &str, &str); 12] = [
+    ("PORT", "server", "port"),
+    ("TLS_CERT", "server", "tls_cert"),
+    ("TLS_KEY", "server", "tls_key"),
+    ("HTTP_REDIRECT_PORT", "server", "redirect_port"),
+    ("RATE_LIMIT_RPS", "server", "rate_limit"),
+    ("RATE_LIMIT_BURST", "server", "rate_burst"),
+    ("BODY_LIMIT_BYTES", "server", "body_limit"),
+    ("REQUEST_TIMEOUT", "server", "request_timeout"),
+    ("SERVER_CONFIG", "server", "config_file"),
+    ("LOG_BODIES_KB", "server", "log_bodies_kb"),
+    (migrate_libsql::DB_PATH_ENV, "migrate", "db_path"),
+    ("LOG_FORMAT", "log", "format"),
+];
+
+/// Seções conhecidas, para separar `PLAYGROUND_<SEÇÃO>_<CHAVE>`.
+const SECTIONS: [&str; 6] = [
+    "server",
+    "migrate",
+    "recorder",
+    "screenshots",
+    "daemon",
+    "log",
+];
+
+/// De onde veio um valor que não é o padrão.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    File(PathBuf),
+    Env(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Env(name) => write!(f, "${name}"),
+        }
+    }
+}
+
+/// A configuração resolvida de todos os binários.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerSection,
+    pub migrate: MigrateSection,
+    pub recorder: RecorderSection,
+    pub screenshots: ScreenshotsSection,
+    pub daemon: DaemonSection,
+    pub log: LogSection,
+    /// `seção.chave` → origem, só para o que não é padrão.
+    #[serde(skip)]
+    origins: BTreeMap<String, Source>,
+}
+
+/// `[server]`: o que o `simple-http-server` aceita também por flag.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSection {
+    /// IPs (combinados com `port`) ou `ip:porta` em que o servidor escuta.
+    pub addrs: Vec<String>,
+    pub port: u16,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// Porta HTTP que só redireciona para a HTTPS.
+    pub redirect_port: Option<u16>,
+    /// Requisições por segundo por cliente; `0` desliga o limite.
+    pub rate_limit: f64,
+    pub rate_burst: u32,
+    pub body_limit: usize,
+    #[serde(with = "duration")]
+    pub request_timeout: Duration,
+    /// O TOML recarregável do servidor (`[log]`, `timeouts`, …).
+    pub config_file: Option<PathBuf>,
+    /// Quantos KB de cada corpo registrar; `0` desliga.
+    pub log_bodies_kb: usize,
+}
+
+impl Default for ServerSection {
+    fn default() -> Self {
+        Self {
+            addrs: vec!["0.0.0.0".to_string()],
+            port: playground_server::DEFAULT_PORT,
+            tls_cert: None,
+            tls_key: None,
+            redirect_port: None,
+            rate_limit: playground_server::DEFAULT_RATE_LIMIT,
+            rate_burst: playground_server::DEFAULT_RATE_BURST,
+            body_limit: playground_server::DEFAULT_BODY_LIMIT,
+            request_timeout: playground_server::DEFAULT_REQUEST_TIMEOUT,
+            config_file: None,
+            log_bodies_kb: 0,
+        }
+    }
+}
+
+/// `[migrate]`: o banco usado pelas migrações, pelo `create-admin` e pelo
+/// servidor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MigrateSection {
+    pub db_path: String,
+    /// URL do banco do `migrate-to-latest` (`postgres://…`, `file://…`),
+    /// aberta pelo [`registry`](migrate_core::registry); sem ela, o libSQL
+    /// em `db_path`. O servidor e o `create-admin` usam sempre o libSQL.
+    pub backend: Option<String>,
+}
+
+impl Default for MigrateSection {
+    fn default() -> Self {
+        Self {
+            db_path: migrate_libsql::DEFAULT_DB_PATH.to_string(),
+            backend: None,
+        }
+    }
+}
+
+/// `[recorder]`: os padrões do gravador de áudio.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecorderSection {
+    #[serde(with = "duration")]
+    pub duration: Duration,
+    /// Nome do sink (`wav`, `flac`, `icecast`, `rtp`, `null` ou outro
+    /// registrado com `audio_capture::sink::register_sink`).
+    pub format: String,
+    /// Caminho ou URL de saída; sem ele, `.tmp/meu_audio.<formato>`.
+    pub out: Option<String>,
+    /// Só grava depois que o sinal passar de `threshold`.
+    pub arm: bool,
+    /// Limiar do modo `arm`, em dBFS.
+    pub threshold: f32,
+    /// Segundos de silêncio que encerram a gravação no modo `arm`.
+    pub silence: f32,
+}
+
+impl Default for RecorderSection {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(5),
+            format: "wav".to_string(),
+            out: None,
+            arm: false,
+            threshold: -40.0,
+            silence: 2.0,
+        }
+    }
+}
+
+/// `[screenshots]`: os padrões do binário `screenshots`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScreenshotsSection {
+    pub out_dir: PathBuf,
+    /// `png`, `jpg` ou `bmp`.
+    pub format: String,
+    /// Modelo do nome de cada arquivo.
+    pub name: String,
+    /// Espera entre capturas; sem ela, 5 s (ou 500 ms no modo stream).
+    #[serde(with = "optional_duration")]
+    pub interval: Option<Duration>,
+    pub sidecar: bool,
+    pub logical: bool,
+    pub cursor: bool,
+    /// `warn`, `retry` ou `skip`.
+    pub on_blank: String,
+    /// Destino `s3://bucket/prefixo` de cada arquivo gravado.
+    pub upload: Option<String>,
+    pub webhook: Option<String>,
+    pub keep_last: Option<usize>,
+    pub keep_days: Option<u64>,
+}
+
+impl Default for ScreenshotsSection {
+    fn default() -> Self {
+        Self {
+            out_dir: PathBuf::from(".tmp"),
+            format: "png".to_string(),
+            name: DEFAULT_NAME.to_string(),
+            interval: None,
+            sidecar: false,
+            logical: false,
+            cursor: false,
+            on_blank: "warn".to_string(),
+            upload: None,
+            webhook: None,
+            keep_last: None,
+            keep_days: None,
+        }
+    }
+}
+
+/// `[daemon]`: o que o `playground daemon` roda e como reinicia o que falha.
+/// O servidor usa `[server]`, as capturas `[screenshots]` e as gravações
+/// `[recorder]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonSection {
+    /// Roda o servidor HTTP.
+    pub server: bool,
+    /// Espera entre capturas de todos os monitores; sem ela, o daemon não
+    /// captura a tela.
+    #[serde(with = "optional_duration")]
+    pub screenshot_interval: Option<Duration>,
+    /// Onde ficam as gravações pedidas com `SIGUSR2`.
+    pub recordings_dir: PathBuf,
+    /// Falhas seguidas de um subsistema antes de o daemon desistir e sair.
+    pub max_restarts: u32,
+    /// Espera antes de reiniciar um subsistema; dobra a cada falha seguida.
+    #[serde(with = "duration")]
+    pub restart_backoff: Duration,
+}
+
+impl Default for DaemonSection {
+    fn default() -> Self {
+        Self {
+            server: true,
+            screenshot_interval: None,
+            recordings_dir: PathBuf::from(".tmp"),
+            max_restarts: 5,
+            restart_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// `[log]`: formato e nível dos logs de todos os binários.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSection {
+    pub format: LogFormat,
+    /// Diretivas do `EnvFilter` (ex.: `playground_server=debug`); sem elas, cada
+    /// binário usa o seu padrão. `RUST_LOG` vence as duas.
+    pub level: Option<String>,
+}
+
+impl LogSection {
+    /// [`LogSection::level`] ou o padrão do binário.
+    pub fn level_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.level.as_deref().unwrap_or(default)
+    }
+}
+
+impl Config {
+    /// Padrões, o arquivo de [`CONFIG_ENV`] (ou [`DEFAULT_CONFIG_FILE`], se
+    /// existir) e o ambiente.
+    pub fn load() -> Result<Self, ConfigError> {
+        let file = match std::env::var_os(CONFIG_ENV) {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.is_file()),
+        };
+        Self::layered(file.as_deref())
+    }
+
+    /// Como [`Config::load`], mas lendo `path` como arquivo.
+    pub fn load_file(path: &Path) -> Result<Self, ConfigError> {
+        Self::layered(Some(path))
+    }
+
+    fn layered(file: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut merged =
+            toml::Table::try_from(Self::default()).expect("defaults are always serializable");
+        let mut origins = BTreeMap::new();
+
+        if let Some(path) = file {
+            let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+            let table: toml::Table =
+                toml::from_str(&text).map_err(|source| ConfigError::Parse {
+                    path: path.to_owned(),
+                    source,
+                })?;
+            let origin = Source::File(path.to_owned());
+            for (section, value) in table {
+                match (value, merged.get_mut(&section)) {
+                    (toml::Value::Table(keys), Some(toml::Value::Table(base))) => {
+                        for (key, value) in keys {
+                            origins.insert(format!("{section}.{key}"), origin.clone());
+                            base.insert(key, value);
+                        }
+                    }
+                    // Seção desconhecida ou que não é tabela: o erro sai na
+                    // conversão final, com o nome dela.
+                    (value, _) => {
+                        merged.insert(section, value);
+                    }
+                }
+            }
+        }
+
+        let legacy = LEGACY_ENV
+            .iter()
+            .filter_map(|(name, section, key)| {
+                let value = std::env::var(name).ok()?;
+                Some((
+                    name.to_string(),
+                    section.to_string(),
+                    key.to_string(),
+                    value,
+                ))
+            })
+            .collect::<Vec<_>>();
+        let mut namespaced = Vec::new();
+        for (name, value) in std::env::vars() {
+            let Some(rest) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if name == CONFIG_ENV {
+                continue;
+            }
+            let (section, key) = SECTIONS
+                .iter()
+                .find_map(|section| {
+                    let key = rest
+                        .to_ascii_lowercase()
+                        .strip_prefix(&format!("{section}_"))?
+                        .to_string();
+                    Some((section.to_string(), key))
+                })
+                .ok_or_else(|| ConfigError::UnknownEnv(name.clone()))?;
+            namespaced.push((name, section, key, value));
+        }
+        namespaced.sort();
+        for (name, section, key, value) in legacy.into_iter().chain(namespaced) {
+            if let Some(toml::Value::Table(base)) = merged.get_mut(&section) {
+                base.insert(key.clone(), env_value(&value));
+            }
+            origins.insert(format!("{section}.{key}"), Source::Env(name));
+        }
+
+        let mut config: Self = toml::Value::Table(merged)
+            .try_into()
+            .map_err(ConfigError::Invalid)?;
+        config.origins = origins;
+        Ok(config)
+    }
+
+    /// De onde veio `key` (`seção.chave`), se não é o padrão.
+    pub fn origin(&self, key: &str) -> Option<&Source> {
+        self.origins.get(key)
+    }
+
+    /// A configuração como TOML, com a origem de cada valor que não é o
+    /// padrão num comentário no fim da linha.
+    pub fn to_annotated_toml(&self) -> String {
+        let text = toml::to_string(self).expect("config is always serializable");
+        let mut section = String::new();
+        let mut out = String::new();
+        for line in text.lines() {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.to_string();
+            }
+            out.push_str(line);
+            let origin = line
+                .split_once(" = ")
+                .and_then(|(key, _)| self.origin(&format!("{section}.{}", key.trim())));
+            if let Some(origin) = origin {
+                out.push_str(&format!("  # {origin}"));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// O valor de uma variável como TOML, ou como texto se não for um valor
+/// TOML válido.
+fn env_value(raw: &str) -> toml::Value {
+    #[derive(Deserialize)]
+    struct Wrapper {
+        value: toml::Value,
+    }
+    toml::from_str::<Wrapper>(&format!("value = {raw}"))
+        .map(|wrapper| wrapper.value)
+        .unwrap_or_else(|_| toml::Value::String(raw.to_string()))
+}
+
+/// Durações como texto do `humantime` (`30s`, `1m30s`) ou segundos inteiros.
+mod duration {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub(super) enum Raw {
+        Secs(u64),
+        Text(String),
+    }
+
+    impl Raw {
+        pub(super) fn into_duration<E: Error>(self) -> Result<Duration, E> {
+            match self {
+                Self::Secs(secs) => Ok(Duration::from_secs(secs)),
+                Self::Text(text) => humantime::parse_duration(&text).map_err(|_| {
+                    E::custom(format!(
+                        "invalid duration {text:?}: expected 30, \"30s\", \"1m30s\"..."
+                    ))
+                }),
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&humantime::format_duration(*value).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Raw::deserialize(deserializer)?.into_duration()
+    }
+}
+
+/// [`duration`] para campos opcionais.
+mod optional_duration {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::duration::Raw;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => super::duration::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<Raw>::deserialize(deserializer)?
+            .map(Raw::into_duration)
+            .transpose()
+    }
+}
@@ -0,0 +1,363 @@
+//! `playground daemon`: o servidor HTTP, as capturas de tela periódicas e as
+//! gravações de áudio sob demanda num único runtime do tokio.
+//!
+//! Cada subsistema roda numa task supervisionada: quando falha (ou entra em
+//! pânico), é reiniciado depois de `daemon.restart_backoff`, que dobra a cada
+//! falha seguida; uma execução que durou mais de um minuto zera a
+//! contagem. Depois de `daemon.max_restarts` falhas seguidas o daemon desiste:
+//! desliga os outros subsistemas e sai com o código de saída do erro.
+//!
+//! SIGINT e SIGTERM desligam tudo junto: o servidor drena as requisições, a
+//! captura em andamento termina e a gravação em andamento é encerrada e
+//! finalizada. No unix, `SIGUSR2` pede uma gravação com a seção
+//! `[recorder]`, salva em `daemon.recordings_dir`; uma de cada vez, já que o
+//! microfone é um só (`SIGUSR1` pausa a que estiver em andamento).
+
+use std::{
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use playground_common::{error::PlaygroundError, telemetry::Telemetry};
+use screen_capture::{capture_to_dir, error::ScreenshotError};
+use tokio::{sync::watch, task::JoinSet};
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::{
+    config::{Config, DaemonSection},
+    server,
+};
+
+/// Filtro de log quando nem `RUST_LOG` nem `log.level` definem um: o de
+/// cada subsistema, mais o do próprio daemon.
+pub const DEFAULT_LOG_FILTER: &str = "playground_cli=info,playground_server=info,migrate_core=info,screen_capture=info,audio_capture=info";
+
+/// Uma execução que durou pelo menos isso conta como estável: a próxima
+/// falha volta a esperar só `restart_backoff`.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+/// Teto da espera entre reinícios.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Roda os subsistemas ligados em `config` até SIGINT/SIGTERM, ou até um
+/// deles esgotar os reinícios.
+pub async fn run(config: Config, telemetry: Arc<Telemetry>) -> Result<(), PlaygroundError> {
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let policy = config.daemon.clone();
+    let mut subsystems = JoinSet::new();
+
+    if config.daemon.server {
+        // Valida as opções agora, para que um erro de configuração saia com
+        // o código `2` em vez de virar uma sequência de reinícios.
+        server::options(config.clone(), []).map_err(PlaygroundError::usage)?;
+        let config = config.clone();
+        subsystems.spawn(supervise(
+            "server",
+            policy.clone(),
+            shutdown_rx.clone(),
+            move |shutdown| {
+                let options = server::options(config.clone(), []);
+                let telemetry = Arc::clone(&telemetry);
+                async move {
+                    playground_server::run_until(options?, &telemetry, changed(shutdown)).await?;
+                    Ok(())
+                }
+            },
+        ));
+    }
+
+    if let Some(interval) = config.daemon.screenshot_interval {
+        let out_dir = config.screenshots.out_dir.clone();
+        subsystems.spawn(supervise(
+            "screenshots",
+            policy.clone(),
+            shutdown_rx.clone(),
+            move |shutdown| screenshots(out_dir.clone(), interval, shutdown),
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        let recorder = config.recorder.clone();
+        let dir = config.daemon.recordings_dir.clone();
+        subsystems.spawn(supervise(
+            "recorder",
+            policy.clone(),
+            shutdown_rx.clone(),
+            move |shutdown| recordings::run(recorder.clone(), dir.clone(), shutdown),
+        ));
+    }
+
+    info!(subsystems = subsystems.len(), "daemon iniciado");
+    let mut failure = tokio::select! {
+        () = shutdown_signal() => None,
+        err = first_failure(&mut subsystems) => Some(err),
+    };
+
+    let _ = shutdown_tx.send(());
+    while let Some(result) = subsystems.join_next().await {
+        if let Err(err) = flatten_join(result) {
+            failure.get_or_insert(err);
+        }
+    }
+    match failure {
+        Some(err) => {
+            error!(error = %format!("{err:#}"), "daemon encerrado por falha");
+            Err(err)
+        }
+        None => {
+            info!("daemon encerrado");
+            Ok(())
+        }
+    }
+}
+
+/// Espera o primeiro subsistema que desistir; os que terminam bem (ex.: sem
+/// nada para fazer) só saem do conjunto.
+async fn first_failure(subsystems: &mut JoinSet<Result<(), PlaygroundError>>) -> PlaygroundError {
+    while let Some(result) = subsystems.join_next().await {
+        if let Err(err) = flatten_join(result) {
+            return err;
+        }
+    }
+    // Nenhum subsistema ficou rodando; espera o sinal de desligamento.
+    std::future::pending().await
+}
+
+fn flatten_join(
+    result: Result<Result<(), PlaygroundError>, tokio::task::JoinError>,
+) -> Result<(), PlaygroundError> {
+    result.map_err(|err| PlaygroundError::Other(anyhow::anyhow!("supervisor panicked: {err}")))?
+}
+
+/// Roda `start` até terminar bem ou até o desligamento, reiniciando-o (com
+/// espera crescente) quando devolve erro ou entra em pânico.
+async fn supervise<F, Fut>(
+    name: &'static str,
+    policy: DaemonSection,
+    mut shutdown: watch::Receiver<()>,
+    mut start: F,
+) -> Result<(), PlaygroundError>
+where
+    F: FnMut(watch::Receiver<()>) -> Fut,
+    Fut: Future<Output = Result<(), PlaygroundError>> + Send + 'static,
+{
+    let span = info_span!("subsystem", subsystem = name);
+    let mut failures = 0u32;
+    loop {
+        let started = Instant::now();
+        let child = tokio::spawn(start(shutdown.clone()).instrument(span.clone()));
+        let result = child.await.unwrap_or_else(|err| {
+            Err(PlaygroundError::Other(anyhow::anyhow!(
+                "{name} panicked: {err}"
+            )))
+        });
+        let err = match result {
+            Ok(()) => return Ok(()),
+            // Falhar durante o desligamento não é motivo para reiniciar.
+            Err(_) if shutting_down(&shutdown) => return Ok(()),
+            Err(err) => err,
+        };
+
+        if started.elapsed() >= STABLE_AFTER {
+            failures = 0;
+        }
+        failures += 1;
+        if failures > policy.max_restarts {
+            error!(
+                subsystem = name,
+                error = %format!("{err:#}"),
+                failures,
+                "subsistema falhou vezes demais; desistindo"
+            );
+            return Err(err);
+        }
+        let delay = policy
+            .restart_backoff
+            .saturating_mul(1 << (failures - 1).min(16))
+            .min(MAX_BACKOFF);
+        warn!(
+            subsystem = name,
+            error = %format!("{err:#}"),
+            failures,
+            retry_in_secs = delay.as_secs_f64(),
+            "subsistema falhou; reiniciando"
+        );
+        tokio::select! {
+            () = tokio::time::sleep(delay) => {}
+            _ = shutdown.changed() => return Ok(()),
+        }
+    }
+}
+
+/// Se o desligamento já foi pedido (ou quem o pediria não existe mais).
+fn shutting_down(shutdown: &watch::Receiver<()>) -> bool {
+    shutdown.has_changed().unwrap_or(true)
+}
+
+/// Resolve quando o desligamento é pedido.
+async fn changed(mut shutdown: watch::Receiver<()>) {
+    // Erro quer dizer que o daemon já foi embora, o que também é desligar.
+    let _ = shutdown.changed().await;
+}
+
+/// Captura todos os monitores a cada `interval` em `out_dir`, como o
+/// `POST /screenshots` do servidor. Qualquer falha encerra a task, para que
+/// o supervisor decida se reinicia.
+async fn screenshots(
+    out_dir: PathBuf,
+    interval: Duration,
+    mut shutdown: watch::Receiver<()>,
+) -> Result<(), PlaygroundError> {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    info!(dir = %out_dir.display(), every = %humantime::format_duration(interval), "capturando a tela periodicamente");
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = shutdown.changed() => return Ok(()),
+        }
+        let prefix = format!("{}-", unix_millis());
+        let dir = out_dir.clone();
+        let saved = tokio::task::spawn_blocking(move || capture_to_dir(&dir, &prefix))
+            .await
+            .map_err(|err| PlaygroundError::Other(err.into()))?
+            .map_err(ScreenshotError::from)?;
+        for capture in &saved {
+            info!(display = capture.display_id, path = %capture.path.display(), "captura salva");
+        }
+    }
+}
+
+/// Gravações sob demanda. Só no unix, onde existe `SIGUSR2`.
+#[cfg(unix)]
+mod recordings {
+    use std::{
+        path::{Path, PathBuf},
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+    };
+
+    use anyhow::Context;
+    use audio_capture::recorder::{self, ArmConfig, Options};
+    use playground_common::error::PlaygroundError;
+    use tokio::{
+        signal::unix::{SignalKind, signal},
+        sync::watch,
+    };
+    use tracing::{info, info_span, warn};
+
+    use super::unix_millis;
+    use crate::config::RecorderSection;
+
+    /// Espera pedidos de gravação (`SIGUSR2`) e grava um de cada vez. Uma
+    /// gravação que falha só é registrada: o pedido seguinte tenta de novo.
+    pub(super) async fn run(
+        section: RecorderSection,
+        dir: PathBuf,
+        mut shutdown: watch::Receiver<()>,
+    ) -> Result<(), PlaygroundError> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Falha ao criar {}", dir.display()))?;
+        let mut requests = signal(SignalKind::user_defined2())
+            .context("Falha ao instalar o handler de SIGUSR2")?;
+        info!(
+            pid = std::process::id(),
+            "gravações sob demanda: kill -USR2 <pid>"
+        );
+        loop {
+            tokio::select! {
+                received = requests.recv() => if received.is_none() { return Ok(()) },
+                _ = shutdown.changed() => return Ok(()),
+            }
+            let cancel = Arc::new(AtomicBool::new(false));
+            let options = options(&section, &dir, Arc::clone(&cancel));
+            let span = info_span!(
+                "recording",
+                out = options.out.as_deref().unwrap_or_default()
+            );
+            let mut job =
+                tokio::task::spawn_blocking(move || span.in_scope(|| recorder::run(&options)));
+            let result = tokio::select! {
+                result = &mut job => result,
+                _ = shutdown.changed() => {
+                    info!("desligando; encerrando a gravação em andamento");
+                    cancel.store(true, Ordering::Relaxed);
+                    let _ = job.await;
+                    return Ok(());
+                }
+            };
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => warn!(error = %format!("{err:#}"), "gravação falhou"),
+                Err(err) => return Err(PlaygroundError::Other(err.into())),
+            }
+        }
+    }
+
+    /// As opções de uma gravação do daemon: a seção `[recorder]`, com um
+    /// arquivo novo em `dir` a cada pedido (streams usam o `out` da seção).
+    fn options(section: &RecorderSection, dir: &Path, cancel: Arc<AtomicBool>) -> Options {
+        let out = match section.format.as_str() {
+            "icecast" | "rtp" => section.out.clone(),
+            format => Some(
+                dir.join(format!("daemon-{}.{format}", unix_millis()))
+                    .display()
+                    .to_string(),
+            ),
+        };
+        Options {
+            duration: section.duration,
+            arm: section.arm.then_some(ArmConfig {
+                threshold_db: section.threshold,
+                silence_secs: section.silence,
+            }),
+            format: section.format.clone(),
+            out,
+            append: None,
+            interactive: false,
+            cancel: Some(cancel),
+        }
+    }
+}
+
+fn unix_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// Resolve no primeiro SIGINT (Ctrl+C) ou SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!(error = %err, "falha ao instalar o handler de Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                error!(error = %err, "falha ao instalar o handler de SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => info!(signal = "SIGINT", "desligamento pedido"),
+        () = terminate => info!(signal = "SIGTERM", "desligamento pedido"),
+    }
+}
@@ -0,0 +1,11 @@
+//! O que os binários do projeto compartilham e que não pertence a nenhum
+//! crate de biblioteca: a configuração em camadas de `playground.toml`.
+//!
+//! Os binários em si só ligam as peças: o motor de migrações fica em
+//! `migrate-core`/`migrate-libsql`, o gravador em `audio-capture`, as
+//! capturas de tela em `screen-capture` e o servidor HTTP em
+//! `playground-server`.
+
+pub mod config;
+pub mod daemon;
+pub mod server;
@@ -0,0 +1,149 @@
+//! The `[server]` section of the shared configuration, with the
+//! `simple-http-server` flags on top, turned into [`ServerOptions`]; shared
+//! by that binary and `playground daemon`.
+
+use std::net::{IpAddr, SocketAddr};
+
+use anyhow::Context;
+use playground_server::{BodyLogging, RateLimitConfig, ServerOptions, TlsPaths, parse_duration};
+
+use crate::config::Config;
+
+/// Server options: the `[server]` section of `config` (whose environment
+/// layer still honours `PORT`, `TLS_CERT`, `TLS_KEY`, `HTTP_REDIRECT_PORT`,
+/// `RATE_LIMIT_RPS`, `RATE_LIMIT_BURST`, `BODY_LIMIT_BYTES`,
+/// `REQUEST_TIMEOUT`, `SERVER_CONFIG` and `LOG_BODIES_KB`), overridden by
+/// `args` (the `simple-http-server` flags; empty for `playground daemon`).
+pub fn options(
+    config: Config,
+    args: impl IntoIterator<Item = String>,
+) -> anyhow::Result<ServerOptions> {
+    let section = config.server;
+    let mut cert = section.tls_cert;
+    let mut key = section.tls_key;
+    let mut redirect_port = None;
+    let mut port = None;
+    let mut rate = None;
+    let mut burst = None;
+    let mut body_limit = None;
+    let mut request_timeout = None;
+    let mut server_config = section.config_file;
+    let mut log_bodies = None;
+    let mut addrs = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--addr" => addrs.push(args.next().context("--addr needs an address")?),
+            "--port" => port = Some(args.next().context("--port needs a port")?),
+            "--tls-cert" => cert = Some(args.next().context("--tls-cert needs a path")?.into()),
+            "--tls-key" => key = Some(args.next().context("--tls-key needs a path")?.into()),
+            "--redirect-port" => {
+                redirect_port = Some(args.next().context("--redirect-port needs a port")?)
+            }
+            "--rate-limit" => {
+                rate = Some(
+                    args.next()
+                        .context("--rate-limit needs requests per second")?,
+                )
+            }
+            "--rate-burst" => burst = Some(args.next().context("--rate-burst needs a size")?),
+            "--body-limit" => {
+                body_limit = Some(args.next().context("--body-limit needs a size in bytes")?)
+            }
+            "--request-timeout" => {
+                request_timeout = Some(args.next().context("--request-timeout needs a duration")?)
+            }
+            "--config" => {
+                server_config = Some(args.next().context("--config needs a path")?.into())
+            }
+            "--log-bodies-kb" => {
+                log_bodies = Some(args.next().context("--log-bodies-kb needs a size")?)
+            }
+            other => anyhow::bail!("unknown argument: {other}"),
+        }
+    }
+
+    let tls = match (cert, key) {
+        (Some(cert), Some(key)) => Some(TlsPaths { cert, key }),
+        (None, None) => None,
+        _ => anyhow::bail!("--tls-cert and --tls-key must be given together"),
+    };
+    let redirect_port = match redirect_port {
+        Some(port) => Some(
+            port.parse::<u16>()
+                .with_context(|| format!("invalid redirect port: {port}"))?,
+        ),
+        None => section.redirect_port,
+    };
+    if redirect_port.is_some() && tls.is_none() {
+        anyhow::bail!("--redirect-port only makes sense together with TLS");
+    }
+
+    let port = match port {
+        Some(port) => port
+            .parse::<u16>()
+            .with_context(|| format!("invalid port {port:?}: expected 0-65535"))?,
+        None => section.port,
+    };
+    // `--addr` replaces the configured addresses rather than adding to them.
+    if addrs.is_empty() {
+        addrs = section.addrs;
+    }
+    // `--addr` accepts a bare IP (combined with the port) or a full `ip:port`.
+    let addrs = addrs
+        .iter()
+        .map(|addr| {
+            addr.parse::<SocketAddr>()
+                .or_else(|_| addr.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, port)))
+                .with_context(|| format!("invalid --addr {addr:?}: expected an IP or IP:PORT"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let rate = rate.unwrap_or_else(|| section.rate_limit.to_string());
+    let per_second = rate
+        .parse::<f64>()
+        .ok()
+        .filter(|r| r.is_finite() && *r >= 0.0)
+        .with_context(|| format!("invalid rate limit {rate:?}: expected requests per second"))?;
+    let burst = burst.unwrap_or_else(|| section.rate_burst.to_string());
+    let burst = burst
+        .parse::<u32>()
+        .ok()
+        .filter(|b| *b > 0)
+        .with_context(|| format!("invalid rate burst {burst:?}: expected a positive integer"))?;
+    let rate_limit = (per_second > 0.0).then_some(RateLimitConfig { per_second, burst });
+
+    let body_limit = match body_limit {
+        Some(limit) => limit
+            .parse::<usize>()
+            .with_context(|| format!("invalid body limit {limit:?}: expected bytes"))?,
+        None => section.body_limit,
+    };
+    let request_timeout = match request_timeout {
+        Some(timeout) => parse_duration(&timeout)?,
+        None => section.request_timeout,
+    };
+    // `0` is the same as leaving it out.
+    let log_bodies = match log_bodies {
+        Some(kb) => kb
+            .parse::<usize>()
+            .with_context(|| format!("invalid body logging limit {kb:?}: expected KB"))?,
+        None => section.log_bodies_kb,
+    };
+    let log_bodies = (log_bodies > 0).then(|| BodyLogging {
+        max_bytes: log_bodies.saturating_mul(1024),
+    });
+
+    Ok(ServerOptions {
+        addrs,
+        tls,
+        redirect_port,
+        rate_limit,
+        body_limit,
+        request_timeout,
+        config: server_config,
+        log_bodies,
+        db_path: config.migrate.db_path,
+    })
+}
@@ -0,0 +1,140 @@
+//! Erro comum aos binários do projeto e os códigos de saída de cada tipo,
+//! para que scripts possam decidir o que fazer sem ler a mensagem.
+//!
+//! | código | variante                          | quando                                              |
+//! |--------|-----------------------------------|-----------------------------------------------------|
+//! | 0      | —                                 | tudo certo                                          |
+//! | 1      | [`Other`](PlaygroundError::Other) | qualquer outra falha                                |
+//! | 2      | [`Usage`](PlaygroundError::Usage), [`Config`](PlaygroundError::Config) | argumentos ou configuração inválidos; nada foi feito |
+//! | 3      | [`Partial`](PlaygroundError::Partial) | parte do trabalho foi feita, parte falhou       |
+//! | 4      | [`Unavailable`](PlaygroundError::Unavailable) | banco, dispositivo de áudio, monitor ou porta indisponível; tentar de novo pode resolver |
+//! | 130    | [`Cancelled`](PlaygroundError::Cancelled) | a pessoa desistiu (ex.: fechou a entrada padrão); o mesmo código que o shell dá para Ctrl+C |
+//!
+//! As bibliotecas continuam devolvendo `anyhow::Error` ou seus próprios
+//! erros; quem sabe que a falha é de uma dependência a marca com
+//! [`PlaygroundError::unavailable`], e a conversão de `anyhow::Error`
+//! recupera essa marca mesmo depois de `.context(...)`.
+
+use std::{fmt, path::PathBuf, process::ExitCode};
+
+use migrate_core::MigrationError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PlaygroundError {
+    /// Argumentos de linha de comando inválidos.
+    #[error("{0}")]
+    Usage(String),
+    /// A configuração compartilhada (`playground.toml`, `PLAYGROUND_*`) é
+    /// inválida.
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    /// Algo de que o binário depende não está disponível agora.
+    #[error("{0:#}")]
+    Unavailable(anyhow::Error),
+    /// Algumas unidades de trabalho (monitores, arquivos...) falharam; os
+    /// erros de cada uma já foram impressos.
+    #[error("{failed} of {total} failed")]
+    Partial { failed: usize, total: usize },
+    #[error("cancelled")]
+    Cancelled,
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl PlaygroundError {
+    /// Atalho para [`PlaygroundError::Usage`]; aceita um `anyhow::Error` e
+    /// mantém as causas na mensagem.
+    pub fn usage(message: impl fmt::Display) -> Self {
+        Self::Usage(format!("{message:#}"))
+    }
+
+    /// Atalho para [`PlaygroundError::Unavailable`] em `map_err`.
+    pub fn unavailable(err: impl Into<anyhow::Error>) -> Self {
+        Self::Unavailable(err.into())
+    }
+
+    /// O código de saída estável desta falha (ver a tabela do módulo).
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other(_) => 1,
+            Self::Usage(_) | Self::Config(_) => 2,
+            Self::Partial { .. } => 3,
+            Self::Unavailable(_) => 4,
+            Self::Cancelled => 130,
+        }
+    }
+}
+
+impl From<anyhow::Error> for PlaygroundError {
+    /// Procura um `PlaygroundError` ou um `ConfigError` por baixo dos
+    /// contextos; o resto vira [`PlaygroundError::Other`].
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<PlaygroundError>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        match err.downcast::<ConfigError>() {
+            Ok(err) => Self::Config(err),
+            Err(err) => Self::Other(err),
+        }
+    }
+}
+
+impl From<MigrationError> for PlaygroundError {
+    /// Banco que não abre sai com o código de "indisponível", URL de um
+    /// adaptador não registrado é erro de uso; o resto é falha comum.
+    fn from(err: MigrationError) -> Self {
+        match err {
+            MigrationError::Open(..) => Self::unavailable(flatten(err)),
+            MigrationError::UnknownBackend(..) => Self::usage(err),
+            err => Self::Other(flatten(err)),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+/// Erros ao montar a configuração compartilhada (a `Config` do
+/// `playground-cli`); fica aqui para que [`PlaygroundError::Config`] exista
+/// sem que este crate dependa dos binários.
+pub enum ConfigError {
+    #[error("I/O error reading {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// O arquivo não é TOML válido.
+    #[error("invalid TOML in {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    /// `PLAYGROUND_*` que não aponta para nenhuma seção.
+    #[error(
+        "unknown configuration variable {0}: expected PLAYGROUND_<SECTION>_<KEY> with section server, migrate, recorder, screenshots, daemon or log"
+    )]
+    UnknownEnv(String),
+    /// As camadas juntas não formam uma configuração válida (chave
+    /// desconhecida, tipo errado, …).
+    #[error("invalid configuration: {0}")]
+    Invalid(#[source] toml::de::Error),
+}
+
+/// Embrulha um erro cuja mensagem já traz as causas (o caso dos enums de
+/// erro deste projeto), para que o `{:#}` de [`report`] não as repita.
+pub fn flatten(err: impl fmt::Display) -> anyhow::Error {
+    anyhow::Error::msg(err.to_string())
+}
+
+/// Imprime o erro (com as causas) e devolve o código de saída do `main`.
+pub fn report(result: Result<(), PlaygroundError>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Erro: {err:#}");
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! Peças comuns a todos os crates do projeto: o erro com os códigos de saída
+//! dos binários ([`error`]) e a inicialização do `tracing` ([`telemetry`]).
+//!
+//! Fica separado do `playground-cli` para que o servidor e os crates de
+//! captura possam marcar suas falhas (ex.: "dispositivo indisponível") sem
+//! depender dos binários.
+
+pub mod error;
+pub mod telemetry;
@@ -0,0 +1,184 @@
+//! Inicialização do `tracing` compartilhada pelos binários, para que o
+//! gravador, o `screenshots` e as ferramentas de banco registrem eventos do
+//! mesmo jeito que o servidor HTTP.
+//!
+//! Os eventos vão para a saída de erro (a saída padrão fica para o que o
+//! comando produz, como `--list-displays`), em texto compacto ou em JSON, uma
+//! linha por evento, com os campos do span atual. O filtro vem de
+//! `RUST_LOG` e, sem ela, do nível pedido por quem chama.
+//!
+//! Com a feature `otlp` e `OTEL_EXPORTER_OTLP_ENDPOINT` (ou
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`) definida, os spans também são
+//! exportados por OTLP/HTTP, com `service.name` igual ao nome do binário.
+
+use std::io::IsTerminal;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
+
+use crate::error::{self, PlaygroundError};
+
+/// Variáveis que ligam a exportação OTLP.
+const OTLP_ENDPOINT_ENV: [&str; 2] = [
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+];
+
+/// Formato das linhas de log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Texto curto para terminal.
+    #[default]
+    Compact,
+    /// Um objeto JSON por linha, para Loki/ELK.
+    Json,
+}
+
+/// Handle do filtro instalado, para trocá-lo com o programa rodando.
+pub type FilterHandle = reload::Handle<EnvFilter, Registry>;
+
+#[derive(Error, Debug)]
+/// Erros ao instalar o `tracing`.
+pub enum TelemetryError {
+    /// O nível pedido não é uma diretiva válida do `EnvFilter`.
+    #[error("invalid log level {0:?}: {1}")]
+    Level(String, #[source] tracing_subscriber::filter::ParseError),
+    /// Já havia um subscriber global (`init` chamado duas vezes).
+    #[error("failed to install the log subscriber: {0}")]
+    Init(#[from] tracing_subscriber::util::TryInitError),
+    #[cfg(feature = "otlp")]
+    #[error("failed to create the OTLP exporter: {0}")]
+    Exporter(#[from] opentelemetry_otlp::ExporterBuildError),
+}
+
+impl From<TelemetryError> for PlaygroundError {
+    /// Nível inválido é erro de configuração (`2`); o resto é falha comum.
+    fn from(err: TelemetryError) -> Self {
+        match err {
+            TelemetryError::Level(..) => Self::usage(err),
+            err => Self::Other(error::flatten(err)),
+        }
+    }
+}
+
+/// O `tracing` instalado por [`init`]. Guarde-o até o fim do `main`: ao
+/// sair de escopo ele envia os spans que ainda estão na fila do OTLP.
+pub struct Telemetry {
+    filter: FilterHandle,
+    startup: String,
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Telemetry {
+    /// Handle do filtro, para recarregá-lo (o `[log]` do servidor).
+    pub fn filter_handle(&self) -> FilterHandle {
+        self.filter.clone()
+    }
+
+    /// As diretivas com que o filtro começou (`RUST_LOG` ou `level`).
+    pub fn startup_filter(&self) -> &str {
+        &self.startup
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take()
+            && let Err(err) = provider.shutdown()
+        {
+            eprintln!("Aviso: falha ao enviar os spans pendentes: {err}");
+        }
+    }
+}
+
+/// Instala o subscriber global de `service_name` com `format`, filtrando
+/// por `RUST_LOG` ou, sem ela, por `level` (ex.: `screen_capture=info`).
+pub fn init(
+    service_name: &str,
+    format: LogFormat,
+    level: &str,
+) -> Result<Telemetry, TelemetryError> {
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => EnvFilter::try_new(level)
+            .map_err(|source| TelemetryError::Level(level.to_string(), source))?,
+    };
+    let startup = filter.to_string();
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false);
+    let fmt = match format {
+        LogFormat::Json => fmt
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+        LogFormat::Compact => fmt.compact().boxed(),
+    };
+    let subscriber = tracing_subscriber::registry().with(filter).with(fmt);
+
+    let otlp_requested = OTLP_ENDPOINT_ENV
+        .iter()
+        .any(|name| std::env::var_os(name).is_some());
+
+    #[cfg(feature = "otlp")]
+    let (subscriber, provider) = {
+        use opentelemetry::trace::TracerProvider as _;
+
+        let provider = otlp_requested
+            .then(|| otlp_provider(service_name))
+            .transpose()?;
+        let layer = provider.as_ref().map(|provider| {
+            tracing_opentelemetry::layer().with_tracer(provider.tracer(service_name.to_string()))
+        });
+        (subscriber.with(layer), provider)
+    };
+
+    subscriber.try_init()?;
+
+    // Fora do filtro de cada binário de propósito: é sobre o próprio log.
+    #[cfg(not(feature = "otlp"))]
+    if otlp_requested {
+        eprintln!(
+            "Aviso: {service_name} compilado sem a feature `otlp`; OTEL_EXPORTER_OTLP_ENDPOINT ignorada"
+        );
+    }
+
+    Ok(Telemetry {
+        filter: handle,
+        startup,
+        #[cfg(feature = "otlp")]
+        provider,
+    })
+}
+
+/// Exportador OTLP/HTTP configurado pelas variáveis `OTEL_*` padrão
+/// (endpoint, headers, timeout).
+#[cfg(feature = "otlp")]
+fn otlp_provider(
+    service_name: &str,
+) -> Result<opentelemetry_sdk::trace::SdkTracerProvider, TelemetryError> {
+    // Como no envio de capturas: o rustls precisa de um provedor de
+    // criptografia antes de o cliente HTTP do exportador existir.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()?;
+    let resource = opentelemetry_sdk::Resource::builder()
+        .with_service_name(service_name.to_string())
+        .build();
+    Ok(opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build())
+}
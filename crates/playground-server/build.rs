@@ -0,0 +1,12 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // A vendored `protoc`, so building needs nothing installed; `PROTOC`
+    // still wins when set.
+    if std::env::var_os("PROTOC").is_none() {
+        // SAFETY: build scripts are single-threaded.
+        unsafe { std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
+    }
+    tonic_prost_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/playground.proto"], &["proto"])?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use axum::{Json, extract::State, http::StatusCode};
+use migrate_core::{MigrationError, MigrationState, migration_status, run_migrations};
+use migrate_libsql::LibSqlAdapter;
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{Admin, RequireRole},
+    db::DbPool,
+    error::ApiError,
+    events::EventBus,
+};
+
+/// Serializes `POST /admin/migrations`: two concurrent runs would both try
+/// to apply the same pending files.
+static MIGRATION_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Serialize, ToSchema)]
+pub struct MigrationEntry {
+    name: String,
+    checksum: String,
+    /// `applied`, `pending`, `modified` (changed on disk after it was
+    /// applied) or `missing` (applied, but the file is gone).
+    state: &'static str,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MigrationsResponse {
+    applied: usize,
+    pending: usize,
+    migrations: Vec<MigrationEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MigrationRunResponse {
+    /// Migrations executed by this run, in order.
+    applied: Vec<String>,
+}
+
+fn state_name(state: MigrationState) -> &'static str {
+    match state {
+        MigrationState::Applied => "applied",
+        MigrationState::Pending => "pending",
+        MigrationState::Modified => "modified",
+        MigrationState::Missing => "missing",
+    }
+}
+
+fn db_error(err: libsql::Error) -> ApiError {
+    ApiError::internal("database failure", err)
+}
+
+fn migration_error(err: MigrationError) -> ApiError {
+    match err {
+        MigrationError::ChecksumMismatch(..) => {
+            ApiError::new(StatusCode::CONFLICT, err.to_string())
+        }
+        err => ApiError::internal("migration failure", err),
+    }
+}
+
+/// `GET /admin/migrations`: which migrations are applied and which are
+/// still pending, without running anything.
+#[utoipa::path(
+    get,
+    path = "/admin/migrations",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = MigrationsResponse),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn list_migrations(
+    _admin: RequireRole<Admin>,
+    State(pool): State<DbPool>,
+) -> Result<Json<MigrationsResponse>, ApiError> {
+    let conn = pool.get().await.map_err(db_error)?;
+    let report = migration_status(&LibSqlAdapter::new(conn.clone()))
+        .await
+        .map_err(migration_error)?;
+    let count = |state| report.iter().filter(|m| m.state == state).count();
+    Ok(Json(MigrationsResponse {
+        applied: count(MigrationState::Applied),
+        pending: count(MigrationState::Pending),
+        migrations: report
+            .iter()
+            .map(|m| MigrationEntry {
+                name: m.name.clone(),
+                checksum: m.checksum.clone(),
+                state: state_name(m.state),
+            })
+            .collect(),
+    }))
+}
+
+/// `POST /admin/migrations`: applies pending migrations now, the same way
+/// the server does on startup.
+#[utoipa::path(
+    post,
+    path = "/admin/migrations",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = MigrationRunResponse),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 409, description = "An applied migration was changed on disk")
+    )
+)]
+pub async fn run_pending_migrations(
+    RequireRole(admin, _): RequireRole<Admin>,
+    State(pool): State<DbPool>,
+    State(events): State<EventBus>,
+) -> Result<Json<MigrationRunResponse>, ApiError> {
+    let _guard = MIGRATION_LOCK.lock().await;
+    let conn = pool.get().await.map_err(db_error)?;
+    let applied = run_migrations(&LibSqlAdapter::new(conn.clone()))
+        .await
+        .map_err(migration_error)?;
+    info!(admin_id = %admin.id, applied = applied.len(), "ran migrations");
+    if !applied.is_empty() {
+        events.publish("migrations.applied", json!({ "migrations": applied }));
+    }
+    Ok(Json(MigrationRunResponse { applied }))
+}
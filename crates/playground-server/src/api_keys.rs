@@ -0,0 +1,446 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::{Method, StatusCode},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use libsql::Row;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{auth::User, db::DbPool, error::ApiError};
+
+/// Header API clients send their key in.
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// Every generated key starts with this, so leaked keys are easy to grep for.
+const KEY_PREFIX: &str = "pk_";
+/// Scope granting access to every route.
+pub const ALL_SCOPES: &str = "*";
+
+/// An API key as listed to its owner (never the key itself).
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiKeyRecord {
+    pub id: i64,
+    pub name: String,
+    /// First characters of the key, to tell keys apart.
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKey {
+    pub name: String,
+    /// `<resource>:read` / `<resource>:write` (e.g. `recordings:read`), or
+    /// `*` for everything.
+    pub scopes: Vec<String>,
+}
+
+/// Returned once, on creation: the only time the full key is visible.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub record: ApiKeyRecord,
+    pub key: String,
+}
+
+const API_KEY_COLUMNS: &str = "id, name, prefix, scopes, created_at, last_used_at, revoked_at";
+
+/// Scope a request needs: the first path segment plus `read` for safe
+/// methods and `write` for everything else (`POST /recordings` needs
+/// `recordings:write`). `/graphql` only has queries, so it is always a
+/// `read`; its fields then need the scopes of their REST counterparts.
+pub fn required_scope(method: &Method, path: &str) -> String {
+    let resource = path.trim_start_matches('/').split('/').next().unwrap_or("");
+    let access = if method.is_safe() || resource == "graphql" {
+        "read"
+    } else {
+        "write"
+    };
+    format!("{resource}:{access}")
+}
+
+fn valid_scope(scope: &str) -> bool {
+    scope == ALL_SCOPES
+        || scope.split_once(':').is_some_and(|(resource, access)| {
+            !resource.is_empty()
+                && resource
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+                && matches!(access, "read" | "write")
+        })
+}
+
+fn internal(err: impl std::fmt::Display) -> ApiError {
+    ApiError::internal("api key store failure", err)
+}
+
+/// API keys stored hashed (SHA-256) in the `api_keys` table. Keys are 256
+/// random bits, so a fast hash is enough. Cheap to clone.
+#[derive(Clone)]
+pub struct ApiKeyStore {
+    pool: DbPool,
+}
+
+impl ApiKeyStore {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn hash(key: &str) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(key.as_bytes()))
+    }
+
+    fn from_row(row: &Row) -> Result<ApiKeyRecord, libsql::Error> {
+        Ok(ApiKeyRecord {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            prefix: row.get(2)?,
+            scopes: row
+                .get::<String>(3)?
+                .split_whitespace()
+                .map(str::to_owned)
+                .collect(),
+            created_at: row.get(4)?,
+            last_used_at: row.get(5)?,
+            revoked_at: row.get(6)?,
+        })
+    }
+
+    /// The owner of a live (not revoked) key, with the key's scopes.
+    pub async fn authenticate(&self, key: &str) -> Result<Option<User>, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let hash = Self::hash(key);
+        let mut rows = conn
+            .query(
+                "SELECT u.id, u.email, k.scopes, u.role FROM api_keys k JOIN users u ON u.id = k.user_id \
+                 WHERE k.key_hash = ?1 AND k.revoked_at IS NULL AND u.is_active = 1",
+                libsql::params![hash.as_str()],
+            )
+            .await?;
+        let Some(row) = rows.next().await? else {
+            return Ok(None);
+        };
+        let user = User {
+            id: row.get::<i64>(0)?.to_string(),
+            email: row.get(1)?,
+            roles: vec![row.get(3)?],
+            scopes: Some(
+                row.get::<String>(2)?
+                    .split_whitespace()
+                    .map(str::to_owned)
+                    .collect(),
+            ),
+        };
+        conn.execute(
+            "UPDATE api_keys SET last_used_at = CURRENT_TIMESTAMP WHERE key_hash = ?1",
+            libsql::params![hash],
+        )
+        .await?;
+        Ok(Some(user))
+    }
+
+    pub async fn list(&self, user_id: i64) -> Result<Vec<ApiKeyRecord>, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                &format!("SELECT {API_KEY_COLUMNS} FROM api_keys WHERE user_id = ?1 ORDER BY id"),
+                libsql::params![user_id],
+            )
+            .await?;
+        let mut keys = Vec::new();
+        while let Some(row) = rows.next().await? {
+            keys.push(Self::from_row(&row)?);
+        }
+        Ok(keys)
+    }
+
+    pub async fn create(
+        &self,
+        user_id: i64,
+        input: CreateApiKey,
+    ) -> Result<CreatedApiKey, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut bytes = [0u8; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        let key = format!("{KEY_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes));
+        let prefix = key[..KEY_PREFIX.len() + 6].to_owned();
+
+        let mut rows = conn
+            .query(
+                &format!(
+                    "INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes) \
+                     VALUES (?1, ?2, ?3, ?4, ?5) RETURNING {API_KEY_COLUMNS}"
+                ),
+                libsql::params![
+                    user_id,
+                    input.name,
+                    prefix,
+                    Self::hash(&key),
+                    input.scopes.join(" ")
+                ],
+            )
+            .await?;
+        let row = rows
+            .next()
+            .await?
+            .ok_or(libsql::Error::QueryReturnedNoRows)?;
+        Ok(CreatedApiKey {
+            record: Self::from_row(&row)?,
+            key,
+        })
+    }
+
+    /// Revokes one of `user_id`'s keys; `false` if there is no such live key.
+    pub async fn revoke(&self, user_id: i64, id: i64) -> Result<bool, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let affected = conn
+            .execute(
+                "UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP \
+                 WHERE id = ?1 AND user_id = ?2 AND revoked_at IS NULL",
+                libsql::params![id, user_id],
+            )
+            .await?;
+        Ok(affected > 0)
+    }
+}
+
+fn owner_id(user: &User) -> Result<i64, ApiError> {
+    user.id.parse().map_err(|_| {
+        warn!(user_id = %user.id, "caller has no local account to own API keys");
+        ApiError::forbidden("API keys need a local user account")
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api-keys",
+    tag = "api-keys",
+    security(("bearer" = []), ("api_key" = [])),
+    responses((status = 200, body = [ApiKeyRecord]), (status = 401))
+)]
+pub async fn list_api_keys(
+    State(store): State<ApiKeyStore>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ApiKeyRecord>>, ApiError> {
+    let keys = store.list(owner_id(&user)?).await.map_err(internal)?;
+    Ok(Json(keys))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api-keys",
+    tag = "api-keys",
+    security(("bearer" = []), ("api_key" = [])),
+    request_body = CreateApiKey,
+    responses(
+        (status = 201, body = CreatedApiKey),
+        (status = 400, description = "Empty name or unknown scope"),
+        (status = 401)
+    )
+)]
+pub async fn create_api_key(
+    State(store): State<ApiKeyStore>,
+    Extension(user): Extension<User>,
+    Json(input): Json<CreateApiKey>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), ApiError> {
+    if input.name.trim().is_empty() {
+        return Err(ApiError::bad_request("name must not be empty"));
+    }
+    if input.scopes.is_empty() {
+        return Err(ApiError::bad_request("at least one scope is required"));
+    }
+    if let Some(scope) = input.scopes.iter().find(|scope| !valid_scope(scope)) {
+        return Err(ApiError::bad_request(format!(
+            "invalid scope {scope:?}: expected <resource>:read, <resource>:write or *"
+        )));
+    }
+    // A scoped key must not mint keys with more access than it has.
+    if let Some(scopes) = &user.scopes
+        && !scopes.iter().any(|s| s == ALL_SCOPES)
+        && !input.scopes.iter().all(|scope| scopes.contains(scope))
+    {
+        return Err(ApiError::forbidden(
+            "a key cannot grant scopes its creator does not have",
+        ));
+    }
+
+    let created = store
+        .create(owner_id(&user)?, input)
+        .await
+        .map_err(internal)?;
+    info!(key_id = created.record.id, prefix = %created.record.prefix, "created API key");
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api-keys/{id}",
+    tag = "api-keys",
+    security(("bearer" = []), ("api_key" = [])),
+    params(("id" = i64, Path)),
+    responses((status = 204), (status = 401), (status = 404))
+)]
+pub async fn revoke_api_key(
+    State(store): State<ApiKeyStore>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    if store.revoke(owner_id(&user)?, id).await.map_err(internal)? {
+        info!(key_id = id, "revoked API key");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found(format!("no active API key {id}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+
+    use super::*;
+    use crate::credentials::create_initial_admin;
+
+    #[test]
+    fn scope_is_the_first_segment_and_the_access() {
+        assert_eq!(
+            required_scope(&Method::GET, "/recordings"),
+            "recordings:read"
+        );
+        assert_eq!(
+            required_scope(&Method::HEAD, "/recordings/3"),
+            "recordings:read"
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/recordings"),
+            "recordings:write"
+        );
+        assert_eq!(required_scope(&Method::DELETE, "/users/3"), "users:write");
+        assert_eq!(
+            required_scope(&Method::PATCH, "/users/3/role"),
+            "users:write"
+        );
+        // GraphQL is queries only; its fields check their own scopes.
+        assert_eq!(required_scope(&Method::POST, "/graphql"), "graphql:read");
+    }
+
+    #[test]
+    fn validates_scope_syntax() {
+        for valid in ["*", "recordings:read", "api-keys:write", "v2:read"] {
+            assert!(valid_scope(valid), "{valid}");
+        }
+        for invalid in [
+            "",
+            "recordings",
+            ":read",
+            "recordings:admin",
+            "Users:read",
+            "a b:read",
+        ] {
+            assert!(!valid_scope(invalid), "{invalid}");
+        }
+    }
+
+    fn key_user(id: i64, scopes: Option<&[&str]>) -> User {
+        User {
+            id: id.to_string(),
+            email: "admin@example.com".to_owned(),
+            roles: vec!["admin".to_owned()],
+            scopes: scopes.map(|scopes| scopes.iter().map(|s| s.to_string()).collect()),
+        }
+    }
+
+    async fn create(store: &ApiKeyStore, user: User, scopes: &[&str]) -> StatusCode {
+        let input = CreateApiKey {
+            name: "key".to_owned(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        };
+        match create_api_key(State(store.clone()), Extension(user), Json(input)).await {
+            Ok((status, _)) => status,
+            Err(err) => err.into_response().status(),
+        }
+    }
+
+    #[tokio::test]
+    async fn scoped_keys_cannot_mint_broader_keys() {
+        let pool = DbPool::migrated_in_memory().await;
+        let conn = pool.get().await.unwrap();
+        let id = create_initial_admin(&conn, "Admin", "admin@example.com", "unused")
+            .await
+            .unwrap();
+        drop(conn);
+        let store = ApiKeyStore::new(pool);
+
+        // Bearer tokens and sessions have full access.
+        assert_eq!(
+            create(&store, key_user(id, None), &["*"]).await,
+            StatusCode::CREATED
+        );
+
+        let scoped = || key_user(id, Some(&["recordings:read", "api-keys:write"]));
+        assert_eq!(
+            create(&store, scoped(), &["recordings:read"]).await,
+            StatusCode::CREATED
+        );
+        for broader in [
+            &["recordings:write"][..],
+            &["*"],
+            &["recordings:read", "users:read"],
+        ] {
+            assert_eq!(
+                create(&store, scoped(), broader).await,
+                StatusCode::FORBIDDEN
+            );
+        }
+
+        let everything = key_user(id, Some(&["*"]));
+        assert_eq!(
+            create(&store, everything, &["users:write"]).await,
+            StatusCode::CREATED
+        );
+
+        assert_eq!(
+            create(&store, key_user(id, None), &["bogus"]).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            create(&store, key_user(id, None), &[]).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn created_keys_authenticate_with_their_scopes() {
+        let pool = DbPool::migrated_in_memory().await;
+        let conn = pool.get().await.unwrap();
+        let id = create_initial_admin(&conn, "Admin", "admin@example.com", "unused")
+            .await
+            .unwrap();
+        drop(conn);
+        let store = ApiKeyStore::new(pool);
+        let created = store
+            .create(
+                id,
+                CreateApiKey {
+                    name: "ci".to_owned(),
+                    scopes: vec!["recordings:read".to_owned()],
+                },
+            )
+            .await
+            .unwrap();
+        assert!(created.key.starts_with(KEY_PREFIX));
+
+        let user = store.authenticate(&created.key).await.unwrap().unwrap();
+        assert_eq!(user.id, id.to_string());
+        assert!(user.has_scope("recordings:read"));
+        assert!(!user.has_scope("recordings:write"));
+
+        assert!(store.revoke(id, created.record.id).await.unwrap());
+        assert!(store.authenticate(&created.key).await.unwrap().is_none());
+    }
+}
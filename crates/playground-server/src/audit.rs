@@ -0,0 +1,210 @@
+use std::time::Instant;
+
+use axum::{
+    Json,
+    extract::{OriginalUri, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use libsql::Row;
+use serde::Serialize;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{
+    REQUEST_ID_HEADER,
+    auth::{Admin, RequireRole, User},
+    db::DbPool,
+    error::ApiError,
+    filters::{FilterField, FilterQuery, SqlFilter},
+    pagination::{PageQuery, PageRequest, Paginated},
+};
+
+/// A row of the `audit_log` table: one authenticated request.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuditRecord {
+    pub id: i64,
+    /// The caller's `users.id`, or the token subject for external tokens.
+    pub user_id: String,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub latency_ms: i64,
+    pub request_id: Option<String>,
+    pub created_at: String,
+}
+
+const AUDIT_COLUMNS: &str = "id, user_id, method, path, status, latency_ms, request_id, created_at";
+/// Columns `GET /admin/audit?sort=` accepts.
+const AUDIT_SORT_COLUMNS: &[&str] = &["id", "user_id", "status", "latency_ms", "created_at"];
+/// Filters `GET /admin/audit` accepts.
+const AUDIT_FILTERS: &[FilterField] = &[
+    FilterField::text("user_id", "user_id"),
+    FilterField::text("method", "method"),
+    FilterField::text("path", "path"),
+    FilterField::integer("status", "status"),
+    FilterField::text("request_id", "request_id"),
+    FilterField::timestamp("created", "created_at"),
+];
+
+/// Append-only log of authenticated requests in the `audit_log` table.
+/// Cheap to clone.
+#[derive(Clone)]
+pub struct AuditLog {
+    pool: DbPool,
+}
+
+impl AuditLog {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn from_row(row: &Row) -> Result<AuditRecord, libsql::Error> {
+        Ok(AuditRecord {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            method: row.get(2)?,
+            path: row.get(3)?,
+            status: row.get::<u32>(4)? as u16,
+            latency_ms: row.get(5)?,
+            request_id: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    }
+
+    async fn insert(&self, entry: &AuditRecord) -> Result<(), libsql::Error> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "INSERT INTO audit_log (user_id, method, path, status, latency_ms, request_id) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            libsql::params![
+                entry.user_id.as_str(),
+                entry.method.as_str(),
+                entry.path.as_str(),
+                entry.status,
+                entry.latency_ms,
+                entry.request_id.as_deref()
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// One page of the entries matching `filter` in `order_by` order (an
+    /// already validated `ORDER BY` clause), plus how many match in total.
+    pub async fn list(
+        &self,
+        filter: &SqlFilter,
+        order_by: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<AuditRecord>, u64), libsql::Error> {
+        let conn = self.pool.get().await?;
+        let where_clause = filter.where_clause();
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT {AUDIT_COLUMNS} FROM audit_log {where_clause} \
+                     ORDER BY {order_by} LIMIT ? OFFSET ?"
+                ),
+                filter.params([limit.into(), offset.into()]),
+            )
+            .await?;
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next().await? {
+            entries.push(Self::from_row(&row)?);
+        }
+
+        let mut rows = conn
+            .query(
+                &format!("SELECT COUNT(*) FROM audit_log {where_clause}"),
+                filter.params([]),
+            )
+            .await?;
+        let total = match rows.next().await? {
+            Some(row) => row.get::<u64>(0)?,
+            None => 0,
+        };
+        Ok((entries, total))
+    }
+}
+
+/// Records every request that made it through authentication. Goes inside
+/// [`crate::auth::auth_inject_user`], which provides the [`User`]. The row
+/// is written in the background so the response is not held up by it; a
+/// failed write is logged and otherwise ignored.
+pub async fn audit_requests(State(log): State<AuditLog>, req: Request, next: Next) -> Response {
+    let Some(user) = req.extensions().get::<User>() else {
+        return next.run(req).await;
+    };
+    let user_id = user.id.clone();
+    let method = req.method().to_string();
+    // The original URI, so the entry shows the version prefix too.
+    let path = match req.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path().to_owned(),
+        None => req.uri().path().to_owned(),
+    };
+    let request_id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+    let start = Instant::now();
+
+    let response = next.run(req).await;
+
+    let entry = AuditRecord {
+        id: 0,
+        user_id,
+        method,
+        path,
+        status: response.status().as_u16(),
+        latency_ms: start.elapsed().as_millis() as i64,
+        request_id,
+        created_at: String::new(),
+    };
+    tokio::spawn(async move {
+        if let Err(err) = log.insert(&entry).await {
+            warn!(error = %err, user_id = %entry.user_id, path = %entry.path, "failed to write audit entry");
+        }
+    });
+    response
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    tag = "admin",
+    security(("bearer" = [])),
+    params(
+        PageQuery,
+        ("user_id" = Option<String>, Query, description = "Exact user id, `*` as a wildcard"),
+        ("method" = Option<String>, Query),
+        ("path" = Option<String>, Query, description = "Exact path, `*` as a wildcard"),
+        ("status" = Option<u16>, Query),
+        ("request_id" = Option<String>, Query),
+        ("created_after" = Option<String>, Query, description = "Date or date-time, inclusive"),
+        ("created_before" = Option<String>, Query, description = "Date or date-time, exclusive")
+    ),
+    responses(
+        (status = 200, body = Paginated<AuditRecord>),
+        (status = 400, description = "Invalid page, sort column or filter"),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn list_audit(
+    _admin: RequireRole<Admin>,
+    State(log): State<AuditLog>,
+    page: PageRequest,
+    filters: FilterQuery,
+) -> Result<Json<Paginated<AuditRecord>>, ApiError> {
+    // Newest first unless asked otherwise.
+    let order_by = page.order_by(AUDIT_SORT_COLUMNS, "id DESC")?;
+    let filter = filters.to_sql(AUDIT_FILTERS)?;
+    let (entries, total) = log
+        .list(&filter, &order_by, page.limit(), page.offset())
+        .await
+        .map_err(|err| ApiError::internal("audit log failure", err))?;
+    Ok(Json(page.into_page(entries, total)))
+}
@@ -0,0 +1,479 @@
+use std::{
+    env,
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderMap, Method, StatusCode, header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, jwk::JwkSet};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::{
+    api_keys::{ALL_SCOPES, API_KEY_HEADER, ApiKeyStore, required_scope},
+    error::ApiError,
+    sessions::{SessionStore, session_cookie},
+};
+
+/// The authenticated caller, inserted as a request extension by
+/// [`auth_inject_user`].
+#[derive(Debug, Clone, Serialize)]
+pub struct User {
+    /// The local `users.id`, or [`EXTERNAL_SUBJECT_PREFIX`] and the `sub` of
+    /// a token from an external issuer, which has no local account.
+    pub id: String,
+    pub email: String,
+    /// Roles from the `role`/`roles` claims of the tokens we issue, or the
+    /// account's `role` column for sessions and API keys. Always empty for
+    /// tokens from an external issuer.
+    pub roles: Vec<String>,
+    /// Scopes of the API key the request was made with; `None` (full access)
+    /// for bearer tokens and sessions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+}
+
+impl User {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    /// Whether this caller may perform `method` on `path`.
+    fn allows(&self, method: &Method, path: &str) -> bool {
+        self.has_scope(&required_scope(method, path))
+    }
+
+    /// Whether the caller's API key grants `scope` (`users:read`); always
+    /// true for bearer tokens and sessions.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes.iter().any(|s| s == ALL_SCOPES || s == scope),
+            None => true,
+        }
+    }
+}
+
+/// Prefix of the [`User::id`] of callers authenticated by an external
+/// issuer, so their subject never collides with a local user id.
+pub const EXTERNAL_SUBJECT_PREFIX: &str = "external:";
+
+/// Claims we read from the token; `exp`, `aud` and `iss` are checked by
+/// `jsonwebtoken` itself through [`Validation`].
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    email: Option<String>,
+    /// Single role, as some issuers send it.
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// A role [`RequireRole`] can ask for.
+pub trait Role {
+    const NAME: &'static str;
+}
+
+/// Administrators: user management and operational endpoints.
+pub struct Admin;
+
+impl Role for Admin {
+    const NAME: &'static str = "admin";
+}
+
+/// Extractor that only succeeds when the authenticated caller has role `R`
+/// (`RequireRole<Admin>`), answering 403 otherwise. Routes using it must sit
+/// behind [`auth_inject_user`].
+pub struct RequireRole<R: Role>(pub User, pub PhantomData<R>);
+
+impl<R: Role, S: Send + Sync> FromRequestParts<S> for RequireRole<R> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user = parts
+            .extensions
+            .get::<User>()
+            .cloned()
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        if !user.has_role(R::NAME) {
+            warn!(user_id = %user.id, role = R::NAME, path = %parts.uri.path(), "missing required role");
+            return Err(ApiError::forbidden(format!(
+                "requires the {} role",
+                R::NAME
+            )));
+        }
+        Ok(Self(user, PhantomData))
+    }
+}
+
+/// Minimum time between JWKS refreshes triggered by an unknown `kid`, so a
+/// flood of forged tokens cannot turn into a flood of JWKS fetches.
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+enum KeySource {
+    /// HS256 shared secret (`JWT_SECRET`).
+    Hmac(DecodingKey),
+    /// RS256 public key in PEM form (`JWT_PUBLIC_KEY_FILE`).
+    RsaPem(DecodingKey),
+    /// RS256 keys published as a JWK set (`JWT_JWKS_URL`), cached in memory.
+    Jwks {
+        url: String,
+        client: reqwest::Client,
+        cache: RwLock<(Instant, JwkSet)>,
+    },
+}
+
+/// Validates bearer tokens against the configured key, audience and issuer.
+pub struct JwtVerifier {
+    source: Option<KeySource>,
+    audience: Vec<String>,
+    issuer: Option<String>,
+}
+
+impl JwtVerifier {
+    /// Builds the verifier from `JWT_SECRET`, `JWT_PUBLIC_KEY_FILE` or
+    /// `JWT_JWKS_URL` (first one set wins), plus optional `JWT_AUDIENCE`
+    /// (comma-separated) and `JWT_ISSUER`.
+    pub async fn from_env() -> anyhow::Result<Self> {
+        let source = if let Ok(secret) = env::var("JWT_SECRET") {
+            Some(KeySource::Hmac(DecodingKey::from_secret(secret.as_bytes())))
+        } else if let Ok(path) = env::var("JWT_PUBLIC_KEY_FILE") {
+            let pem = std::fs::read(&path)
+                .with_context(|| format!("failed to read JWT public key {path}"))?;
+            let key = DecodingKey::from_rsa_pem(&pem)
+                .with_context(|| format!("invalid RSA public key in {path}"))?;
+            Some(KeySource::RsaPem(key))
+        } else if let Ok(url) = env::var("JWT_JWKS_URL") {
+            let client = reqwest::Client::new();
+            let keys = fetch_jwks(&client, &url).await?;
+            Some(KeySource::Jwks {
+                url,
+                client,
+                cache: RwLock::new((Instant::now(), keys)),
+            })
+        } else {
+            warn!(
+                "no JWT key configured (JWT_SECRET, JWT_PUBLIC_KEY_FILE or JWT_JWKS_URL); all authenticated routes will answer 401"
+            );
+            None
+        };
+
+        let audience = env::var("JWT_AUDIENCE")
+            .map(|aud| {
+                aud.split(',')
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            source,
+            audience,
+            issuer: env::var("JWT_ISSUER").ok(),
+        })
+    }
+
+    /// Decodes and validates `token`, returning the user it identifies.
+    pub async fn verify(&self, token: &str) -> Result<User, String> {
+        let source = self.source.as_ref().ok_or("no JWT key configured")?;
+        let header = jsonwebtoken::decode_header(token).map_err(|err| err.to_string())?;
+
+        let (key, algorithm) = match source {
+            KeySource::Hmac(key) => (key.clone(), Algorithm::HS256),
+            KeySource::RsaPem(key) => (key.clone(), Algorithm::RS256),
+            KeySource::Jwks { url, client, cache } => {
+                let kid = header.kid.as_deref().ok_or("token has no kid")?;
+                (jwks_key(url, client, cache, kid).await?, Algorithm::RS256)
+            }
+        };
+        // Pin the algorithm to the key type so an HS256 token signed with an
+        // RSA public key cannot slip through (algorithm confusion).
+        if header.alg != algorithm {
+            return Err(format!("unexpected algorithm {:?}", header.alg));
+        }
+
+        let mut validation = Validation::new(algorithm);
+        if self.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&self.audience);
+        }
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let data = jsonwebtoken::decode::<Claims>(token, &key, &validation)
+            .map_err(|err| err.to_string())?;
+        let claims = data.claims;
+        // Only HS256 tokens are ours (`TokenIssuer`): their `sub` is a local
+        // user id and their roles are the account's. An external identity
+        // provider knows neither, so it grants no local roles and its
+        // subjects stay apart from local ids.
+        if matches!(source, KeySource::Hmac(_)) {
+            let mut roles = claims.roles;
+            roles.extend(claims.role);
+            Ok(User {
+                id: claims.sub,
+                email: claims.email.unwrap_or_default(),
+                roles,
+                scopes: None,
+            })
+        } else {
+            Ok(User {
+                id: format!("{EXTERNAL_SUBJECT_PREFIX}{}", claims.sub),
+                email: claims.email.unwrap_or_default(),
+                roles: Vec::new(),
+                scopes: None,
+            })
+        }
+    }
+}
+
+async fn fetch_jwks(client: &reqwest::Client, url: &str) -> anyhow::Result<JwkSet> {
+    let keys = client
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("failed to fetch JWKS from {url}"))?
+        .json::<JwkSet>()
+        .await
+        .with_context(|| format!("invalid JWKS document at {url}"))?;
+    info!(%url, keys = keys.keys.len(), "loaded JWKS");
+    Ok(keys)
+}
+
+/// Looks `kid` up in the cached JWKS, refetching once if it is unknown and
+/// the cache is older than [`JWKS_REFRESH_INTERVAL`] (key rotation).
+async fn jwks_key(
+    url: &str,
+    client: &reqwest::Client,
+    cache: &RwLock<(Instant, JwkSet)>,
+    kid: &str,
+) -> Result<DecodingKey, String> {
+    {
+        let cached = cache.read().await;
+        if let Some(jwk) = cached.1.find(kid) {
+            return DecodingKey::from_jwk(jwk).map_err(|err| err.to_string());
+        }
+        if cached.0.elapsed() < JWKS_REFRESH_INTERVAL {
+            return Err(format!("unknown kid {kid}"));
+        }
+    }
+
+    let mut cached = cache.write().await;
+    if cached.0.elapsed() >= JWKS_REFRESH_INTERVAL {
+        let keys = fetch_jwks(client, url)
+            .await
+            .map_err(|err| format!("{err:#}"))?;
+        *cached = (Instant::now(), keys);
+    }
+    let jwk = cached.1.find(kid).ok_or(format!("unknown kid {kid}"))?;
+    DecodingKey::from_jwk(jwk).map_err(|err| err.to_string())
+}
+
+/// Everything [`auth_inject_user`] can authenticate a request with.
+#[derive(Clone)]
+pub struct AuthState {
+    pub verifier: Arc<JwtVerifier>,
+    pub sessions: SessionStore,
+    pub api_keys: ApiKeyStore,
+}
+
+impl AuthState {
+    /// Identifies the caller by, in this order, a bearer token, an
+    /// `x-api-key` header or a session cookie set by `/login`. `None` when
+    /// none of them is present and valid; `method` and `path` are only
+    /// logged.
+    pub async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+        method: &Method,
+        path: &str,
+    ) -> Result<Option<User>, ApiError> {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+
+        if let Some(token) = bearer {
+            match self.verifier.verify(token).await {
+                Ok(user) => Ok(Some(user)),
+                Err(reason) => {
+                    warn!(%method, %path, %reason, "invalid bearer token");
+                    Ok(None)
+                }
+            }
+        } else if let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+            match self.api_keys.authenticate(key).await {
+                Ok(Some(user)) => Ok(Some(user)),
+                Ok(None) => {
+                    warn!(%method, %path, "unknown or revoked API key");
+                    Ok(None)
+                }
+                Err(err) => Err(ApiError::internal("API key lookup failed", err)),
+            }
+        } else if let Some(cookie) = session_cookie(headers) {
+            match self.sessions.resolve(cookie).await {
+                Ok(Some(user)) => Ok(Some(user)),
+                Ok(None) => {
+                    warn!(%method, %path, "invalid or expired session");
+                    Ok(None)
+                }
+                Err(err) => Err(ApiError::internal("session lookup failed", err)),
+            }
+        } else {
+            warn!(%method, %path, "no bearer token, API key or session cookie");
+            Ok(None)
+        }
+    }
+}
+
+/// Lets through requests [`AuthState::authenticate`] identifies, with the
+/// [`User`] as an extension. API keys are further restricted to their
+/// scopes (403 outside of them).
+pub async fn auth_inject_user(
+    State(auth): State<AuthState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_owned();
+
+    let user = auth
+        .authenticate(req.headers(), &method, &path)
+        .await?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    tracing::Span::current().record("user_id", user.id.as_str());
+    if !user.allows(&method, &path) {
+        let required = required_scope(&method, &path);
+        warn!(%method, %path, %required, "API key lacks scope");
+        return Err(ApiError::forbidden(format!(
+            "API key lacks the {required} scope"
+        )));
+    }
+    info!(%method, %path, "authenticated request");
+    req.extensions_mut().insert(user);
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    use super::*;
+
+    const RSA_PRIVATE_KEY: &[u8] = include_bytes!("../testdata/jwt_rsa_private.pem");
+    const RSA_PUBLIC_KEY: &[u8] = include_bytes!("../testdata/jwt_rsa_public.pem");
+    const SECRET: &[u8] = b"test secret";
+
+    fn verifier(source: KeySource) -> JwtVerifier {
+        JwtVerifier {
+            source: Some(source),
+            audience: Vec::new(),
+            issuer: None,
+        }
+    }
+
+    fn hmac_verifier() -> JwtVerifier {
+        verifier(KeySource::Hmac(DecodingKey::from_secret(SECRET)))
+    }
+
+    fn rsa_verifier() -> JwtVerifier {
+        verifier(KeySource::RsaPem(
+            DecodingKey::from_rsa_pem(RSA_PUBLIC_KEY).unwrap(),
+        ))
+    }
+
+    fn claims() -> serde_json::Value {
+        json!({
+            "sub": "3",
+            "email": "someone@example.com",
+            "role": "admin",
+            "roles": ["editor"],
+            "exp": 4_000_000_000u64,
+        })
+    }
+
+    fn hs256(claims: &serde_json::Value, secret: &[u8]) -> String {
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            claims,
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap()
+    }
+
+    fn rs256(claims: &serde_json::Value) -> String {
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::RS256),
+            claims,
+            &EncodingKey::from_rsa_pem(RSA_PRIVATE_KEY).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn our_tokens_carry_the_local_id_and_roles() {
+        let user = hmac_verifier()
+            .verify(&hs256(&claims(), SECRET))
+            .await
+            .unwrap();
+        assert_eq!(user.id, "3");
+        assert_eq!(user.email, "someone@example.com");
+        assert!(user.has_role("admin"));
+        assert!(user.has_role("editor"));
+    }
+
+    #[tokio::test]
+    async fn external_tokens_get_no_local_roles_or_ids() {
+        let user = rsa_verifier().verify(&rs256(&claims())).await.unwrap();
+        assert_eq!(user.id, "external:3");
+        assert!(user.id.parse::<i64>().is_err());
+        assert!(user.roles.is_empty());
+        assert!(!user.has_role(Admin::NAME));
+    }
+
+    #[tokio::test]
+    async fn the_algorithm_is_pinned_to_the_key() {
+        // HS256 signed with the RSA public key, which anyone has.
+        let confused = hs256(&claims(), RSA_PUBLIC_KEY);
+        let err = rsa_verifier().verify(&confused).await.unwrap_err();
+        assert!(err.contains("unexpected algorithm"), "{err}");
+
+        let err = hmac_verifier().verify(&rs256(&claims())).await.unwrap_err();
+        assert!(err.contains("unexpected algorithm"), "{err}");
+
+        assert!(
+            hmac_verifier()
+                .verify(&hs256(&claims(), b"another secret"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_tokens_are_rejected() {
+        let mut claims = claims();
+        claims["exp"] = json!(1);
+        assert!(
+            hmac_verifier()
+                .verify(&hs256(&claims, SECRET))
+                .await
+                .is_err()
+        );
+    }
+}
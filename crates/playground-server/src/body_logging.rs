@@ -0,0 +1,250 @@
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use tracing::info;
+
+use crate::{api_keys::API_KEY_HEADER, error::ApiError};
+
+const REDACTED: &str = "[redacted]";
+
+/// Headers logged as [`REDACTED`].
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    API_KEY_HEADER,
+    "x-setup-token",
+];
+
+/// JSON keys and form fields whose values are logged as [`REDACTED`]: any
+/// name containing one of these (`password`, `new_password`,
+/// `refresh_token`, `client_secret`...), plus `key` itself (new API keys).
+const SECRET_FIELDS: &[&str] = &["password", "token", "secret", "authorization", "api_key"];
+
+/// Opt-in (`--log-bodies-kb`) logging of request and response bodies for
+/// debugging client integrations, with credentials redacted. Bodies are
+/// only logged when they are text, at most `max_bytes` long and of a known
+/// size; uploads, downloads and event streams pass through untouched.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLogging {
+    pub max_bytes: usize,
+}
+
+/// Logs the (redacted) headers and body of each request and response. A
+/// no-op unless body logging was enabled.
+pub async fn log_bodies(
+    State(logging): State<Option<BodyLogging>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(logging) = logging else {
+        return next.run(req).await;
+    };
+
+    let (parts, body) = req.into_parts();
+    let (body, logged) = match capture(&parts.headers, body, logging.max_bytes).await {
+        Ok(captured) => captured,
+        Err(err) => {
+            return ApiError::bad_request(format!("failed to read body: {err}")).into_response();
+        }
+    };
+    info!(
+        headers = %redact_headers(&parts.headers),
+        body = %logged,
+        "request body"
+    );
+    let response = next.run(Request::from_parts(parts, body)).await;
+
+    let (parts, body) = response.into_parts();
+    let (body, logged) = match capture(&parts.headers, body, logging.max_bytes).await {
+        Ok(captured) => captured,
+        Err(err) => return ApiError::internal("failed to read response body", err).into_response(),
+    };
+    info!(
+        status = parts.status.as_u16(),
+        headers = %redact_headers(&parts.headers),
+        body = %logged,
+        "response body"
+    );
+    Response::from_parts(parts, body)
+}
+
+/// Reads `body` into memory when it is worth logging, returning it (or an
+/// identical one) along with what to log.
+async fn capture(
+    headers: &HeaderMap,
+    body: Body,
+    max_bytes: usize,
+) -> Result<(Body, String), axum::Error> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+    let Some(len) = body.size_hint().exact() else {
+        return Ok((body, "<streamed, not logged>".to_owned()));
+    };
+    if len == 0 {
+        return Ok((body, String::new()));
+    }
+    if !is_text(&content_type) || headers.contains_key(header::CONTENT_ENCODING) {
+        return Ok((body, format!("<{len} bytes of {content_type}, not logged>")));
+    }
+    if len > max_bytes as u64 {
+        return Ok((body, format!("<{len} bytes, over the logging limit>")));
+    }
+    let bytes = axum::body::to_bytes(body, max_bytes).await?;
+    let logged = redact_body(&content_type, &bytes);
+    Ok((Body::from(bytes), logged))
+}
+
+fn is_text(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || content_type.starts_with("application/json")
+        || content_type.starts_with("application/problem+json")
+        || content_type.starts_with("application/x-www-form-urlencoded")
+}
+
+fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name == "key" || SECRET_FIELDS.iter().any(|field| name.contains(field))
+}
+
+fn redact_headers(headers: &HeaderMap) -> String {
+    let mut out = String::new();
+    for (name, value) in headers {
+        if !out.is_empty() {
+            out.push_str(", ");
+        }
+        let value = if SECRET_HEADERS.contains(&name.as_str()) {
+            REDACTED
+        } else {
+            value.to_str().unwrap_or("<binary>")
+        };
+        out.push_str(name.as_str());
+        out.push_str(": ");
+        out.push_str(value);
+    }
+    out
+}
+
+fn redact_body(content_type: &str, bytes: &Bytes) -> String {
+    if content_type.starts_with("application/x-www-form-urlencoded") {
+        let body = String::from_utf8_lossy(bytes);
+        return body
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if is_secret(name) => format!("{name}={REDACTED}"),
+                _ => pair.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+    }
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(mut value) => {
+            redact_json(&mut value);
+            value.to_string()
+        }
+        // Nothing can be redacted in JSON that does not parse, and a
+        // slightly malformed login body still has the password in it.
+        Err(_) if content_type.contains("json") || looks_like_json(bytes) => {
+            format!("<unparseable JSON, {} bytes, not logged>", bytes.len())
+        }
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+fn looks_like_json(bytes: &[u8]) -> bool {
+    matches!(bytes.trim_ascii_start().first(), Some(b'{' | b'['))
+}
+
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                if is_secret(key) {
+                    *value = Value::String(REDACTED.to_owned());
+                } else {
+                    redact_json(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redact(content_type: &str, body: &str) -> String {
+        redact_body(content_type, &Bytes::copy_from_slice(body.as_bytes()))
+    }
+
+    #[test]
+    fn redacts_json_fields_at_any_depth() {
+        let logged = redact(
+            "application/json",
+            r#"{"email":"a@example.com","password":"hunter2","nested":[{"refresh_token":"r1","key":"k1","name":"n"}]}"#,
+        );
+        let value: Value = serde_json::from_str(&logged).unwrap();
+        assert_eq!(value["email"], "a@example.com");
+        assert_eq!(value["password"], REDACTED);
+        assert_eq!(value["nested"][0]["refresh_token"], REDACTED);
+        assert_eq!(value["nested"][0]["key"], REDACTED);
+        assert_eq!(value["nested"][0]["name"], "n");
+        assert!(!logged.contains("hunter2"));
+    }
+
+    #[test]
+    fn redacts_form_fields() {
+        let logged = redact(
+            "application/x-www-form-urlencoded",
+            "email=a%40example.com&new_password=hunter2&client_secret=s&remember=on",
+        );
+        assert_eq!(
+            logged,
+            "email=a%40example.com&new_password=[redacted]&client_secret=[redacted]&remember=on"
+        );
+    }
+
+    #[test]
+    fn malformed_json_is_not_logged() {
+        let body = r#"{"email":"a@example.com","password":"hunter2""#;
+        for content_type in [
+            "application/json",
+            "application/json; charset=utf-8",
+            "text/plain",
+        ] {
+            let logged = redact(content_type, body);
+            assert_eq!(
+                logged,
+                format!("<unparseable JSON, {} bytes, not logged>", body.len())
+            );
+        }
+    }
+
+    #[test]
+    fn plain_text_is_logged_as_is() {
+        assert_eq!(redact("text/plain", "hello"), "hello");
+    }
+
+    #[test]
+    fn secret_headers_are_redacted() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer abc".parse().unwrap());
+        headers.insert(header::ACCEPT, "application/json".parse().unwrap());
+        let logged = redact_headers(&headers);
+        assert_eq!(
+            logged,
+            "authorization: [redacted], accept: application/json"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use axum::http::HeaderValue;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+use utoipa::ToSchema;
+
+use crate::{
+    db::DbPool,
+    health::{
+        DEFAULT_CACHE_TTL, DEFAULT_MIN_FREE_DISK_MB, DatabaseCheck, HealthCheck, HealthChecks,
+        UpstreamCheck,
+    },
+    load_shed::{DEFAULT_MAX_API, DEFAULT_MAX_UPLOADS, LoadShedder},
+    rate_limit::RateLimitConfig,
+    security_headers::{DEFAULT_CSP, DEFAULT_DASHBOARD_CSP, DEFAULT_HSTS_MAX_AGE, SecurityHeaders},
+};
+
+/// Contents of the file given with `--config` (or `SERVER_CONFIG`), in
+/// TOML. Every section is optional. `[log]`, `[rate_limit]` and
+/// `[features]` are re-read on `SIGHUP` or `POST /admin/reload`; timeouts,
+/// load shedding, webhooks and security headers only change on restart.
+///
+/// ```toml
+/// [timeouts]
+/// api = "5s"
+/// auth = "10s"
+/// uploads = "120s"
+/// ops = "5s"
+///
+/// [load_shedding]
+/// api = 512
+/// uploads = 8
+///
+/// [health]
+/// cache_ttl = "5s"
+/// disk_path = ".tmp"
+/// min_free_disk_mb = 100
+///
+/// [[health.upstreams]]
+/// name = "identity"
+/// url = "https://id.example.com/healthz"
+///
+/// [log]
+/// level = "playground_server=debug"
+///
+/// [rate_limit]
+/// per_second = 10.0
+/// burst = 20
+///
+/// [features]
+/// dashboard = false
+///
+/// [[webhooks]]
+/// url = "https://hooks.example.com/playground"
+/// secret = "change-me"
+/// events = ["recording.uploaded", "user.created"]
+///
+/// [security_headers]
+/// content_security_policy = "default-src 'self'"
+/// hsts_max_age = "180days"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub timeouts: TimeoutsSection,
+    #[serde(default)]
+    pub load_shedding: LoadSheddingSection,
+    #[serde(default)]
+    pub health: HealthSection,
+    #[serde(default)]
+    pub log: LogSection,
+    #[serde(default)]
+    pub rate_limit: RateLimitSection,
+    #[serde(default)]
+    pub features: Features,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub security_headers: SecurityHeadersSection,
+}
+
+/// `[timeouts]`: how long each route group's handlers may take, as a number
+/// of seconds or a `humantime` string. Groups left out use the defaults.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeoutsSection {
+    /// The versioned JSON API.
+    api: Option<String>,
+    /// Login, token and setup endpoints, which hash passwords.
+    auth: Option<String>,
+    /// `POST /recordings`.
+    uploads: Option<String>,
+    /// Health checks, metrics, docs and the dashboard.
+    ops: Option<String>,
+}
+
+/// `[load_shedding]`: how many requests may be in flight at once before
+/// new ones are answered 503, for uploads and for everything else. `0`
+/// removes the limit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoadSheddingSection {
+    api: Option<usize>,
+    uploads: Option<usize>,
+}
+
+/// `[health]`: what `/readyz` checks besides the database, and how long it
+/// reuses a report before checking again.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HealthSection {
+    cache_ttl: Option<String>,
+    /// Directory whose file system must keep `min_free_disk_mb` free
+    /// (default `.tmp`); `0` MB drops the disk check.
+    disk_path: Option<PathBuf>,
+    min_free_disk_mb: Option<u64>,
+    #[serde(default)]
+    upstreams: Vec<UpstreamConfig>,
+}
+
+/// `[[health.upstreams]]`: a service that must answer `url` with a 2xx or
+/// 3xx for this one to be ready, reported under `name`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamConfig {
+    name: String,
+    url: String,
+}
+
+/// `[log]`: an `EnvFilter` directive (`info`, `playground_server=debug`)
+/// replacing the one from `RUST_LOG`. Left out, `RUST_LOG` applies again.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogSection {
+    level: Option<String>,
+}
+
+/// `[rate_limit]`: overrides `--rate-limit` / `--rate-burst`. A rate of `0`
+/// disables limiting.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitSection {
+    per_second: Option<f64>,
+    burst: Option<u32>,
+}
+
+/// `[features]`: parts of the server that can be switched off without a
+/// restart. Disabled endpoints answer 404. Everything is on by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// `GET /dashboard`.
+    pub dashboard: bool,
+    /// `GET /events`.
+    pub events: bool,
+    /// `POST /recordings`.
+    pub uploads: bool,
+    /// `POST /screenshots`.
+    pub screenshots: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            dashboard: true,
+            events: true,
+            uploads: true,
+            screenshots: true,
+        }
+    }
+}
+
+/// `[[webhooks]]`: a subscriber that gets application events POSTed to
+/// `url`, signed with `secret` (see [`crate::webhooks::WebhookDispatcher`]).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub secret: String,
+    /// Event kinds to send (`recording.uploaded`, `migrations.applied`,
+    /// ...). Left out or empty, every event is sent.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl WebhookConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let url = reqwest::Url::parse(&self.url)
+            .with_context(|| format!("invalid webhooks.url {:?}", self.url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            anyhow::bail!(
+                "invalid webhooks.url {:?}: expected http or https",
+                self.url
+            );
+        }
+        if self.secret.is_empty() {
+            anyhow::bail!("webhooks.secret for {} must not be empty", self.url);
+        }
+        Ok(())
+    }
+
+    pub fn wants(&self, kind: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|event| event == kind)
+    }
+}
+
+/// `[security_headers]`: the `Content-Security-Policy` of every response
+/// and of `/dashboard`, and how long browsers stick to HTTPS
+/// (`Strict-Transport-Security`, only sent with TLS; `0` turns it off).
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityHeadersSection {
+    content_security_policy: Option<String>,
+    dashboard_content_security_policy: Option<String>,
+    hsts_max_age: Option<String>,
+}
+
+/// Resolved timeout of each route group.
+#[derive(Debug, Clone, Copy)]
+pub struct RouteTimeouts {
+    pub api: Duration,
+    pub auth: Duration,
+    pub uploads: Duration,
+    pub ops: Duration,
+}
+
+impl ServerConfig {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("invalid config file {}", path.display()))
+    }
+}
+
+impl TimeoutsSection {
+    /// Fills in the groups the file leaves out: `default` (`REQUEST_TIMEOUT`)
+    /// for everything but uploads, which get `upload_default`.
+    pub fn resolve(
+        &self,
+        default: Duration,
+        upload_default: Duration,
+    ) -> anyhow::Result<RouteTimeouts> {
+        let get = |value: &Option<String>, fallback: Duration, name: &str| {
+            value.as_deref().map_or(Ok(fallback), |value| {
+                crate::parse_duration(value).with_context(|| format!("invalid timeouts.{name}"))
+            })
+        };
+        Ok(RouteTimeouts {
+            api: get(&self.api, default, "api")?,
+            auth: get(&self.auth, default, "auth")?,
+            uploads: get(&self.uploads, upload_default, "uploads")?,
+            ops: get(&self.ops, default, "ops")?,
+        })
+    }
+}
+
+impl LoadSheddingSection {
+    pub fn resolve(&self) -> LoadShedder {
+        LoadShedder::new(
+            self.api.unwrap_or(DEFAULT_MAX_API),
+            self.uploads.unwrap_or(DEFAULT_MAX_UPLOADS),
+        )
+    }
+}
+
+impl HealthSection {
+    /// The database check, the disk check unless turned off, and one check
+    /// per upstream, whose names and URLs are validated here.
+    pub fn resolve(&self, pool: DbPool) -> anyhow::Result<HealthChecks> {
+        let cache_ttl = self
+            .cache_ttl
+            .as_deref()
+            .map_or(Ok(DEFAULT_CACHE_TTL), |value| {
+                crate::parse_duration(value).context("invalid health.cache_ttl")
+            })?;
+        let mut checks: Vec<Box<dyn HealthCheck>> = vec![Box::new(DatabaseCheck(pool))];
+
+        #[cfg(unix)]
+        {
+            let min_free_mb = self.min_free_disk_mb.unwrap_or(DEFAULT_MIN_FREE_DISK_MB);
+            if min_free_mb > 0 {
+                checks.push(Box::new(crate::health::DiskSpaceCheck {
+                    path: self
+                        .disk_path
+                        .clone()
+                        .unwrap_or_else(|| PathBuf::from(".tmp")),
+                    min_free_bytes: min_free_mb * 1024 * 1024,
+                }));
+            }
+        }
+
+        if !self.upstreams.is_empty() {
+            let client = reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .context("failed to build the health check HTTP client")?;
+            for upstream in &self.upstreams {
+                let url = reqwest::Url::parse(&upstream.url)
+                    .with_context(|| format!("invalid health.upstreams.url {:?}", upstream.url))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    anyhow::bail!(
+                        "invalid health.upstreams.url {:?}: expected http or https",
+                        upstream.url
+                    );
+                }
+                if upstream.name.is_empty()
+                    || checks.iter().any(|check| check.name() == upstream.name)
+                {
+                    anyhow::bail!(
+                        "health.upstreams.name {:?} must be non-empty and unique",
+                        upstream.name
+                    );
+                }
+                checks.push(Box::new(UpstreamCheck {
+                    name: upstream.name.clone(),
+                    url: upstream.url.clone(),
+                    client: client.clone(),
+                }));
+            }
+        }
+        Ok(HealthChecks::new(checks, cache_ttl))
+    }
+}
+
+impl SecurityHeadersSection {
+    /// Fills in the defaults and checks every value is a valid header.
+    pub fn resolve(&self, tls: bool) -> anyhow::Result<SecurityHeaders> {
+        let policy = |value: &Option<String>, default: &str, name: &str| {
+            HeaderValue::from_str(value.as_deref().unwrap_or(default))
+                .with_context(|| format!("invalid security_headers.{name}"))
+        };
+        let max_age = self
+            .hsts_max_age
+            .as_deref()
+            .map_or(Ok(DEFAULT_HSTS_MAX_AGE), |value| {
+                crate::parse_duration(value).context("invalid security_headers.hsts_max_age")
+            })?;
+        let strict_transport_security = (tls && !max_age.is_zero())
+            .then(|| HeaderValue::try_from(format!("max-age={}", max_age.as_secs())))
+            .transpose()?;
+        Ok(SecurityHeaders {
+            content_security_policy: policy(
+                &self.content_security_policy,
+                DEFAULT_CSP,
+                "content_security_policy",
+            )?,
+            dashboard_content_security_policy: policy(
+                &self.dashboard_content_security_policy,
+                DEFAULT_DASHBOARD_CSP,
+                "dashboard_content_security_policy",
+            )?,
+            strict_transport_security,
+        })
+    }
+}
+
+impl LogSection {
+    /// The filter to install, or `None` to go back to the startup one.
+    pub fn resolve(&self) -> anyhow::Result<Option<EnvFilter>> {
+        self.level
+            .as_deref()
+            .map(|level| {
+                EnvFilter::try_new(level).with_context(|| format!("invalid log.level {level:?}"))
+            })
+            .transpose()
+    }
+}
+
+impl RateLimitSection {
+    /// Fills in what the file leaves out from `base` (the command line),
+    /// or `default_burst` when the command line disabled limiting.
+    pub fn resolve(
+        &self,
+        base: Option<RateLimitConfig>,
+        default_burst: u32,
+    ) -> anyhow::Result<Option<RateLimitConfig>> {
+        let per_second = match self.per_second {
+            Some(rate) if !rate.is_finite() || rate < 0.0 => {
+                anyhow::bail!("invalid rate_limit.per_second {rate}: expected requests per second")
+            }
+            Some(rate) => rate,
+            None => base.map_or(0.0, |base| base.per_second),
+        };
+        let burst = match self.burst {
+            Some(0) => anyhow::bail!("invalid rate_limit.burst 0: expected a positive integer"),
+            Some(burst) => burst,
+            None => base.map_or(default_burst, |base| base.burst),
+        };
+        Ok((per_second > 0.0).then_some(RateLimitConfig { per_second, burst }))
+    }
+}
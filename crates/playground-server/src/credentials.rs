@@ -0,0 +1,242 @@
+//! Password hashing and verification with argon2id, plus creation of the
+//! first administrator.
+//!
+//! Lives in the library so the HTTP server (login, `POST /setup/admin`) and
+//! the `create-admin` binary hash with the same parameters and store users
+//! the same way.
+
+use std::env;
+use std::sync::Arc;
+
+// `argon2` implements the algorithm; the `password_hash` traits give the
+// hash/verify API over PHC strings (`$argon2id$v=19$m=...`), which keep the
+// salt and parameters next to the hash.
+use argon2::{
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+    password_hash::{SaltString, rand_core::OsRng},
+};
+use libsql::Connection;
+use thiserror::Error;
+
+use playground_common::error::{self, PlaygroundError};
+
+/// Passwords shorter than this are refused when creating credentials.
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// Errors hashing passwords or storing credentials.
+#[derive(Error, Debug)]
+pub enum CredentialsError {
+    /// argon2 parameters outside the limits the algorithm accepts.
+    #[error("invalid argon2 parameters: {0}")]
+    InvalidParams(String),
+    /// An environment variable whose value is not a number.
+    #[error("invalid {0} {1:?}: expected a positive integer")]
+    InvalidEnv(&'static str, String),
+    #[error("password must be at least {MIN_PASSWORD_LEN} characters long")]
+    WeakPassword,
+    #[error("failed to hash password: {0}")]
+    Hash(String),
+    /// An administrator already exists; the first one can only be created
+    /// once.
+    #[error("an admin user already exists")]
+    AdminExists,
+    /// The email belongs to another user.
+    #[error("email is already in use")]
+    EmailInUse,
+    #[error("database error: {0}")]
+    Db(#[from] libsql::Error),
+}
+
+impl From<CredentialsError> for PlaygroundError {
+    /// Refused parameters and passwords are usage errors (`2`): nothing was
+    /// stored and retrying with the same input will not help.
+    fn from(err: CredentialsError) -> Self {
+        match err {
+            CredentialsError::InvalidParams(_)
+            | CredentialsError::InvalidEnv(..)
+            | CredentialsError::WeakPassword => Self::usage(err),
+            err => Self::Other(error::flatten(err)),
+        }
+    }
+}
+
+/// argon2id cost. The defaults are OWASP's recommendation (19 MiB, 2
+/// iterations, 1 thread) and can be tuned through environment variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashParams {
+    /// Memory in KiB (`ARGON2_MEMORY_KIB`).
+    pub memory_kib: u32,
+    /// Number of passes (`ARGON2_ITERATIONS`).
+    pub iterations: u32,
+    /// Degree of parallelism (`ARGON2_PARALLELISM`).
+    pub parallelism: u32,
+}
+
+impl Default for HashParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl HashParams {
+    /// Reads the parameters from the environment, with the default for any
+    /// that is not set.
+    pub fn from_env() -> Result<Self, CredentialsError> {
+        let defaults = Self::default();
+        let read = |name: &'static str, default: u32| match env::var(name) {
+            Ok(value) => value
+                .parse::<u32>()
+                .map_err(|_| CredentialsError::InvalidEnv(name, value)),
+            Err(_) => Ok(default),
+        };
+        Ok(Self {
+            memory_kib: read("ARGON2_MEMORY_KIB", defaults.memory_kib)?,
+            iterations: read("ARGON2_ITERATIONS", defaults.iterations)?,
+            parallelism: read("ARGON2_PARALLELISM", defaults.parallelism)?,
+        })
+    }
+}
+
+/// Hashes and verifies passwords. Cheap to clone.
+#[derive(Clone)]
+pub struct Credentials {
+    params: Params,
+    /// A hash with the current parameters, verified in place of the hash of
+    /// an account that does not exist (see [`verify_dummy`](Self::verify_dummy)).
+    dummy_hash: Arc<str>,
+}
+
+impl Credentials {
+    /// Validates the parameters once, up front, so a bad value stops the
+    /// process at startup rather than at the first login. Costs one hash,
+    /// the one [`verify_dummy`](Self::verify_dummy) uses.
+    pub fn new(params: HashParams) -> Result<Self, CredentialsError> {
+        let params = Params::new(
+            params.memory_kib,
+            params.iterations,
+            params.parallelism,
+            None,
+        )
+        .map_err(|err| CredentialsError::InvalidParams(err.to_string()))?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone());
+        let dummy_hash = argon2
+            .hash_password(b"", &SaltString::generate(&mut OsRng))
+            .map_err(|err| CredentialsError::Hash(err.to_string()))?
+            .to_string();
+        Ok(Self {
+            params,
+            dummy_hash: dummy_hash.into(),
+        })
+    }
+
+    /// Shorthand for `Credentials::new(HashParams::from_env()?)`.
+    pub fn from_env() -> Result<Self, CredentialsError> {
+        Self::new(HashParams::from_env()?)
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    /// The PHC hash of `password` with a random salt. Refuses passwords
+    /// shorter than [`MIN_PASSWORD_LEN`].
+    pub fn hash(&self, password: &str) -> Result<String, CredentialsError> {
+        if password.chars().count() < MIN_PASSWORD_LEN {
+            return Err(CredentialsError::WeakPassword);
+        }
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|err| CredentialsError::Hash(err.to_string()))
+    }
+
+    /// Checks `password` against a PHC hash. The parameters come from the
+    /// hash itself, so old hashes keep working after the configuration
+    /// changes. Unreadable hashes (like the `!` of accounts without a
+    /// password) never match, but take the same time, as in
+    /// [`verify_dummy`](Self::verify_dummy).
+    pub fn verify(&self, password: &str, hash: &str) -> bool {
+        match PasswordHash::new(hash) {
+            Ok(hash) => self
+                .argon2()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(_) => {
+                self.verify_dummy(password);
+                false
+            }
+        }
+    }
+
+    /// Checks `password` against a throwaway hash, only to spend the time a
+    /// real [`verify`](Self::verify) would. Login does this when the email
+    /// has no account, so response times do not reveal which accounts
+    /// exist.
+    pub fn verify_dummy(&self, password: &str) {
+        if let Ok(hash) = PasswordHash::new(&self.dummy_hash) {
+            let _ = self.argon2().verify_password(password.as_bytes(), &hash);
+        }
+    }
+
+    /// Whether the hash was made with another algorithm or other parameters
+    /// than the current ones, in which case it is worth redoing on the next
+    /// successful login.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+        if parsed.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+        match Params::try_from(&parsed) {
+            Ok(params) => {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+/// Creates the first administrator with a hash from [`Credentials::hash`]
+/// (hashing is left to the caller because it is slow and the server would
+/// rather do it off the async threads). Fails with
+/// [`CredentialsError::AdminExists`] if there is any admin already: the
+/// check and the `INSERT` are a single statement, so two concurrent calls
+/// cannot create two. Returns the new user's id.
+pub async fn create_initial_admin(
+    conn: &Connection,
+    name: &str,
+    email: &str,
+    password_hash: &str,
+) -> Result<i64, CredentialsError> {
+    let mut rows = conn
+        .query(
+            "INSERT INTO users (name, email, password_hash, role) \
+             SELECT ?1, ?2, ?3, 'admin' \
+             WHERE NOT EXISTS (SELECT 1 FROM users WHERE role = 'admin') \
+             RETURNING id",
+            libsql::params![name, email, password_hash],
+        )
+        .await
+        .map_err(insert_error)?;
+    match rows.next().await.map_err(insert_error)? {
+        Some(row) => Ok(row.get(0)?),
+        None => Err(CredentialsError::AdminExists),
+    }
+}
+
+/// Turns a `UNIQUE` violation on `users.email` into its own error.
+fn insert_error(err: libsql::Error) -> CredentialsError {
+    if err.to_string().contains("UNIQUE constraint failed") {
+        CredentialsError::EmailInUse
+    } else {
+        CredentialsError::Db(err)
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use maud::{DOCTYPE, Markup, html};
+
+use crate::{
+    error::ApiError,
+    filters::SqlFilter,
+    http_metrics,
+    recordings::{RecordingRecord, RecordingStore},
+    screenshots::{ScreenshotStore, StoredScreenshot},
+    versioning,
+};
+
+/// How many recordings and screenshots the dashboard shows.
+const RECENT_ITEMS: usize = 8;
+/// The page reloads itself this often, in seconds.
+const REFRESH_SECS: u32 = 30;
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+h1 { margin-bottom: 0; }
+.subtitle { color: #666; margin-top: .25rem; }
+.stats { display: flex; flex-wrap: wrap; gap: 1rem; margin: 1.5rem 0; }
+.stat { border: 1px solid #ddd; border-radius: 6px; padding: .75rem 1.25rem; min-width: 8rem; }
+.stat .value { font-size: 1.5rem; font-weight: 600; }
+.stat .label { color: #666; font-size: .85rem; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: .4rem .6rem; border-bottom: 1px solid #eee; }
+.gallery { display: flex; flex-wrap: wrap; gap: 1rem; }
+.gallery figure { margin: 0; }
+.gallery img { max-width: 240px; border: 1px solid #ddd; }
+.gallery figcaption { color: #666; font-size: .8rem; }
+.empty { color: #888; }
+";
+
+/// When the server started, for the uptime shown on the dashboard.
+#[derive(Clone, Copy)]
+pub struct StartedAt(pub Instant);
+
+fn uptime(started: Instant) -> String {
+    // Whole seconds; `humantime` would otherwise print down to nanoseconds.
+    humantime::format_duration(Duration::from_secs(started.elapsed().as_secs())).to_string()
+}
+
+fn human_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn stat(label: &str, value: impl std::fmt::Display) -> Markup {
+    html! {
+        div.stat {
+            div.value { (value) }
+            div.label { (label) }
+        }
+    }
+}
+
+fn recordings_table(recordings: &[RecordingRecord]) -> Markup {
+    html! {
+        @if recordings.is_empty() {
+            p.empty { "No recordings uploaded yet." }
+        } @else {
+            table {
+                thead {
+                    tr { th { "#" } th { "Name" } th { "Duration" } th { "Format" } th { "Size" } th { "Uploaded by" } th { "At" } }
+                }
+                tbody {
+                    @for recording in recordings {
+                        tr {
+                            td { (recording.id) }
+                            td {
+                                a href={ (versioning::CURRENT) "/recordings/" (recording.id) } {
+                                    (recording.original_name)
+                                }
+                            }
+                            td { (format!("{:.1} s", recording.duration_ms as f64 / 1000.0)) }
+                            td { (recording.channels) " ch · " (recording.sample_rate) " Hz · " (recording.bits_per_sample) " bit" }
+                            td { (human_bytes(recording.size_bytes)) }
+                            td { (recording.uploaded_by) }
+                            td { (recording.created_at) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn screenshot_gallery(screenshots: &[StoredScreenshot]) -> Markup {
+    html! {
+        @if screenshots.is_empty() {
+            p.empty { "No screenshots captured yet." }
+        } @else {
+            div.gallery {
+                @for shot in screenshots {
+                    figure {
+                        a href=(shot.url) {
+                            img src=(shot.url) alt=(shot.name) loading="lazy";
+                        }
+                        figcaption { (httpdate::fmt_http_date(shot.modified)) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `GET /dashboard`: a server-rendered overview for humans (uptime, request
+/// counters, the latest recordings and screenshots). It reloads itself every
+/// [`REFRESH_SECS`] seconds.
+#[utoipa::path(
+    get,
+    path = "/dashboard",
+    tag = "meta",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, content_type = "text/html", body = String),
+        (status = 401)
+    )
+)]
+pub async fn dashboard(
+    State(StartedAt(started)): State<StartedAt>,
+    State(recordings): State<RecordingStore>,
+    State(screenshots): State<ScreenshotStore>,
+) -> Result<Markup, ApiError> {
+    let (recent, recording_count) = recordings
+        .list(&SqlFilter::default(), "id DESC", RECENT_ITEMS as i64, 0)
+        .await
+        .map_err(|err| ApiError::internal("dashboard failure", err))?;
+    let shots = screenshots
+        .latest(RECENT_ITEMS)
+        .await
+        .map_err(|err| ApiError::internal("dashboard failure", err))?;
+    let requests = http_metrics::request_totals();
+
+    Ok(html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                meta http-equiv="refresh" content=(REFRESH_SECS);
+                title { "simple-http-server dashboard" }
+                style { (STYLE) }
+            }
+            body {
+                h1 { "simple-http-server" }
+                p.subtitle {
+                    "v" (env!("CARGO_PKG_VERSION")) " · up " (uptime(started)) " · "
+                    a href="/docs" { "API docs" } " · "
+                    a href="/metrics" { "metrics" }
+                }
+                div.stats {
+                    (stat("requests", requests.total))
+                    (stat("in flight", requests.in_flight))
+                    (stat("4xx responses", requests.client_errors))
+                    (stat("5xx responses", requests.server_errors))
+                    (stat("recordings", recording_count))
+                }
+                h2 { "Recent recordings" }
+                (recordings_table(&recent))
+                h2 { "Latest screenshots" }
+                (screenshot_gallery(&shots))
+            }
+        }
+    })
+}
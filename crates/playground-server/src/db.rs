@@ -0,0 +1,140 @@
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use libsql::{Builder, Connection, Database};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How long a checkout took, including the wait for a free connection.
+pub const CHECKOUT_DURATION: &str = "db_pool_checkout_seconds";
+const CONNECTIONS_IN_USE: &str = "db_pool_connections_in_use";
+const CONNECTIONS_OPEN: &str = "db_pool_connections_open";
+
+/// Checkout latency buckets in seconds: an idle connection is handed out in
+/// microseconds, anything slower means handlers queue for the pool.
+pub const CHECKOUT_BUCKETS: &[f64] = &[
+    0.000_01, 0.000_05, 0.000_1, 0.000_5, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0,
+];
+
+/// Connections kept per server (`DB_POOL_SIZE`).
+pub const DEFAULT_POOL_SIZE: usize = 8;
+
+/// How long a statement waits on another connection's write lock before
+/// failing with `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A bounded set of connections to the server's libsql database, so
+/// concurrent handlers each get their own instead of queueing on a single
+/// one. Connections are opened on first use and reused afterwards. Cheap to
+/// clone.
+#[derive(Clone)]
+pub struct DbPool(Arc<Inner>);
+
+struct Inner {
+    db: Database,
+    idle: Mutex<Vec<Connection>>,
+    permits: Arc<Semaphore>,
+}
+
+impl DbPool {
+    /// Opens (or creates) the database file and switches it to WAL, so
+    /// readers do not block on a writer.
+    pub async fn open(path: &str, size: usize) -> Result<Self, libsql::Error> {
+        let db = Builder::new_local(path).build().await?;
+        let pool = Self(Arc::new(Inner {
+            db,
+            idle: Mutex::new(Vec::with_capacity(size)),
+            permits: Arc::new(Semaphore::new(size.max(1))),
+        }));
+        let conn = pool.get().await?;
+        // The pragma answers with the resulting mode, so it is a query.
+        conn.query("PRAGMA journal_mode = WAL", ()).await?;
+        drop(conn);
+        Ok(pool)
+    }
+
+    /// Checks out a connection, waiting while all of them are in use. It
+    /// goes back to the pool when the guard is dropped.
+    pub async fn get(&self) -> Result<PooledConnection, libsql::Error> {
+        let start = Instant::now();
+        let permit = self
+            .0
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the pool semaphore is never closed");
+        let idle = self.0.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => {
+                let conn = self.0.db.connect()?;
+                conn.busy_timeout(BUSY_TIMEOUT)?;
+                // SQLite leaves foreign keys off per connection; without
+                // this, the schema's `ON DELETE CASCADE`s do nothing.
+                conn.execute("PRAGMA foreign_keys = ON", ()).await?;
+                metrics::gauge!(CONNECTIONS_OPEN).increment(1);
+                conn
+            }
+        };
+        metrics::histogram!(CHECKOUT_DURATION).record(start.elapsed().as_secs_f64());
+        metrics::gauge!(CONNECTIONS_IN_USE).increment(1);
+        Ok(PooledConnection {
+            conn: Some(conn),
+            pool: self.0.clone(),
+            _permit: permit,
+        })
+    }
+}
+
+/// A connection checked out of a [`DbPool`].
+pub struct PooledConnection {
+    conn: Option<Connection>,
+    pool: Arc<Inner>,
+    // Released after the connection is back in the idle list (fields drop
+    // after `Drop::drop`), so the next waiter finds it there.
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection taken before drop")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool
+                .idle
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(conn);
+        }
+        metrics::gauge!(CONNECTIONS_IN_USE).decrement(1);
+    }
+}
+
+#[cfg(test)]
+impl DbPool {
+    /// A private in-memory database with every migration in the workspace's
+    /// `migrations/` applied. One connection only: each connection to
+    /// `:memory:` would get a database of its own.
+    pub async fn migrated_in_memory() -> Self {
+        use migrate_core::run_migrations_in;
+        use migrate_libsql::LibSqlAdapter;
+
+        let pool = Self::open(":memory:", 1).await.expect("open in-memory db");
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../migrations");
+        let conn = pool.get().await.expect("check out connection");
+        run_migrations_in(&LibSqlAdapter::new(conn.clone()), &dir)
+            .await
+            .expect("apply migrations");
+        drop(conn);
+        pool
+    }
+}
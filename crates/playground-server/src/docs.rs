@@ -0,0 +1,94 @@
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+/// OpenAPI document for every route, served as JSON at [`OPENAPI_PATH`] and
+/// browsable through Swagger UI at `/docs`. The versioned API is documented
+/// under its prefix only, not at the deprecated unversioned paths.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "simple-http-server"),
+    paths(
+        crate::hello_world,
+        crate::status_server,
+        crate::health::healthz,
+        crate::health::readyz,
+        crate::http_metrics::metrics_handler,
+        crate::dashboard::dashboard,
+    ),
+    nest((path = "/v1", api = V1Api)),
+    components(schemas(crate::error::Problem)),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+/// Routes mounted under `/v1`.
+#[derive(OpenApi)]
+#[openapi(paths(
+    crate::events::events,
+    crate::events::poll_events,
+    crate::sessions::login,
+    crate::sessions::logout,
+    crate::tokens::issue_token,
+    crate::tokens::refresh_token,
+    crate::setup::create_admin,
+    crate::users::me,
+    crate::users::list_users,
+    crate::users::create_user,
+    crate::users::get_user,
+    crate::users::update_user,
+    crate::users::delete_user,
+    crate::notes::list_notes,
+    crate::notes::create_note,
+    crate::notes::get_note,
+    crate::notes::update_note,
+    crate::notes::delete_note,
+    crate::recordings::list_recordings,
+    crate::recordings::upload_recording,
+    crate::recordings::download_recording,
+    crate::screenshots::capture_screenshots,
+    crate::screenshots::list_screenshots,
+    crate::screenshots::get_screenshot,
+    crate::jobs::create_job,
+    crate::jobs::get_job,
+    crate::admin::list_migrations,
+    crate::admin::run_pending_migrations,
+    crate::reload::reload_config,
+    crate::maintenance::get_maintenance,
+    crate::maintenance::enable_maintenance,
+    crate::maintenance::disable_maintenance,
+    crate::audit::list_audit,
+    crate::webhooks::list_deliveries,
+    crate::api_keys::list_api_keys,
+    crate::api_keys::create_api_key,
+    crate::api_keys::revoke_api_key,
+))]
+struct V1Api;
+
+pub const OPENAPI_PATH: &str = "/api-docs/openapi.json";
+
+/// Declares the `bearer` and `api_key` schemes referenced by the routes'
+/// `security(...)` attributes.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(
+                crate::api_keys::API_KEY_HEADER,
+            ))),
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use std::{any::Any, backtrace::Backtrace, collections::BTreeMap, fmt::Display, sync::Once};
+
+use axum::{
+    Json,
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{HeaderValue, Method, StatusCode, Uri, header, response::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{REQUEST_ID_HEADER, proxy::Proxied};
+
+/// Media type of RFC 7807 problem documents.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Longest plain-text error body turned into a `detail`.
+const MAX_TEXT_DETAIL: u64 = 1024;
+
+/// RFC 7807 problem details, the body of every error response.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Problem {
+    /// Always `about:blank`: the status code says what kind of problem it is.
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// The status code's reason phrase.
+    pub title: &'static str,
+    pub status: u16,
+    /// What was wrong with this particular request, when there is more to
+    /// say than the title.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Same value as the `x-request-id` response header.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Per-field messages of a 422, keyed by field name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<FieldErrors>,
+    /// Methods the resource supports, on a 405 (same as the `Allow` header).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_methods: Option<Vec<String>>,
+}
+
+/// Validation messages per field, e.g. `{"email": ["must be a valid email
+/// address"]}`.
+pub type FieldErrors = BTreeMap<String, Vec<String>>;
+
+/// Error returned by handlers and extractors; renders as a problem+json
+/// document. Build one from a [`StatusCode`] (`StatusCode::NOT_FOUND.into()`)
+/// or with the helpers below, which also take a `detail`.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    status: StatusCode,
+    detail: Option<String>,
+    errors: Option<FieldErrors>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            detail: Some(detail.into()),
+            errors: None,
+        }
+    }
+
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, detail)
+    }
+
+    pub fn forbidden(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, detail)
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, detail)
+    }
+
+    /// 422 listing what is wrong with each field of the request.
+    pub fn invalid_fields(errors: FieldErrors) -> Self {
+        Self {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            detail: Some("request failed validation".to_owned()),
+            errors: Some(errors),
+        }
+    }
+
+    /// Logs `err` with `context` and returns a 500 that does not leak it.
+    pub fn internal(context: &str, err: impl Display) -> Self {
+        error!(error = %err, "{context}");
+        StatusCode::INTERNAL_SERVER_ERROR.into()
+    }
+}
+
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        Self {
+            status,
+            detail: None,
+            errors: None,
+        }
+    }
+}
+
+impl Problem {
+    fn new(status: StatusCode, detail: Option<String>) -> Self {
+        Self {
+            kind: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            detail,
+            request_id: None,
+            errors: None,
+            allowed_methods: None,
+        }
+    }
+
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(self)).into_response();
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        response
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut problem = Problem::new(self.status, self.detail);
+        problem.errors = self.errors;
+        let mut response = problem.clone().into_response();
+        // Picked up by `problem_details`, which adds the request id.
+        response.extensions_mut().insert(problem);
+        response
+    }
+}
+
+/// The same error for a gRPC caller: the closest status code, with the
+/// detail (or the reason phrase) as the message.
+impl From<ApiError> for tonic::Status {
+    fn from(err: ApiError) -> Self {
+        use tonic::Code;
+        let code = match err.status {
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => Code::InvalidArgument,
+            StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+            StatusCode::FORBIDDEN => Code::PermissionDenied,
+            StatusCode::NOT_FOUND => Code::NotFound,
+            StatusCode::CONFLICT => Code::AlreadyExists,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => Code::DeadlineExceeded,
+            StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+            StatusCode::SERVICE_UNAVAILABLE => Code::Unavailable,
+            StatusCode::INTERNAL_SERVER_ERROR => Code::Internal,
+            _ => Code::Unknown,
+        };
+        let message = err
+            .detail
+            .unwrap_or_else(|| err.status.canonical_reason().unwrap_or("error").to_owned());
+        tonic::Status::new(code, message)
+    }
+}
+
+/// The same error inside a GraphQL response: the detail (or the reason
+/// phrase) as the message, the status code as the `status` extension.
+impl From<ApiError> for async_graphql::Error {
+    fn from(err: ApiError) -> Self {
+        use async_graphql::ErrorExtensions;
+        let message = err
+            .detail
+            .unwrap_or_else(|| err.status.canonical_reason().unwrap_or("error").to_owned());
+        async_graphql::Error::new(message)
+            .extend_with(|_, extensions| extensions.set("status", err.status.as_u16()))
+    }
+}
+
+/// Gives every error response a problem+json body carrying the request id:
+/// [`ApiError`]s, but also the bare or plain-text errors produced by layers
+/// and extractors (401 from auth, 408/413 from the limits, 429, JSON
+/// rejections). Error responses that already have a JSON body are left
+/// alone.
+pub async fn problem_details(req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+    let response = next.run(req).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error())
+        || response.extensions().get::<Proxied>().is_some()
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let mut problem = match parts.extensions.remove::<Problem>() {
+        Some(problem) => problem,
+        None => {
+            let is_json = parts.headers.get(header::CONTENT_TYPE).is_some_and(|v| {
+                v.as_bytes().starts_with(b"application/json")
+                    || v.as_bytes().starts_with(PROBLEM_JSON.as_bytes())
+            });
+            if is_json {
+                return Response::from_parts(parts, body);
+            }
+            let detail = match plain_text(&parts, body).await {
+                Some(text) => Some(text),
+                None => default_detail(status).map(str::to_owned),
+            };
+            Problem::new(status, detail)
+        }
+    };
+    problem.request_id = request_id;
+    if status == StatusCode::METHOD_NOT_ALLOWED {
+        problem.allowed_methods = allowed_methods(&parts);
+    }
+
+    // Re-rendered on top of the original parts to keep headers such as
+    // `Retry-After` or `WWW-Authenticate`.
+    let (rendered, body) = problem.into_response().into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.extend(rendered.headers);
+    Response::from_parts(parts, body)
+}
+
+/// The methods listed in a 405's `Allow` header, which the router only adds
+/// after the handler ran.
+fn allowed_methods(parts: &Parts) -> Option<Vec<String>> {
+    let allow = parts.headers.get(header::ALLOW)?.to_str().ok()?;
+    Some(
+        allow
+            .split(',')
+            .map(str::trim)
+            .filter(|method| !method.is_empty())
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+/// Fallback for paths no route matches.
+pub async fn not_found(method: Method, uri: Uri) -> ApiError {
+    ApiError::not_found(format!("no route for {method} {}", uri.path()))
+}
+
+/// Fallback for known paths requested with a method they don't support;
+/// [`problem_details`] adds the allowed ones.
+pub async fn method_not_allowed(method: Method, uri: Uri) -> ApiError {
+    ApiError::new(
+        StatusCode::METHOD_NOT_ALLOWED,
+        format!("{method} is not allowed on {}", uri.path()),
+    )
+}
+
+/// Short `text/plain` bodies, such as axum's extractor rejections, explain
+/// what went wrong; keep them as the problem's `detail`.
+async fn plain_text(parts: &Parts, body: Body) -> Option<String> {
+    let is_text = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/plain"));
+    let short = body
+        .size_hint()
+        .exact()
+        .is_some_and(|len| len <= MAX_TEXT_DETAIL);
+    if !is_text || !short {
+        return None;
+    }
+    let bytes = axum::body::to_bytes(body, usize::MAX).await.ok()?;
+    let text = String::from_utf8_lossy(&bytes).trim().to_owned();
+    (!text.is_empty()).then_some(text)
+}
+
+fn default_detail(status: StatusCode) -> Option<&'static str> {
+    match status {
+        StatusCode::REQUEST_TIMEOUT => Some("request timed out"),
+        StatusCode::PAYLOAD_TOO_LARGE => Some("request body too large"),
+        StatusCode::TOO_MANY_REQUESTS => Some("rate limit exceeded; see Retry-After"),
+        _ => None,
+    }
+}
+
+/// Logs every panic through `tracing`, with a backtrace of where it
+/// happened. Inside a handler that is the request's span, so the log line
+/// carries its request id.
+///
+/// The hook is process-wide, so it is installed once however many times the
+/// server starts, and hands each panic on to whatever hook was there before
+/// (the default one, or the embedding binary's).
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let backtrace = Backtrace::force_capture();
+            error!(panic = %info, %backtrace, "panicked");
+            previous(info);
+        }));
+    });
+}
+
+/// Response for a handler that panicked (`CatchPanicLayer`): a plain 500,
+/// so the connection survives and the client gets a request id to report.
+/// The panic itself was already logged by the hook.
+pub fn panic_response(_payload: Box<dyn Any + Send + 'static>) -> Response {
+    metrics::counter!("http_panics_total").increment(1);
+    ApiError::from(StatusCode::INTERNAL_SERVER_ERROR).into_response()
+}
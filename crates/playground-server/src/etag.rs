@@ -0,0 +1,105 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header, response::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::proxy::Proxied;
+
+/// JSON bodies larger than this are sent without an ETag rather than
+/// buffered for hashing.
+const MAX_HASHED_BODY: u64 = 1024 * 1024;
+
+/// Whether an `If-None-Match` header matches `etag`, using the weak
+/// comparison RFC 9110 prescribes for it (`W/"x"` matches `"x"`).
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Weak ETag over a response body. Weak, because compression further out
+/// changes the bytes on the wire but not what they mean.
+fn body_etag(body: &[u8]) -> String {
+    format!(
+        "W/\"{}\"",
+        URL_SAFE_NO_PAD.encode(&Sha256::digest(body)[..16])
+    )
+}
+
+/// ETag for responses that only carry file metadata (`ServeDir`), derived
+/// from size and modification time instead of reading the file again.
+fn metadata_etag(headers: &HeaderMap) -> Option<String> {
+    let length = headers.get(header::CONTENT_LENGTH)?.to_str().ok()?;
+    let modified = headers.get(header::LAST_MODIFIED)?.to_str().ok()?;
+    let digest = Sha256::digest(format!("{length}:{modified}").as_bytes());
+    Some(format!("W/\"{}\"", URL_SAFE_NO_PAD.encode(&digest[..16])))
+}
+
+fn not_modified(mut parts: Parts) -> Response {
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(header::CONTENT_TYPE);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_DISPOSITION);
+    Response::from_parts(parts, Body::empty())
+}
+
+/// Adds an `ETag` to successful `GET`/`HEAD` responses and answers `304 Not
+/// Modified` when it matches the request's `If-None-Match`. Handlers that
+/// know a cheaper validator (file downloads) set `ETag` themselves; JSON
+/// bodies are hashed, and `ServeDir` files get one from their metadata.
+pub async fn conditional_get(req: Request, next: Next) -> Response {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return next.run(req).await;
+    }
+    let request_headers = req.headers().clone();
+    let response = next.run(req).await;
+    if response.status() != StatusCode::OK || response.extensions().get::<Proxied>().is_some() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let is_json = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+
+    let (etag, body) = if let Some(etag) = parts.headers.get(header::ETAG) {
+        (etag.to_str().unwrap_or_default().to_owned(), body)
+    } else if is_json
+        && body
+            .size_hint()
+            .exact()
+            .is_some_and(|len| len <= MAX_HASHED_BODY)
+    {
+        // Exact size hint: the body is already in memory, so this never
+        // waits on a stream.
+        let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+        (body_etag(&bytes), Body::from(bytes))
+    } else if let Some(etag) = metadata_etag(&parts.headers) {
+        (etag, body)
+    } else {
+        return Response::from_parts(parts, body);
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        parts.headers.insert(header::ETAG, value);
+    }
+    if if_none_match(&request_headers, &etag) {
+        debug!(%etag, "not modified");
+        return not_modified(parts);
+    }
+    Response::from_parts(parts, body)
+}
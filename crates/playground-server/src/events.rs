@@ -0,0 +1,427 @@
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{Stream, stream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::ApiError;
+
+/// How many past events are kept for clients resuming with `Last-Event-ID`.
+const REPLAY_CAPACITY: usize = 256;
+/// Comment line sent on idle streams so proxies don't time the connection out.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// Reconnection delay suggested to `EventSource` clients.
+const RETRY_AFTER: Duration = Duration::from_secs(3);
+/// Longest a `/events/poll` request is held open; under the default 30s
+/// route timeout (`timeouts.api`).
+const MAX_POLL_WAIT: Duration = Duration::from_secs(25);
+
+/// One structured application event, as sent in the SSE `data` field.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AppEvent {
+    pub id: u64,
+    pub kind: &'static str,
+    pub at: String,
+    #[schema(value_type = Object)]
+    pub data: Value,
+}
+
+/// Fan-out of application events to every `/events` subscriber, keeping a
+/// short history for reconnecting clients. Cheap to clone.
+#[derive(Clone)]
+pub struct EventBus {
+    inner: Arc<EventBusInner>,
+}
+
+struct EventBusInner {
+    /// Random per-process prefix of every cursor, so ids handed out before a
+    /// restart are told apart from the restarted counter.
+    epoch: String,
+    next_id: AtomicU64,
+    sender: broadcast::Sender<AppEvent>,
+    history: Mutex<VecDeque<AppEvent>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(REPLAY_CAPACITY);
+        Self {
+            inner: Arc::new(EventBusInner {
+                epoch: format!("{:08x}", rand::random::<u32>()),
+                next_id: AtomicU64::new(1),
+                sender,
+                history: Mutex::new(VecDeque::with_capacity(REPLAY_CAPACITY)),
+            }),
+        }
+    }
+
+    /// Records `kind` with its payload and pushes it to live subscribers.
+    pub fn publish(&self, kind: &'static str, data: Value) {
+        let mut history = self.history();
+        // Ids are assigned under the history lock so they reach the history
+        // and the channel in order.
+        let event = AppEvent {
+            id: self.inner.next_id.fetch_add(1, Ordering::Relaxed),
+            kind,
+            at: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            data,
+        };
+        if history.len() == REPLAY_CAPACITY {
+            history.pop_front();
+        }
+        history.push_back(event.clone());
+        // No receivers just means nobody is watching right now.
+        let _ = self.inner.sender.send(event);
+    }
+
+    fn history(&self) -> std::sync::MutexGuard<'_, VecDeque<AppEvent>> {
+        self.inner.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A receiver for events published from now on, for consumers other
+    /// than `/events` (e.g. webhooks).
+    pub fn subscribe(&self) -> broadcast::Receiver<AppEvent> {
+        self.inner.sender.subscribe()
+    }
+
+    /// Id of the newest event published so far, 0 before the first one.
+    pub fn latest_id(&self) -> u64 {
+        self.history().back().map_or(0, |event| event.id)
+    }
+
+    /// The cursor a client sends back to resume after event `id`:
+    /// `"{epoch}-{id}"`.
+    pub fn cursor(&self, id: u64) -> String {
+        format!("{}-{id}", self.inner.epoch)
+    }
+
+    /// The event id a client's `cursor` resumes after, `None` if it is not a
+    /// cursor at all. One from another process (before a restart), a bare id
+    /// from before cursors carried the epoch, or one ahead of this bus
+    /// resumes from 0, the start of the history.
+    pub fn resume_after(&self, cursor: &str) -> Option<u64> {
+        let cursor = cursor.trim();
+        let (epoch, id) = cursor.split_once('-').unwrap_or(("", cursor));
+        let id = id.parse::<u64>().ok()?;
+        if epoch == self.inner.epoch && id <= self.latest_id() {
+            Some(id)
+        } else {
+            Some(0)
+        }
+    }
+
+    /// Events newer than `last_id` that are still in the history.
+    pub fn since(&self, last_id: u64) -> VecDeque<AppEvent> {
+        self.history()
+            .iter()
+            .filter(|event| event.id > last_id)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Subscription {
+    bus: EventBus,
+    receiver: broadcast::Receiver<AppEvent>,
+    pending: VecDeque<AppEvent>,
+    last_id: u64,
+    sent_retry: bool,
+}
+
+fn to_sse(bus: &EventBus, event: &AppEvent) -> Event {
+    Event::default()
+        .id(bus.cursor(event.id))
+        .event(event.kind)
+        .json_data(event)
+        .unwrap_or_else(|_| Event::default().comment("unserializable event"))
+}
+
+/// `GET /events`: streams application events as Server-Sent Events. A client
+/// reconnecting with `Last-Event-ID` first gets whatever it missed that is
+/// still in the replay buffer; one whose id predates a server restart gets
+/// the whole buffer.
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    security(("bearer" = []), ("api_key" = [])),
+    params(("Last-Event-ID" = Option<String>, Header, description = "Resume after this event id")),
+    responses(
+        (status = 200, content_type = "text/event-stream", body = String),
+        (status = 401)
+    )
+)]
+pub async fn events(
+    State(bus): State<EventBus>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let resume = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| bus.resume_after(v));
+    // Subscribe before reading the history so nothing published in between
+    // is lost; duplicates are filtered out by id below.
+    let receiver = bus.inner.sender.subscribe();
+    let pending = resume.map(|id| bus.since(id)).unwrap_or_default();
+    let last_id = resume.unwrap_or(0);
+    info!(
+        last_id,
+        replay = pending.len(),
+        "events subscriber connected"
+    );
+
+    let subscription = Subscription {
+        bus,
+        receiver,
+        pending,
+        last_id,
+        sent_retry: false,
+    };
+    let stream = stream::unfold(subscription, |mut sub| async move {
+        if !sub.sent_retry {
+            sub.sent_retry = true;
+            return Some((Ok(Event::default().retry(RETRY_AFTER)), sub));
+        }
+        loop {
+            if let Some(event) = sub.pending.pop_front() {
+                sub.last_id = event.id;
+                return Some((Ok(to_sse(&sub.bus, &event)), sub));
+            }
+            match sub.receiver.recv().await {
+                Ok(event) if event.id <= sub.last_id => continue,
+                Ok(event) => {
+                    sub.last_id = event.id;
+                    return Some((Ok(to_sse(&sub.bus, &event)), sub));
+                }
+                // A slow client fell behind the channel: refill from the
+                // history, which holds as many events as the channel does.
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "events subscriber lagged, replaying from history");
+                    sub.pending = sub.bus.since(sub.last_id);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(HEARTBEAT_INTERVAL))
+}
+
+/// `?since=` and `?wait=` of `GET /events/poll`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PollQuery {
+    /// Cursor from the previous response; without it only events published
+    /// after the request arrives are returned.
+    pub since: Option<String>,
+    /// Seconds to wait for an event when there is none yet (default and
+    /// maximum 25).
+    pub wait: Option<u64>,
+}
+
+/// Events since the cursor, oldest first, and the cursor to send next.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PollResponse {
+    pub events: Vec<AppEvent>,
+    pub cursor: String,
+}
+
+/// `GET /events/poll`: long-polling alternative to `/events` for clients
+/// that cannot keep a stream open. Answers right away with whatever is in
+/// the replay buffer after `since`, otherwise waits for the next event or
+/// until `wait` runs out and answers with an empty list. A cursor older
+/// than the replay buffer only gets what the buffer still holds; one from
+/// before a server restart starts over from the oldest event in it.
+#[utoipa::path(
+    get,
+    path = "/events/poll",
+    tag = "events",
+    security(("bearer" = []), ("api_key" = [])),
+    params(PollQuery),
+    responses(
+        (status = 200, body = PollResponse),
+        (status = 400, description = "Invalid cursor or wait"),
+        (status = 401)
+    )
+)]
+pub async fn poll_events(
+    State(bus): State<EventBus>,
+    Query(query): Query<PollQuery>,
+) -> Result<Json<PollResponse>, ApiError> {
+    let wait = query
+        .wait
+        .map_or(MAX_POLL_WAIT, Duration::from_secs)
+        .min(MAX_POLL_WAIT);
+    // Subscribe before reading the history, as in `events`, so nothing
+    // published in between is missed.
+    let mut receiver = bus.subscribe();
+    let since = match query.since {
+        Some(cursor) => bus
+            .resume_after(&cursor)
+            .ok_or_else(|| ApiError::bad_request("`since` is not a cursor from /events/poll"))?,
+        None => bus.latest_id(),
+    };
+    let respond = |events: Vec<AppEvent>| {
+        let cursor = bus.cursor(events.last().map_or(since, |event| event.id));
+        Ok(Json(PollResponse { events, cursor }))
+    };
+    let events: Vec<_> = bus.since(since).into();
+    if !events.is_empty() {
+        return respond(events);
+    }
+
+    let deadline = tokio::time::sleep(wait);
+    tokio::pin!(deadline);
+    loop {
+        let event = tokio::select! {
+            event = receiver.recv() => event,
+            () = &mut deadline => break,
+        };
+        match event {
+            Ok(event) if event.id <= since => continue,
+            Ok(event) => {
+                // Whatever else was published meanwhile goes out too.
+                let mut events = vec![event];
+                while let Ok(event) = receiver.try_recv() {
+                    events.push(event);
+                }
+                return respond(events);
+            }
+            Err(RecvError::Lagged(_)) => return respond(bus.since(since).into()),
+            Err(RecvError::Closed) => break,
+        }
+    }
+    respond(Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::HeaderValue, response::IntoResponse};
+    use futures_util::StreamExt;
+    use serde_json::json;
+
+    use super::*;
+
+    /// The bus of a process that ran before a restart, and a cursor a client
+    /// kept from it that is ahead of everything the new process publishes.
+    fn stale_cursor() -> String {
+        let before = EventBus::new();
+        for n in 0..5 {
+            before.publish("old", json!({ "n": n }));
+        }
+        before.cursor(before.latest_id())
+    }
+
+    #[test]
+    fn cursors_resume_within_a_process() {
+        let bus = EventBus::new();
+        bus.publish("a", json!({}));
+        bus.publish("b", json!({}));
+        let first = bus.cursor(1);
+        assert_eq!(bus.resume_after(&first), Some(1));
+        let kinds: Vec<_> = bus.since(1).iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, ["b"]);
+        assert_eq!(bus.resume_after("not a cursor"), None);
+        assert_eq!(bus.resume_after("abc-x"), None);
+    }
+
+    #[test]
+    fn a_cursor_from_before_a_restart_resumes_from_the_start() {
+        let stale = stale_cursor();
+        let bus = EventBus::new();
+        bus.publish("new", json!({}));
+        assert_eq!(bus.resume_after(&stale), Some(0));
+        // A bare id, as sent before cursors carried the epoch, too.
+        assert_eq!(bus.resume_after("500"), Some(0));
+        assert_eq!(bus.since(0).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sse_resumes_with_a_stale_last_event_id() {
+        let stale = stale_cursor();
+        let bus = EventBus::new();
+        bus.publish("replayed", json!({}));
+        let mut headers = HeaderMap::new();
+        headers.insert("last-event-id", HeaderValue::from_str(&stale).unwrap());
+        let response = events(State(bus.clone()), headers).await.into_response();
+        let mut body = Body::into_data_stream(response.into_body());
+
+        let retry = body.next().await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&retry).starts_with("retry:"));
+        let replayed = body.next().await.unwrap().unwrap();
+        let replayed = String::from_utf8_lossy(&replayed).into_owned();
+        assert!(replayed.contains("event: replayed"), "{replayed}");
+        assert!(
+            replayed.contains(&format!("id: {}", bus.cursor(1))),
+            "{replayed}"
+        );
+
+        // Live events keep flowing even though the stale id was higher.
+        bus.publish("live", json!({}));
+        let live = body.next().await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&live).contains("event: live"));
+    }
+
+    #[tokio::test]
+    async fn poll_with_a_stale_cursor_answers_from_the_history() {
+        let stale = stale_cursor();
+        let bus = EventBus::new();
+        bus.publish("first", json!({}));
+        bus.publish("second", json!({}));
+        let query = PollQuery {
+            since: Some(stale),
+            wait: Some(0),
+        };
+        let Json(response) = poll_events(State(bus.clone()), Query(query)).await.unwrap();
+        let kinds: Vec<_> = response.events.iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, ["first", "second"]);
+        assert_eq!(response.cursor, bus.cursor(2));
+
+        // The reset cursor then waits for live events as usual.
+        let waiting = tokio::spawn(poll_events(
+            State(bus.clone()),
+            Query(PollQuery {
+                since: Some(response.cursor),
+                wait: None,
+            }),
+        ));
+        tokio::task::yield_now().await;
+        bus.publish("third", json!({}));
+        let Json(response) = waiting.await.unwrap().unwrap();
+        let kinds: Vec<_> = response.events.iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, ["third"]);
+    }
+
+    #[tokio::test]
+    async fn poll_rejects_a_malformed_cursor() {
+        let bus = EventBus::new();
+        let query = PollQuery {
+            since: Some("latest".to_owned()),
+            wait: Some(0),
+        };
+        assert!(poll_events(State(bus), Query(query)).await.is_err());
+    }
+}
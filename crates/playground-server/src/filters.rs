@@ -0,0 +1,401 @@
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use libsql::Value;
+use tracing::warn;
+
+use crate::{error::ApiError, pagination::PAGE_PARAMS};
+
+/// How a filterable column is matched against its query parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    /// Exact match, or a `LIKE` pattern when the value contains `*`
+    /// (`?email=*@example.com`). Patterns ignore ASCII case.
+    Text,
+    /// `?size_bytes=1024`.
+    Integer,
+    /// `?is_active=true` (or `false`, `1`, `0`).
+    Bool,
+    /// `?created_after=` (inclusive) and `?created_before=` (exclusive),
+    /// each a date (`2024-01-01`) or date-time (`2024-01-01T12:00:00Z`).
+    Timestamp,
+}
+
+/// A query parameter a list endpoint accepts and the column it filters.
+#[derive(Debug, Clone, Copy)]
+pub struct FilterField {
+    /// Parameter name; `Timestamp` fields take it with `_after`/`_before`.
+    pub param: &'static str,
+    /// Column in the SQL text. Only ever a compile-time constant.
+    pub column: &'static str,
+    pub kind: FilterKind,
+}
+
+impl FilterField {
+    pub const fn text(param: &'static str, column: &'static str) -> Self {
+        Self {
+            param,
+            column,
+            kind: FilterKind::Text,
+        }
+    }
+
+    pub const fn integer(param: &'static str, column: &'static str) -> Self {
+        Self {
+            param,
+            column,
+            kind: FilterKind::Integer,
+        }
+    }
+
+    pub const fn bool(param: &'static str, column: &'static str) -> Self {
+        Self {
+            param,
+            column,
+            kind: FilterKind::Bool,
+        }
+    }
+
+    pub const fn timestamp(param: &'static str, column: &'static str) -> Self {
+        Self {
+            param,
+            column,
+            kind: FilterKind::Timestamp,
+        }
+    }
+
+    /// The parameter names this field answers to.
+    fn names(&self) -> Vec<String> {
+        match self.kind {
+            FilterKind::Timestamp => vec![
+                format!("{}_after", self.param),
+                format!("{}_before", self.param),
+            ],
+            _ => vec![self.param.to_owned()],
+        }
+    }
+}
+
+/// The query string of a list request minus the pagination parameters,
+/// still unchecked: every endpoint accepts its own fields, so validation
+/// happens in [`FilterQuery::to_sql`].
+#[derive(Debug, Clone, Default)]
+pub struct FilterQuery(Vec<(String, String)>);
+
+impl<S: Send + Sync> FromRequestParts<S> for FilterQuery {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(pairs) = Query::<Vec<(String, String)>>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| {
+                warn!(error = %err, "invalid filter query");
+                ApiError::bad_request(err.body_text())
+            })?;
+        Ok(Self(
+            pairs
+                .into_iter()
+                .filter(|(name, _)| !PAGE_PARAMS.contains(&name.as_str()))
+                .collect(),
+        ))
+    }
+}
+
+impl FilterQuery {
+    /// Turns the parameters into `WHERE` conditions over `fields`. Values
+    /// are always bound, never spliced into the SQL; unknown parameters and
+    /// values of the wrong type are a 400.
+    pub fn to_sql(&self, fields: &[FilterField]) -> Result<SqlFilter, ApiError> {
+        let mut filter = SqlFilter::default();
+        for (name, value) in &self.0 {
+            let Some((field, bound)) = fields.iter().find_map(|field| match_param(field, name))
+            else {
+                warn!(%name, "unsupported filter");
+                let expected: Vec<_> = fields.iter().flat_map(FilterField::names).collect();
+                return Err(ApiError::bad_request(format!(
+                    "cannot filter by {name:?}; expected one of {}",
+                    expected.join(", ")
+                )));
+            };
+            let column = field.column;
+            let invalid = |expected: &str| {
+                warn!(%name, %value, "invalid filter value");
+                ApiError::bad_request(format!("invalid {name} {value:?}: expected {expected}"))
+            };
+            match field.kind {
+                FilterKind::Text if value.contains('*') => {
+                    filter.push(format!("{column} LIKE ? ESCAPE '\\'"), like_pattern(value))
+                }
+                FilterKind::Text => filter.push(format!("{column} = ?"), value.as_str()),
+                FilterKind::Integer => {
+                    let value: i64 = value.parse().map_err(|_| invalid("an integer"))?;
+                    filter.push(format!("{column} = ?"), value);
+                }
+                FilterKind::Bool => {
+                    let value = match value.as_str() {
+                        "true" | "1" => 1,
+                        "false" | "0" => 0,
+                        _ => return Err(invalid("true or false")),
+                    };
+                    filter.push(format!("{column} = ?"), value);
+                }
+                FilterKind::Timestamp => {
+                    if !valid_timestamp(value) {
+                        return Err(invalid("a date (YYYY-MM-DD) or date-time"));
+                    }
+                    // `datetime()` brings `T`/`Z`/offsets to the
+                    // `YYYY-MM-DD HH:MM:SS` UTC form the columns are stored in.
+                    let op = if bound == Bound::After { ">=" } else { "<" };
+                    filter.push(format!("{column} {op} datetime(?)"), value.as_str());
+                }
+            }
+        }
+        Ok(filter)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    After,
+    Before,
+}
+
+fn match_param<'a>(field: &'a FilterField, name: &str) -> Option<(&'a FilterField, Bound)> {
+    if field.kind != FilterKind::Timestamp {
+        return (name == field.param).then_some((field, Bound::Exact));
+    }
+    match name.strip_prefix(field.param)? {
+        "_after" => Some((field, Bound::After)),
+        "_before" => Some((field, Bound::Before)),
+        _ => None,
+    }
+}
+
+/// `*` becomes `%`; `%`, `_` and `\` in the value match themselves.
+fn like_pattern(value: &str) -> String {
+    let mut pattern = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '*' => pattern.push('%'),
+            '%' | '_' | '\\' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            c => pattern.push(c),
+        }
+    }
+    pattern
+}
+
+/// `YYYY-MM-DD`, optionally followed by a time and a zone. Only the shape is
+/// checked here; SQLite's `datetime()` parses the rest.
+fn valid_timestamp(value: &str) -> bool {
+    let Some(date) = value.get(..10) else {
+        return false;
+    };
+    let bytes = date.as_bytes();
+    let digits = |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_digit);
+    if !(digits(0..4) && bytes[4] == b'-' && digits(5..7) && bytes[7] == b'-' && digits(8..10)) {
+        return false;
+    }
+    let month: u8 = date[5..7].parse().unwrap_or(0);
+    let day: u8 = date[8..10].parse().unwrap_or(0);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return false;
+    }
+    match value[10..].strip_prefix(['T', ' ']) {
+        None => value.len() == 10,
+        Some(time) => {
+            time.len() >= 5
+                && time
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || matches!(b, b':' | b'.' | b'Z' | b'+' | b'-'))
+        }
+    }
+}
+
+/// `WHERE` conditions with their bound values. Placeholders are plain `?`,
+/// numbered by SQLite in order, so whatever the query binds after the
+/// conditions (`LIMIT`/`OFFSET`) also uses `?` and goes last.
+#[derive(Debug, Clone, Default)]
+pub struct SqlFilter {
+    conditions: Vec<String>,
+    params: Vec<Value>,
+}
+
+impl SqlFilter {
+    /// Adds `condition`, whose single `?` is bound to `value`.
+    pub fn push(&mut self, condition: impl Into<String>, value: impl Into<Value>) {
+        self.conditions.push(condition.into());
+        self.params.push(value.into());
+    }
+
+    /// `WHERE a AND b`, or an empty string without conditions.
+    pub fn where_clause(&self) -> String {
+        if self.conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", self.conditions.join(" AND "))
+        }
+    }
+
+    /// The bound values followed by `extra`, in placeholder order.
+    pub fn params(&self, extra: impl IntoIterator<Item = Value>) -> Vec<Value> {
+        self.params.iter().cloned().chain(extra).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: &[FilterField] = &[
+        FilterField::text("email", "email"),
+        FilterField::integer("size", "size_bytes"),
+        FilterField::bool("is_active", "is_active"),
+        FilterField::timestamp("created", "created_at"),
+    ];
+
+    fn query(pairs: &[(&str, &str)]) -> FilterQuery {
+        FilterQuery(
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn like_patterns_escape_wildcards_in_the_value() {
+        assert_eq!(like_pattern("*@example.com"), "%@example.com");
+        assert_eq!(like_pattern("100%_off*"), "100\\%\\_off%");
+        assert_eq!(like_pattern("a\\b*"), "a\\\\b%");
+        assert_eq!(like_pattern("josé*"), "josé%");
+    }
+
+    #[test]
+    fn timestamps_are_checked_for_shape() {
+        for valid in [
+            "2024-01-01",
+            "2024-12-31T23:59:59Z",
+            "2024-02-29 12:00",
+            "2024-01-01T12:00:00.123+02:00",
+        ] {
+            assert!(valid_timestamp(valid), "{valid}");
+        }
+        for invalid in [
+            "",
+            "2024-1-01",
+            "2024-13-01",
+            "2024-00-10",
+            "2024-01-32",
+            "2024/01/01",
+            "2024-01-01x",
+            "2024-01-01T",
+            "2024-01-01T12:00'; DROP TABLE users; --",
+            // Multibyte characters around byte 10 must not panic.
+            "2024-01-0é",
+            "2024-01-01é",
+            "ééééé",
+        ] {
+            assert!(!valid_timestamp(invalid), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn unknown_parameters_and_bad_values_are_rejected() {
+        let err = |pairs: &[(&str, &str)]| query(pairs).to_sql(FIELDS).unwrap_err();
+        for pairs in [
+            &[("password_hash", "x")][..],
+            &[("created", "2024-01-01")],
+            &[("size_after", "1")],
+            &[("size", "ten")],
+            &[("is_active", "yes")],
+            &[("created_after", "yesterday")],
+        ] {
+            let response = axum::response::IntoResponse::into_response(err(pairs));
+            assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[test]
+    fn values_are_bound_in_placeholder_order() {
+        let filter = query(&[
+            ("email", "*'; DROP TABLE users; --"),
+            ("size", "42"),
+            ("is_active", "false"),
+            ("created_after", "2024-01-01"),
+            ("created_before", "2024-02-01"),
+        ])
+        .to_sql(FIELDS)
+        .unwrap();
+        assert_eq!(
+            filter.where_clause(),
+            "WHERE email LIKE ? ESCAPE '\\' AND size_bytes = ? AND is_active = ? \
+             AND created_at >= datetime(?) AND created_at < datetime(?)"
+        );
+        assert_eq!(
+            filter.params([Value::Integer(10), Value::Integer(20)]),
+            [
+                Value::Text("%'; DROP TABLE users; --".to_owned()),
+                Value::Integer(42),
+                Value::Integer(0),
+                Value::Text("2024-01-01".to_owned()),
+                Value::Text("2024-02-01".to_owned()),
+                Value::Integer(10),
+                Value::Integer(20),
+            ]
+        );
+        assert_eq!(SqlFilter::default().where_clause(), "");
+    }
+
+    #[tokio::test]
+    async fn filters_run_against_sqlite_with_limit_and_offset() {
+        let db = libsql::Builder::new_local(":memory:")
+            .build()
+            .await
+            .unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute(
+            "CREATE TABLE t (email TEXT, size_bytes INTEGER, is_active INTEGER, created_at TIMESTAMP)",
+            (),
+        )
+        .await
+        .unwrap();
+        for (email, size) in [
+            ("a_1@x.com", 1),
+            ("ab1@x.com", 2),
+            ("a_2@x.com", 3),
+            ("b@x.com", 4),
+        ] {
+            conn.execute(
+                "INSERT INTO t VALUES (?1, ?2, 1, '2024-01-15 00:00:00')",
+                libsql::params![email, size],
+            )
+            .await
+            .unwrap();
+        }
+
+        let filter = query(&[("email", "a_*"), ("created_after", "2024-01-01")])
+            .to_sql(FIELDS)
+            .unwrap();
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT size_bytes FROM t {} ORDER BY size_bytes LIMIT ? OFFSET ?",
+                    filter.where_clause()
+                ),
+                filter.params([Value::Integer(1), Value::Integer(1)]),
+            )
+            .await
+            .unwrap();
+        let mut sizes = Vec::new();
+        while let Some(row) = rows.next().await.unwrap() {
+            sizes.push(row.get::<i64>(0).unwrap());
+        }
+        // `_` matched itself, so `ab1` is out; the page skips `a_1`.
+        assert_eq!(sizes, [3]);
+    }
+}
@@ -0,0 +1,197 @@
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject, http::GraphiQLSource,
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{
+    Extension,
+    extract::{OriginalUri, State},
+    response::Html,
+};
+use tracing::warn;
+
+use crate::{
+    auth::{Admin, Role, User},
+    error::ApiError,
+    filters::SqlFilter,
+    notes::{NoteRecord, NoteRepository, owner_id},
+    pagination::limit_offset,
+    recordings::{RecordingRecord, RecordingStore},
+    users::{UserRecord, UserRepository},
+};
+
+/// Read-only schema over users, notes and recordings, served at `/graphql`.
+/// Cheap to clone.
+pub type ApiSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub fn schema(
+    users: UserRepository,
+    notes: NoteRepository,
+    recordings: RecordingStore,
+) -> ApiSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(users)
+        .data(notes)
+        .data(recordings)
+        .finish()
+}
+
+/// One page of a listing, with how many items match in total.
+#[derive(SimpleObject)]
+#[graphql(concrete(name = "UserPage", params(UserRecord)))]
+#[graphql(concrete(name = "NotePage", params(NoteRecord)))]
+#[graphql(concrete(name = "RecordingPage", params(RecordingRecord)))]
+pub struct Page<T: async_graphql::OutputType> {
+    items: Vec<T>,
+    total: u64,
+}
+
+/// The caller, put into every request by [`graphql`], which must hold
+/// `scope` when it came with an API key. Fields follow the rules of the
+/// matching REST routes.
+fn caller<'a>(ctx: &Context<'a>, scope: &str) -> async_graphql::Result<&'a User> {
+    let user = ctx.data::<User>()?;
+    if !user.has_scope(scope) {
+        warn!(user_id = %user.id, required = %scope, "API key lacks scope");
+        return Err(ApiError::forbidden(format!("API key lacks the {scope} scope")).into());
+    }
+    Ok(user)
+}
+
+fn admin<'a>(ctx: &Context<'a>, scope: &str) -> async_graphql::Result<&'a User> {
+    let user = caller(ctx, scope)?;
+    if !user.has_role(Admin::NAME) {
+        warn!(user_id = %user.id, role = Admin::NAME, "missing required role");
+        return Err(ApiError::forbidden(format!("requires the {} role", Admin::NAME)).into());
+    }
+    Ok(user)
+}
+
+fn internal(err: impl std::fmt::Display) -> ApiError {
+    ApiError::internal("graphql resolver failure", err)
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// The authenticated caller, like `GET /me`.
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserRecord>> {
+        let user = caller(ctx, "me:read")?;
+        let Ok(id) = user.id.parse() else {
+            return Ok(None);
+        };
+        Ok(ctx
+            .data::<UserRepository>()?
+            .get(id)
+            .await
+            .map_err(ApiError::from)?)
+    }
+
+    /// Admins only.
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20)] per_page: u32,
+    ) -> async_graphql::Result<Page<UserRecord>> {
+        admin(ctx, "users:read")?;
+        let (limit, offset) = limit_offset(page, per_page);
+        let (items, total) = ctx
+            .data::<UserRepository>()?
+            .list(&SqlFilter::default(), "id", limit, offset)
+            .await
+            .map_err(ApiError::from)?;
+        Ok(Page { items, total })
+    }
+
+    /// Admins only.
+    async fn user(&self, ctx: &Context<'_>, id: i64) -> async_graphql::Result<Option<UserRecord>> {
+        admin(ctx, "users:read")?;
+        Ok(ctx
+            .data::<UserRepository>()?
+            .get(id)
+            .await
+            .map_err(ApiError::from)?)
+    }
+
+    /// The caller's own notes.
+    async fn notes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20)] per_page: u32,
+    ) -> async_graphql::Result<Page<NoteRecord>> {
+        let user_id = owner_id(caller(ctx, "notes:read")?)?;
+        let (limit, offset) = limit_offset(page, per_page);
+        let (items, total) = ctx
+            .data::<NoteRepository>()?
+            .list(user_id, SqlFilter::default(), "id", limit, offset)
+            .await
+            .map_err(internal)?;
+        Ok(Page { items, total })
+    }
+
+    /// One of the caller's notes; someone else's is `null`, as if missing.
+    async fn note(&self, ctx: &Context<'_>, id: i64) -> async_graphql::Result<Option<NoteRecord>> {
+        let user_id = owner_id(caller(ctx, "notes:read")?)?;
+        Ok(ctx
+            .data::<NoteRepository>()?
+            .get(user_id, id)
+            .await
+            .map_err(internal)?)
+    }
+
+    /// Metadata only; the audio is downloaded from `/v1/recordings/{id}`.
+    async fn recordings(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20)] per_page: u32,
+    ) -> async_graphql::Result<Page<RecordingRecord>> {
+        caller(ctx, "recordings:read")?;
+        let (limit, offset) = limit_offset(page, per_page);
+        let (items, total) = ctx
+            .data::<RecordingStore>()?
+            .list(&SqlFilter::default(), "id", limit, offset)
+            .await
+            .map_err(internal)?;
+        Ok(Page { items, total })
+    }
+
+    async fn recording(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+    ) -> async_graphql::Result<Option<RecordingRecord>> {
+        caller(ctx, "recordings:read")?;
+        Ok(ctx
+            .data::<RecordingStore>()?
+            .get(id)
+            .await
+            .map_err(internal)?)
+    }
+}
+
+/// `POST /graphql`: runs a query as the authenticated caller. Errors are
+/// reported in the GraphQL response (with a `status` extension), not as
+/// problem+json.
+pub async fn graphql(
+    State(schema): State<ApiSchema>,
+    Extension(user): Extension<User>,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    schema.execute(request.into_inner().data(user)).await.into()
+}
+
+/// Policy of the explorer page, which loads GraphiQL and React from unpkg
+/// and starts them from an inline script.
+pub const GRAPHIQL_CSP: &str = "default-src 'self'; script-src 'self' 'unsafe-inline' \
+     https://unpkg.com; style-src 'self' 'unsafe-inline' https://unpkg.com; \
+     frame-ancestors 'none'";
+
+/// `GET /graphql`, debug builds only: the GraphiQL explorer, posting to the
+/// path it was loaded from. It runs in the browser, so it authenticates
+/// with the session cookie of `/login` (or a header set in its UI).
+pub async fn graphiql(OriginalUri(uri): OriginalUri) -> Html<String> {
+    Html(GraphiQLSource::build().endpoint(uri.path()).finish())
+}
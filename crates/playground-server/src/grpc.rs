@@ -0,0 +1,224 @@
+use axum::{
+    Router,
+    extract::OriginalUri,
+    http::{Method, StatusCode},
+};
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::{
+    auth::{Admin, AuthState, Role, User},
+    dashboard::StartedAt,
+    error::ApiError,
+    filters::SqlFilter,
+    pagination::limit_offset,
+    recordings::{RecordingRecord, RecordingStore},
+    state::AppState,
+    users::{UserRecord, UserRepository},
+};
+
+/// Code generated from `proto/playground.proto` by the build script.
+pub mod pb {
+    tonic::include_proto!("playground.v1");
+}
+
+use pb::{
+    recording_service_server::{RecordingService, RecordingServiceServer},
+    status_service_server::{StatusService, StatusServiceServer},
+    user_service_server::{UserService, UserServiceServer},
+};
+
+/// The gRPC services of `proto/playground.proto`, over the same stores as
+/// the JSON API and with the same credentials, checked by
+/// [`AuthState::authenticate`]. API keys need the scope the matching HTTP
+/// route would (`users:read`, `recordings:read`). Cheap to clone.
+#[derive(Clone)]
+pub struct GrpcApi {
+    auth: AuthState,
+    users: UserRepository,
+    recordings: RecordingStore,
+    started_at: StartedAt,
+}
+
+/// Routes for every service, to be merged into the HTTP router: gRPC
+/// requests are plain HTTP/2 `POST`s to `/playground.v1.UserService/...`.
+pub fn routes(state: &AppState, auth: AuthState) -> Router {
+    let api = GrpcApi {
+        auth,
+        users: state.users.clone(),
+        recordings: state.recordings.clone(),
+        started_at: state.started_at,
+    };
+    tonic::service::Routes::new(StatusServiceServer::new(api.clone()))
+        .add_service(UserServiceServer::new(api.clone()))
+        .add_service(RecordingServiceServer::new(api))
+        .into_axum_router()
+}
+
+impl GrpcApi {
+    /// The authenticated caller of `request`, which must hold `scope` when
+    /// it came with an API key.
+    async fn caller<T>(&self, request: &Request<T>, scope: &str) -> Result<User, Status> {
+        let path = request
+            .extensions()
+            .get::<tonic::GrpcMethod>()
+            .map(|method| format!("/{}/{}", method.service(), method.method()))
+            .unwrap_or_default();
+        let headers = request.metadata().clone().into_headers();
+        let user = self
+            .auth
+            .authenticate(&headers, &Method::POST, &path)
+            .await?
+            .ok_or_else(|| ApiError::from(StatusCode::UNAUTHORIZED))?;
+        tracing::Span::current().record("user_id", user.id.as_str());
+        if !user.has_scope(scope) {
+            warn!(%path, required = %scope, "API key lacks scope");
+            return Err(Status::permission_denied(format!(
+                "API key lacks the {scope} scope"
+            )));
+        }
+        info!(%path, "authenticated grpc call");
+        Ok(user)
+    }
+
+    /// Like [`Self::caller`], and the caller must be an admin.
+    async fn admin<T>(&self, request: &Request<T>, scope: &str) -> Result<User, Status> {
+        let user = self.caller(request, scope).await?;
+        if !user.has_role(Admin::NAME) {
+            warn!(user_id = %user.id, role = Admin::NAME, "missing required role");
+            return Err(Status::permission_denied(format!(
+                "requires the {} role",
+                Admin::NAME
+            )));
+        }
+        Ok(user)
+    }
+}
+
+impl From<UserRecord> for pb::User {
+    fn from(user: UserRecord) -> Self {
+        Self {
+            id: user.id,
+            name: user.name,
+            email: user.email,
+            role: user.role,
+            is_active: user.is_active,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+impl From<RecordingRecord> for pb::Recording {
+    fn from(recording: RecordingRecord) -> Self {
+        Self {
+            id: recording.id,
+            original_name: recording.original_name,
+            uploaded_by: recording.uploaded_by,
+            size_bytes: recording.size_bytes,
+            channels: recording.channels,
+            sample_rate: recording.sample_rate,
+            bits_per_sample: recording.bits_per_sample,
+            duration_ms: recording.duration_ms,
+            created_at: recording.created_at,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl StatusService for GrpcApi {
+    async fn get_status(
+        &self,
+        request: Request<pb::GetStatusRequest>,
+    ) -> Result<Response<pb::GetStatusResponse>, Status> {
+        // HTTP/2 carries the host as `:authority`, part of the URI rather
+        // than a header, so it is not in the metadata.
+        let hostname = request
+            .extensions()
+            .get::<OriginalUri>()
+            .and_then(|OriginalUri(uri)| uri.authority())
+            .map_or_else(|| "<unknown>".to_owned(), ToString::to_string);
+        let StartedAt(started) = self.started_at;
+        Ok(Response::new(pb::GetStatusResponse {
+            hostname,
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            uptime_seconds: started.elapsed().as_secs(),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl UserService for GrpcApi {
+    async fn list_users(
+        &self,
+        request: Request<pb::ListUsersRequest>,
+    ) -> Result<Response<pb::ListUsersResponse>, Status> {
+        self.admin(&request, "users:read").await?;
+        let pb::ListUsersRequest { page, per_page } = request.into_inner();
+        let (limit, offset) = limit_offset(page, per_page);
+        let (users, total) = self
+            .users
+            .list(&SqlFilter::default(), "id", limit, offset)
+            .await
+            .map_err(ApiError::from)?;
+        Ok(Response::new(pb::ListUsersResponse {
+            users: users.into_iter().map(Into::into).collect(),
+            total,
+        }))
+    }
+
+    async fn get_user(
+        &self,
+        request: Request<pb::GetUserRequest>,
+    ) -> Result<Response<pb::User>, Status> {
+        self.admin(&request, "users:read").await?;
+        let id = request.into_inner().id;
+        let user = self
+            .users
+            .get(id)
+            .await
+            .map_err(ApiError::from)?
+            .ok_or_else(|| Status::not_found(format!("no user {id}")))?;
+        Ok(Response::new(user.into()))
+    }
+}
+
+fn recording_store_error(err: libsql::Error) -> Status {
+    ApiError::internal("recording store failure", err).into()
+}
+
+#[tonic::async_trait]
+impl RecordingService for GrpcApi {
+    async fn list_recordings(
+        &self,
+        request: Request<pb::ListRecordingsRequest>,
+    ) -> Result<Response<pb::ListRecordingsResponse>, Status> {
+        self.caller(&request, "recordings:read").await?;
+        let pb::ListRecordingsRequest { page, per_page } = request.into_inner();
+        let (limit, offset) = limit_offset(page, per_page);
+        let (recordings, total) = self
+            .recordings
+            .list(&SqlFilter::default(), "id", limit, offset)
+            .await
+            .map_err(recording_store_error)?;
+        Ok(Response::new(pb::ListRecordingsResponse {
+            recordings: recordings.into_iter().map(Into::into).collect(),
+            total,
+        }))
+    }
+
+    async fn get_recording(
+        &self,
+        request: Request<pb::GetRecordingRequest>,
+    ) -> Result<Response<pb::Recording>, Status> {
+        self.caller(&request, "recordings:read").await?;
+        let id = request.into_inner().id;
+        let recording = self
+            .recordings
+            .get(id)
+            .await
+            .map_err(recording_store_error)?
+            .ok_or_else(|| Status::not_found(format!("no recording {id}")))?;
+        Ok(Response::new(recording.into()))
+    }
+}
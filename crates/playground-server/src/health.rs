@@ -0,0 +1,254 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use async_trait::async_trait;
+use axum::{Json, extract::State, http::StatusCode};
+use futures_util::future::join_all;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::db::DbPool;
+
+/// How long a single dependency check may take before it counts as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long `/readyz` reuses a report when `[health]` does not say.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(5);
+/// Free space under which the disk check fails when `[health]` does not say.
+pub const DEFAULT_MIN_FREE_DISK_MB: u64 = 100;
+
+/// A dependency `/readyz` waits on. `check` runs under [`CHECK_TIMEOUT`];
+/// its error is reported as is, so it must not leak credentials.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Key of the check in the `checks` of [`HealthResponse`].
+    fn name(&self) -> &str;
+
+    async fn check(&self) -> Result<(), String>;
+}
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct CheckResult {
+    status: &'static str,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct HealthResponse {
+    status: &'static str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    checks: BTreeMap<String, CheckResult>,
+    /// When the checks ran (RFC 3339); reports are reused for a few
+    /// seconds, so it can be older than the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checked_at: Option<String>,
+}
+
+/// The checks behind `/readyz` and their last report. Checks run
+/// concurrently, and at most once per `cache_ttl` however many probes and
+/// load balancers ask. Cheap to clone.
+#[derive(Clone)]
+pub struct HealthChecks {
+    checks: Arc<[Box<dyn HealthCheck>]>,
+    cache_ttl: Duration,
+    /// Held while checks run, so concurrent requests wait for that report
+    /// instead of starting their own.
+    last: Arc<Mutex<Option<(Instant, bool, HealthResponse)>>>,
+}
+
+impl HealthChecks {
+    pub fn new(checks: Vec<Box<dyn HealthCheck>>, cache_ttl: Duration) -> Self {
+        Self {
+            checks: checks.into(),
+            cache_ttl,
+            last: Arc::default(),
+        }
+    }
+
+    /// Whether every check passed, and the report.
+    async fn report(&self) -> (bool, HealthResponse) {
+        let mut last = self.last.lock().await;
+        if let Some((at, ready, report)) = &*last
+            && at.elapsed() < self.cache_ttl
+        {
+            return (*ready, report.clone());
+        }
+
+        let results = join_all(self.checks.iter().map(|check| run(check.as_ref()))).await;
+        let ready = results.iter().all(|(_, result)| result.status == "ok");
+        let report = HealthResponse {
+            status: if ready { "ok" } else { "unavailable" },
+            checks: results.into_iter().collect(),
+            checked_at: Some(humantime::format_rfc3339_seconds(SystemTime::now()).to_string()),
+        };
+        *last = Some((Instant::now(), ready, report.clone()));
+        (ready, report)
+    }
+}
+
+async fn run(check: &dyn HealthCheck) -> (String, CheckResult) {
+    let start = Instant::now();
+    let outcome = tokio::time::timeout(CHECK_TIMEOUT, check.check()).await;
+    let latency_ms = start.elapsed().as_millis();
+
+    let error = match outcome {
+        Ok(Ok(())) => None,
+        Ok(Err(err)) => Some(err),
+        Err(_) => Some(format!("timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    if let Some(error) = &error {
+        warn!(check = check.name(), %error, "readiness check failed");
+    }
+
+    let result = CheckResult {
+        status: if error.is_none() { "ok" } else { "down" },
+        latency_ms,
+        error,
+    };
+    (check.name().to_owned(), result)
+}
+
+/// Liveness: the process is up and the runtime is answering requests. It
+/// deliberately checks nothing else so a slow database never gets the pod
+/// restarted.
+#[utoipa::path(get, path = "/healthz", tag = "health", responses((status = 200, body = HealthResponse)))]
+pub async fn healthz() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok",
+        checks: BTreeMap::new(),
+        checked_at: None,
+    })
+}
+
+/// Readiness: every dependency answered. Returns 503 (so orchestrators stop
+/// routing traffic here) when any check fails.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, body = HealthResponse),
+        (status = 503, description = "A dependency is down", body = HealthResponse)
+    )
+)]
+pub async fn readyz(State(checks): State<HealthChecks>) -> (StatusCode, Json<HealthResponse>) {
+    let (ready, report) = checks.report().await;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+/// `SELECT 1`, including the pool checkout, so an exhausted pool shows up
+/// as latency (or a timeout) here too.
+pub struct DatabaseCheck(pub DbPool);
+
+#[async_trait]
+impl HealthCheck for DatabaseCheck {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        let result = async {
+            let conn = self.0.get().await?;
+            let mut rows = conn.query("SELECT 1", ()).await?;
+            rows.next().await.map(|_| ())
+        };
+        result.await.map_err(|err| err.to_string())
+    }
+}
+
+/// Free space on the file system holding `path` (`.tmp`, where captures
+/// and recordings are staged), which must stay above `min_free_bytes`.
+#[cfg(unix)]
+pub struct DiskSpaceCheck {
+    pub path: PathBuf,
+    pub min_free_bytes: u64,
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl HealthCheck for DiskSpaceCheck {
+    fn name(&self) -> &str {
+        "disk"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        let path = self.path.clone();
+        let free = tokio::task::spawn_blocking(move || free_bytes(&path))
+            .await
+            .map_err(|err| err.to_string())?
+            .map_err(|err| format!("{}: {err}", self.path.display()))?;
+        if free < self.min_free_bytes {
+            return Err(format!(
+                "{} MB free under {}, below {} MB",
+                free / 1024 / 1024,
+                self.path.display(),
+                self.min_free_bytes / 1024 / 1024
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Bytes available to this process on the file system of `path`, or of its
+/// closest existing ancestor while `path` itself is not created yet.
+#[cfg(unix)]
+fn free_bytes(path: &std::path::Path) -> std::io::Result<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let existing = path
+        .ancestors()
+        .find(|dir| dir.as_os_str().is_empty() || dir.exists())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    let c_path = CString::new(existing.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `c_path` is NUL-terminated and `stat` is only read after
+    // `statvfs` reports it filled in.
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// An upstream HTTP service (`[[health.upstreams]]`), up when `url` answers
+/// with a 2xx or 3xx.
+pub struct UpstreamCheck {
+    pub name: String,
+    pub url: String,
+    pub client: reqwest::Client,
+}
+
+#[async_trait]
+impl HealthCheck for UpstreamCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .map_err(|err| err.without_url().to_string())?;
+        let status = response.status();
+        if status.is_client_error() || status.is_server_error() {
+            return Err(format!("answered {status}"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use anyhow::Context;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+const REQUESTS_TOTAL: &str = "http_requests_total";
+const REQUEST_DURATION: &str = "http_request_duration_seconds";
+const REQUESTS_IN_FLIGHT: &str = "http_requests_in_flight";
+
+/// Latency buckets in seconds, from a fast in-memory handler up to a slow
+/// database round trip.
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Running totals kept next to the Prometheus series, so the dashboard
+/// does not have to parse the exposition format back.
+static TOTAL: AtomicU64 = AtomicU64::new(0);
+static CLIENT_ERRORS: AtomicU64 = AtomicU64::new(0);
+static SERVER_ERRORS: AtomicU64 = AtomicU64::new(0);
+static IN_FLIGHT: AtomicU64 = AtomicU64::new(0);
+
+/// Requests handled since the server started.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestTotals {
+    pub total: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    pub in_flight: u64,
+}
+
+pub fn request_totals() -> RequestTotals {
+    RequestTotals {
+        total: TOTAL.load(Ordering::Relaxed),
+        client_errors: CLIENT_ERRORS.load(Ordering::Relaxed),
+        server_errors: SERVER_ERRORS.load(Ordering::Relaxed),
+        in_flight: IN_FLIGHT.load(Ordering::Relaxed),
+    }
+}
+
+/// Installs the global Prometheus recorder and returns the handle used to
+/// render `/metrics`. Only the first call installs it; later ones (a server
+/// restarted in the same process) get the same handle, counters included.
+pub fn install_recorder() -> anyhow::Result<PrometheusHandle> {
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+    if let Some(handle) = HANDLE.get() {
+        return Ok(handle.clone());
+    }
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(REQUEST_DURATION.to_string()),
+            DURATION_BUCKETS,
+        )
+        .context("invalid histogram buckets")?
+        .set_buckets_for_metric(
+            Matcher::Full(crate::db::CHECKOUT_DURATION.to_string()),
+            crate::db::CHECKOUT_BUCKETS,
+        )
+        .context("invalid histogram buckets")?
+        .install_recorder()
+        .context("failed to install the Prometheus recorder")?;
+    Ok(HANDLE.get_or_init(|| handle).clone())
+}
+
+/// Keeps the in-flight gauge honest even when the client disconnects and the
+/// request future is dropped halfway.
+struct InFlight(metrics::Gauge);
+
+impl InFlight {
+    fn enter() -> Self {
+        let gauge = metrics::gauge!(REQUESTS_IN_FLIGHT);
+        gauge.increment(1);
+        IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+        Self(gauge)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.decrement(1);
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Records count, latency and in-flight requests for every request. Routes
+/// are labelled by their pattern (`/users/{id}`), not the raw path, so the
+/// number of series stays bounded.
+pub async fn track_metrics(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_owned(), |path| path.as_str().to_owned());
+    let method = req.method().to_string();
+    let start = Instant::now();
+
+    let in_flight = InFlight::enter();
+    let response = next.run(req).await;
+    drop(in_flight);
+
+    let status = response.status();
+    TOTAL.fetch_add(1, Ordering::Relaxed);
+    if status.is_client_error() {
+        CLIENT_ERRORS.fetch_add(1, Ordering::Relaxed);
+    } else if status.is_server_error() {
+        SERVER_ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", status.as_u16().to_string()),
+    ];
+    metrics::counter!(REQUESTS_TOTAL, &labels).increment(1);
+    metrics::histogram!(REQUEST_DURATION, &labels).record(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Prometheus text exposition of everything recorded so far.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "meta",
+    responses((status = 200, content_type = "text/plain", body = String))
+)]
+pub async fn metrics_handler(State(handle): State<PrometheusHandle>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+}
@@ -0,0 +1,533 @@
+use std::{sync::Arc, time::Duration};
+
+use audio_capture::sink::{AudioSink, FlacSink, SinkSpec};
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use libsql::Row;
+use screen_capture::CaptureError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::{
+    sync::{Notify, watch},
+    task::JoinSet,
+};
+use tracing::{Instrument, error, info, info_span, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{Admin, Role, User},
+    db::DbPool,
+    error::ApiError,
+    events::EventBus,
+    queue::{backoff_delay, poll_worker},
+    recordings::{AudioFormat, RecordingStore},
+    screenshots::{ScreenshotStore, file_url},
+    versioning,
+};
+
+/// Attempts per job when `POST /jobs` does not say otherwise.
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const MAX_ATTEMPTS_LIMIT: u32 = 10;
+/// Delay before the first retry; doubles with every failed attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+/// Upper bounds for screenshot batches.
+const MAX_SCREENSHOT_COUNT: u32 = 100;
+const MAX_SCREENSHOT_INTERVAL_SECS: u64 = 60 * 60;
+
+/// A row of the `jobs` table. `status` is one of `queued`, `running`,
+/// `succeeded` or `failed`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct JobRecord {
+    pub id: i64,
+    pub kind: String,
+    pub payload: Value,
+    pub status: String,
+    pub attempts: u32,
+    pub max_attempts: u32,
+    /// What the job produced, once it succeeded.
+    pub result: Option<Value>,
+    /// Error of the last failed attempt.
+    pub error: Option<String>,
+    pub created_by: String,
+    /// Earliest time the next attempt may start.
+    pub run_after: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+const JOB_COLUMNS: &str = "id, kind, payload, status, attempts, max_attempts, result, error, \
+     created_by, run_after, created_at, updated_at, started_at, finished_at";
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateJob {
+    /// `transcode` (`{"recording_id": 1}`) or `screenshot`
+    /// (`{"count": 5, "interval_secs": 10}`).
+    pub kind: String,
+    #[serde(default)]
+    pub payload: Value,
+    /// Attempts before the job is marked `failed` (default 3, at most 10).
+    pub max_attempts: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TranscodeJob {
+    recording_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScreenshotJob {
+    #[serde(default = "one")]
+    count: u32,
+    #[serde(default)]
+    interval_secs: u64,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// The work a job describes, parsed from its kind and payload.
+enum JobSpec {
+    /// Re-encodes an uploaded WAV recording as FLAC next to the original.
+    Transcode(TranscodeJob),
+    /// Captures every display `count` times, `interval_secs` apart.
+    Screenshot(ScreenshotJob),
+}
+
+impl JobSpec {
+    fn parse(kind: &str, payload: &Value) -> Result<Self, String> {
+        let payload = payload.clone();
+        match kind {
+            "transcode" => serde_json::from_value(payload)
+                .map(Self::Transcode)
+                .map_err(|err| err.to_string()),
+            "screenshot" => {
+                let job: ScreenshotJob =
+                    serde_json::from_value(payload).map_err(|err| err.to_string())?;
+                if !(1..=MAX_SCREENSHOT_COUNT).contains(&job.count)
+                    || job.interval_secs > MAX_SCREENSHOT_INTERVAL_SECS
+                {
+                    return Err(format!(
+                        "count must be 1..={MAX_SCREENSHOT_COUNT} and interval_secs at most {MAX_SCREENSHOT_INTERVAL_SECS}"
+                    ));
+                }
+                Ok(Self::Screenshot(job))
+            }
+            other => Err(format!("unknown job kind {other:?}")),
+        }
+    }
+}
+
+/// Why an attempt failed: `Retry` errors may go away on their own (no
+/// display yet, disk full), `Fatal` ones never will.
+enum JobError {
+    Retry(String),
+    Fatal(String),
+}
+
+fn internal(err: impl std::fmt::Display) -> ApiError {
+    ApiError::internal("job store failure", err)
+}
+
+/// In-process job queue persisted in the `jobs` table, so queued work
+/// survives restarts. Workers are plain tokio tasks started by
+/// [`JobQueue::start`]. Cheap to clone.
+#[derive(Clone)]
+pub struct JobQueue {
+    pool: DbPool,
+    /// Wakes an idle worker as soon as a job is enqueued.
+    notify: Arc<Notify>,
+    recordings: RecordingStore,
+    screenshots: ScreenshotStore,
+    events: EventBus,
+}
+
+impl JobQueue {
+    pub fn new(
+        pool: DbPool,
+        recordings: RecordingStore,
+        screenshots: ScreenshotStore,
+        events: EventBus,
+    ) -> Self {
+        Self {
+            pool,
+            notify: Arc::new(Notify::new()),
+            recordings,
+            screenshots,
+            events,
+        }
+    }
+
+    fn from_row(row: &Row) -> Result<JobRecord, libsql::Error> {
+        let json =
+            |text: Option<String>| text.map(|t| serde_json::from_str(&t).unwrap_or_default());
+        Ok(JobRecord {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            payload: json(Some(row.get(2)?)).unwrap_or_default(),
+            status: row.get(3)?,
+            attempts: row.get(4)?,
+            max_attempts: row.get(5)?,
+            result: json(row.get(6)?),
+            error: row.get(7)?,
+            created_by: row.get(8)?,
+            run_after: row.get(9)?,
+            created_at: row.get(10)?,
+            updated_at: row.get(11)?,
+            started_at: row.get(12)?,
+            finished_at: row.get(13)?,
+        })
+    }
+
+    async fn query_one(
+        &self,
+        sql: &str,
+        params: impl libsql::params::IntoParams,
+    ) -> Result<Option<JobRecord>, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn.query(sql, params).await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(Self::from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Jobs left `running` by a crash or an expired drain are queued again,
+    /// or failed if that was their last attempt. Call before [`Self::start`].
+    pub async fn recover(&self) -> Result<u64, libsql::Error> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE jobs SET \
+             status = CASE WHEN attempts >= max_attempts THEN 'failed' ELSE 'queued' END, \
+             error = 'interrupted by a server restart', \
+             finished_at = CASE WHEN attempts >= max_attempts THEN CURRENT_TIMESTAMP END, \
+             updated_at = CURRENT_TIMESTAMP \
+             WHERE status = 'running'",
+            (),
+        )
+        .await
+    }
+
+    async fn enqueue(
+        &self,
+        kind: &str,
+        payload: &Value,
+        max_attempts: u32,
+        created_by: &str,
+    ) -> Result<JobRecord, libsql::Error> {
+        let job = self
+            .query_one(
+                &format!(
+                    "INSERT INTO jobs (kind, payload, max_attempts, created_by) \
+                     VALUES (?1, ?2, ?3, ?4) RETURNING {JOB_COLUMNS}"
+                ),
+                libsql::params![kind, payload.to_string(), max_attempts, created_by],
+            )
+            .await?
+            .ok_or(libsql::Error::QueryReturnedNoRows)?;
+        self.notify.notify_one();
+        Ok(job)
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<JobRecord>, libsql::Error> {
+        self.query_one(
+            &format!("SELECT {JOB_COLUMNS} FROM jobs WHERE id = ?1"),
+            libsql::params![id],
+        )
+        .await
+    }
+
+    /// Marks the oldest due job `running` and returns it. A single
+    /// statement, so two workers never claim the same job.
+    async fn claim(&self) -> Result<Option<JobRecord>, libsql::Error> {
+        self.query_one(
+            &format!(
+                "UPDATE jobs SET status = 'running', attempts = attempts + 1, \
+                 started_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP \
+                 WHERE id = (SELECT id FROM jobs WHERE status = 'queued' \
+                 AND run_after <= CURRENT_TIMESTAMP ORDER BY id LIMIT 1) \
+                 RETURNING {JOB_COLUMNS}"
+            ),
+            (),
+        )
+        .await
+    }
+
+    async fn succeed(&self, id: i64, result: &Value) -> Result<(), libsql::Error> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE jobs SET status = 'succeeded', result = ?2, error = NULL, \
+             finished_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+            libsql::params![id, result.to_string()],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Records a failed attempt: back to `queued` after a backoff delay, or
+    /// `failed` for good when the error is fatal or attempts ran out.
+    async fn fail(&self, job: &JobRecord, err: JobError) -> Result<bool, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let (message, retry) = match err {
+            JobError::Retry(message) => (message, job.attempts < job.max_attempts),
+            JobError::Fatal(message) => (message, false),
+        };
+        if retry {
+            let delay = backoff_delay(RETRY_BASE_DELAY, RETRY_MAX_DELAY, job.attempts);
+            conn.execute(
+                "UPDATE jobs SET status = 'queued', error = ?2, \
+                 run_after = datetime('now', ?3), updated_at = CURRENT_TIMESTAMP \
+                 WHERE id = ?1",
+                libsql::params![job.id, message, format!("+{} seconds", delay.as_secs())],
+            )
+            .await?;
+        } else {
+            conn.execute(
+                "UPDATE jobs SET status = 'failed', error = ?2, \
+                 finished_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP \
+                 WHERE id = ?1",
+                libsql::params![job.id, message],
+            )
+            .await?;
+        }
+        Ok(retry)
+    }
+
+    /// Spawns `workers` tasks that run jobs until `shutdown` fires. A worker
+    /// finishes the job it is on before stopping, so awaiting the returned
+    /// set drains the queue's in-flight work.
+    pub fn start(&self, workers: usize, shutdown: watch::Receiver<()>) -> JoinSet<()> {
+        let mut set = JoinSet::new();
+        for worker in 0..workers {
+            let queue = self.clone();
+            let shutdown = shutdown.clone();
+            set.spawn(
+                async move { queue.work(shutdown).await }
+                    .instrument(info_span!("job_worker", worker)),
+            );
+        }
+        set
+    }
+
+    async fn work(self, shutdown: watch::Receiver<()>) {
+        poll_worker(
+            "job",
+            &self.notify,
+            shutdown,
+            || self.claim(),
+            |job| self.process(job),
+        )
+        .await;
+    }
+
+    async fn process(&self, job: JobRecord) {
+        let span = info_span!("job", job_id = job.id, kind = %job.kind, attempt = job.attempts);
+        async {
+            info!("job started");
+            // Run in its own task so a panicking job fails instead of taking
+            // the worker down with it.
+            let outcome = match JobSpec::parse(&job.kind, &job.payload) {
+                Ok(spec) => {
+                    let queue = self.clone();
+                    tokio::spawn(async move { queue.execute(spec).await })
+                        .await
+                        .unwrap_or_else(|err| Err(JobError::Fatal(format!("job panicked: {err}"))))
+                }
+                Err(err) => Err(JobError::Fatal(err)),
+            };
+
+            match outcome {
+                Ok(result) => match self.succeed(job.id, &result).await {
+                    Ok(()) => {
+                        info!("job succeeded");
+                        self.events.publish(
+                            "job.succeeded",
+                            json!({ "job_id": job.id, "kind": job.kind }),
+                        );
+                    }
+                    Err(err) => error!(error = %err, "failed to record job result"),
+                },
+                Err(err) => {
+                    let message = match &err {
+                        JobError::Retry(message) | JobError::Fatal(message) => message.clone(),
+                    };
+                    match self.fail(&job, err).await {
+                        Ok(true) => warn!(error = %message, "job failed; will retry"),
+                        Ok(false) => {
+                            warn!(error = %message, "job failed");
+                            self.events.publish(
+                                "job.failed",
+                                json!({ "job_id": job.id, "kind": job.kind, "error": message }),
+                            );
+                        }
+                        Err(err) => error!(error = %err, "failed to record job failure"),
+                    }
+                }
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+
+    async fn execute(&self, spec: JobSpec) -> Result<Value, JobError> {
+        match spec {
+            JobSpec::Transcode(job) => self.transcode(job).await,
+            JobSpec::Screenshot(job) => self.screenshot(job).await,
+        }
+    }
+
+    async fn transcode(&self, job: TranscodeJob) -> Result<Value, JobError> {
+        let recording = self
+            .recordings
+            .get(job.recording_id)
+            .await
+            .map_err(|err| JobError::Retry(err.to_string()))?
+            .ok_or_else(|| JobError::Fatal(format!("recording {} not found", job.recording_id)))?;
+        let source = self.recordings.dir().join(&recording.file_name);
+        let file_name = recording.stored_name(AudioFormat::Flac);
+        let target = self.recordings.dir().join(&file_name);
+
+        let size_bytes = tokio::task::spawn_blocking(move || {
+            let mut reader = hound::WavReader::open(&source)
+                .map_err(|err| JobError::Fatal(format!("cannot read recording: {err}")))?;
+            let spec = reader.spec();
+            if spec.sample_format != hound::SampleFormat::Int || spec.bits_per_sample != 16 {
+                return Err(JobError::Fatal(format!(
+                    "only 16-bit PCM can be transcoded, recording is {}-bit {:?}",
+                    spec.bits_per_sample, spec.sample_format
+                )));
+            }
+            let samples = reader
+                .samples::<i16>()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| JobError::Fatal(format!("corrupt recording: {err}")))?;
+            let mut sink = Box::new(FlacSink::new(
+                &target,
+                SinkSpec {
+                    channels: spec.channels,
+                    sample_rate: spec.sample_rate,
+                },
+            ));
+            sink.write(&samples)
+                .and_then(|()| sink.finish())
+                .map_err(|err| JobError::Retry(err.to_string()))?;
+            std::fs::metadata(&target)
+                .map(|meta| meta.len())
+                .map_err(|err| JobError::Retry(err.to_string()))
+        })
+        .await
+        .map_err(|err| JobError::Fatal(format!("transcode task panicked: {err}")))??;
+
+        Ok(json!({
+            "recording_id": recording.id,
+            "format": "flac",
+            "file_name": file_name,
+            "size_bytes": size_bytes,
+        }))
+    }
+
+    async fn screenshot(&self, job: ScreenshotJob) -> Result<Value, JobError> {
+        let mut urls = Vec::new();
+        for shot in 0..job.count {
+            if shot > 0 {
+                tokio::time::sleep(Duration::from_secs(job.interval_secs)).await;
+            }
+            let saved = self.screenshots.capture().await.map_err(|err| match err {
+                // No display right now; one may show up before the retry.
+                CaptureError::Displays(_)
+                | CaptureError::Capture(..)
+                | CaptureError::NoSuchDisplay(_)
+                | CaptureError::NoSuchWindow(_)
+                | CaptureError::Io(_) => JobError::Retry(err.to_string()),
+                CaptureError::Save(_) | CaptureError::Unsupported(_) => {
+                    JobError::Fatal(err.to_string())
+                }
+            })?;
+            urls.extend(saved.iter().map(file_url));
+        }
+        self.events
+            .publish("screenshots.captured", json!({ "urls": urls }));
+        Ok(json!({ "urls": urls }))
+    }
+}
+
+/// `POST /jobs`: queues a job and answers right away; poll the returned
+/// `Location` for its status.
+#[utoipa::path(
+    post,
+    path = "/jobs",
+    tag = "jobs",
+    security(("bearer" = []), ("api_key" = [])),
+    request_body = CreateJob,
+    responses(
+        (status = 202, body = JobRecord, headers(("location" = String))),
+        (status = 400, description = "Unknown kind, invalid payload or max_attempts"),
+        (status = 401)
+    )
+)]
+pub async fn create_job(
+    State(queue): State<JobQueue>,
+    State(events): State<EventBus>,
+    Extension(user): Extension<User>,
+    Json(mut input): Json<CreateJob>,
+) -> Result<Response, ApiError> {
+    if input.payload.is_null() {
+        input.payload = json!({});
+    }
+    let max_attempts = input.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS);
+    if !(1..=MAX_ATTEMPTS_LIMIT).contains(&max_attempts) {
+        return Err(ApiError::bad_request(format!(
+            "max_attempts must be 1..={MAX_ATTEMPTS_LIMIT}"
+        )));
+    }
+    if let Err(err) = JobSpec::parse(&input.kind, &input.payload) {
+        warn!(kind = %input.kind, error = %err, "invalid job");
+        return Err(ApiError::bad_request(err));
+    }
+
+    let job = queue
+        .enqueue(&input.kind, &input.payload, max_attempts, &user.id)
+        .await
+        .map_err(internal)?;
+    info!(job_id = job.id, kind = %job.kind, user_id = %user.id, "queued job");
+    events.publish("job.queued", json!({ "job_id": job.id, "kind": job.kind }));
+    Ok((
+        StatusCode::ACCEPTED,
+        [(
+            header::LOCATION,
+            format!("{}/jobs/{}", versioning::CURRENT, job.id),
+        )],
+        Json(job),
+    )
+        .into_response())
+}
+
+/// `GET /jobs/{id}`: a job's status, attempts and result. Only its creator
+/// and admins can see it.
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    tag = "jobs",
+    security(("bearer" = []), ("api_key" = [])),
+    params(("id" = i64, Path)),
+    responses((status = 200, body = JobRecord), (status = 401), (status = 404))
+)]
+pub async fn get_job(
+    State(queue): State<JobQueue>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i64>,
+) -> Result<Json<JobRecord>, ApiError> {
+    match queue.get(id).await.map_err(internal)? {
+        Some(job) if job.created_by == user.id || user.has_role(Admin::NAME) => Ok(Json(job)),
+        _ => Err(ApiError::not_found(format!("no job {id}"))),
+    }
+}
@@ -0,0 +1,960 @@
+//! The HTTP API behind the `simple-http-server` binary: routing, the
+//! middleware stack, background workers and graceful shutdown. The binary
+//! only turns flags and environment variables into [`ServerOptions`] and
+//! calls [`run`].
+
+mod admin;
+mod api_keys;
+mod audit;
+mod auth;
+mod body_logging;
+mod config;
+pub mod credentials;
+mod dashboard;
+mod db;
+mod docs;
+mod error;
+mod etag;
+mod events;
+mod filters;
+mod graphql;
+mod grpc;
+mod health;
+mod http_metrics;
+mod jobs;
+mod load_shed;
+mod maintenance;
+mod notes;
+mod pagination;
+mod proxy;
+mod queue;
+mod rate_limit;
+mod recordings;
+mod reload;
+mod screenshots;
+mod security_headers;
+mod sessions;
+mod setup;
+mod state;
+mod tokens;
+mod users;
+mod validation;
+mod versioning;
+mod webhooks;
+
+use std::{
+    env,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, Request, State},
+    handler::Handler,
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, Uri, header},
+    middleware::{self, Next},
+    response::{Redirect, Response},
+    routing::{delete, get, post},
+};
+use axum_server::tls_rustls::RustlsConfig;
+use serde::Serialize;
+use tokio::{sync::watch, task::JoinSet};
+use tower_http::compression::{
+    CompressionLayer,
+    predicate::{NotForContentType, Predicate, SizeAbove},
+};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    limit::RequestBodyLimitLayer,
+    services::ServeDir,
+    timeout::{RequestBodyTimeoutLayer, TimeoutLayer},
+};
+use tracing::{Instrument, error, info, info_span, warn};
+use utoipa::OpenApi;
+use utoipa::ToSchema;
+use utoipa_swagger_ui::SwaggerUi;
+
+use migrate_core::run_migrations;
+use migrate_libsql::LibSqlAdapter;
+use playground_common::{error::PlaygroundError, telemetry::Telemetry};
+
+use crate::{
+    api_keys::ApiKeyStore,
+    audit::AuditLog,
+    auth::{AuthState, JwtVerifier, auth_inject_user},
+    config::ServerConfig,
+    credentials::Credentials,
+    db::DbPool,
+    error::ApiError,
+    events::EventBus,
+    jobs::JobQueue,
+    maintenance::Maintenance,
+    notes::NoteRepository,
+    rate_limit::RateLimiter,
+    recordings::RecordingStore,
+    reload::{Feature, LiveConfig, LogFilter},
+    screenshots::ScreenshotStore,
+    sessions::SessionStore,
+    setup::SetupToken,
+    state::AppState,
+    tokens::{RefreshTokenStore, TokenIssuer},
+    users::UserRepository,
+    versioning::{ApiVersion, Deprecation},
+    webhooks::WebhookDispatcher,
+};
+
+pub use body_logging::BodyLogging;
+pub use rate_limit::RateLimitConfig;
+
+#[utoipa::path(get, path = "/", tag = "meta", responses((status = 200, body = String)))]
+async fn hello_world() -> &'static str {
+    info!("responding with hello world");
+    "Hello, world!"
+}
+
+#[derive(Serialize, ToSchema)]
+struct StatusServerResponse {
+    hostname: String,
+}
+
+/// Echoes the `Host` header the request arrived with.
+#[utoipa::path(get, path = "/status", tag = "meta", responses((status = 200, body = StatusServerResponse)))]
+async fn status_server(headers: HeaderMap) -> Json<StatusServerResponse> {
+    let hostname = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("<unknown>")
+        .to_string();
+
+    info!(%hostname, "status endpoint resolved hostname");
+
+    Json(StatusServerResponse { hostname })
+}
+
+/// Header carrying the correlation id of a request, both ways.
+const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Reuses the caller's `x-request-id` when it looks sane (so one id follows a
+/// request across services) and mints a fresh UUID otherwise.
+fn request_id(headers: &HeaderMap) -> HeaderValue {
+    headers
+        .get(&REQUEST_ID_HEADER)
+        .filter(|id| {
+            !id.is_empty() && id.len() <= 128 && id.as_bytes().iter().all(u8::is_ascii_graphic)
+        })
+        .cloned()
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&uuid::Uuid::new_v4().to_string())
+                .expect("a UUID is a valid header value")
+        })
+}
+
+async fn log_requests(mut req: Request, next: Next) -> Result<Response, StatusCode> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_owned();
+    let version = req.version();
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned)
+        .unwrap_or_else(|| "-".into());
+    let request_id = request_id(req.headers());
+    // Handlers see the id in the request headers too, whether it was sent or
+    // generated here.
+    req.headers_mut()
+        .insert(REQUEST_ID_HEADER, request_id.clone());
+    // `user_id` is filled in by the auth middleware once the caller is known.
+    let span = info_span!(
+        "request",
+        request_id = request_id.to_str().unwrap_or_default(),
+        user_id = tracing::field::Empty,
+    );
+
+    async move {
+        let start = Instant::now();
+        info!(%method, %path, ?version, %user_agent, "received request");
+
+        let mut response = next.run(req).await;
+        let status = response.status();
+        let elapsed = start.elapsed();
+
+        // Numbers rather than Display strings, so JSON logs can be aggregated.
+        info!(%method, %path, status = status.as_u16(), elapsed_ms = elapsed.as_millis() as u64, "completed request");
+
+        response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+        Ok(response)
+    }
+    .instrument(span)
+    .await
+}
+
+/// Paths to the PEM certificate chain and private key used for HTTPS.
+pub struct TlsPaths {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Port used when neither `--port` nor `PORT` is given.
+pub const DEFAULT_PORT: u16 = 3000;
+
+/// Responses smaller than this are sent as-is; compressing them costs more
+/// than the bytes it saves.
+const COMPRESSION_MIN_BYTES: u16 = 1024;
+
+/// Largest accepted recording upload (`RECORDINGS_MAX_BYTES`).
+const DEFAULT_RECORDINGS_MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Largest request body accepted outside of uploads (`BODY_LIMIT_BYTES`).
+pub const DEFAULT_BODY_LIMIT: usize = 1024 * 1024;
+/// Time a handler gets to produce a response (`REQUEST_TIMEOUT`); also the
+/// longest pause allowed between two chunks of a request body.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Overall time budget of a recording upload, which may legitimately be
+/// much slower than a JSON request (`timeouts.uploads` in the config file).
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Default sustained request rate per client (IP or bearer token).
+pub const DEFAULT_RATE_LIMIT: f64 = 10.0;
+/// Default burst size per client.
+pub const DEFAULT_RATE_BURST: u32 = 20;
+/// Log filter when neither `RUST_LOG` nor `log.level` sets one; migrations
+/// run at startup, so their events are included.
+pub const DEFAULT_LOG_FILTER: &str = "playground_server=info,migrate_core=info";
+
+/// How long shutdown waits for in-flight requests before cutting them off
+/// (`SHUTDOWN_GRACE_PERIOD`).
+const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Background job workers (`JOBS_WORKERS`).
+const DEFAULT_JOB_WORKERS: usize = 2;
+/// How long shutdown waits for running jobs (`JOBS_DRAIN_TIMEOUT`); jobs
+/// still running after that are retried on the next start.
+const DEFAULT_JOBS_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Everything [`run`] needs from the command line; the binary fills it in
+/// from flags on top of the `[server]` and `[migrate]` sections of the
+/// shared configuration (`playground_cli::config::Config`).
+pub struct ServerOptions {
+    /// Every address the server listens on.
+    pub addrs: Vec<SocketAddr>,
+    pub tls: Option<TlsPaths>,
+    /// Plain-HTTP port that only redirects to the HTTPS listener.
+    pub redirect_port: Option<u16>,
+    /// `None` when rate limiting is disabled.
+    pub rate_limit: Option<RateLimitConfig>,
+    pub body_limit: usize,
+    pub request_timeout: Duration,
+    /// TOML file with the settings that don't fit a flag ([`ServerConfig`]).
+    pub config: Option<PathBuf>,
+    /// Off unless request/response bodies should be logged.
+    pub log_bodies: Option<BodyLogging>,
+    /// The libSQL database, shared with `migrate-to-latest`.
+    pub db_path: String,
+}
+
+/// Accepts plain seconds (`30`) or a `humantime` duration (`30s`, `2m`).
+pub fn parse_duration(value: &str) -> anyhow::Result<Duration> {
+    if let Ok(secs) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    humantime::parse_duration(value)
+        .with_context(|| format!("invalid duration {value:?}: expected 30, 30s, 2m..."))
+}
+
+/// Answers every plain-HTTP request with a permanent redirect to the same
+/// host and path on the HTTPS port.
+async fn redirect_to_https(
+    State(https_port): State<u16>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Redirect, ApiError> {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| ApiError::bad_request("missing Host header"))?;
+    // Drop any port from the Host header (but keep IPv6 brackets intact).
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let path = uri.path_and_query().map_or("/", |pq| pq.as_str());
+
+    Ok(Redirect::permanent(&format!(
+        "https://{host}:{https_port}{path}"
+    )))
+}
+
+async fn spawn_https_redirect(
+    redirect_port: u16,
+    https_port: u16,
+    shutdown_rx: watch::Receiver<()>,
+) -> anyhow::Result<()> {
+    let addr = format!("0.0.0.0:{redirect_port}");
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .with_context(|| format!("failed to bind redirect listener to {addr}"))
+        .map_err(PlaygroundError::unavailable)?;
+    info!(%addr, https_port, "redirecting plain http to https");
+
+    let app = Router::new()
+        .fallback(redirect_to_https)
+        .with_state(https_port)
+        .layer(middleware::from_fn(log_requests));
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, app)
+            .with_graceful_shutdown(wait_for_shutdown(shutdown_rx))
+            .await
+        {
+            error!(error = %err, "redirect server terminated with error");
+        }
+    });
+    Ok(())
+}
+
+/// Binds a listener, turning "address in use" into an actionable message.
+fn bind_listener(addr: SocketAddr) -> anyhow::Result<std::net::TcpListener> {
+    let listener = match std::net::TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => {
+            return Err(PlaygroundError::unavailable(anyhow::anyhow!(
+                "{addr} is already in use; stop the other process or pick another port with --port/PORT"
+            ))
+            .into());
+        }
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("failed to bind to {addr}"))
+                .map_err(|err| PlaygroundError::unavailable(err).into());
+        }
+    };
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Serves `app` on one listener (HTTP or HTTPS) until shutdown is signalled.
+/// Both speak HTTP/1.1 and HTTP/2: HTTPS negotiates `h2` through ALPN, and
+/// plain HTTP accepts cleartext HTTP/2 with prior knowledge (h2c, as gRPC
+/// clients and `curl --http2-prior-knowledge` send it).
+async fn serve_listener(
+    listener: std::net::TcpListener,
+    app: Router,
+    tls_config: Option<RustlsConfig>,
+    shutdown_rx: watch::Receiver<()>,
+) -> std::io::Result<()> {
+    let local_addr = listener.local_addr()?;
+
+    match tls_config {
+        Some(config) => {
+            let handle = axum_server::Handle::new();
+            tokio::spawn({
+                let handle = handle.clone();
+                async move {
+                    wait_for_shutdown(shutdown_rx).await;
+                    handle.graceful_shutdown(None);
+                }
+            });
+
+            let listen_addr = format!("https://{local_addr}");
+            info!(%listen_addr, protocols = "h2, http/1.1", "listening");
+
+            axum_server::from_tcp_rustls(listener, config)?
+                .handle(handle)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+        }
+        None => {
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+
+            let listen_addr = format!("http://{local_addr}");
+            info!(%listen_addr, protocols = "h2c, http/1.1", "listening");
+
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(wait_for_shutdown(shutdown_rx))
+            .await
+        }
+    }
+}
+
+/// Waits for every server task and returns the last error, if any.
+async fn join_servers(servers: &mut JoinSet<std::io::Result<()>>) -> std::io::Result<()> {
+    let mut result = Ok(());
+    while let Some(joined) = servers.join_next().await {
+        match joined {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => result = Err(err),
+            Err(err) => result = Err(std::io::Error::other(err)),
+        }
+    }
+    result
+}
+
+async fn wait_for_shutdown(mut shutdown_rx: watch::Receiver<()>) {
+    // An error means the sender is gone, which also means we are shutting down.
+    let _ = shutdown_rx.changed().await;
+}
+
+/// Resolves when the process receives SIGINT (Ctrl+C) or SIGTERM (systemd,
+/// Kubernetes), letting `axum::serve` stop accepting connections and finish
+/// in-flight requests.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!(error = %err, "failed to install Ctrl+C handler");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                error!(error = %err, "failed to install SIGTERM handler");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => info!(signal = "SIGINT", "shutdown requested"),
+        () = terminate => info!(signal = "SIGTERM", "shutdown requested"),
+    }
+}
+
+/// Runs the server until a shutdown signal (or a listener failure), then
+/// drains in-flight requests and background jobs.
+///
+/// `telemetry` is the subscriber the binary installed; the config file's
+/// `[log]` section swaps its filter at runtime.
+pub async fn run(options: ServerOptions, telemetry: &Telemetry) -> anyhow::Result<()> {
+    run_until(options, telemetry, shutdown_signal()).await
+}
+
+/// [`run`], but stopping when `shutdown` resolves instead of on SIGINT or
+/// SIGTERM, for a caller that owns the signals and runs other things next
+/// to the server (`playground daemon`). Safe to call again after it
+/// returns.
+pub async fn run_until(
+    options: ServerOptions,
+    telemetry: &Telemetry,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let log_filter = LogFilter::new(
+        telemetry.filter_handle(),
+        telemetry.startup_filter().to_string(),
+    );
+    error::install_panic_hook();
+
+    let config = match &options.config {
+        Some(path) => ServerConfig::load(path)?,
+        None => ServerConfig::default(),
+    };
+    let timeouts = config
+        .timeouts
+        .resolve(options.request_timeout, UPLOAD_TIMEOUT)?;
+    let security = Arc::new(config.security_headers.resolve(options.tls.is_some())?);
+    info!(
+        api_secs = timeouts.api.as_secs_f64(),
+        auth_secs = timeouts.auth.as_secs_f64(),
+        uploads_secs = timeouts.uploads.as_secs_f64(),
+        ops_secs = timeouts.ops.as_secs_f64(),
+        "route timeouts"
+    );
+    // Always installed so `[rate_limit]` can switch limiting on at runtime;
+    // `LiveConfig` sets its initial configuration.
+    let limiter = Arc::new(RateLimiter::new(None));
+    let live_config = LiveConfig::new(
+        options.config.clone(),
+        &config,
+        log_filter,
+        limiter.clone(),
+        options.rate_limit,
+        DEFAULT_RATE_BURST,
+    )?;
+    // A single signal listener fans out to every server (HTTPS + redirect)
+    // and to the background tasks.
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    tokio::spawn(async move {
+        shutdown.await;
+        let _ = shutdown_tx.send(());
+    });
+    reload::reload_on_sighup(live_config.clone(), shutdown_rx.clone())?;
+    // Both the HTTPS listener and the JWKS client use rustls with `ring`.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let verifier = Arc::new(JwtVerifier::from_env().await?);
+
+    // Same database and migration flow as the `migrate-to-latest` binary, so
+    // the server always starts on the latest schema.
+    let db_path = options.db_path.clone();
+    let pool_size = match env::var("DB_POOL_SIZE") {
+        Ok(size) => size
+            .parse::<usize>()
+            .ok()
+            .filter(|size| *size > 0)
+            .with_context(|| {
+                format!("invalid DB_POOL_SIZE {size:?}: expected a positive integer")
+            })?,
+        Err(_) => db::DEFAULT_POOL_SIZE,
+    };
+    let pool = DbPool::open(&db_path, pool_size)
+        .await
+        .with_context(|| format!("failed to open database {db_path}"))
+        .map_err(PlaygroundError::unavailable)?;
+    {
+        let conn = pool
+            .get()
+            .await
+            .context("failed to connect to the database")
+            .map_err(PlaygroundError::unavailable)?;
+        run_migrations(&LibSqlAdapter::new(conn.clone()))
+            .await
+            .context("failed to apply migrations")?;
+    }
+    info!(%db_path, pool_size, "database ready");
+    let health_checks = config.health.resolve(pool.clone())?;
+    let users = UserRepository::new(pool.clone());
+    let notes = NoteRepository::new(pool.clone());
+    let audit = AuditLog::new(pool.clone());
+    let metrics_handle = http_metrics::install_recorder()?;
+    let events = EventBus::new();
+
+    let recordings_dir = env::var("RECORDINGS_DIR").unwrap_or_else(|_| "recordings".to_string());
+    let recordings_max_bytes = match env::var("RECORDINGS_MAX_BYTES") {
+        Ok(max) => max
+            .parse::<u64>()
+            .with_context(|| format!("invalid RECORDINGS_MAX_BYTES {max:?}"))?,
+        Err(_) => DEFAULT_RECORDINGS_MAX_BYTES,
+    };
+    let recordings = RecordingStore::new(
+        pool.clone(),
+        recordings_dir.clone().into(),
+        recordings_max_bytes,
+    )
+    .await
+    .with_context(|| format!("failed to create recordings directory {recordings_dir}"))?;
+    let maintenance = Maintenance::default();
+    let graphql_schema = graphql::schema(users.clone(), notes.clone(), recordings.clone());
+    let screenshots_retention = env::var("SCREENSHOTS_RETENTION")
+        .ok()
+        .map(|retention| parse_duration(&retention).context("invalid SCREENSHOTS_RETENTION"))
+        .transpose()?;
+    let screenshots = ScreenshotStore::new(
+        env::var("SCREENSHOTS_DIR")
+            .unwrap_or_else(|_| "screenshots".to_string())
+            .into(),
+        screenshots_retention,
+    );
+    let jobs = JobQueue::new(
+        pool.clone(),
+        recordings.clone(),
+        screenshots.clone(),
+        events.clone(),
+    );
+    let recovered = jobs
+        .recover()
+        .await
+        .context("failed to recover interrupted jobs")?;
+    if recovered > 0 {
+        info!(recovered, "requeued jobs interrupted by the last shutdown");
+    }
+    let webhooks = WebhookDispatcher::new(pool.clone(), config.webhooks.clone())?;
+    let recovered = webhooks
+        .recover()
+        .await
+        .context("failed to recover interrupted webhook deliveries")?;
+    if recovered > 0 {
+        info!(
+            recovered,
+            "requeued webhook deliveries interrupted by the last shutdown"
+        );
+    }
+    info!(subscribers = webhooks.subscriber_count(), "webhooks ready");
+    let job_workers = match env::var("JOBS_WORKERS") {
+        Ok(workers) => workers
+            .parse::<usize>()
+            .with_context(|| format!("invalid JOBS_WORKERS {workers:?}"))?,
+        Err(_) => DEFAULT_JOB_WORKERS,
+    };
+    let shutdown_grace = match env::var("SHUTDOWN_GRACE_PERIOD") {
+        Ok(grace) => parse_duration(&grace)?,
+        Err(_) => DEFAULT_SHUTDOWN_GRACE_PERIOD,
+    };
+    let jobs_drain_timeout = match env::var("JOBS_DRAIN_TIMEOUT") {
+        Ok(timeout) => parse_duration(&timeout)?,
+        Err(_) => DEFAULT_JOBS_DRAIN_TIMEOUT,
+    };
+    // The store enforces the real limit while streaming; this only has to
+    // leave room for the multipart framing around the file.
+    let upload_limit = DefaultBodyLimit::max(
+        usize::try_from(recordings.max_bytes())
+            .unwrap_or(usize::MAX)
+            .saturating_add(64 * 1024),
+    );
+
+    let sessions = SessionStore::from_env(pool.clone(), options.tls.is_some())?;
+    let api_keys = ApiKeyStore::new(pool.clone());
+    let tokens = TokenIssuer::from_env()?;
+    let refresh_tokens = RefreshTokenStore::from_env(pool.clone())?;
+    let credentials = Credentials::from_env()?;
+    let setup_token = SetupToken::from_env();
+    let auth = AuthState {
+        verifier: verifier.clone(),
+        sessions: sessions.clone(),
+        api_keys: api_keys.clone(),
+    };
+    let require_auth = middleware::from_fn_with_state(auth.clone(), auth_inject_user);
+    // Inside `require_auth`, which provides the caller.
+    let audit_requests = middleware::from_fn_with_state(audit.clone(), audit::audit_requests);
+
+    // Applied per route group rather than globally so each group gets its
+    // own timeout, and uploads their own, larger body limit
+    // (`RECORDINGS_MAX_BYTES`).
+    let feature = |feature: Feature| {
+        middleware::from_fn_with_state((live_config.clone(), feature), reload::require_feature)
+    };
+    let limits = |timeout| {
+        (
+            DefaultBodyLimit::disable(),
+            RequestBodyLimitLayer::new(options.body_limit),
+            TimeoutLayer::with_status_code(StatusCode::REQUEST_TIMEOUT, timeout),
+        )
+    };
+
+    // Everything clients build against; mounted under `/v1` and, deprecated,
+    // at the root for clients from before versioning.
+    let mut v1 = Router::<AppState>::new()
+        .route("/login", post(sessions::login))
+        .route("/logout", post(sessions::logout))
+        .route("/auth/login", post(tokens::issue_token))
+        .route("/auth/refresh", post(tokens::refresh_token))
+        .route("/setup/admin", post(setup::create_admin))
+        .layer(limits(timeouts.auth))
+        .merge(
+            Router::new()
+                .route("/me", get(users::me))
+                .route("/users", get(users::list_users).post(users::create_user))
+                .route(
+                    "/users/{id}",
+                    get(users::get_user)
+                        .patch(users::update_user)
+                        .delete(users::delete_user),
+                )
+                .route("/graphql", post(graphql::graphql))
+                .route(
+                    "/events",
+                    get(events::events).layer(feature(Feature::Events)),
+                )
+                .route(
+                    "/events/poll",
+                    get(events::poll_events).layer(feature(Feature::Events)),
+                )
+                .route("/notes", get(notes::list_notes).post(notes::create_note))
+                .route(
+                    "/notes/{id}",
+                    get(notes::get_note)
+                        .patch(notes::update_note)
+                        .delete(notes::delete_note),
+                )
+                .route("/recordings/{id}", get(recordings::download_recording))
+                .route(
+                    "/admin/migrations",
+                    get(admin::list_migrations).post(admin::run_pending_migrations),
+                )
+                .route("/admin/reload", post(reload::reload_config))
+                .route(
+                    "/admin/maintenance",
+                    get(maintenance::get_maintenance)
+                        .put(maintenance::enable_maintenance)
+                        .delete(maintenance::disable_maintenance),
+                )
+                .route("/admin/audit", get(audit::list_audit))
+                .route("/admin/webhooks/deliveries", get(webhooks::list_deliveries))
+                .route(
+                    "/api-keys",
+                    get(api_keys::list_api_keys).post(api_keys::create_api_key),
+                )
+                .route("/api-keys/{id}", delete(api_keys::revoke_api_key))
+                .route(
+                    "/screenshots",
+                    get(screenshots::list_screenshots).post(
+                        screenshots::capture_screenshots.layer(feature(Feature::Screenshots)),
+                    ),
+                )
+                .route("/screenshots/{id}", get(screenshots::get_screenshot))
+                .route("/jobs", post(jobs::create_job))
+                .route("/jobs/{id}", get(jobs::get_job))
+                .nest_service(screenshots::FILES_PREFIX, ServeDir::new(screenshots.dir()))
+                .layer(limits(timeouts.api))
+                // Added after `limits`, so only its own layers apply.
+                .route(
+                    "/recordings",
+                    get(recordings::list_recordings)
+                        .layer(limits(timeouts.api))
+                        .post(recordings::upload_recording.layer((
+                            upload_limit,
+                            TimeoutLayer::with_status_code(
+                                StatusCode::REQUEST_TIMEOUT,
+                                timeouts.uploads,
+                            ),
+                            feature(Feature::Uploads),
+                        ))),
+                )
+                // `route_layer`, so unknown paths are a 404 rather than a 401.
+                .route_layer(audit_requests.clone())
+                .route_layer(require_auth.clone()),
+        );
+
+    // The explorer is a development aid, left out of release builds; the
+    // page itself is public, the queries it sends are not.
+    if cfg!(debug_assertions) {
+        v1 = v1.route(
+            "/graphql",
+            get(graphql::graphiql).layer(limits(timeouts.api)).layer(
+                middleware::from_fn_with_state(
+                    HeaderValue::from_static(graphql::GRAPHIQL_CSP),
+                    security_headers::content_security_policy,
+                ),
+            ),
+        );
+    }
+
+    // Operational endpoints are not part of the versioned API.
+    let mut ops = Router::<AppState>::new()
+        .route("/", get(hello_world))
+        .route("/status", get(status_server))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route("/metrics", get(http_metrics::metrics_handler))
+        .route(
+            "/dashboard",
+            get(dashboard::dashboard)
+                .layer(audit_requests)
+                .layer(require_auth)
+                .layer(feature(Feature::Dashboard))
+                .layer(middleware::from_fn_with_state(
+                    security.dashboard_content_security_policy.clone(),
+                    security_headers::content_security_policy,
+                )),
+        )
+        .merge(SwaggerUi::new("/docs").url(docs::OPENAPI_PATH, docs::ApiDoc::openapi()))
+        .layer(limits(timeouts.ops));
+    // Development helper: no auth of its own and no body limit, so uploads
+    // can stream through. Our own credentials (bearer token, API key,
+    // session cookie) are stripped before forwarding; the upstream's pass.
+    if let Ok(upstream) = env::var("PROXY_UPSTREAM") {
+        let proxy = proxy::Proxy::new(&upstream, options.tls.is_some())?;
+        info!(upstream = %proxy.upstream(), prefix = proxy::PREFIX, "reverse proxy enabled");
+        ops = ops.merge(proxy::routes(proxy));
+    }
+
+    let state = AppState {
+        db: pool,
+        users,
+        notes,
+        audit,
+        sessions,
+        tokens,
+        refresh_tokens,
+        credentials,
+        setup_token,
+        api_keys,
+        recordings,
+        screenshots,
+        jobs: jobs.clone(),
+        webhooks: webhooks.clone(),
+        events: events.clone(),
+        metrics: metrics_handle,
+        config: live_config,
+        started_at: dashboard::StartedAt(started),
+        graphql: graphql_schema,
+        maintenance: maintenance.clone(),
+        health: health_checks,
+    };
+    let v1 = v1.with_state(state.clone());
+    // gRPC shares the port: its requests are HTTP/2 `POST`s to
+    // `/playground.v1.<Service>/<Method>`, which no other route uses.
+    let grpc = grpc::routes(&state, auth).layer(limits(timeouts.api));
+    let app = versioning::mount(
+        ops.with_state(state),
+        [
+            ApiVersion::new(versioning::CURRENT, v1.clone()),
+            ApiVersion::new("", v1).deprecated(Deprecation::legacy()),
+        ],
+    )
+    .merge(grpc)
+    // After every route is registered: the 405 fallback only reaches the
+    // routes that exist when it is set.
+    .fallback(error::not_found)
+    .method_not_allowed_fallback(error::method_not_allowed)
+    .layer(middleware::from_fn(etag::conditional_get))
+    .layer(RequestBodyTimeoutLayer::new(options.request_timeout))
+    // Inside the metrics, so requests turned away still count.
+    .layer(middleware::from_fn_with_state(
+        config.load_shedding.resolve(),
+        load_shed::shed_load,
+    ))
+    .layer(middleware::from_fn_with_state(
+        maintenance,
+        maintenance::reject_during_maintenance,
+    ))
+    .layer(middleware::from_fn(http_metrics::track_metrics))
+    // Outside the auth layer, so brute-forcing tokens hits the limit too, but
+    // inside the logging so rejected requests still get logged with an id.
+    .layer(middleware::from_fn_with_state(
+        limiter,
+        rate_limit::rate_limit,
+    ));
+    // gzip/br for JSON and text when the client asks for it; images and
+    // audio are already compressed or served in ranges, and event streams
+    // must not be buffered.
+    let compression = CompressionLayer::new().compress_when(
+        SizeAbove::new(COMPRESSION_MIN_BYTES)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::const_new("audio/"))
+            .and(NotForContentType::SSE),
+    );
+    if let Some(logging) = options.log_bodies {
+        warn!(
+            max_kb = logging.max_bytes / 1024,
+            "logging request and response bodies; credentials are redacted"
+        );
+    }
+    // Inside compression, so responses are logged before they are encoded.
+    let app = app
+        .layer(middleware::from_fn_with_state(
+            options.log_bodies,
+            body_logging::log_bodies,
+        ))
+        .layer(compression)
+        .layer(CatchPanicLayer::custom(error::panic_response));
+    // The router adds `Allow` to 405s outside of its own layers, so the
+    // error rendering wraps it as a whole to be able to list the methods.
+    let app = Router::new()
+        .fallback_service(app)
+        .layer(middleware::from_fn(error::problem_details))
+        .layer(middleware::from_fn_with_state(
+            security,
+            security_headers::add_security_headers,
+        ))
+        .layer(middleware::from_fn(log_requests));
+
+    let mut workers = jobs.start(job_workers, shutdown_rx.clone());
+    let mut webhook_workers = webhooks.start(&events, shutdown_rx.clone());
+
+    let tls_config = match &options.tls {
+        Some(tls) => {
+            let config = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+                .await
+                .with_context(|| {
+                    format!(
+                        "failed to load TLS certificate {} / key {}",
+                        tls.cert.display(),
+                        tls.key.display()
+                    )
+                })?;
+            Some(config)
+        }
+        None => None,
+    };
+
+    // Bind everything up front so a busy port fails fast, before serving.
+    let mut listeners = Vec::with_capacity(options.addrs.len());
+    for addr in &options.addrs {
+        info!(%addr, "binding http server");
+        listeners.push(bind_listener(*addr)?);
+    }
+
+    if let (Some(redirect_port), Some(first)) = (options.redirect_port, listeners.first()) {
+        let https_port = first.local_addr()?.port();
+        spawn_https_redirect(redirect_port, https_port, shutdown_rx.clone()).await?;
+    }
+
+    events.publish(
+        "server.started",
+        serde_json::json!({ "addrs": options.addrs.iter().map(ToString::to_string).collect::<Vec<_>>() }),
+    );
+
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        servers.spawn(serve_listener(
+            listener,
+            app.clone(),
+            tls_config.clone(),
+            shutdown_rx.clone(),
+        ));
+    }
+
+    let (result, cut_off) = {
+        let serving = join_servers(&mut servers);
+        tokio::pin!(serving);
+        tokio::select! {
+            // Servers only return on their own when they fail.
+            result = &mut serving => (result, false),
+            () = wait_for_shutdown(shutdown_rx.clone()) => {
+                // Listeners are closed by now; give in-flight requests the
+                // grace period to complete.
+                info!(
+                    in_flight = http_metrics::request_totals().in_flight,
+                    grace_secs = shutdown_grace.as_secs_f64(),
+                    "draining in-flight requests"
+                );
+                match tokio::time::timeout(shutdown_grace, &mut serving).await {
+                    Ok(result) => (result, false),
+                    Err(_) => (Ok(()), true),
+                }
+            }
+        }
+    };
+    if cut_off {
+        warn!(
+            cut_off = http_metrics::request_totals().in_flight,
+            grace_secs = shutdown_grace.as_secs_f64(),
+            "grace period over; aborting the requests still in flight"
+        );
+        servers.shutdown().await;
+    } else {
+        info!("all in-flight requests completed");
+    }
+
+    // Workers stop picking up jobs and webhook deliveries on the same
+    // signal; give the ones they are running a chance to finish.
+    let drained = tokio::time::timeout(jobs_drain_timeout, async {
+        while workers.join_next().await.is_some() {}
+        while webhook_workers.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        warn!(
+            timeout_secs = jobs_drain_timeout.as_secs(),
+            "jobs or webhook deliveries still running after the drain timeout; they will be retried on the next start"
+        );
+        workers.abort_all();
+        webhook_workers.abort_all();
+    }
+
+    match result {
+        Ok(()) if cut_off => info!("server shutdown with requests cut off"),
+        Ok(()) => info!("server shutdown gracefully"),
+        Err(err) => {
+            error!(error = %err, "server terminated with error");
+            return Err(err.into());
+        }
+    }
+
+    Ok(())
+}
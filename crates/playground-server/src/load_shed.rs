@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::{error::ApiError, versioning};
+
+/// Concurrent requests allowed when `[load_shedding]` does not say.
+pub const DEFAULT_MAX_API: usize = 512;
+pub const DEFAULT_MAX_UPLOADS: usize = 8;
+
+/// `Retry-After` of a shed request: slots free up as soon as any request
+/// in flight completes.
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// First path segments never shed, so an overloaded instance still answers
+/// its health checks and reports its metrics.
+const EXEMPT: &[&str] = &["healthz", "readyz", "metrics"];
+
+/// Caps on requests in flight: one pool for uploads (`POST /recordings`),
+/// which hold a connection and disk I/O for minutes, and one for everything
+/// else. `None` is unlimited. Cheap to clone.
+#[derive(Clone)]
+pub struct LoadShedder {
+    api: Option<Arc<Semaphore>>,
+    uploads: Option<Arc<Semaphore>>,
+}
+
+impl LoadShedder {
+    /// `0` disables the corresponding limit.
+    pub fn new(max_api: usize, max_uploads: usize) -> Self {
+        let pool = |max: usize| (max > 0).then(|| Arc::new(Semaphore::new(max)));
+        Self {
+            api: pool(max_api),
+            uploads: pool(max_uploads),
+        }
+    }
+
+    /// The pool `req` counts against, and its name; `None` for exempt
+    /// routes.
+    fn pool(&self, req: &Request) -> Option<(&'static str, Option<&Arc<Semaphore>>)> {
+        let path = req.uri().path();
+        let path = path.strip_prefix(versioning::CURRENT).unwrap_or(path);
+        let segment = path.trim_start_matches('/').split('/').next().unwrap_or("");
+        if EXEMPT.contains(&segment) {
+            None
+        } else if req.method() == Method::POST && path == "/recordings" {
+            Some(("uploads", self.uploads.as_ref()))
+        } else {
+            Some(("api", self.api.as_ref()))
+        }
+    }
+}
+
+/// Answers 503 with `Retry-After` when the request's pool is full, instead
+/// of queueing it behind the ones in flight.
+pub async fn shed_load(State(shedder): State<LoadShedder>, req: Request, next: Next) -> Response {
+    let Some((name, Some(pool))) = shedder.pool(&req) else {
+        return next.run(req).await;
+    };
+    // Held until the response is produced; streamed bodies finish outside
+    // of it.
+    let Ok(_permit) = pool.clone().try_acquire_owned() else {
+        warn!(pool = name, path = %req.uri().path(), "too many concurrent requests; shedding");
+        metrics::counter!("http_requests_shed_total", "pool" => name).increment(1);
+        return (
+            [(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS))],
+            ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("the server is at capacity ({name}); retry shortly"),
+            ),
+        )
+            .into_response();
+    };
+    next.run(req).await
+}
@@ -0,0 +1,204 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    auth::{Admin, RequireRole},
+    error::ApiError,
+    events::EventBus,
+    validation::ValidJson,
+    versioning,
+};
+
+/// `Retry-After` when `PUT /admin/maintenance` does not say otherwise.
+const DEFAULT_RETRY_AFTER_SECS: u64 = 60;
+const MAX_RETRY_AFTER_SECS: u64 = 24 * 60 * 60;
+const MAX_REASON_LEN: u64 = 500;
+
+/// First path segments still served during maintenance: health checks and
+/// metrics, the admin API and what admins need to sign in to it.
+const EXEMPT: &[&str] = &["healthz", "readyz", "metrics", "admin", "login", "auth"];
+
+/// Whether the server is in maintenance mode, and why. Cheap to clone.
+#[derive(Clone, Default)]
+pub struct Maintenance(Arc<RwLock<Option<MaintenanceStatus>>>);
+
+/// What `/admin/maintenance` reports while maintenance mode is on.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MaintenanceStatus {
+    /// Shown to clients as the `detail` of their 503.
+    reason: String,
+    /// Sent as `Retry-After`, in seconds.
+    retry_after_secs: u64,
+    /// RFC 3339.
+    since: String,
+    /// Id of the admin who turned it on.
+    enabled_by: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct EnableMaintenance {
+    #[serde(default)]
+    #[validate(length(max = MAX_REASON_LEN, message = "must be at most 500 characters"))]
+    pub reason: Option<String>,
+    /// Default 60, at most one day.
+    #[validate(range(min = 1, max = MAX_RETRY_AFTER_SECS, message = "must be 1 to 86400"))]
+    pub retry_after_secs: Option<u64>,
+}
+
+impl Maintenance {
+    /// `None` while the server is serving normally.
+    pub fn status(&self) -> Option<MaintenanceStatus> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Turns maintenance mode on with `status`, keeping the original
+    /// `since` when it already was.
+    fn enable(&self, status: MaintenanceStatus) -> MaintenanceStatus {
+        let mut current = self.0.write().unwrap_or_else(|e| e.into_inner());
+        let status = match current.take() {
+            Some(previous) => MaintenanceStatus {
+                since: previous.since,
+                ..status
+            },
+            None => status,
+        };
+        *current = Some(status.clone());
+        status
+    }
+
+    /// Whether it was on.
+    fn disable(&self) -> bool {
+        self.0
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .is_some()
+    }
+}
+
+/// Path without the version prefix, so `/v1/admin` and `/admin` match alike.
+fn exempt(path: &str) -> bool {
+    let path = path.strip_prefix(versioning::CURRENT).unwrap_or(path);
+    let segment = path.trim_start_matches('/').split('/').next().unwrap_or("");
+    EXEMPT.contains(&segment)
+}
+
+/// Answers 503 with `Retry-After` while maintenance mode is on, except on
+/// the [`EXEMPT`] routes.
+pub async fn reject_during_maintenance(
+    State(maintenance): State<Maintenance>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(status) = maintenance.status() else {
+        return next.run(req).await;
+    };
+    if exempt(req.uri().path()) {
+        return next.run(req).await;
+    }
+    (
+        [(
+            header::RETRY_AFTER,
+            HeaderValue::from(status.retry_after_secs),
+        )],
+        ApiError::new(StatusCode::SERVICE_UNAVAILABLE, status.reason),
+    )
+        .into_response()
+}
+
+/// `GET /admin/maintenance`: the current maintenance window, `null` when
+/// there is none.
+#[utoipa::path(
+    get,
+    path = "/admin/maintenance",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Option<MaintenanceStatus>),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn get_maintenance(
+    _admin: RequireRole<Admin>,
+    State(maintenance): State<Maintenance>,
+) -> Json<Option<MaintenanceStatus>> {
+    Json(maintenance.status())
+}
+
+/// `PUT /admin/maintenance`: turns maintenance mode on (or updates its
+/// reason and `Retry-After`), e.g. before `POST /admin/migrations`.
+#[utoipa::path(
+    put,
+    path = "/admin/maintenance",
+    tag = "admin",
+    security(("bearer" = [])),
+    request_body = EnableMaintenance,
+    responses(
+        (status = 200, body = MaintenanceStatus),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 422, description = "Invalid fields", body = crate::error::Problem)
+    )
+)]
+pub async fn enable_maintenance(
+    RequireRole(admin, _): RequireRole<Admin>,
+    State(maintenance): State<Maintenance>,
+    State(events): State<EventBus>,
+    ValidJson(input): ValidJson<EnableMaintenance>,
+) -> Json<MaintenanceStatus> {
+    let status = MaintenanceStatus {
+        reason: input
+            .reason
+            .unwrap_or_else(|| "the server is under maintenance".to_owned()),
+        retry_after_secs: input.retry_after_secs.unwrap_or(DEFAULT_RETRY_AFTER_SECS),
+        since: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        enabled_by: admin.id,
+    };
+    let status = maintenance.enable(status);
+    warn!(admin_id = %status.enabled_by, reason = %status.reason, "maintenance mode enabled");
+    events.publish(
+        "maintenance.enabled",
+        json!({ "reason": status.reason, "retry_after_secs": status.retry_after_secs }),
+    );
+    Json(status)
+}
+
+/// `DELETE /admin/maintenance`: back to serving every route.
+#[utoipa::path(
+    delete,
+    path = "/admin/maintenance",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn disable_maintenance(
+    RequireRole(admin, _): RequireRole<Admin>,
+    State(maintenance): State<Maintenance>,
+    State(events): State<EventBus>,
+) -> StatusCode {
+    if maintenance.disable() {
+        info!(admin_id = %admin.id, "maintenance mode disabled");
+        events.publish("maintenance.disabled", json!({}));
+    }
+    StatusCode::NO_CONTENT
+}
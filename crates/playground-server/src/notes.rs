@@ -0,0 +1,649 @@
+use async_graphql::SimpleObject;
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use libsql::Row;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    auth::User,
+    db::DbPool,
+    error::ApiError,
+    events::EventBus,
+    filters::{FilterField, FilterQuery, SqlFilter},
+    pagination::{PageQuery, PageRequest, Paginated},
+    validation::ValidJson,
+};
+
+/// A row of the `notes` table. Notes are private: every query is scoped to
+/// the caller's account.
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct NoteRecord {
+    pub id: i64,
+    pub user_id: i64,
+    pub title: String,
+    pub body: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Longest accepted title.
+const MAX_TITLE_LEN: u64 = 200;
+/// Longest accepted body, in characters.
+const MAX_BODY_LEN: u64 = 64 * 1024;
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateNote {
+    #[validate(length(min = 1, max = MAX_TITLE_LEN, message = "must be 1 to 200 characters"))]
+    pub title: String,
+    #[serde(default)]
+    #[validate(length(max = MAX_BODY_LEN, message = "must be at most 65536 characters"))]
+    pub body: String,
+}
+
+/// Partial update: only the fields present in the body are changed.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateNote {
+    #[validate(length(min = 1, max = MAX_TITLE_LEN, message = "must be 1 to 200 characters"))]
+    pub title: Option<String>,
+    #[validate(length(max = MAX_BODY_LEN, message = "must be at most 65536 characters"))]
+    pub body: Option<String>,
+}
+
+const NOTE_COLUMNS: &str = "id, user_id, title, body, created_at, updated_at";
+/// Columns `GET /notes?sort=` accepts.
+const NOTE_SORT_COLUMNS: &[&str] = &["id", "title", "created_at", "updated_at"];
+/// Filters `GET /notes` accepts.
+const NOTE_FILTERS: &[FilterField] = &[
+    FilterField::text("title", "title"),
+    FilterField::text("body", "body"),
+    FilterField::timestamp("created", "created_at"),
+    FilterField::timestamp("updated", "updated_at"),
+];
+
+fn internal(err: impl std::fmt::Display) -> ApiError {
+    ApiError::internal("note repository failure", err)
+}
+
+/// Data access for the `notes` table. Cheap to clone.
+#[derive(Clone)]
+pub struct NoteRepository {
+    pool: DbPool,
+}
+
+impl NoteRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn from_row(row: &Row) -> Result<NoteRecord, libsql::Error> {
+        Ok(NoteRecord {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            title: row.get(2)?,
+            body: row.get(3)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+        })
+    }
+
+    /// One page of `user_id`'s notes matching `filter` in `order_by` order
+    /// (an already validated `ORDER BY` clause), plus how many match in
+    /// total.
+    pub async fn list(
+        &self,
+        user_id: i64,
+        mut filter: SqlFilter,
+        order_by: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<NoteRecord>, u64), libsql::Error> {
+        filter.push("user_id = ?", user_id);
+        let where_clause = filter.where_clause();
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT {NOTE_COLUMNS} FROM notes {where_clause} \
+                     ORDER BY {order_by} LIMIT ? OFFSET ?"
+                ),
+                filter.params([limit.into(), offset.into()]),
+            )
+            .await?;
+        let mut notes = Vec::new();
+        while let Some(row) = rows.next().await? {
+            notes.push(Self::from_row(&row)?);
+        }
+
+        let mut rows = conn
+            .query(
+                &format!("SELECT COUNT(*) FROM notes {where_clause}"),
+                filter.params([]),
+            )
+            .await?;
+        let total = match rows.next().await? {
+            Some(row) => row.get::<u64>(0)?,
+            None => 0,
+        };
+        Ok((notes, total))
+    }
+
+    pub async fn get(&self, user_id: i64, id: i64) -> Result<Option<NoteRecord>, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                &format!("SELECT {NOTE_COLUMNS} FROM notes WHERE id = ?1 AND user_id = ?2"),
+                libsql::params![id, user_id],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(Self::from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn create(
+        &self,
+        user_id: i64,
+        input: CreateNote,
+    ) -> Result<NoteRecord, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                &format!(
+                    "INSERT INTO notes (user_id, title, body) VALUES (?1, ?2, ?3) \
+                     RETURNING {NOTE_COLUMNS}"
+                ),
+                libsql::params![user_id, input.title, input.body],
+            )
+            .await?;
+        let row = rows
+            .next()
+            .await?
+            .ok_or(libsql::Error::QueryReturnedNoRows)?;
+        Self::from_row(&row)
+    }
+
+    pub async fn update(
+        &self,
+        user_id: i64,
+        id: i64,
+        input: UpdateNote,
+    ) -> Result<Option<NoteRecord>, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                &format!(
+                    "UPDATE notes SET \
+                        title = COALESCE(?3, title), \
+                        body = COALESCE(?4, body), \
+                        updated_at = CURRENT_TIMESTAMP \
+                     WHERE id = ?1 AND user_id = ?2 RETURNING {NOTE_COLUMNS}"
+                ),
+                libsql::params![id, user_id, input.title, input.body],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(Self::from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn delete(&self, user_id: i64, id: i64) -> Result<bool, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let affected = conn
+            .execute(
+                "DELETE FROM notes WHERE id = ?1 AND user_id = ?2",
+                libsql::params![id, user_id],
+            )
+            .await?;
+        Ok(affected > 0)
+    }
+}
+
+/// The caller's account id, which owns their notes; 403 for callers
+/// without a local account (tokens from an external issuer).
+pub fn owner_id(user: &User) -> Result<i64, ApiError> {
+    user.id.parse().map_err(|_| {
+        warn!(user_id = %user.id, "caller has no local account to own notes");
+        ApiError::forbidden("notes need a local user account")
+    })
+}
+
+/// Someone else's note is reported as missing, not forbidden, so ids do not
+/// leak which notes exist.
+fn no_note(id: i64) -> ApiError {
+    ApiError::not_found(format!("no note {id}"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/notes",
+    tag = "notes",
+    security(("bearer" = []), ("api_key" = [])),
+    params(
+        PageQuery,
+        ("title" = Option<String>, Query, description = "Exact title, `*` as a wildcard"),
+        ("body" = Option<String>, Query, description = "Exact body, `*` as a wildcard"),
+        ("created_after" = Option<String>, Query, description = "Date or date-time, inclusive"),
+        ("created_before" = Option<String>, Query, description = "Date or date-time, exclusive"),
+        ("updated_after" = Option<String>, Query, description = "Date or date-time, inclusive"),
+        ("updated_before" = Option<String>, Query, description = "Date or date-time, exclusive")
+    ),
+    responses(
+        (status = 200, body = Paginated<NoteRecord>),
+        (status = 400, description = "Invalid page, sort column or filter"),
+        (status = 401),
+        (status = 403, description = "Caller has no local account")
+    )
+)]
+pub async fn list_notes(
+    State(repo): State<NoteRepository>,
+    Extension(user): Extension<User>,
+    page: PageRequest,
+    filters: FilterQuery,
+) -> Result<Json<Paginated<NoteRecord>>, ApiError> {
+    let order_by = page.order_by(NOTE_SORT_COLUMNS, "id")?;
+    let filter = filters.to_sql(NOTE_FILTERS)?;
+    let (notes, total) = repo
+        .list(
+            owner_id(&user)?,
+            filter,
+            &order_by,
+            page.limit(),
+            page.offset(),
+        )
+        .await
+        .map_err(internal)?;
+    Ok(Json(page.into_page(notes, total)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/notes/{id}",
+    tag = "notes",
+    security(("bearer" = []), ("api_key" = [])),
+    params(("id" = i64, Path)),
+    responses(
+        (status = 200, body = NoteRecord),
+        (status = 401),
+        (status = 403, description = "Caller has no local account"),
+        (status = 404)
+    )
+)]
+pub async fn get_note(
+    State(repo): State<NoteRepository>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i64>,
+) -> Result<Json<NoteRecord>, ApiError> {
+    repo.get(owner_id(&user)?, id)
+        .await
+        .map_err(internal)?
+        .map(Json)
+        .ok_or_else(|| no_note(id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/notes",
+    tag = "notes",
+    security(("bearer" = []), ("api_key" = [])),
+    request_body = CreateNote,
+    responses(
+        (status = 201, body = NoteRecord),
+        (status = 401),
+        (status = 403, description = "Caller has no local account"),
+        (status = 422, description = "Invalid fields", body = crate::error::Problem)
+    )
+)]
+pub async fn create_note(
+    State(repo): State<NoteRepository>,
+    State(events): State<EventBus>,
+    Extension(user): Extension<User>,
+    ValidJson(input): ValidJson<CreateNote>,
+) -> Result<(StatusCode, Json<NoteRecord>), ApiError> {
+    let note = repo
+        .create(owner_id(&user)?, input)
+        .await
+        .map_err(internal)?;
+    info!(note_id = note.id, user_id = note.user_id, "created note");
+    events.publish(
+        "note.created",
+        json!({ "note_id": note.id, "user_id": note.user_id }),
+    );
+    Ok((StatusCode::CREATED, Json(note)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/notes/{id}",
+    tag = "notes",
+    security(("bearer" = []), ("api_key" = [])),
+    params(("id" = i64, Path)),
+    request_body = UpdateNote,
+    responses(
+        (status = 200, body = NoteRecord),
+        (status = 401),
+        (status = 403, description = "Caller has no local account"),
+        (status = 404),
+        (status = 422, description = "Invalid fields", body = crate::error::Problem)
+    )
+)]
+pub async fn update_note(
+    State(repo): State<NoteRepository>,
+    State(events): State<EventBus>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i64>,
+    ValidJson(input): ValidJson<UpdateNote>,
+) -> Result<Json<NoteRecord>, ApiError> {
+    let note = repo
+        .update(owner_id(&user)?, id, input)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| no_note(id))?;
+    info!(note_id = note.id, user_id = note.user_id, "updated note");
+    events.publish(
+        "note.updated",
+        json!({ "note_id": note.id, "user_id": note.user_id }),
+    );
+    Ok(Json(note))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/notes/{id}",
+    tag = "notes",
+    security(("bearer" = []), ("api_key" = [])),
+    params(("id" = i64, Path)),
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 403, description = "Caller has no local account"),
+        (status = 404)
+    )
+)]
+pub async fn delete_note(
+    State(repo): State<NoteRepository>,
+    State(events): State<EventBus>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = owner_id(&user)?;
+    if repo.delete(user_id, id).await.map_err(internal)? {
+        info!(note_id = id, user_id, "deleted note");
+        events.publish("note.deleted", json!({ "note_id": id, "user_id": user_id }));
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(no_note(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{extract::FromRequestParts, http::Request, response::IntoResponse};
+
+    use super::*;
+    use crate::users::{CreateUser, UserRepository};
+
+    struct Fixture {
+        repo: NoteRepository,
+        events: EventBus,
+        alice: User,
+        bob: User,
+    }
+
+    async fn account(users: &UserRepository, name: &str) -> User {
+        let email = format!("{name}@example.com");
+        let record = users
+            .create(CreateUser {
+                name: name.to_owned(),
+                email: email.clone(),
+                role: None,
+            })
+            .await
+            .expect("create user");
+        User {
+            id: record.id.to_string(),
+            email,
+            roles: vec![record.role],
+            scopes: None,
+        }
+    }
+
+    async fn fixture() -> Fixture {
+        let pool = DbPool::migrated_in_memory().await;
+        let users = UserRepository::new(pool.clone());
+        Fixture {
+            alice: account(&users, "alice").await,
+            bob: account(&users, "bob").await,
+            repo: NoteRepository::new(pool),
+            events: EventBus::new(),
+        }
+    }
+
+    /// Runs the query-string extractor `T` on a `GET uri`.
+    async fn extract<T>(uri: &str) -> T
+    where
+        T: FromRequestParts<()>,
+        T::Rejection: std::fmt::Debug,
+    {
+        let (mut parts, ()) = Request::get(uri).body(()).unwrap().into_parts();
+        T::from_request_parts(&mut parts, &()).await.unwrap()
+    }
+
+    async fn list(fx: &Fixture, user: &User, uri: &str) -> Paginated<NoteRecord> {
+        let Json(page) = list_notes(
+            State(fx.repo.clone()),
+            Extension(user.clone()),
+            extract(uri).await,
+            extract(uri).await,
+        )
+        .await
+        .unwrap();
+        page
+    }
+
+    async fn create(fx: &Fixture, user: &User, title: &str, body: &str) -> NoteRecord {
+        let (status, Json(note)) = create_note(
+            State(fx.repo.clone()),
+            State(fx.events.clone()),
+            Extension(user.clone()),
+            ValidJson(CreateNote {
+                title: title.to_owned(),
+                body: body.to_owned(),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        note
+    }
+
+    fn status(err: ApiError) -> StatusCode {
+        err.into_response().status()
+    }
+
+    #[tokio::test]
+    async fn crud_round_trip() {
+        let fx = fixture().await;
+        let note = create(&fx, &fx.alice, "Shopping", "milk").await;
+        assert_eq!(note.user_id.to_string(), fx.alice.id);
+
+        let Json(fetched) = get_note(
+            State(fx.repo.clone()),
+            Extension(fx.alice.clone()),
+            Path(note.id),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fetched.body, "milk");
+
+        let Json(updated) = update_note(
+            State(fx.repo.clone()),
+            State(fx.events.clone()),
+            Extension(fx.alice.clone()),
+            Path(note.id),
+            ValidJson(UpdateNote {
+                title: None,
+                body: Some("milk, eggs".to_owned()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(updated.title, "Shopping");
+        assert_eq!(updated.body, "milk, eggs");
+
+        let deleted = delete_note(
+            State(fx.repo.clone()),
+            State(fx.events.clone()),
+            Extension(fx.alice.clone()),
+            Path(note.id),
+        )
+        .await
+        .unwrap();
+        assert_eq!(deleted, StatusCode::NO_CONTENT);
+        let err = get_note(
+            State(fx.repo.clone()),
+            Extension(fx.alice.clone()),
+            Path(note.id),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status(err), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn notes_are_scoped_to_their_owner() {
+        let fx = fixture().await;
+        let note = create(&fx, &fx.alice, "Private", "").await;
+        create(&fx, &fx.bob, "Bob's", "").await;
+
+        let page = list(&fx, &fx.bob, "/notes").await;
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].title, "Bob's");
+
+        let err = get_note(
+            State(fx.repo.clone()),
+            Extension(fx.bob.clone()),
+            Path(note.id),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status(err), StatusCode::NOT_FOUND);
+
+        let err = update_note(
+            State(fx.repo.clone()),
+            State(fx.events.clone()),
+            Extension(fx.bob.clone()),
+            Path(note.id),
+            ValidJson(UpdateNote {
+                title: Some("Mine now".to_owned()),
+                body: None,
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status(err), StatusCode::NOT_FOUND);
+
+        let err = delete_note(
+            State(fx.repo.clone()),
+            State(fx.events.clone()),
+            Extension(fx.bob.clone()),
+            Path(note.id),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status(err), StatusCode::NOT_FOUND);
+
+        // Still there, untouched, for its owner.
+        let page = list(&fx, &fx.alice, "/notes").await;
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].title, "Private");
+    }
+
+    #[tokio::test]
+    async fn list_paginates_and_sorts() {
+        let fx = fixture().await;
+        for title in ["c", "a", "e", "b", "d"] {
+            create(&fx, &fx.alice, title, "").await;
+        }
+
+        let first = list(&fx, &fx.alice, "/notes?per_page=2&sort=title").await;
+        let titles: Vec<_> = first.items.iter().map(|note| note.title.as_str()).collect();
+        assert_eq!(titles, ["a", "b"]);
+        assert_eq!((first.total, first.total_pages), (5, 3));
+        assert_eq!(
+            first.next.as_deref(),
+            Some("/notes?page=2&per_page=2&sort=title")
+        );
+        assert!(first.prev.is_none());
+
+        let last = list(&fx, &fx.alice, "/notes?page=3&per_page=2&sort=-title").await;
+        let titles: Vec<_> = last.items.iter().map(|note| note.title.as_str()).collect();
+        assert_eq!(titles, ["a"]);
+        assert!(last.next.is_none());
+
+        let err = list_notes(
+            State(fx.repo.clone()),
+            Extension(fx.alice.clone()),
+            extract("/notes?sort=user_id").await,
+            extract("/notes?sort=user_id").await,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status(err), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn list_applies_filters() {
+        let fx = fixture().await;
+        create(&fx, &fx.alice, "Shopping", "milk").await;
+        create(&fx, &fx.alice, "Shop hours", "9 to 5").await;
+        create(&fx, &fx.alice, "Ideas", "milk bar").await;
+        create(&fx, &fx.bob, "Shopping", "bread").await;
+
+        let page = list(&fx, &fx.alice, "/notes?title=shop*&sort=id").await;
+        let titles: Vec<_> = page.items.iter().map(|note| note.title.as_str()).collect();
+        assert_eq!(titles, ["Shopping", "Shop hours"]);
+
+        let page = list(&fx, &fx.alice, "/notes?body=milk").await;
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].title, "Shopping");
+
+        let page = list(&fx, &fx.alice, "/notes?per_page=1&title=shop*").await;
+        assert_eq!(page.total, 2);
+        assert_eq!(
+            page.next.as_deref(),
+            Some("/notes?page=2&per_page=1&title=shop*")
+        );
+
+        assert_eq!(
+            list(&fx, &fx.alice, "/notes?created_after=2000-01-01")
+                .await
+                .total,
+            3
+        );
+        assert_eq!(
+            list(&fx, &fx.alice, "/notes?created_before=2000-01-01")
+                .await
+                .total,
+            0
+        );
+
+        let err = list_notes(
+            State(fx.repo.clone()),
+            Extension(fx.alice.clone()),
+            extract("/notes?user_id=2").await,
+            extract("/notes?user_id=2").await,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status(err), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,166 @@
+use axum::{
+    extract::{FromRequestParts, OriginalUri, Query},
+    http::request::Parts,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::ApiError;
+
+const DEFAULT_PER_PAGE: u32 = 20;
+const MAX_PER_PAGE: u32 = 100;
+
+/// Query parameters owned by pagination; everything else is left to the
+/// endpoint (e.g. filters) and carried over into the links.
+pub const PAGE_PARAMS: &[&str] = &["page", "per_page", "sort"];
+
+/// `?page=`, `?per_page=` and `?sort=` as sent by the client. `sort` is a
+/// column name, prefixed with `-` for descending order.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// 1-based page number (default 1).
+    pub page: Option<u32>,
+    /// Items per page (default 20, at most 100).
+    pub per_page: Option<u32>,
+    /// Column to sort by, `-column` for descending.
+    pub sort: Option<String>,
+}
+
+/// Validated pagination request, extracted from the query string together
+/// with the path it was made on (for the `next`/`prev` links).
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub page: u32,
+    pub per_page: u32,
+    sort: Option<String>,
+    path: String,
+    /// The other query parameters, still encoded, for the links.
+    extra_query: String,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for PageRequest {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<PageQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| {
+                warn!(error = %err, "invalid pagination query");
+                ApiError::bad_request(err.body_text())
+            })?;
+        // The original URI, so links stay right when the router is nested.
+        let path = match parts.extensions.get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.path().to_owned(),
+            None => parts.uri.path().to_owned(),
+        };
+        let extra_query = parts
+            .uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| {
+                let name = pair.split_once('=').map_or(*pair, |(name, _)| name);
+                !pair.is_empty() && !PAGE_PARAMS.contains(&name)
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        Ok(Self {
+            page: query.page.unwrap_or(1).max(1),
+            per_page: query
+                .per_page
+                .unwrap_or(DEFAULT_PER_PAGE)
+                .clamp(1, MAX_PER_PAGE),
+            sort: query.sort.filter(|sort| !sort.is_empty()),
+            path,
+            extra_query,
+        })
+    }
+}
+
+impl PageRequest {
+    pub fn limit(&self) -> i64 {
+        i64::from(self.per_page)
+    }
+
+    pub fn offset(&self) -> i64 {
+        i64::from(self.page - 1) * i64::from(self.per_page)
+    }
+
+    /// Turns `?sort=` into an `ORDER BY` clause. Only names in `allowed` are
+    /// accepted (they end up in the SQL text), anything else is a 400. `id`
+    /// is the tie-breaker so pages are stable; without `?sort=` the
+    /// `default` clause is used as is.
+    pub fn order_by(&self, allowed: &[&str], default: &str) -> Result<String, ApiError> {
+        let Some(sort) = &self.sort else {
+            return Ok(default.to_owned());
+        };
+        let (column, direction) = match sort.strip_prefix('-') {
+            Some(column) => (column, "DESC"),
+            None => (sort.as_str(), "ASC"),
+        };
+        if !allowed.contains(&column) {
+            warn!(%sort, "unsupported sort column");
+            return Err(ApiError::bad_request(format!(
+                "cannot sort by {column:?}; expected one of {}",
+                allowed.join(", ")
+            )));
+        }
+        Ok(format!("{column} {direction}, id {direction}"))
+    }
+
+    fn link(&self, page: u32) -> String {
+        let mut link = format!("{}?page={page}&per_page={}", self.path, self.per_page);
+        if let Some(sort) = &self.sort {
+            link.push_str("&sort=");
+            link.push_str(sort);
+        }
+        if !self.extra_query.is_empty() {
+            link.push('&');
+            link.push_str(&self.extra_query);
+        }
+        link
+    }
+
+    /// Wraps one page of `items` with the counts and navigation links.
+    pub fn into_page<T>(self, items: Vec<T>, total: u64) -> Paginated<T> {
+        let total_pages = total.div_ceil(u64::from(self.per_page)) as u32;
+        Paginated {
+            next: (self.page < total_pages).then(|| self.link(self.page + 1)),
+            prev: (self.page > 1).then(|| self.link((self.page - 1).min(total_pages.max(1)))),
+            page: self.page,
+            per_page: self.per_page,
+            total,
+            total_pages,
+            items,
+        }
+    }
+}
+
+/// `LIMIT`/`OFFSET` for a 1-based `page` of `per_page` items, for APIs
+/// other than the JSON one (gRPC, GraphQL) with the same defaults and cap;
+/// 0 means the default.
+pub fn limit_offset(page: u32, per_page: u32) -> (i64, i64) {
+    let per_page = match per_page {
+        0 => DEFAULT_PER_PAGE,
+        n => n.min(MAX_PER_PAGE),
+    };
+    let limit = i64::from(per_page);
+    (limit, i64::from(page.max(1) - 1) * limit)
+}
+
+/// Response envelope shared by every list endpoint.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: u64,
+    pub total_pages: u32,
+    /// Link to the next page, absent on the last one.
+    pub next: Option<String>,
+    /// Link to the previous page, absent on the first one.
+    pub prev: Option<String>,
+}
@@ -0,0 +1,227 @@
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header},
+    response::Response,
+    routing::any,
+};
+use reqwest::Url;
+use tracing::{debug, warn};
+
+use crate::{api_keys::API_KEY_HEADER, error::ApiError, sessions::strip_session_cookie};
+
+/// Requests under this prefix are forwarded; the rest of the path is
+/// appended to the upstream URL.
+pub const PREFIX: &str = "/proxy";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connection-level headers (RFC 9110 §7.6.1) that describe one hop and
+/// must not be forwarded.
+const HOP_BY_HOP: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// Marks responses relayed from the upstream, so our own middleware
+/// (problem details, ETags) leaves them exactly as they came.
+#[derive(Debug, Clone, Copy)]
+pub struct Proxied;
+
+/// Forwards `/proxy/*` to a single upstream (`PROXY_UPSTREAM`). Cheap to
+/// clone.
+#[derive(Clone)]
+pub struct Proxy {
+    client: reqwest::Client,
+    upstream: Url,
+    /// Scheme clients used to reach us, for `X-Forwarded-Proto`.
+    scheme: &'static str,
+}
+
+impl Proxy {
+    pub fn new(upstream: &str, tls: bool) -> anyhow::Result<Self> {
+        let upstream = Url::parse(upstream)
+            .map_err(|err| anyhow::anyhow!("invalid PROXY_UPSTREAM {upstream:?}: {err}"))?;
+        if !matches!(upstream.scheme(), "http" | "https") {
+            anyhow::bail!("PROXY_UPSTREAM must be an http:// or https:// URL");
+        }
+        let client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            // Redirects are the client's business, not ours.
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        Ok(Self {
+            client,
+            upstream,
+            scheme: if tls { "https" } else { "http" },
+        })
+    }
+
+    pub fn upstream(&self) -> &Url {
+        &self.upstream
+    }
+
+    /// Upstream URL for a request path under [`PREFIX`].
+    fn target(&self, path: &str, query: Option<&str>) -> String {
+        let rest = path.strip_prefix(PREFIX).unwrap_or(path);
+        let mut url = format!(
+            "{}/{}",
+            self.upstream.as_str().trim_end_matches('/'),
+            rest.trim_start_matches('/')
+        );
+        if let Some(query) = query {
+            url.push('?');
+            url.push_str(query);
+        }
+        url
+    }
+}
+
+/// The proxy's routes, with the [`Proxy`] as their state; they merge into a
+/// router of any state.
+pub fn routes<S: Clone + Send + Sync + 'static>(proxy: Proxy) -> Router<S> {
+    Router::new()
+        .route(PREFIX, any(forward))
+        .route(&format!("{PREFIX}/{{*rest}}"), any(forward))
+        .with_state(proxy)
+}
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    // Headers named in `Connection` are hop-by-hop too.
+    let listed: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::try_from(name.trim()).ok())
+        .collect();
+    for name in HOP_BY_HOP.iter().chain(&listed) {
+        headers.remove(name);
+    }
+}
+
+/// Removes the credentials that authenticate the caller with us: a bearer
+/// token, an API key and the session cookie. The route is public, so the
+/// upstream must never see them; its own credentials (Basic auth, its
+/// cookies) pass through.
+fn strip_our_credentials(headers: &mut HeaderMap) {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("Bearer "));
+    if bearer {
+        headers.remove(header::AUTHORIZATION);
+    }
+    headers.remove(API_KEY_HEADER);
+    strip_session_cookie(headers);
+}
+
+/// Forwards the request (method, headers minus our own credentials,
+/// streamed body) and streams the upstream's answer back. 502 when the
+/// upstream cannot be reached, 504 when it does not answer in time.
+async fn forward(
+    State(proxy): State<Proxy>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    req: Request,
+) -> Result<Response, ApiError> {
+    let (parts, body) = req.into_parts();
+    let url = proxy.target(parts.uri.path(), parts.uri.query());
+
+    let mut headers = parts.headers;
+    strip_hop_by_hop(&mut headers);
+    strip_our_credentials(&mut headers);
+    // reqwest sets the upstream's own Host.
+    let host = headers.remove(header::HOST);
+    let forwarded_for = match headers.get(&X_FORWARDED_FOR).and_then(|v| v.to_str().ok()) {
+        Some(previous) => format!("{previous}, {}", peer.ip()),
+        None => peer.ip().to_string(),
+    };
+    if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+        headers.insert(X_FORWARDED_FOR, value);
+    }
+    if let Some(host) = host {
+        headers.insert(X_FORWARDED_HOST, host);
+    }
+    headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static(proxy.scheme));
+
+    debug!(method = %parts.method, %url, "proxying request");
+    let upstream = proxy
+        .client
+        .request(parts.method, &url)
+        .headers(headers)
+        .body(reqwest::Body::wrap_stream(body.into_data_stream()))
+        .send()
+        .await
+        .map_err(|err| {
+            warn!(error = %err, %url, "proxy upstream request failed");
+            if err.is_timeout() {
+                ApiError::new(
+                    StatusCode::GATEWAY_TIMEOUT,
+                    "upstream did not answer in time",
+                )
+            } else {
+                ApiError::new(StatusCode::BAD_GATEWAY, "upstream is unreachable")
+            }
+        })?;
+
+    let mut response = Response::builder()
+        .status(upstream.status())
+        .body(Body::empty())
+        .map_err(|err| ApiError::internal("invalid upstream response", err))?;
+    *response.headers_mut() = upstream.headers().clone();
+    strip_hop_by_hop(response.headers_mut());
+    *response.body_mut() = Body::from_stream(upstream.bytes_stream());
+    response.extensions_mut().insert(Proxied);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_only_our_credentials() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer abc"),
+        );
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("pk_123"));
+        headers.append(
+            header::COOKIE,
+            HeaderValue::from_static("theme=dark; session=tok.mac"),
+        );
+        headers.append(header::COOKIE, HeaderValue::from_static("lang=pt"));
+        headers.insert(header::ACCEPT, HeaderValue::from_static("*/*"));
+        strip_our_credentials(&mut headers);
+
+        assert!(headers.get(header::AUTHORIZATION).is_none());
+        assert!(headers.get(API_KEY_HEADER).is_none());
+        assert_eq!(headers[header::COOKIE], "theme=dark; lang=pt");
+        assert_eq!(headers[header::ACCEPT], "*/*");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Basic dTpw"),
+        );
+        headers.insert(header::COOKIE, HeaderValue::from_static("session=tok.mac"));
+        strip_our_credentials(&mut headers);
+
+        assert_eq!(headers[header::AUTHORIZATION], "Basic dTpw");
+        assert!(headers.get(header::COOKIE).is_none());
+    }
+}
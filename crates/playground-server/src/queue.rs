@@ -0,0 +1,70 @@
+//! The claim/backoff/poll loop shared by the database-backed queues: jobs
+//! and webhook deliveries.
+
+use std::{fmt::Display, future::Future, time::Duration};
+
+use tokio::sync::{Notify, watch};
+use tracing::error;
+
+/// How often idle workers look for retries that became due.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait before retrying something that failed its `attempts`th
+/// attempt: `base`, doubled for every attempt before that, capped at `max`.
+pub fn backoff_delay(base: Duration, max: Duration, attempts: u32) -> Duration {
+    base.saturating_mul(1 << attempts.saturating_sub(1).min(16))
+        .min(max)
+}
+
+/// Claims and processes items until `shutdown` fires, sleeping until
+/// `notify` wakes it or the next poll whenever nothing is due. An item that
+/// was claimed is always processed before the worker stops. `what` names the
+/// items in logs.
+pub async fn poll_worker<T, E, C, P>(
+    what: &str,
+    notify: &Notify,
+    mut shutdown: watch::Receiver<()>,
+    claim: impl Fn() -> C,
+    process: impl Fn(T) -> P,
+) where
+    E: Display,
+    C: Future<Output = Result<Option<T>, E>>,
+    P: Future<Output = ()>,
+{
+    loop {
+        // Err means the sender is gone, which also means shutdown.
+        if shutdown.has_changed().unwrap_or(true) {
+            break;
+        }
+        match claim().await {
+            Ok(Some(item)) => {
+                process(item).await;
+                continue;
+            }
+            Ok(None) => {}
+            Err(err) => error!(error = %err, "failed to claim {what}"),
+        }
+        tokio::select! {
+            () = notify.notified() => {}
+            () = tokio::time::sleep(POLL_INTERVAL) => {}
+            _ = shutdown.changed() => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let base = Duration::from_secs(2);
+        let max = Duration::from_secs(60);
+        assert_eq!(backoff_delay(base, max, 0), base);
+        assert_eq!(backoff_delay(base, max, 1), base);
+        assert_eq!(backoff_delay(base, max, 2), Duration::from_secs(4));
+        assert_eq!(backoff_delay(base, max, 5), Duration::from_secs(32));
+        assert_eq!(backoff_delay(base, max, 6), max);
+        assert_eq!(backoff_delay(base, max, u32::MAX), max);
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::api_keys::API_KEY_HEADER;
+
+/// Bucket refill rate and size, shared by every client.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct RateLimitConfig {
+    /// Tokens added per second (sustained requests per second).
+    pub per_second: f64,
+    /// Bucket capacity (how many requests may arrive in a burst).
+    pub burst: u32,
+}
+
+/// Once this many buckets exist, full (idle) ones are dropped so a scan over
+/// many source addresses cannot grow the map forever.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ClientKey {
+    Ip(IpAddr),
+    /// SHA-256 of the bearer token or API key, so raw credentials never sit
+    /// in memory longer than the request.
+    Token([u8; 32]),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token-bucket limiter keyed by client IP and, when present, by bearer
+/// token or API key. A request must get a token from every bucket it maps
+/// to. The configuration can be swapped at runtime (config reload); `None`
+/// lets every request through.
+pub struct RateLimiter {
+    config: RwLock<Option<RateLimitConfig>>,
+    buckets: Mutex<HashMap<ClientKey, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: Option<RateLimitConfig>) -> Self {
+        Self {
+            config: RwLock::new(config),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> Option<RateLimitConfig> {
+        *self.config.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Applies to the next request. Existing buckets are kept (and capped at
+    /// the new burst on their next refill), so tightening the limit does not
+    /// hand every client a fresh burst.
+    pub fn set_config(&self, config: Option<RateLimitConfig>) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+        if config.is_none() {
+            self.buckets
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clear();
+        }
+    }
+
+    /// Takes one token from each bucket, or returns how long the caller has
+    /// to wait before the emptiest one has a token again.
+    fn acquire(&self, config: RateLimitConfig, keys: &[ClientKey]) -> Result<(), Duration> {
+        let now = Instant::now();
+        let burst = f64::from(config.burst);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            let per_second = config.per_second;
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second
+                    < burst
+            });
+        }
+
+        // Refill first and only spend once every bucket has a token, so a
+        // rejected request does not drain the buckets that did have room.
+        let mut wait = Duration::ZERO;
+        for key in keys {
+            let bucket = buckets.entry(key.clone()).or_insert(Bucket {
+                tokens: burst,
+                updated: now,
+            });
+            let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * config.per_second).min(burst);
+            bucket.updated = now;
+            if bucket.tokens < 1.0 {
+                // A tiny rate can put the wait past what a Duration holds.
+                let missing = (1.0 - bucket.tokens) / config.per_second;
+                wait = wait.max(Duration::try_from_secs_f64(missing).unwrap_or(Duration::MAX));
+            }
+        }
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        for key in keys {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The buckets a request from `ip` with `headers` draws from: its IP, plus
+/// its bearer token or API key if it sent one.
+fn client_keys(ip: IpAddr, headers: &HeaderMap) -> Vec<ClientKey> {
+    let mut keys = vec![ClientKey::Ip(ip)];
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let api_key = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok());
+    if let Some(token) = bearer.or(api_key) {
+        keys.push(ClientKey::Token(Sha256::digest(token.as_bytes()).into()));
+    }
+    keys
+}
+
+/// `Retry-After` only takes whole seconds; round up so clients that honour
+/// it do not come back too early.
+fn retry_after_secs(wait: Duration) -> u64 {
+    let secs = wait
+        .as_secs()
+        .saturating_add(u64::from(wait.subsec_nanos() > 0));
+    secs.max(1)
+}
+
+/// Answers 429 with `Retry-After` once the caller's IP or credential runs
+/// out of tokens. A no-op while rate limiting is disabled.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(config) = limiter.config() else {
+        return next.run(req).await;
+    };
+    let keys = client_keys(peer.ip(), req.headers());
+    match limiter.acquire(config, &keys) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            let path = req.uri().path();
+            warn!(client = %peer.ip(), %path, retry_after_ms = wait.as_millis(), "rate limit exceeded");
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(
+                    header::RETRY_AFTER,
+                    HeaderValue::from(retry_after_secs(wait)),
+                )],
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const CONFIG: RateLimitConfig = RateLimitConfig {
+        per_second: 2.0,
+        burst: 3,
+    };
+
+    fn ip(last: u8) -> ClientKey {
+        ClientKey::Ip(IpAddr::V4(Ipv4Addr::new(192, 0, 2, last)))
+    }
+
+    /// Moves every bucket's last refill `by` into the past, as if that much
+    /// time had gone by.
+    fn age(limiter: &RateLimiter, by: Duration) {
+        for bucket in limiter.buckets.lock().unwrap().values_mut() {
+            bucket.updated -= by;
+        }
+    }
+
+    #[test]
+    fn allows_a_burst_then_waits_for_the_refill() {
+        let limiter = RateLimiter::new(Some(CONFIG));
+        let keys = [ip(1)];
+        for _ in 0..CONFIG.burst {
+            limiter.acquire(CONFIG, &keys).unwrap();
+        }
+        let wait = limiter.acquire(CONFIG, &keys).unwrap_err();
+        // One token at 2/s takes half a second.
+        assert!(wait <= Duration::from_millis(500), "{wait:?}");
+        assert!(wait > Duration::from_millis(400), "{wait:?}");
+
+        age(&limiter, Duration::from_millis(500));
+        limiter.acquire(CONFIG, &keys).unwrap();
+        assert!(limiter.acquire(CONFIG, &keys).is_err());
+    }
+
+    #[test]
+    fn refill_is_capped_at_the_burst() {
+        let limiter = RateLimiter::new(Some(CONFIG));
+        let keys = [ip(1)];
+        limiter.acquire(CONFIG, &keys).unwrap();
+        age(&limiter, Duration::from_secs(10));
+        for _ in 0..CONFIG.burst {
+            limiter.acquire(CONFIG, &keys).unwrap();
+        }
+        assert!(limiter.acquire(CONFIG, &keys).is_err());
+    }
+
+    #[test]
+    fn every_bucket_must_have_a_token() {
+        let limiter = RateLimiter::new(Some(CONFIG));
+        let token = ClientKey::Token([7; 32]);
+        for _ in 0..CONFIG.burst {
+            limiter.acquire(CONFIG, &[ip(1), token.clone()]).unwrap();
+        }
+        // The same credential from another address is still limited...
+        assert!(limiter.acquire(CONFIG, &[ip(2), token.clone()]).is_err());
+        // ...and the rejection did not spend the other address's token.
+        for _ in 0..CONFIG.burst {
+            limiter.acquire(CONFIG, &[ip(2)]).unwrap();
+        }
+        // Other clients on the first address only share its IP bucket.
+        assert!(limiter.acquire(CONFIG, &[ip(1)]).is_err());
+        limiter.acquire(CONFIG, &[ip(3)]).unwrap();
+    }
+
+    #[test]
+    fn keys_by_ip_and_hashed_credential() {
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(client_keys(addr, &HeaderMap::new()), [ClientKey::Ip(addr)]);
+
+        let mut bearer = HeaderMap::new();
+        bearer.insert(header::AUTHORIZATION, "Bearer abc".parse().unwrap());
+        let mut api_key = HeaderMap::new();
+        api_key.insert(API_KEY_HEADER, "abc".parse().unwrap());
+        let hashed = ClientKey::Token(Sha256::digest(b"abc").into());
+        assert_eq!(
+            client_keys(addr, &bearer),
+            [ClientKey::Ip(addr), hashed.clone()]
+        );
+        assert_eq!(client_keys(addr, &api_key), [ClientKey::Ip(addr), hashed]);
+
+        // Only bearer tokens count from the Authorization header.
+        let mut basic = HeaderMap::new();
+        basic.insert(header::AUTHORIZATION, "Basic abc".parse().unwrap());
+        assert_eq!(client_keys(addr, &basic), [ClientKey::Ip(addr)]);
+    }
+
+    #[test]
+    fn retry_after_rounds_up_to_whole_seconds() {
+        assert_eq!(retry_after_secs(Duration::ZERO), 1);
+        assert_eq!(retry_after_secs(Duration::from_millis(1)), 1);
+        assert_eq!(retry_after_secs(Duration::from_secs(2)), 2);
+        assert_eq!(retry_after_secs(Duration::from_millis(2001)), 3);
+    }
+
+    #[test]
+    fn tiny_rates_wait_as_long_as_a_duration_holds() {
+        let config = RateLimitConfig {
+            per_second: 1e-20,
+            burst: 1,
+        };
+        let limiter = RateLimiter::new(Some(config));
+        limiter.acquire(config, &[ip(1)]).unwrap();
+        let wait = limiter.acquire(config, &[ip(1)]).unwrap_err();
+        assert_eq!(wait, Duration::MAX);
+        assert_eq!(retry_after_secs(wait), u64::MAX);
+    }
+
+    #[test]
+    fn disabling_drops_the_buckets() {
+        let limiter = RateLimiter::new(Some(CONFIG));
+        limiter.acquire(CONFIG, &[ip(1)]).unwrap();
+        limiter.set_config(None);
+        assert!(limiter.buckets.lock().unwrap().is_empty());
+        assert_eq!(limiter.config(), None);
+    }
+}
@@ -0,0 +1,573 @@
+use std::path::{Path as FsPath, PathBuf};
+
+use async_graphql::SimpleObject;
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Multipart, Path, Query, Request, State, multipart::Field},
+    http::{HeaderValue, StatusCode, header},
+    response::Response,
+};
+use libsql::Row;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{fs, io::AsyncWriteExt};
+use tower_http::services::ServeFile;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::User,
+    db::DbPool,
+    error::{ApiError, FieldErrors},
+    events::EventBus,
+    filters::{FilterField, FilterQuery, SqlFilter},
+    pagination::{PageQuery, PageRequest, Paginated},
+};
+
+/// A row of the `recordings` table.
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct RecordingRecord {
+    pub id: i64,
+    pub original_name: String,
+    pub uploaded_by: String,
+    pub size_bytes: i64,
+    pub channels: i64,
+    pub sample_rate: i64,
+    pub bits_per_sample: i64,
+    pub duration_ms: i64,
+    pub created_at: String,
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub file_name: String,
+}
+
+const RECORDING_COLUMNS: &str = "id, original_name, uploaded_by, size_bytes, channels, \
+     sample_rate, bits_per_sample, duration_ms, created_at, file_name";
+
+/// Longest original file name kept for an upload.
+const MAX_FILE_NAME_LEN: usize = 255;
+
+/// Columns `GET /recordings?sort=` accepts.
+const RECORDING_SORT_COLUMNS: &[&str] = &[
+    "id",
+    "original_name",
+    "uploaded_by",
+    "size_bytes",
+    "duration_ms",
+    "created_at",
+];
+/// Filters `GET /recordings` accepts.
+const RECORDING_FILTERS: &[FilterField] = &[
+    FilterField::text("original_name", "original_name"),
+    FilterField::text("uploaded_by", "uploaded_by"),
+    FilterField::integer("channels", "channels"),
+    FilterField::integer("sample_rate", "sample_rate"),
+    FilterField::integer("bits_per_sample", "bits_per_sample"),
+    FilterField::timestamp("created", "created_at"),
+];
+
+/// Where uploads are stored and how large they may be. Cheap to clone.
+#[derive(Clone)]
+pub struct RecordingStore {
+    pool: DbPool,
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+/// What `hound` read from the uploaded file's header.
+struct WavInfo {
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+    duration_ms: u64,
+}
+
+fn internal(err: impl std::fmt::Display) -> ApiError {
+    ApiError::internal("recording store failure", err)
+}
+
+impl RecordingStore {
+    /// Creates `dir` if needed; files larger than `max_bytes` are rejected
+    /// while they are still being received.
+    pub async fn new(pool: DbPool, dir: PathBuf, max_bytes: u64) -> std::io::Result<Self> {
+        fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            pool,
+            dir,
+            max_bytes,
+        })
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Directory uploads (and files derived from them) are stored in.
+    pub fn dir(&self) -> &FsPath {
+        &self.dir
+    }
+
+    fn from_row(row: &Row) -> Result<RecordingRecord, libsql::Error> {
+        Ok(RecordingRecord {
+            id: row.get(0)?,
+            original_name: row.get(1)?,
+            uploaded_by: row.get(2)?,
+            size_bytes: row.get(3)?,
+            channels: row.get(4)?,
+            sample_rate: row.get(5)?,
+            bits_per_sample: row.get(6)?,
+            duration_ms: row.get(7)?,
+            created_at: row.get(8)?,
+            file_name: row.get(9)?,
+        })
+    }
+
+    /// One page of the recordings matching `filter` in `order_by` order (an
+    /// already validated `ORDER BY` clause), plus how many match in total.
+    pub async fn list(
+        &self,
+        filter: &SqlFilter,
+        order_by: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<RecordingRecord>, u64), libsql::Error> {
+        let conn = self.pool.get().await?;
+        let where_clause = filter.where_clause();
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT {RECORDING_COLUMNS} FROM recordings {where_clause} \
+                     ORDER BY {order_by} LIMIT ? OFFSET ?"
+                ),
+                filter.params([limit.into(), offset.into()]),
+            )
+            .await?;
+        let mut recordings = Vec::new();
+        while let Some(row) = rows.next().await? {
+            recordings.push(Self::from_row(&row)?);
+        }
+
+        let mut rows = conn
+            .query(
+                &format!("SELECT COUNT(*) FROM recordings {where_clause}"),
+                filter.params([]),
+            )
+            .await?;
+        let total = match rows.next().await? {
+            Some(row) => row.get::<u64>(0)?,
+            None => 0,
+        };
+        Ok((recordings, total))
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<RecordingRecord>, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                &format!("SELECT {RECORDING_COLUMNS} FROM recordings WHERE id = ?1"),
+                libsql::params![id],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(Self::from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Streams one multipart field to `path`, enforcing the size limit and
+    /// checking the RIFF/WAVE magic as soon as the first bytes arrive.
+    async fn receive(&self, field: &mut Field<'_>, path: &FsPath) -> Result<u64, ApiError> {
+        let mut file = fs::File::create(path).await.map_err(internal)?;
+        let mut written = 0u64;
+        let mut head = Vec::with_capacity(12);
+
+        while let Some(chunk) = field.chunk().await.map_err(|err| {
+            warn!(error = %err, "recording upload interrupted");
+            // 413 when the body limit tripped, 400 for broken multipart.
+            ApiError::new(err.status(), err.body_text())
+        })? {
+            written += chunk.len() as u64;
+            if written > self.max_bytes {
+                warn!(max_bytes = self.max_bytes, "recording upload too large");
+                return Err(ApiError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("recordings may be at most {} bytes", self.max_bytes),
+                ));
+            }
+            if head.len() < 12 {
+                let take = (12 - head.len()).min(chunk.len());
+                head.extend_from_slice(&chunk[..take]);
+                if head.len() == 12 && (&head[..4] != b"RIFF" || &head[8..12] != b"WAVE") {
+                    warn!("recording upload is not a RIFF/WAVE file");
+                    return Err(ApiError::bad_request("not a RIFF/WAVE file"));
+                }
+            }
+            file.write_all(&chunk).await.map_err(internal)?;
+        }
+        file.flush().await.map_err(internal)?;
+        if head.len() < 12 {
+            return Err(ApiError::bad_request("not a RIFF/WAVE file"));
+        }
+        Ok(written)
+    }
+
+    /// Reads the full header with `hound`, which also rejects truncated or
+    /// unsupported `fmt` chunks.
+    async fn inspect(path: PathBuf) -> Result<WavInfo, ApiError> {
+        tokio::task::spawn_blocking(move || {
+            let reader = hound::WavReader::open(&path)?;
+            let spec = reader.spec();
+            Ok::<_, hound::Error>(WavInfo {
+                channels: spec.channels,
+                sample_rate: spec.sample_rate,
+                bits_per_sample: spec.bits_per_sample,
+                duration_ms: u64::from(reader.duration()) * 1000
+                    / u64::from(spec.sample_rate.max(1)),
+            })
+        })
+        .await
+        .map_err(internal)?
+        .map_err(|err| {
+            warn!(error = %err, "recording upload has an invalid WAV header");
+            ApiError::bad_request(format!("invalid WAV header: {err}"))
+        })
+    }
+
+    /// Stores the upload under a random name, validates it and records its
+    /// metadata. The file is removed again if any step fails.
+    async fn save(
+        &self,
+        field: &mut Field<'_>,
+        original_name: String,
+        uploaded_by: &str,
+    ) -> Result<RecordingRecord, ApiError> {
+        let file_name = format!("{}.wav", uuid::Uuid::new_v4());
+        let path = self.dir.join(&file_name);
+
+        let result = async {
+            let size = self.receive(field, &path).await?;
+            let info = Self::inspect(path.clone()).await?;
+            // Checked out only now, not for the whole (slow) upload.
+            let conn = self.pool.get().await.map_err(internal)?;
+            let mut rows = conn
+                .query(
+                    &format!(
+                        "INSERT INTO recordings (file_name, original_name, uploaded_by, size_bytes, \
+                         channels, sample_rate, bits_per_sample, duration_ms) \
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) RETURNING {RECORDING_COLUMNS}"
+                    ),
+                    libsql::params![
+                        file_name.as_str(),
+                        original_name,
+                        uploaded_by,
+                        size as i64,
+                        info.channels,
+                        info.sample_rate,
+                        info.bits_per_sample,
+                        info.duration_ms as i64
+                    ],
+                )
+                .await
+                .map_err(internal)?;
+            let row = rows
+                .next()
+                .await
+                .map_err(internal)?
+                .ok_or_else(|| internal("INSERT returned no row"))?;
+            Self::from_row(&row).map_err(internal)
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = fs::remove_file(&path).await;
+        }
+        result
+    }
+}
+
+/// Shape of the multipart body, for the OpenAPI document only.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct RecordingUpload {
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+}
+
+/// `POST /recordings`: multipart upload with the WAV in a `file` field.
+#[utoipa::path(
+    post,
+    path = "/recordings",
+    tag = "recordings",
+    security(("bearer" = [])),
+    request_body(content = RecordingUpload, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, body = RecordingRecord),
+        (status = 400, description = "Missing `file` field or not a valid WAV"),
+        (status = 401),
+        (status = 413, description = "Larger than `RECORDINGS_MAX_BYTES`"),
+        (status = 422, description = "File name too long", body = crate::error::Problem)
+    )
+)]
+pub async fn upload_recording(
+    State(store): State<RecordingStore>,
+    State(events): State<EventBus>,
+    Extension(user): Extension<User>,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<RecordingRecord>), ApiError> {
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| ApiError::new(err.status(), err.body_text()))?
+    {
+        if field.name() != Some("file") {
+            continue;
+        }
+        // Only the last path component, so a crafted file name never ends
+        // up meaning anything to the file system (we don't use it there
+        // anyway, but it is shown back to clients).
+        let original_name = field
+            .file_name()
+            .and_then(|name| name.rsplit(['/', '\\']).next())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("recording.wav")
+            .to_owned();
+        if original_name.chars().count() > MAX_FILE_NAME_LEN {
+            return Err(ApiError::invalid_fields(FieldErrors::from([(
+                "file".to_owned(),
+                vec![format!(
+                    "file name must be at most {MAX_FILE_NAME_LEN} characters"
+                )],
+            )])));
+        }
+
+        let recording = store.save(&mut field, original_name, &user.id).await?;
+        info!(
+            recording_id = recording.id,
+            size_bytes = recording.size_bytes,
+            user_id = %user.id,
+            "stored recording"
+        );
+        events.publish(
+            "recording.uploaded",
+            json!({ "recording_id": recording.id }),
+        );
+        return Ok((StatusCode::CREATED, Json(recording)));
+    }
+
+    warn!("recording upload without a `file` field");
+    Err(ApiError::bad_request("missing `file` field"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/recordings",
+    tag = "recordings",
+    security(("bearer" = [])),
+    params(
+        PageQuery,
+        ("original_name" = Option<String>, Query, description = "Exact name, `*` as a wildcard"),
+        ("uploaded_by" = Option<String>, Query, description = "Exact user id, `*` as a wildcard"),
+        ("channels" = Option<i64>, Query),
+        ("sample_rate" = Option<i64>, Query),
+        ("bits_per_sample" = Option<i64>, Query),
+        ("created_after" = Option<String>, Query, description = "Date or date-time, inclusive"),
+        ("created_before" = Option<String>, Query, description = "Date or date-time, exclusive")
+    ),
+    responses(
+        (status = 200, body = Paginated<RecordingRecord>),
+        (status = 400, description = "Invalid page, sort column or filter"),
+        (status = 401)
+    )
+)]
+pub async fn list_recordings(
+    State(store): State<RecordingStore>,
+    page: PageRequest,
+    filters: FilterQuery,
+) -> Result<Json<Paginated<RecordingRecord>>, ApiError> {
+    let order_by = page.order_by(RECORDING_SORT_COLUMNS, "id")?;
+    let filter = filters.to_sql(RECORDING_FILTERS)?;
+    let (recordings, total) = store
+        .list(&filter, &order_by, page.limit(), page.offset())
+        .await
+        .map_err(internal)?;
+    Ok(Json(page.into_page(recordings, total)))
+}
+
+/// Formats a recording can be downloaded in.
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// The file as uploaded.
+    #[default]
+    Wav,
+    /// The copy made by a `transcode` job.
+    Flac,
+}
+
+impl AudioFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Wav => "audio/wav",
+            Self::Flac => "audio/flac",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Flac => "flac",
+        }
+    }
+}
+
+impl RecordingRecord {
+    /// Name of the stored file in `format`, in the recordings directory.
+    pub fn stored_name(&self, format: AudioFormat) -> String {
+        match format {
+            AudioFormat::Wav => self.file_name.clone(),
+            AudioFormat::Flac => {
+                let stem = self
+                    .file_name
+                    .strip_suffix(".wav")
+                    .unwrap_or(&self.file_name);
+                format!("{stem}.flac")
+            }
+        }
+    }
+
+    /// `original_name` with the extension of `format`, for
+    /// `Content-Disposition`.
+    fn download_name(&self, format: AudioFormat) -> String {
+        let stem = match self.original_name.rsplit_once('.') {
+            Some((stem, _)) if !stem.is_empty() => stem,
+            _ => &self.original_name,
+        };
+        format!("{stem}.{}", format.extension()).replace(['"', '\\'], "_")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    #[serde(default)]
+    format: AudioFormat,
+    #[serde(default, deserialize_with = "query_flag")]
+    download: bool,
+}
+
+/// `1`/`true` or `0`/`false`, as a query parameter.
+fn query_flag<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    match String::deserialize(deserializer)?.as_str() {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        other => Err(serde::de::Error::custom(format!(
+            "expected 1 or 0, got {other:?}"
+        ))),
+    }
+}
+
+/// `GET /recordings/{id}`: streams the stored file back, as uploaded (WAV)
+/// or as transcoded (`?format=flac`). Served `inline`, so a browser plays
+/// it in place; `?download=1` makes it an attachment instead. Honours
+/// `Range` (answering 206 with just those bytes), so players can seek
+/// without downloading the whole file.
+#[utoipa::path(
+    get,
+    path = "/recordings/{id}",
+    tag = "recordings",
+    security(("bearer" = [])),
+    params(
+        ("id" = i64, Path),
+        ("format" = Option<AudioFormat>, Query, description = "`wav` (default) or `flac`"),
+        ("download" = Option<bool>, Query, description = "`1` to save as a file rather than play inline")
+    ),
+    responses(
+        (status = 200, content_type = "audio/wav", body = Vec<u8>, headers(("etag" = String), ("accept-ranges" = String))),
+        (status = 206, description = "The bytes asked for with `Range`", content_type = "audio/wav", body = Vec<u8>),
+        (status = 304, description = "`If-None-Match` matched the stored file"),
+        (status = 401),
+        (status = 404, description = "No such recording, or no FLAC copy of it yet"),
+        (status = 416, description = "`Range` outside of the file")
+    )
+)]
+pub async fn download_recording(
+    State(store): State<RecordingStore>,
+    Path(id): Path<i64>,
+    Query(DownloadQuery { format, download }): Query<DownloadQuery>,
+    req: Request,
+) -> Result<Response, ApiError> {
+    let recording = store
+        .get(id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| ApiError::not_found(format!("no recording {id}")))?;
+    let stored_name = recording.stored_name(format);
+    let path = store.dir.join(&stored_name);
+    if !fs::try_exists(&path).await.map_err(internal)? {
+        return Err(match format {
+            AudioFormat::Wav => internal(format!("recording file {stored_name} is missing")),
+            AudioFormat::Flac => {
+                ApiError::not_found(format!("recording {id} has not been transcoded to FLAC"))
+            }
+        });
+    }
+
+    // `ServeFile` does the range handling (206, 416, `If-Range`), as well
+    // as `Content-Length`, `Last-Modified` and `HEAD`.
+    let response = ServeFile::new(&path)
+        .try_call(req)
+        .await
+        .map_err(internal)?
+        .map(Body::new);
+    let (mut parts, body) = response.into_parts();
+    if parts.status.is_success() {
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(format.content_type()),
+        );
+        let disposition = format!(
+            "{}; filename=\"{}\"",
+            if download { "attachment" } else { "inline" },
+            recording.download_name(format)
+        );
+        if let Ok(disposition) = HeaderValue::from_str(&disposition) {
+            parts
+                .headers
+                .insert(header::CONTENT_DISPOSITION, disposition);
+        }
+        // Uploads never change, so their unique name is enough; a FLAC copy
+        // can be transcoded again and is left to `Last-Modified`.
+        if let AudioFormat::Wav = format
+            && let Ok(etag) = HeaderValue::from_str(&format!("\"{stored_name}\""))
+        {
+            parts.headers.insert(header::ETAG, etag);
+        }
+    }
+    Ok(Response::from_parts(parts, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{extract::Query, http::Uri};
+
+    use super::*;
+
+    fn query(uri: &'static str) -> Option<DownloadQuery> {
+        Query::try_from_uri(&Uri::from_static(uri))
+            .ok()
+            .map(|Query(query)| query)
+    }
+
+    #[test]
+    fn download_is_opt_in() {
+        assert!(!query("/recordings/1").unwrap().download);
+        assert!(query("/recordings/1?download=1").unwrap().download);
+        assert!(
+            query("/recordings/1?format=flac&download=true")
+                .unwrap()
+                .download
+        );
+        assert!(!query("/recordings/1?download=0").unwrap().download);
+        assert!(query("/recordings/1?download=yes").is_none());
+    }
+}
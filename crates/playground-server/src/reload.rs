@@ -0,0 +1,312 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tokio::sync::{Mutex, watch};
+use tracing::{info, warn};
+use tracing_subscriber::{EnvFilter, Registry, reload};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{Admin, RequireRole},
+    config::{Features, ServerConfig, TimeoutsSection, WebhookConfig},
+    error::ApiError,
+    rate_limit::{RateLimitConfig, RateLimiter},
+};
+
+/// Handle on the log filter installed by
+/// [`telemetry::init`](playground_common::telemetry::init).
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Directives the server started with (`RUST_LOG` or the default),
+    /// restored when the file stops setting a level.
+    startup: String,
+}
+
+impl LogFilter {
+    pub fn new(handle: reload::Handle<EnvFilter, Registry>, startup: String) -> Self {
+        Self { handle, startup }
+    }
+}
+
+/// The settings currently in effect, as `POST /admin/reload` reports them.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AppliedConfig {
+    /// `EnvFilter` directives of the log output.
+    log_filter: String,
+    /// `null` while rate limiting is disabled.
+    rate_limit: Option<RateLimitConfig>,
+    features: Features,
+}
+
+/// The part of the server configuration that can change while it runs:
+/// log level, rate limits and feature toggles. Cheap to clone.
+#[derive(Clone)]
+pub struct LiveConfig(Arc<Inner>);
+
+struct Inner {
+    /// `--config`; without it there is nothing to reload.
+    path: Option<PathBuf>,
+    log: LogFilter,
+    limiter: Arc<RateLimiter>,
+    /// Rate limit from the command line, which the file overrides.
+    base_rate_limit: Option<RateLimitConfig>,
+    default_burst: u32,
+    /// Timeouts the routes were built with, to warn when a reload asks for
+    /// different ones.
+    timeouts: TimeoutsSection,
+    /// Same for the webhook subscribers.
+    webhooks: Vec<WebhookConfig>,
+    applied: RwLock<AppliedConfig>,
+    /// Serializes reloads, so a signal and a request cannot interleave.
+    reloading: Mutex<()>,
+}
+
+impl LiveConfig {
+    /// Applies the reloadable sections of `config`, the file as read at
+    /// startup.
+    pub fn new(
+        path: Option<PathBuf>,
+        config: &ServerConfig,
+        log: LogFilter,
+        limiter: Arc<RateLimiter>,
+        base_rate_limit: Option<RateLimitConfig>,
+        default_burst: u32,
+    ) -> anyhow::Result<Self> {
+        let live = Self(Arc::new(Inner {
+            path,
+            applied: RwLock::new(AppliedConfig {
+                log_filter: log.startup.clone(),
+                rate_limit: limiter.config(),
+                features: Features::default(),
+            }),
+            log,
+            limiter,
+            base_rate_limit,
+            default_burst,
+            timeouts: config.timeouts.clone(),
+            webhooks: config.webhooks.clone(),
+            reloading: Mutex::new(()),
+        }));
+        live.apply(config)?;
+        Ok(live)
+    }
+
+    pub fn features(&self) -> Features {
+        self.applied().features
+    }
+
+    fn applied(&self) -> AppliedConfig {
+        self.0
+            .applied
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Re-reads the config file and applies it. A file that fails to parse
+    /// or validate changes nothing.
+    pub async fn reload(&self) -> Result<AppliedConfig, ReloadError> {
+        let path = self.0.path.clone().ok_or(ReloadError::NoConfigFile)?;
+        let _guard = self.0.reloading.lock().await;
+        let config = tokio::task::spawn_blocking(move || ServerConfig::load(&path))
+            .await
+            .context("config loader panicked")
+            .and_then(|result| result)
+            .map_err(ReloadError::Invalid)?;
+        if config.timeouts != self.0.timeouts {
+            warn!("[timeouts] changed; they only take effect after a restart");
+        }
+        if config.webhooks != self.0.webhooks {
+            warn!("[[webhooks]] changed; they only take effect after a restart");
+        }
+        self.apply(&config).map_err(ReloadError::Invalid)
+    }
+
+    /// Validates every section before touching anything, so a bad value in
+    /// one does not leave the others half-applied.
+    fn apply(&self, config: &ServerConfig) -> anyhow::Result<AppliedConfig> {
+        let filter = config.log.resolve()?;
+        let rate_limit = config
+            .rate_limit
+            .resolve(self.0.base_rate_limit, self.0.default_burst)?;
+
+        let log_filter = match filter {
+            Some(filter) => {
+                let directives = filter.to_string();
+                self.0
+                    .log
+                    .handle
+                    .reload(filter)
+                    .context("failed to swap the log filter")?;
+                directives
+            }
+            None => {
+                self.0
+                    .log
+                    .handle
+                    .reload(EnvFilter::new(&self.0.log.startup))
+                    .context("failed to swap the log filter")?;
+                self.0.log.startup.clone()
+            }
+        };
+        self.0.limiter.set_config(rate_limit);
+
+        let applied = AppliedConfig {
+            log_filter,
+            rate_limit,
+            features: config.features,
+        };
+        *self.0.applied.write().unwrap_or_else(|e| e.into_inner()) = applied.clone();
+        info!(
+            log_filter = %applied.log_filter,
+            rate_limit_per_second = rate_limit.map(|config| config.per_second),
+            rate_limit_burst = rate_limit.map(|config| config.burst),
+            features = ?applied.features,
+            "configuration applied"
+        );
+        Ok(applied)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReloadError {
+    /// The server was started without `--config`.
+    NoConfigFile,
+    /// The file could not be read, parsed or validated.
+    Invalid(anyhow::Error),
+}
+
+impl std::fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoConfigFile => f.write_str("the server was started without a config file"),
+            Self::Invalid(err) => write!(f, "{err:#}"),
+        }
+    }
+}
+
+impl From<ReloadError> for ApiError {
+    fn from(err: ReloadError) -> Self {
+        match err {
+            ReloadError::NoConfigFile => ApiError::new(StatusCode::CONFLICT, err.to_string()),
+            ReloadError::Invalid(_) => {
+                ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, err.to_string())
+            }
+        }
+    }
+}
+
+/// Reloads the configuration on every `SIGHUP` until shutdown. Also keeps
+/// the signal from terminating the process, which is its default action.
+#[cfg(unix)]
+pub fn reload_on_sighup(
+    live: LiveConfig,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> anyhow::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup =
+        signal(SignalKind::hangup()).context("failed to install the SIGHUP handler")?;
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                received = hangup.recv() => if received.is_none() { break },
+                _ = shutdown_rx.changed() => break,
+            }
+            info!(signal = "SIGHUP", "reloading configuration");
+            if let Err(err) = live.reload().await {
+                warn!(error = %err, "configuration reload failed; keeping the current one");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// There is no `SIGHUP` here; only `POST /admin/reload` reloads.
+#[cfg(not(unix))]
+pub fn reload_on_sighup(
+    _live: LiveConfig,
+    _shutdown_rx: watch::Receiver<()>,
+) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// `POST /admin/reload`: re-reads the config file, same as a `SIGHUP`, and
+/// returns the settings now in effect.
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = AppliedConfig),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 409, description = "The server was started without a config file"),
+        (status = 422, description = "The file is invalid; the running configuration is kept", body = crate::error::Problem)
+    )
+)]
+pub async fn reload_config(
+    _admin: RequireRole<Admin>,
+    State(live): State<LiveConfig>,
+) -> Result<Json<AppliedConfig>, ApiError> {
+    info!("reloading configuration");
+    Ok(Json(live.reload().await?))
+}
+
+/// A part of the server that `[features]` can switch off.
+#[derive(Debug, Clone, Copy)]
+pub enum Feature {
+    Dashboard,
+    Events,
+    Uploads,
+    Screenshots,
+}
+
+impl Feature {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Dashboard => "dashboard",
+            Self::Events => "events",
+            Self::Uploads => "uploads",
+            Self::Screenshots => "screenshots",
+        }
+    }
+
+    fn enabled(self, features: Features) -> bool {
+        match self {
+            Self::Dashboard => features.dashboard,
+            Self::Events => features.events,
+            Self::Uploads => features.uploads,
+            Self::Screenshots => features.screenshots,
+        }
+    }
+}
+
+/// Route layer answering 404 while `feature` is switched off.
+pub async fn require_feature(
+    State((live, feature)): State<(LiveConfig, Feature)>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if feature.enabled(live.features()) {
+        next.run(req).await
+    } else {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("the {} feature is disabled", feature.name()),
+        )
+        .into_response()
+    }
+}
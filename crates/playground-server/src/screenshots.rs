@@ -0,0 +1,368 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Path, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    response::Response,
+};
+use screen_capture::{CaptureError, SavedCapture, capture_to_dir};
+use serde::Serialize;
+use serde_json::json;
+use tokio::{fs, io::AsyncReadExt, sync::Mutex};
+use tower_http::services::ServeFile;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::User,
+    error::ApiError,
+    events::EventBus,
+    pagination::{PageRequest, Paginated},
+    versioning,
+};
+
+/// Where captured PNGs are served from, relative to the server root.
+pub const FILES_PREFIX: &str = "/screenshots/files";
+
+/// Output directory for on-demand captures. Cheap to clone.
+#[derive(Clone)]
+pub struct ScreenshotStore {
+    dir: PathBuf,
+    /// How long captures are kept (`SCREENSHOTS_RETENTION`); `None` keeps
+    /// them forever.
+    retention: Option<Duration>,
+    /// One capture at a time: display servers don't like concurrent grabs
+    /// and two simultaneous requests would get the same frame anyway.
+    capture_lock: Arc<Mutex<()>>,
+}
+
+impl ScreenshotStore {
+    pub fn new(dir: PathBuf, retention: Option<Duration>) -> Self {
+        Self {
+            dir,
+            retention,
+            capture_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Captures every display into the store's directory, one capture at a
+    /// time. Used by `POST /screenshots` and by screenshot jobs.
+    pub async fn capture(&self) -> Result<Vec<SavedCapture>, CaptureError> {
+        let _guard = self.capture_lock.lock().await;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let dir = self.dir.clone();
+        let saved =
+            tokio::task::spawn_blocking(move || capture_to_dir(&dir, &format!("{millis}-")))
+                .await
+                .map_err(|err| CaptureError::Io(std::io::Error::other(err)))??;
+        if let Err(err) = self.prune().await {
+            warn!(error = %err, "failed to remove expired screenshots");
+        }
+        Ok(saved)
+    }
+
+    /// When a capture taken at `modified` stops being kept.
+    fn expires_at(&self, modified: SystemTime) -> Option<SystemTime> {
+        self.retention.map(|retention| modified + retention)
+    }
+
+    /// Deletes the captures older than the retention period.
+    async fn prune(&self) -> std::io::Result<()> {
+        let Some(retention) = self.retention else {
+            return Ok(());
+        };
+        let cutoff = SystemTime::now() - retention;
+        let mut removed = 0;
+        for shot in self.stored(true).await? {
+            if shot.modified < cutoff {
+                fs::remove_file(self.dir.join(&shot.name)).await?;
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            info!(
+                removed,
+                retention_secs = retention.as_secs(),
+                "removed expired screenshots"
+            );
+        }
+        Ok(())
+    }
+
+    /// Every PNG in the store, newest first; with `expired`, also those
+    /// past the retention period that were not pruned yet. An empty list
+    /// when nothing was ever captured (the directory does not exist).
+    async fn stored(&self, expired: bool) -> std::io::Result<Vec<StoredScreenshot>> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let now = SystemTime::now();
+        let mut shots = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".png") {
+                continue;
+            }
+            let metadata = entry.metadata().await?;
+            let modified = metadata.modified()?;
+            let live = self.expires_at(modified).is_none_or(|at| at > now);
+            if metadata.is_file() && (expired || live) {
+                shots.push(StoredScreenshot {
+                    url: url_for(&name),
+                    name,
+                    modified,
+                    size_bytes: metadata.len(),
+                });
+            }
+        }
+        shots.sort_by_key(|shot| std::cmp::Reverse(shot.modified));
+        Ok(shots)
+    }
+
+    /// The `limit` most recent PNGs in the store, newest first.
+    pub async fn latest(&self, limit: usize) -> std::io::Result<Vec<StoredScreenshot>> {
+        let mut shots = self.stored(false).await?;
+        shots.truncate(limit);
+        Ok(shots)
+    }
+}
+
+/// A PNG already in the store's directory.
+pub struct StoredScreenshot {
+    pub name: String,
+    pub url: String,
+    pub modified: SystemTime,
+    pub size_bytes: u64,
+}
+
+fn url_for(name: &str) -> String {
+    format!("{}{FILES_PREFIX}/{name}", versioning::CURRENT)
+}
+
+/// URL a stored capture is served at.
+pub fn file_url(shot: &SavedCapture) -> String {
+    let name = shot
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    url_for(&name)
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ScreenshotResponse {
+    display_id: u32,
+    width: u32,
+    height: u32,
+    url: String,
+}
+
+/// `POST /screenshots`: captures every display right now and returns where
+/// each PNG can be downloaded.
+#[utoipa::path(
+    post,
+    path = "/screenshots",
+    tag = "screenshots",
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = [ScreenshotResponse]),
+        (status = 401),
+        (status = 503, description = "No display available to capture")
+    )
+)]
+pub async fn capture_screenshots(
+    State(store): State<ScreenshotStore>,
+    State(events): State<EventBus>,
+    Extension(user): Extension<User>,
+) -> Result<(StatusCode, Json<Vec<ScreenshotResponse>>), ApiError> {
+    let saved = store.capture().await.map_err(|err| match err {
+        // Usually a headless host: nothing to capture, not a bug.
+        CaptureError::Displays(_)
+        | CaptureError::Capture(..)
+        | CaptureError::NoSuchDisplay(_)
+        | CaptureError::NoSuchWindow(_)
+        | CaptureError::Unsupported(_) => {
+            warn!(error = %err, "screen capture unavailable");
+            ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "no display available to capture",
+            )
+        }
+        CaptureError::Save(_) | CaptureError::Io(_) => {
+            ApiError::internal("failed to store screenshot", err)
+        }
+    })?;
+
+    let shots: Vec<_> = saved
+        .into_iter()
+        .map(|shot| ScreenshotResponse {
+            url: file_url(&shot),
+            display_id: shot.display_id,
+            width: shot.width,
+            height: shot.height,
+        })
+        .collect();
+    info!(user_id = %user.id, displays = shots.len(), "captured screenshots");
+    events.publish(
+        "screenshots.captured",
+        json!({ "urls": shots.iter().map(|s| s.url.as_str()).collect::<Vec<_>>() }),
+    );
+    Ok((StatusCode::CREATED, Json(shots)))
+}
+
+/// Width and height from the PNG's `IHDR` chunk, which always comes first;
+/// `None` when the file is not a PNG after all.
+async fn png_dimensions(path: &std::path::Path) -> std::io::Result<Option<(u32, u32)>> {
+    let mut header = [0u8; 24];
+    let mut file = fs::File::open(path).await?;
+    if file.read_exact(&mut header).await.is_err()
+        || &header[..8] != b"\x89PNG\r\n\x1a\n"
+        || &header[12..16] != b"IHDR"
+    {
+        return Ok(None);
+    }
+    let width = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+    let height = u32::from_be_bytes([header[20], header[21], header[22], header[23]]);
+    Ok(Some((width, height)))
+}
+
+/// A stored capture, as listed by `GET /screenshots`.
+#[derive(Serialize, ToSchema)]
+pub struct ScreenshotInfo {
+    /// File name without `.png`; `GET /screenshots/{id}` serves it.
+    id: String,
+    url: String,
+    /// Absent when the file could not be read as a PNG.
+    width: Option<u32>,
+    height: Option<u32>,
+    size_bytes: u64,
+    /// RFC 3339.
+    captured_at: String,
+    /// When the retention period removes it; absent when captures are
+    /// kept forever.
+    expires_at: Option<String>,
+}
+
+/// Capture ids are file names the server generated; anything that could
+/// leave the directory is rejected before touching the file system.
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+fn internal(err: impl std::fmt::Display) -> ApiError {
+    ApiError::internal("screenshot store failure", err)
+}
+
+/// `GET /screenshots`: captures still kept, newest first.
+#[utoipa::path(
+    get,
+    path = "/screenshots",
+    tag = "screenshots",
+    security(("bearer" = []), ("api_key" = [])),
+    params(
+        ("page" = Option<u32>, Query, description = "1-based page number (default 1)"),
+        ("per_page" = Option<u32>, Query, description = "Items per page (default 20, at most 100)")
+    ),
+    responses(
+        (status = 200, body = Paginated<ScreenshotInfo>),
+        (status = 400, description = "Invalid page"),
+        (status = 401)
+    )
+)]
+pub async fn list_screenshots(
+    State(store): State<ScreenshotStore>,
+    page: PageRequest,
+) -> Result<Json<Paginated<ScreenshotInfo>>, ApiError> {
+    let shots = store.stored(false).await.map_err(internal)?;
+    let total = shots.len() as u64;
+    let offset = usize::try_from(page.offset()).unwrap_or(usize::MAX);
+    let limit = usize::try_from(page.limit()).unwrap_or(usize::MAX);
+    let mut items = Vec::new();
+    for shot in shots.into_iter().skip(offset).take(limit) {
+        let dimensions = png_dimensions(&store.dir.join(&shot.name))
+            .await
+            .map_err(internal)?;
+        let id = shot.name.trim_end_matches(".png").to_owned();
+        items.push(ScreenshotInfo {
+            url: format!("{}/screenshots/{id}", versioning::CURRENT),
+            id,
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            size_bytes: shot.size_bytes,
+            captured_at: humantime::format_rfc3339_seconds(shot.modified).to_string(),
+            expires_at: store
+                .expires_at(shot.modified)
+                .map(|at| humantime::format_rfc3339_seconds(at).to_string()),
+        });
+    }
+    Ok(Json(page.into_page(items, total)))
+}
+
+/// `GET /screenshots/{id}`: the PNG itself.
+#[utoipa::path(
+    get,
+    path = "/screenshots/{id}",
+    tag = "screenshots",
+    security(("bearer" = []), ("api_key" = [])),
+    params(("id" = String, Path)),
+    responses(
+        (status = 200, content_type = "image/png", body = Vec<u8>),
+        (status = 304, description = "`If-None-Match` matched the stored file"),
+        (status = 401),
+        (status = 404, description = "No such capture, or it expired")
+    )
+)]
+pub async fn get_screenshot(
+    State(store): State<ScreenshotStore>,
+    Path(id): Path<String>,
+    req: Request,
+) -> Result<Response, ApiError> {
+    let not_found = || ApiError::not_found(format!("no screenshot {id}"));
+    if !valid_id(&id) {
+        return Err(not_found());
+    }
+    let path = store.dir.join(format!("{id}.png"));
+    let modified = match fs::metadata(&path).await {
+        Ok(metadata) if metadata.is_file() => metadata.modified().map_err(internal)?,
+        Ok(_) => return Err(not_found()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Err(not_found()),
+        Err(err) => return Err(internal(err)),
+    };
+    if store
+        .expires_at(modified)
+        .is_some_and(|at| at <= SystemTime::now())
+    {
+        return Err(not_found());
+    }
+
+    let response = ServeFile::new(&path)
+        .try_call(req)
+        .await
+        .map_err(internal)?
+        .map(Body::new);
+    let (mut parts, body) = response.into_parts();
+    if parts.status.is_success() {
+        parts
+            .headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("image/png"));
+    }
+    Ok(Response::from_parts(parts, body))
+}
@@ -0,0 +1,69 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+
+/// Policy of every response that does not set its own: only same-origin
+/// resources, and never framed.
+pub const DEFAULT_CSP: &str = "default-src 'self'; frame-ancestors 'none'";
+/// Policy of `/dashboard`, whose stylesheet is inlined in the page.
+pub const DEFAULT_DASHBOARD_CSP: &str =
+    "default-src 'self'; style-src 'self' 'unsafe-inline'; frame-ancestors 'none'";
+/// `Strict-Transport-Security` lifetime when HTTPS is on.
+pub const DEFAULT_HSTS_MAX_AGE: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Headers added to every response by [`add_security_headers`], built from
+/// `[security_headers]`.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    pub content_security_policy: HeaderValue,
+    pub dashboard_content_security_policy: HeaderValue,
+    /// `None` without TLS: browsers ignore HSTS over plain HTTP.
+    pub strict_transport_security: Option<HeaderValue>,
+}
+
+/// Sets HSTS, `X-Content-Type-Options`, `X-Frame-Options` and the default
+/// CSP, each only when the response does not carry one already, so routes
+/// override them with [`content_security_policy`] and proxied responses
+/// keep the upstream's.
+pub async fn add_security_headers(
+    State(security): State<Arc<SecurityHeaders>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers
+        .entry(header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+    headers
+        .entry(header::X_FRAME_OPTIONS)
+        .or_insert(HeaderValue::from_static("DENY"));
+    headers
+        .entry(header::CONTENT_SECURITY_POLICY)
+        .or_insert_with(|| security.content_security_policy.clone());
+    if let Some(hsts) = &security.strict_transport_security {
+        headers
+            .entry(header::STRICT_TRANSPORT_SECURITY)
+            .or_insert_with(|| hsts.clone());
+    }
+    response
+}
+
+/// Route layer (`from_fn_with_state(policy, content_security_policy)`)
+/// replacing the default policy for one route.
+pub async fn content_security_policy(
+    State(policy): State<HeaderValue>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_SECURITY_POLICY, policy);
+    response
+}
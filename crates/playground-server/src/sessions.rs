@@ -0,0 +1,380 @@
+use std::{env, sync::Arc, time::Duration};
+
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    auth::User,
+    credentials::Credentials,
+    db::DbPool,
+    error::ApiError,
+    users::UserRepository,
+    validation::{MAX_PASSWORD_LEN, ValidJson},
+};
+
+/// Name of the cookie carrying the session token.
+const COOKIE_NAME: &str = "session";
+/// Session lifetime when `SESSION_TTL` is not set.
+const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Server-side sessions stored in the `sessions` table. The cookie holds a
+/// random token plus an HMAC of it, so forged cookies are rejected without a
+/// database round trip; the table only keeps a SHA-256 of the token. Cheap to
+/// clone.
+#[derive(Clone)]
+pub struct SessionStore {
+    pool: DbPool,
+    secret: Arc<[u8]>,
+    ttl: Duration,
+    /// Adds `Secure` to the cookie; set when serving HTTPS.
+    secure: bool,
+}
+
+impl SessionStore {
+    /// Reads `SESSION_SECRET` and `SESSION_TTL` (seconds or `humantime`, e.g.
+    /// `12h`). Without a secret a random one is generated, which logs
+    /// everybody out on restart.
+    pub fn from_env(pool: DbPool, secure: bool) -> anyhow::Result<Self> {
+        let secret: Arc<[u8]> = match env::var("SESSION_SECRET") {
+            Ok(secret) if secret.len() >= 32 => secret.into_bytes().into(),
+            Ok(_) => anyhow::bail!("SESSION_SECRET must be at least 32 bytes long"),
+            Err(_) => {
+                warn!("SESSION_SECRET not set; sessions will not survive a restart");
+                let mut secret = [0u8; 32];
+                rand::rng().fill_bytes(&mut secret);
+                secret.into()
+            }
+        };
+        let ttl = match env::var("SESSION_TTL") {
+            Ok(ttl) => crate::parse_duration(&ttl)?,
+            Err(_) => DEFAULT_SESSION_TTL,
+        };
+        Ok(Self {
+            pool,
+            secret,
+            ttl,
+            secure,
+        })
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+
+    /// Row id for a token: its SHA-256, so a leaked table is useless.
+    fn session_id(token: &str) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+    }
+
+    /// Checks the signature of a `token.signature` cookie value and returns
+    /// the token part.
+    fn verify_cookie<'a>(&self, value: &'a str) -> Option<&'a str> {
+        let (token, signature) = value.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        let mut mac = self.mac();
+        mac.update(token.as_bytes());
+        mac.verify_slice(&signature).ok()?;
+        Some(token)
+    }
+
+    /// Opens a session for `user_id` and returns the signed cookie value.
+    pub async fn create(&self, user_id: i64) -> Result<String, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut bytes = [0u8; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        let token = URL_SAFE_NO_PAD.encode(bytes);
+
+        // Expired rows are only useful to nobody; clear them on the way.
+        conn.execute(
+            "DELETE FROM sessions WHERE expires_at <= CURRENT_TIMESTAMP",
+            (),
+        )
+        .await?;
+        conn.execute(
+            "INSERT INTO sessions (id, user_id, expires_at) \
+             VALUES (?1, ?2, datetime('now', ?3))",
+            libsql::params![
+                Self::session_id(&token),
+                user_id,
+                format!("+{} seconds", self.ttl.as_secs())
+            ],
+        )
+        .await?;
+
+        let mut mac = self.mac();
+        mac.update(token.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        Ok(format!("{token}.{signature}"))
+    }
+
+    /// The active user behind a cookie value, if the session is valid, not
+    /// expired and the account is still active.
+    pub async fn resolve(&self, value: &str) -> Result<Option<User>, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let Some(token) = self.verify_cookie(value) else {
+            return Ok(None);
+        };
+        let mut rows = conn
+            .query(
+                "SELECT u.id, u.email, u.role FROM sessions s JOIN users u ON u.id = s.user_id \
+                 WHERE s.id = ?1 AND s.expires_at > CURRENT_TIMESTAMP AND u.is_active = 1",
+                libsql::params![Self::session_id(token)],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(User {
+                id: row.get::<i64>(0)?.to_string(),
+                email: row.get(1)?,
+                roles: vec![row.get(2)?],
+                scopes: None,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn destroy(&self, value: &str) -> Result<(), libsql::Error> {
+        let conn = self.pool.get().await?;
+        if let Some(token) = self.verify_cookie(value) {
+            conn.execute(
+                "DELETE FROM sessions WHERE id = ?1",
+                libsql::params![Self::session_id(token)],
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    fn cookie(&self, value: &str, max_age: u64) -> HeaderValue {
+        let secure = if self.secure { "; Secure" } else { "" };
+        HeaderValue::from_str(&format!(
+            "{COOKIE_NAME}={value}; Path=/; HttpOnly; SameSite=Lax; Max-Age={max_age}{secure}"
+        ))
+        .expect("session cookies only contain base64url characters")
+    }
+}
+
+/// The session cookie's value, if the request carries one.
+pub fn session_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| pair.trim().strip_prefix(COOKIE_NAME)?.strip_prefix('='))
+}
+
+/// Removes the session cookie from `headers`, keeping any other cookie, for
+/// requests that leave the server (the reverse proxy).
+pub fn strip_session_cookie(headers: &mut HeaderMap) {
+    let cookies: Vec<String> = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .map(str::trim)
+        .filter(|pair| {
+            !pair.is_empty() && pair.split_once('=').map_or(*pair, |(name, _)| name) != COOKIE_NAME
+        })
+        .map(str::to_owned)
+        .collect();
+    headers.remove(header::COOKIE);
+    if cookies.is_empty() {
+        return;
+    }
+    if let Ok(value) = HeaderValue::from_str(&cookies.join("; ")) {
+        headers.insert(header::COOKIE, value);
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct LoginRequest {
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+    #[validate(length(max = MAX_PASSWORD_LEN, message = "must be at most 1024 characters"))]
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginResponse {
+    user_id: i64,
+    expires_in: u64,
+}
+
+fn internal(err: impl std::fmt::Display) -> ApiError {
+    ApiError::internal("session store failure", err)
+}
+
+/// Same answer for unknown emails and wrong passwords, so the endpoint does
+/// not reveal which accounts exist.
+fn invalid_credentials() -> ApiError {
+    ApiError::new(StatusCode::UNAUTHORIZED, "invalid email or password")
+}
+
+/// Id of the active account `input` identifies, or 401 when the email is
+/// unknown or the password wrong. Shared by every way of logging in. Hashes
+/// made with other argon2 parameters than the current ones are upgraded on
+/// the way.
+///
+/// An unknown email is still checked against a dummy hash, so it takes as
+/// long as a wrong password and timing does not reveal which accounts exist.
+pub async fn check_credentials(
+    users: &UserRepository,
+    credentials: &Credentials,
+    input: LoginRequest,
+) -> Result<i64, ApiError> {
+    let account = users.password_hash(&input.email).await?;
+    let known_user = account.as_ref().map(|(user_id, _)| *user_id);
+
+    // Argon2 is deliberately slow; keep it off the async workers.
+    let credentials = credentials.clone();
+    let checked = tokio::task::spawn_blocking(move || {
+        let Some((user_id, password_hash)) = account else {
+            credentials.verify_dummy(&input.password);
+            return None;
+        };
+        if !credentials.verify(&input.password, &password_hash) {
+            return None;
+        }
+        if !credentials.needs_rehash(&password_hash) {
+            return Some((user_id, None));
+        }
+        // Only fails for passwords shorter than today's minimum; those
+        // keep their old hash.
+        Some((user_id, credentials.hash(&input.password).ok()))
+    })
+    .await
+    .map_err(internal)?;
+    let Some((user_id, rehashed)) = checked else {
+        match known_user {
+            Some(user_id) => warn!(user_id, "login with wrong password"),
+            None => warn!("login for unknown or inactive account"),
+        }
+        return Err(invalid_credentials());
+    };
+    if let Some(hash) = rehashed {
+        users.set_password_hash(user_id, &hash).await?;
+        debug!(
+            user_id,
+            "password rehashed with the current argon2 parameters"
+        );
+    }
+    Ok(user_id)
+}
+
+/// `POST /login`: checks email and password and sets a session cookie that
+/// authenticates later requests just like a bearer token.
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, body = LoginResponse, headers(("set-cookie" = String))),
+        (status = 401, description = "Unknown email, wrong password or inactive account"),
+        (status = 422, description = "Invalid fields", body = crate::error::Problem)
+    )
+)]
+pub async fn login(
+    State(sessions): State<SessionStore>,
+    State(users): State<UserRepository>,
+    State(credentials): State<Credentials>,
+    ValidJson(input): ValidJson<LoginRequest>,
+) -> Result<Response, ApiError> {
+    let user_id = check_credentials(&users, &credentials, input).await?;
+    let cookie = sessions.create(user_id).await.map_err(internal)?;
+    info!(user_id, "session created");
+    Ok((
+        [(
+            header::SET_COOKIE,
+            sessions.cookie(&cookie, sessions.ttl.as_secs()),
+        )],
+        Json(LoginResponse {
+            user_id,
+            expires_in: sessions.ttl.as_secs(),
+        }),
+    )
+        .into_response())
+}
+
+/// `POST /logout`: ends the current session, if any, and clears the cookie.
+#[utoipa::path(post, path = "/logout", tag = "auth", responses((status = 204)))]
+pub async fn logout(
+    State(sessions): State<SessionStore>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    if let Some(value) = session_cookie(&headers) {
+        sessions.destroy(value).await.map_err(internal)?;
+    }
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, sessions.cookie("", 0))],
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::{HashParams, create_initial_admin};
+
+    fn login(email: &str, password: &str) -> LoginRequest {
+        LoginRequest {
+            email: email.to_owned(),
+            password: password.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_email_and_wrong_password_get_the_same_answer() {
+        let pool = DbPool::migrated_in_memory().await;
+        let users = UserRepository::new(pool.clone());
+        // The cheapest parameters argon2 accepts; the tests are about the
+        // answers, not the cost.
+        let credentials = Credentials::new(HashParams {
+            memory_kib: 8,
+            iterations: 1,
+            parallelism: 1,
+        })
+        .unwrap();
+        let hash = credentials.hash("correct horse").unwrap();
+        let conn = pool.get().await.unwrap();
+        let admin = create_initial_admin(&conn, "Admin", "admin@example.com", &hash)
+            .await
+            .unwrap();
+        drop(conn);
+
+        let user_id = check_credentials(
+            &users,
+            &credentials,
+            login("admin@example.com", "correct horse"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(user_id, admin);
+
+        for input in [
+            login("admin@example.com", "wrong horse"),
+            login("nobody@example.com", "correct horse"),
+        ] {
+            let err = check_credentials(&users, &credentials, input)
+                .await
+                .unwrap_err();
+            let response = err.into_response();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::{env, sync::Arc};
+
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, HeaderName, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    credentials::{Credentials, CredentialsError, create_initial_admin},
+    db::DbPool,
+    error::ApiError,
+    events::EventBus,
+    users::UserRepository,
+    validation::{MAX_PASSWORD_LEN, MIN_PASSWORD_LEN, ValidJson},
+    versioning,
+};
+
+/// Header carrying the `SETUP_TOKEN`.
+const SETUP_TOKEN_HEADER: HeaderName = HeaderName::from_static("x-setup-token");
+
+/// Shared secret that unlocks `POST /setup/admin` (`SETUP_TOKEN`). Without
+/// it the endpoint is disabled and the first admin has to be created with
+/// the `create-admin` binary.
+#[derive(Clone)]
+pub struct SetupToken(Option<Arc<[u8]>>);
+
+impl SetupToken {
+    pub fn from_env() -> Self {
+        Self(
+            env::var("SETUP_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty())
+                .map(|token| Sha256::digest(token.as_bytes()).to_vec().into()),
+        )
+    }
+
+    /// Compares digests rather than the tokens themselves, so the time taken
+    /// does not depend on how much of a guess was right.
+    fn matches(&self, presented: &str) -> bool {
+        self.0
+            .as_deref()
+            .is_some_and(|expected| *expected == Sha256::digest(presented.as_bytes())[..])
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateAdmin {
+    #[validate(length(min = 1, max = 100, message = "must be 1 to 100 characters"))]
+    pub name: String,
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+    #[validate(length(
+        min = MIN_PASSWORD_LEN,
+        max = MAX_PASSWORD_LEN,
+        message = "must be 8 to 1024 characters"
+    ))]
+    pub password: String,
+}
+
+fn credentials_error(err: CredentialsError) -> ApiError {
+    match err {
+        CredentialsError::WeakPassword => ApiError::bad_request(err.to_string()),
+        CredentialsError::AdminExists | CredentialsError::EmailInUse => {
+            ApiError::new(StatusCode::CONFLICT, err.to_string())
+        }
+        err => ApiError::internal("setup failure", err),
+    }
+}
+
+/// `POST /setup/admin`: creates the first admin account. Needs the
+/// `x-setup-token` header to match `SETUP_TOKEN` and only works while no
+/// admin exists.
+#[utoipa::path(
+    post,
+    path = "/setup/admin",
+    tag = "auth",
+    request_body = CreateAdmin,
+    params(("x-setup-token" = String, Header, description = "Value of SETUP_TOKEN")),
+    responses(
+        (status = 201, body = crate::users::UserRecord, headers(("location" = String))),
+        (status = 400, description = "Password too short"),
+        (status = 403, description = "Missing or wrong setup token"),
+        (status = 404, description = "SETUP_TOKEN is not configured"),
+        (status = 409, description = "An admin already exists or the email is taken"),
+        (status = 422, description = "Invalid fields", body = crate::error::Problem)
+    )
+)]
+pub async fn create_admin(
+    State(token): State<SetupToken>,
+    State(credentials): State<Credentials>,
+    State(pool): State<DbPool>,
+    State(users): State<UserRepository>,
+    State(events): State<EventBus>,
+    headers: HeaderMap,
+    ValidJson(input): ValidJson<CreateAdmin>,
+) -> Result<Response, ApiError> {
+    if token.0.is_none() {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let presented = headers
+        .get(&SETUP_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !token.matches(presented) {
+        warn!("setup attempt with a wrong token");
+        return Err(ApiError::forbidden("missing or wrong setup token"));
+    }
+
+    // Argon2 is deliberately slow; keep it off the async workers.
+    let password = input.password;
+    let hash = tokio::task::spawn_blocking(move || credentials.hash(&password))
+        .await
+        .map_err(|err| ApiError::internal("setup failure", err))?
+        .map_err(credentials_error)?;
+    let conn = pool
+        .get()
+        .await
+        .map_err(|err| ApiError::internal("setup failure", err))?;
+    let id = create_initial_admin(&conn, &input.name, &input.email, &hash)
+        .await
+        .map_err(credentials_error)?;
+
+    let user = users
+        .get(id)
+        .await?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    info!(user_id = id, "initial admin created");
+    events.publish("user.created", json!({ "user_id": id }));
+    Ok((
+        StatusCode::CREATED,
+        [(
+            header::LOCATION,
+            format!("{}/users/{id}", versioning::CURRENT),
+        )],
+        Json(user),
+    )
+        .into_response())
+}
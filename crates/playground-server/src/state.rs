@@ -0,0 +1,98 @@
+use axum::extract::FromRef;
+use metrics_exporter_prometheus::PrometheusHandle;
+
+use crate::{
+    api_keys::ApiKeyStore, audit::AuditLog, credentials::Credentials, dashboard::StartedAt,
+    db::DbPool, events::EventBus, graphql::ApiSchema, health::HealthChecks, jobs::JobQueue,
+    maintenance::Maintenance, notes::NoteRepository, recordings::RecordingStore,
+    reload::LiveConfig, screenshots::ScreenshotStore, sessions::SessionStore, setup::SetupToken,
+    tokens::RefreshTokenStore, tokens::TokenIssuer, users::UserRepository,
+    webhooks::WebhookDispatcher,
+};
+
+/// Everything the handlers share, built once in `main` and handed to the
+/// router with `with_state`.
+///
+/// Handlers ask only for the parts they use (`State<UserRepository>`,
+/// `State<EventBus>`, ...), which `FromRef` pulls out of this struct, so
+/// one can be called directly with just those values, for instance a
+/// repository over an in-memory database.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub db: DbPool,
+    pub users: UserRepository,
+    pub notes: NoteRepository,
+    pub audit: AuditLog,
+    pub sessions: SessionStore,
+    pub tokens: TokenIssuer,
+    pub refresh_tokens: RefreshTokenStore,
+    pub credentials: Credentials,
+    pub setup_token: SetupToken,
+    pub api_keys: ApiKeyStore,
+    pub recordings: RecordingStore,
+    pub screenshots: ScreenshotStore,
+    pub jobs: JobQueue,
+    pub webhooks: WebhookDispatcher,
+    pub events: EventBus,
+    pub metrics: PrometheusHandle,
+    /// The reloadable part of the configuration.
+    pub config: LiveConfig,
+    pub started_at: StartedAt,
+    pub graphql: ApiSchema,
+    pub maintenance: Maintenance,
+    pub health: HealthChecks,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use axum::{
+        Extension, Json,
+        extract::{Path, State},
+        http::StatusCode,
+        response::IntoResponse,
+    };
+
+    use crate::{
+        auth::{RequireRole, User},
+        db::DbPool,
+        users::{self, CreateUser, UserRepository},
+    };
+
+    /// What the doc comment above promises: a handler runs on just the state
+    /// it asks for, here a repository over an in-memory database, with no
+    /// `AppState` around it.
+    #[tokio::test]
+    async fn handlers_run_on_the_parts_they_ask_for() {
+        let repo = UserRepository::new(DbPool::migrated_in_memory().await);
+        let record = repo
+            .create(CreateUser {
+                name: "Ada".to_owned(),
+                email: "ada@example.com".to_owned(),
+                role: Some("admin".to_owned()),
+            })
+            .await
+            .unwrap();
+        let caller = User {
+            id: record.id.to_string(),
+            email: record.email.clone(),
+            roles: vec![record.role.clone()],
+            scopes: None,
+        };
+
+        let Json(me) = users::me(Extension(caller.clone()), State(repo.clone()))
+            .await
+            .unwrap();
+        assert_eq!(me.email, "ada@example.com");
+
+        let err = users::get_user(
+            RequireRole(caller, PhantomData),
+            State(repo),
+            Path(record.id + 1),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::NOT_FOUND);
+    }
+}
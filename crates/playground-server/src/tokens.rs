@@ -0,0 +1,544 @@
+use std::{
+    env,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{Json, extract::State, http::StatusCode};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{EncodingKey, Header};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    credentials::Credentials,
+    db::DbPool,
+    error::ApiError,
+    sessions::{LoginRequest, check_credentials},
+    users::{UserRecord, UserRepository},
+    validation::ValidJson,
+};
+
+/// Access token lifetime when `ACCESS_TOKEN_TTL` is not set.
+const DEFAULT_ACCESS_TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+/// Refresh token lifetime when `REFRESH_TOKEN_TTL` is not set.
+const DEFAULT_REFRESH_TOKEN_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Claims of the tokens we issue; what [`crate::auth::JwtVerifier`] reads
+/// back.
+#[derive(Debug, Serialize)]
+struct AccessClaims<'a> {
+    sub: String,
+    email: &'a str,
+    role: &'a str,
+    iat: u64,
+    exp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iss: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    aud: &'a [String],
+}
+
+/// Signs HS256 access tokens with `JWT_SECRET`, the same key the verifier
+/// checks them with. Cheap to clone.
+#[derive(Clone)]
+pub struct TokenIssuer {
+    /// `None` when the server verifies tokens from an external issuer
+    /// (RSA key or JWKS) and so cannot sign its own.
+    key: Option<EncodingKey>,
+    ttl: Duration,
+    issuer: Option<String>,
+    audience: Vec<String>,
+}
+
+impl TokenIssuer {
+    /// Reads `JWT_SECRET`, `ACCESS_TOKEN_TTL` (seconds or `humantime`, e.g.
+    /// `15m`) and the `JWT_ISSUER`/`JWT_AUDIENCE` the verifier expects.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let key = env::var("JWT_SECRET")
+            .ok()
+            .map(|secret| EncodingKey::from_secret(secret.as_bytes()));
+        let ttl = match env::var("ACCESS_TOKEN_TTL") {
+            Ok(ttl) => crate::parse_duration(&ttl)?,
+            Err(_) => DEFAULT_ACCESS_TOKEN_TTL,
+        };
+        let audience = env::var("JWT_AUDIENCE")
+            .map(|aud| {
+                aud.split(',')
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            key,
+            ttl,
+            issuer: env::var("JWT_ISSUER").ok(),
+            audience,
+        })
+    }
+
+    /// A signed access token for `user` and its expiry (Unix seconds).
+    pub fn issue(&self, user: &UserRecord) -> Result<(String, u64), ApiError> {
+        let Some(key) = &self.key else {
+            return Err(ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "token issuing requires JWT_SECRET",
+            ));
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let expires_at = now + self.ttl.as_secs();
+        let claims = AccessClaims {
+            sub: user.id.to_string(),
+            email: &user.email,
+            role: &user.role,
+            iat: now,
+            exp: expires_at,
+            iss: self.issuer.as_deref(),
+            aud: &self.audience,
+        };
+        let token = jsonwebtoken::encode(&Header::default(), &claims, key)
+            .map_err(|err| ApiError::internal("failed to sign access token", err))?;
+        Ok((token, expires_at))
+    }
+}
+
+/// What a refresh token presented to [`RefreshTokenStore::check`] is.
+pub enum Presented {
+    /// Valid and not spent yet; nothing has changed.
+    Unused { user_id: i64 },
+    /// The token had already been exchanged once: either the client is
+    /// confused or it was stolen. Its whole family has been revoked.
+    Reused { user_id: i64 },
+    /// Unknown, expired or revoked.
+    Invalid,
+}
+
+/// Outcome of presenting a refresh token to [`RefreshTokenStore::rotate`].
+pub enum Rotation {
+    /// The token was valid; it is now spent and `token` replaces it.
+    Rotated { user_id: i64, token: String },
+    /// As in [`Presented::Reused`]; the family has been revoked.
+    Reused { user_id: i64 },
+    /// Unknown, expired or revoked.
+    Invalid,
+}
+
+/// Single-use refresh tokens stored in the `refresh_tokens` table. Each
+/// refresh spends the presented token and issues a new one in the same
+/// family (one family per login); presenting a spent token again revokes
+/// the family. Like sessions, only a SHA-256 of each token is stored. Cheap
+/// to clone.
+#[derive(Clone)]
+pub struct RefreshTokenStore {
+    pool: DbPool,
+    ttl: Duration,
+}
+
+impl RefreshTokenStore {
+    /// Reads `REFRESH_TOKEN_TTL` (seconds or `humantime`, e.g. `30d`).
+    pub fn from_env(pool: DbPool) -> anyhow::Result<Self> {
+        let ttl = match env::var("REFRESH_TOKEN_TTL") {
+            Ok(ttl) => crate::parse_duration(&ttl)?,
+            Err(_) => DEFAULT_REFRESH_TOKEN_TTL,
+        };
+        Ok(Self { pool, ttl })
+    }
+
+    fn token_id(token: &str) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+    }
+
+    /// Issues a refresh token for `user_id`, starting a new family.
+    pub async fn issue(&self, user_id: i64) -> Result<String, libsql::Error> {
+        let conn = self.pool.get().await?;
+        self.insert(&conn, user_id, None).await
+    }
+
+    /// Stores a new token for `user_id` on `conn`, in `family` or a new one.
+    async fn insert(
+        &self,
+        conn: &libsql::Connection,
+        user_id: i64,
+        family: Option<&str>,
+    ) -> Result<String, libsql::Error> {
+        let mut bytes = [0u8; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        let token = URL_SAFE_NO_PAD.encode(bytes);
+        let family = family.map_or_else(|| uuid::Uuid::new_v4().to_string(), str::to_owned);
+
+        conn.execute(
+            "DELETE FROM refresh_tokens WHERE expires_at <= CURRENT_TIMESTAMP",
+            (),
+        )
+        .await?;
+        conn.execute(
+            "INSERT INTO refresh_tokens (id, user_id, family_id, expires_at) \
+             VALUES (?1, ?2, ?3, datetime('now', ?4))",
+            libsql::params![
+                Self::token_id(&token),
+                user_id,
+                family,
+                format!("+{} seconds", self.ttl.as_secs())
+            ],
+        )
+        .await?;
+        Ok(token)
+    }
+
+    /// Looks `token` up without spending it, so the caller can do whatever
+    /// may fail (loading the user, signing the access token) before
+    /// [`rotate`](Self::rotate) makes the token unusable. Presenting a spent
+    /// token revokes its family here already.
+    pub async fn check(&self, token: &str) -> Result<Presented, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                "SELECT user_id, family_id, used_at IS NOT NULL, \
+                        expires_at > CURRENT_TIMESTAMP \
+                 FROM refresh_tokens WHERE id = ?1 AND revoked_at IS NULL",
+                libsql::params![Self::token_id(token)],
+            )
+            .await?;
+        let Some(row) = rows.next().await? else {
+            return Ok(Presented::Invalid);
+        };
+        let user_id: i64 = row.get(0)?;
+        let family: String = row.get(1)?;
+        let used: bool = row.get(2)?;
+        let live: bool = row.get(3)?;
+        drop(rows);
+        drop(conn);
+        if used {
+            self.revoke_family(&family).await?;
+            Ok(Presented::Reused { user_id })
+        } else if live {
+            Ok(Presented::Unused { user_id })
+        } else {
+            Ok(Presented::Invalid)
+        }
+    }
+
+    /// Spends `token` and issues its successor in one transaction, so a
+    /// failure leaves the token usable. Spending is a conditional `UPDATE`:
+    /// of two concurrent refreshes with the same token, the loser is
+    /// treated as reuse.
+    pub async fn rotate(&self, token: &str) -> Result<Rotation, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let tx = conn.transaction().await?;
+        let mut rows = tx
+            .query(
+                "UPDATE refresh_tokens SET used_at = CURRENT_TIMESTAMP \
+                 WHERE id = ?1 AND used_at IS NULL AND revoked_at IS NULL \
+                   AND expires_at > CURRENT_TIMESTAMP \
+                 RETURNING user_id, family_id",
+                libsql::params![Self::token_id(token)],
+            )
+            .await?;
+        let Some(row) = rows.next().await? else {
+            drop(rows);
+            tx.rollback().await?;
+            drop(conn);
+            return Ok(match self.check(token).await? {
+                Presented::Reused { user_id } => Rotation::Reused { user_id },
+                Presented::Unused { .. } | Presented::Invalid => Rotation::Invalid,
+            });
+        };
+        let user_id: i64 = row.get(0)?;
+        let family: String = row.get(1)?;
+        // Step the `UPDATE` to its end; a statement still in progress keeps
+        // the transaction from committing.
+        while rows.next().await?.is_some() {}
+        drop(rows);
+        let token = self.insert(&tx, user_id, Some(&family)).await?;
+        tx.commit().await?;
+        Ok(Rotation::Rotated { user_id, token })
+    }
+
+    async fn revoke_family(&self, family: &str) -> Result<(), libsql::Error> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP \
+             WHERE family_id = ?1 AND revoked_at IS NULL",
+            libsql::params![family],
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenResponse {
+    access_token: String,
+    /// Always `Bearer`.
+    token_type: &'static str,
+    /// Seconds until the access token expires.
+    expires_in: u64,
+    /// Access token expiry as a Unix timestamp.
+    expires_at: u64,
+    /// Single-use token for `POST /auth/refresh`.
+    refresh_token: String,
+    /// Seconds until the refresh token expires, if it is not used first.
+    refresh_expires_in: u64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+fn internal(err: impl std::fmt::Display) -> ApiError {
+    ApiError::internal("refresh token store failure", err)
+}
+
+fn invalid_refresh_token() -> ApiError {
+    ApiError::new(StatusCode::UNAUTHORIZED, "invalid or expired refresh token")
+}
+
+/// The response for a signed access token (and its expiry) from `issuer`
+/// and a refresh token from `refresh_tokens`.
+fn token_pair(
+    issuer: &TokenIssuer,
+    refresh_tokens: &RefreshTokenStore,
+    (access_token, expires_at): (String, u64),
+    refresh_token: String,
+) -> TokenResponse {
+    TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: issuer.ttl.as_secs(),
+        expires_at,
+        refresh_token,
+        refresh_expires_in: refresh_tokens.ttl.as_secs(),
+    }
+}
+
+/// `POST /auth/login`: checks email and password and returns a signed access
+/// token to send as `Authorization: Bearer`, plus a refresh token to get
+/// the next one with.
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, body = TokenResponse),
+        (status = 401, description = "Unknown email, wrong password or inactive account"),
+        (status = 422, description = "Invalid fields", body = crate::error::Problem),
+        (status = 503, description = "The server has no JWT_SECRET to sign tokens with")
+    )
+)]
+pub async fn issue_token(
+    State(issuer): State<TokenIssuer>,
+    State(refresh_tokens): State<RefreshTokenStore>,
+    State(users): State<UserRepository>,
+    State(credentials): State<Credentials>,
+    ValidJson(input): ValidJson<LoginRequest>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let user_id = check_credentials(&users, &credentials, input).await?;
+    let Some(user) = users.get(user_id).await? else {
+        // Deleted between the password check and now.
+        warn!(user_id, "account vanished during login");
+        return Err(StatusCode::UNAUTHORIZED.into());
+    };
+    let access = issuer.issue(&user)?;
+    let refresh = refresh_tokens.issue(user.id).await.map_err(internal)?;
+    let tokens = token_pair(&issuer, &refresh_tokens, access, refresh);
+    info!(user_id, "access token issued");
+    Ok(Json(tokens))
+}
+
+/// `POST /auth/refresh`: exchanges a refresh token for a new access token
+/// and a new refresh token. Each refresh token works once; reusing one
+/// revokes every token descended from the same login.
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, body = TokenResponse),
+        (status = 401, description = "Unknown, expired, revoked or already used refresh token"),
+        (status = 503, description = "The server has no JWT_SECRET to sign tokens with")
+    )
+)]
+pub async fn refresh_token(
+    State(issuer): State<TokenIssuer>,
+    State(refresh_tokens): State<RefreshTokenStore>,
+    State(users): State<UserRepository>,
+    Json(input): Json<RefreshRequest>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    // Everything that can fail happens before the presented token is spent,
+    // so a server-side error does not turn the client's retry into reuse.
+    let user_id = match refresh_tokens
+        .check(&input.refresh_token)
+        .await
+        .map_err(internal)?
+    {
+        Presented::Unused { user_id } => user_id,
+        Presented::Reused { user_id } => {
+            warn!(user_id, "refresh token reused; revoked its family");
+            return Err(invalid_refresh_token());
+        }
+        Presented::Invalid => {
+            warn!("unknown, expired or revoked refresh token");
+            return Err(invalid_refresh_token());
+        }
+    };
+    let user = match users.get(user_id).await? {
+        Some(user) if user.is_active => user,
+        _ => {
+            warn!(user_id, "refresh for inactive or deleted account");
+            return Err(invalid_refresh_token());
+        }
+    };
+    let access = issuer.issue(&user)?;
+    let token = match refresh_tokens
+        .rotate(&input.refresh_token)
+        .await
+        .map_err(internal)?
+    {
+        Rotation::Rotated { token, .. } => token,
+        Rotation::Reused { user_id } => {
+            warn!(user_id, "refresh token reused; revoked its family");
+            return Err(invalid_refresh_token());
+        }
+        Rotation::Invalid => {
+            warn!("refresh token expired or revoked while refreshing");
+            return Err(invalid_refresh_token());
+        }
+    };
+    let tokens = token_pair(&issuer, &refresh_tokens, access, token);
+    info!(user_id, "access token refreshed");
+    Ok(Json(tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+
+    use super::*;
+    use crate::credentials::create_initial_admin;
+
+    async fn setup() -> (RefreshTokenStore, UserRepository, i64) {
+        let pool = DbPool::migrated_in_memory().await;
+        let conn = pool.get().await.unwrap();
+        // The hash is never checked here.
+        let user_id = create_initial_admin(&conn, "Admin", "admin@example.com", "unused")
+            .await
+            .unwrap();
+        drop(conn);
+        let store = RefreshTokenStore {
+            pool: pool.clone(),
+            ttl: DEFAULT_REFRESH_TOKEN_TTL,
+        };
+        (store, UserRepository::new(pool), user_id)
+    }
+
+    fn issuer(secret: Option<&str>) -> TokenIssuer {
+        TokenIssuer {
+            key: secret.map(|secret| EncodingKey::from_secret(secret.as_bytes())),
+            ttl: DEFAULT_ACCESS_TOKEN_TTL,
+            issuer: None,
+            audience: Vec::new(),
+        }
+    }
+
+    async fn refresh(
+        issuer: TokenIssuer,
+        store: &RefreshTokenStore,
+        users: &UserRepository,
+        token: &str,
+    ) -> Result<TokenResponse, StatusCode> {
+        refresh_token(
+            State(issuer),
+            State(store.clone()),
+            State(users.clone()),
+            Json(RefreshRequest {
+                refresh_token: token.to_owned(),
+            }),
+        )
+        .await
+        .map(|Json(tokens)| tokens)
+        .map_err(|err| err.into_response().status())
+    }
+
+    #[tokio::test]
+    async fn reusing_a_spent_token_revokes_its_family() {
+        let (store, _, user_id) = setup().await;
+        let first = store.issue(user_id).await.unwrap();
+        let Rotation::Rotated { token: second, .. } = store.rotate(&first).await.unwrap() else {
+            panic!("a fresh token rotates");
+        };
+        assert!(matches!(
+            store.check(&second).await.unwrap(),
+            Presented::Unused { user_id: id } if id == user_id
+        ));
+
+        assert!(matches!(
+            store.rotate(&first).await.unwrap(),
+            Rotation::Reused { user_id: id } if id == user_id
+        ));
+        // The legitimate successor went down with the family.
+        assert!(matches!(
+            store.check(&second).await.unwrap(),
+            Presented::Invalid
+        ));
+        assert!(matches!(
+            store.rotate(&second).await.unwrap(),
+            Rotation::Invalid
+        ));
+    }
+
+    #[tokio::test]
+    async fn unknown_tokens_are_invalid() {
+        let (store, _, _) = setup().await;
+        assert!(matches!(
+            store.check("nope").await.unwrap(),
+            Presented::Invalid
+        ));
+        assert!(matches!(
+            store.rotate("nope").await.unwrap(),
+            Rotation::Invalid
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_failed_refresh_does_not_spend_the_token() {
+        let (store, users, user_id) = setup().await;
+        let first = store.issue(user_id).await.unwrap();
+
+        // No JWT_SECRET: the access token cannot be signed.
+        let status = refresh(issuer(None), &store, &users, &first)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        // The retry is not mistaken for reuse.
+        let tokens = refresh(issuer(Some("secret")), &store, &users, &first)
+            .await
+            .unwrap();
+        assert_ne!(tokens.refresh_token, first);
+
+        // Once it did go through, the old token is spent for good.
+        let status = refresh(issuer(Some("secret")), &store, &users, &first)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let status = refresh(
+            issuer(Some("secret")),
+            &store,
+            &users,
+            &tokens.refresh_token,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
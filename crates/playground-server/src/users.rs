@@ -0,0 +1,443 @@
+use async_graphql::SimpleObject;
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use libsql::Row;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::{
+    auth::{Admin, RequireRole, User},
+    db::DbPool,
+    error::ApiError,
+    events::EventBus,
+    filters::{FilterField, FilterQuery, SqlFilter},
+    pagination::{PageQuery, PageRequest, Paginated},
+    validation::ValidJson,
+};
+
+/// A row of the `users` table as exposed by the API (never the password hash).
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct UserRecord {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    pub role: String,
+    pub is_active: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Roles an account can have.
+const ROLES: &[&str] = &["admin", "member"];
+/// Longest accepted display name.
+const MAX_NAME_LEN: u64 = 100;
+
+fn known_role(role: &str) -> Result<(), ValidationError> {
+    if ROLES.contains(&role) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("role")
+            .with_message(format!("must be one of: {}", ROLES.join(", ")).into()))
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateUser {
+    #[validate(length(min = 1, max = MAX_NAME_LEN, message = "must be 1 to 100 characters"))]
+    pub name: String,
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+    /// `admin` or `member` (the default).
+    #[serde(default)]
+    #[validate(custom(function = "known_role"))]
+    pub role: Option<String>,
+}
+
+/// Partial update: only the fields present in the body are changed.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateUser {
+    #[validate(length(min = 1, max = MAX_NAME_LEN, message = "must be 1 to 100 characters"))]
+    pub name: Option<String>,
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: Option<String>,
+    #[validate(custom(function = "known_role"))]
+    pub role: Option<String>,
+    pub is_active: Option<bool>,
+}
+
+/// Everything that can go wrong in the repository, so handlers can pick
+/// the right status code.
+#[derive(Debug)]
+pub enum RepoError {
+    /// A UNIQUE constraint fired (e.g. duplicated email).
+    Conflict,
+    Db(libsql::Error),
+}
+
+impl From<libsql::Error> for RepoError {
+    fn from(err: libsql::Error) -> Self {
+        if err.to_string().contains("UNIQUE constraint failed") {
+            RepoError::Conflict
+        } else {
+            RepoError::Db(err)
+        }
+    }
+}
+
+impl From<RepoError> for ApiError {
+    fn from(err: RepoError) -> Self {
+        match err {
+            RepoError::Conflict => ApiError::new(StatusCode::CONFLICT, "email is already in use"),
+            RepoError::Db(err) => ApiError::internal("user repository failure", err),
+        }
+    }
+}
+
+const USER_COLUMNS: &str = "id, name, email, role, is_active, created_at, updated_at";
+/// Columns `GET /users?sort=` accepts.
+const USER_SORT_COLUMNS: &[&str] = &["id", "name", "email", "role", "created_at", "updated_at"];
+/// Filters `GET /users` accepts.
+const USER_FILTERS: &[FilterField] = &[
+    FilterField::text("name", "name"),
+    FilterField::text("email", "email"),
+    FilterField::text("role", "role"),
+    FilterField::bool("is_active", "is_active"),
+    FilterField::timestamp("created", "created_at"),
+    FilterField::timestamp("updated", "updated_at"),
+];
+
+/// Data access for the `users` table. Cheap to clone: it only holds the
+/// connection pool handle.
+#[derive(Clone)]
+pub struct UserRepository {
+    pool: DbPool,
+}
+
+impl UserRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn from_row(row: &Row) -> Result<UserRecord, libsql::Error> {
+        Ok(UserRecord {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            email: row.get(2)?,
+            role: row.get(3)?,
+            is_active: row.get::<i64>(4)? != 0,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    }
+
+    /// One page of the users matching `filter` in `order_by` order (an
+    /// already validated `ORDER BY` clause), plus how many match in total.
+    pub async fn list(
+        &self,
+        filter: &SqlFilter,
+        order_by: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<UserRecord>, u64), RepoError> {
+        let conn = self.pool.get().await?;
+        let where_clause = filter.where_clause();
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT {USER_COLUMNS} FROM users {where_clause} \
+                     ORDER BY {order_by} LIMIT ? OFFSET ?"
+                ),
+                filter.params([limit.into(), offset.into()]),
+            )
+            .await?;
+        let mut users = Vec::new();
+        while let Some(row) = rows.next().await? {
+            users.push(Self::from_row(&row)?);
+        }
+
+        let mut rows = conn
+            .query(
+                &format!("SELECT COUNT(*) FROM users {where_clause}"),
+                filter.params([]),
+            )
+            .await?;
+        let total = match rows.next().await? {
+            Some(row) => row.get::<u64>(0)?,
+            None => 0,
+        };
+        Ok((users, total))
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<UserRecord>, RepoError> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                &format!("SELECT {USER_COLUMNS} FROM users WHERE id = ?1"),
+                libsql::params![id],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(Self::from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn create(&self, input: CreateUser) -> Result<UserRecord, RepoError> {
+        let conn = self.pool.get().await?;
+        // `!` is never a valid hash, so accounts created here cannot log in
+        // with a password until one is set.
+        let mut rows = conn
+            .query(
+                &format!(
+                    "INSERT INTO users (name, email, password_hash, role) \
+                     VALUES (?1, ?2, '!', COALESCE(?3, 'member')) RETURNING {USER_COLUMNS}"
+                ),
+                libsql::params![input.name, input.email, input.role],
+            )
+            .await?;
+        let row = rows
+            .next()
+            .await?
+            .ok_or(libsql::Error::QueryReturnedNoRows)?;
+        Ok(Self::from_row(&row)?)
+    }
+
+    pub async fn update(
+        &self,
+        id: i64,
+        input: UpdateUser,
+    ) -> Result<Option<UserRecord>, RepoError> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                &format!(
+                    "UPDATE users SET \
+                        name = COALESCE(?2, name), \
+                        email = COALESCE(?3, email), \
+                        role = COALESCE(?4, role), \
+                        is_active = COALESCE(?5, is_active), \
+                        updated_at = CURRENT_TIMESTAMP \
+                     WHERE id = ?1 RETURNING {USER_COLUMNS}"
+                ),
+                libsql::params![
+                    id,
+                    input.name,
+                    input.email,
+                    input.role,
+                    input.is_active.map(i64::from)
+                ],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(Self::from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Id and stored password hash of the active account with `email`.
+    pub async fn password_hash(&self, email: &str) -> Result<Option<(i64, String)>, RepoError> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                "SELECT id, password_hash FROM users WHERE email = ?1 AND is_active = 1",
+                libsql::params![email],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some((row.get(0)?, row.get(1)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Replaces the stored password hash, e.g. after a rehash with new
+    /// argon2 parameters.
+    pub async fn set_password_hash(&self, id: i64, hash: &str) -> Result<(), RepoError> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE users SET password_hash = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+            libsql::params![id, hash],
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<bool, RepoError> {
+        let conn = self.pool.get().await?;
+        let affected = conn
+            .execute("DELETE FROM users WHERE id = ?1", libsql::params![id])
+            .await?;
+        Ok(affected > 0)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/users",
+    tag = "users",
+    security(("bearer" = [])),
+    params(
+        PageQuery,
+        ("name" = Option<String>, Query, description = "Exact name, `*` as a wildcard"),
+        ("email" = Option<String>, Query, description = "Exact email, `*` as a wildcard"),
+        ("role" = Option<String>, Query),
+        ("is_active" = Option<bool>, Query),
+        ("created_after" = Option<String>, Query, description = "Date or date-time, inclusive"),
+        ("created_before" = Option<String>, Query, description = "Date or date-time, exclusive"),
+        ("updated_after" = Option<String>, Query, description = "Date or date-time, inclusive"),
+        ("updated_before" = Option<String>, Query, description = "Date or date-time, exclusive")
+    ),
+    responses(
+        (status = 200, body = Paginated<UserRecord>),
+        (status = 400, description = "Invalid page, sort column or filter"),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn list_users(
+    _admin: RequireRole<Admin>,
+    State(repo): State<UserRepository>,
+    page: PageRequest,
+    filters: FilterQuery,
+) -> Result<Json<Paginated<UserRecord>>, ApiError> {
+    let order_by = page.order_by(USER_SORT_COLUMNS, "id")?;
+    let filter = filters.to_sql(USER_FILTERS)?;
+    let (users, total) = repo
+        .list(&filter, &order_by, page.limit(), page.offset())
+        .await?;
+    Ok(Json(page.into_page(users, total)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/{id}",
+    tag = "users",
+    security(("bearer" = [])),
+    params(("id" = i64, Path)),
+    responses(
+        (status = 200, body = UserRecord),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404)
+    )
+)]
+pub async fn get_user(
+    _admin: RequireRole<Admin>,
+    State(repo): State<UserRepository>,
+    Path(id): Path<i64>,
+) -> Result<Json<UserRecord>, ApiError> {
+    repo.get(id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("no user {id}")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users",
+    tag = "users",
+    security(("bearer" = [])),
+    request_body = CreateUser,
+    responses(
+        (status = 201, body = UserRecord),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 409, description = "Email already in use"),
+        (status = 422, description = "Invalid fields", body = crate::error::Problem)
+    )
+)]
+pub async fn create_user(
+    RequireRole(admin, _): RequireRole<Admin>,
+    State(repo): State<UserRepository>,
+    State(events): State<EventBus>,
+    ValidJson(input): ValidJson<CreateUser>,
+) -> Result<(StatusCode, Json<UserRecord>), ApiError> {
+    let user = repo.create(input).await?;
+    info!(user_id = user.id, admin_id = %admin.id, "created user");
+    events.publish("user.created", json!({ "user_id": user.id }));
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/users/{id}",
+    tag = "users",
+    security(("bearer" = [])),
+    params(("id" = i64, Path)),
+    request_body = UpdateUser,
+    responses(
+        (status = 200, body = UserRecord),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404),
+        (status = 409, description = "Email already in use"),
+        (status = 422, description = "Invalid fields", body = crate::error::Problem)
+    )
+)]
+pub async fn update_user(
+    RequireRole(admin, _): RequireRole<Admin>,
+    State(repo): State<UserRepository>,
+    State(events): State<EventBus>,
+    Path(id): Path<i64>,
+    ValidJson(input): ValidJson<UpdateUser>,
+) -> Result<Json<UserRecord>, ApiError> {
+    let user = repo
+        .update(id, input)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("no user {id}")))?;
+    info!(user_id = user.id, admin_id = %admin.id, "updated user");
+    events.publish("user.updated", json!({ "user_id": user.id }));
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/{id}",
+    tag = "users",
+    security(("bearer" = [])),
+    params(("id" = i64, Path)),
+    responses(
+        (status = 204),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404)
+    )
+)]
+pub async fn delete_user(
+    RequireRole(admin, _): RequireRole<Admin>,
+    State(repo): State<UserRepository>,
+    State(events): State<EventBus>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    if repo.delete(id).await? {
+        info!(user_id = id, admin_id = %admin.id, "deleted user");
+        events.publish("user.deleted", json!({ "user_id": id }));
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found(format!("no user {id}")))
+    }
+}
+
+/// The token's `sub` is the `users.id`; the profile itself comes from the
+/// database so changes are visible without issuing a new token.
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "users",
+    security(("bearer" = [])),
+    responses((status = 200, body = UserRecord), (status = 401), (status = 404))
+)]
+pub async fn me(
+    Extension(user): Extension<User>,
+    State(repo): State<UserRepository>,
+) -> Result<Json<UserRecord>, ApiError> {
+    info!(user_id = %user.id, "serving authenticated user info");
+    let no_account = || ApiError::not_found("caller has no local user account");
+    let id = user.id.parse::<i64>().map_err(|_| no_account())?;
+    repo.get(id).await?.map(Json).ok_or_else(no_account)
+}
@@ -0,0 +1,73 @@
+use axum::{
+    Json,
+    extract::{FromRequest, Request},
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::error::{ApiError, FieldErrors};
+
+/// Shortest accepted new password, as a `validator` length.
+pub const MIN_PASSWORD_LEN: u64 = crate::credentials::MIN_PASSWORD_LEN as u64;
+/// Longest accepted password. Argon2 cost grows with the input, so this
+/// keeps a login from being used to burn CPU.
+pub const MAX_PASSWORD_LEN: u64 = 1024;
+
+/// Like [`Json`], but also runs the body's `validator` rules and answers
+/// `422` with per-field messages when they fail. Malformed JSON is still
+/// rejected the way [`Json`] does (400/415).
+pub struct ValidJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        value
+            .validate()
+            .map_err(|errors| ApiError::invalid_fields(field_errors(&errors)).into_response())?;
+        Ok(Self(value))
+    }
+}
+
+/// Flattens `validator`'s nested report into `field -> messages`; nested
+/// structs and lists use dotted paths (`items.0.name`).
+pub fn field_errors(errors: &ValidationErrors) -> FieldErrors {
+    let mut out = FieldErrors::new();
+    collect(errors, "", &mut out);
+    out
+}
+
+fn collect(errors: &ValidationErrors, prefix: &str, out: &mut FieldErrors) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{prefix}.{field}")
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                out.entry(path)
+                    .or_default()
+                    .extend(errors.iter().map(|err| {
+                        err.message
+                            .as_ref()
+                            .map_or_else(|| err.code.to_string(), ToString::to_string)
+                    }));
+            }
+            ValidationErrorsKind::Struct(nested) => collect(nested, &path, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect(nested, &format!("{path}.{index}"), out);
+                }
+            }
+        }
+    }
+}
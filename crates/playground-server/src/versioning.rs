@@ -0,0 +1,129 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    Router,
+    extract::{OriginalUri, Request, State},
+    http::{HeaderName, HeaderValue, header},
+    middleware::{self, Next},
+    response::Response,
+};
+use tracing::debug;
+
+/// Prefix of the current API version. Links the server generates (file
+/// URLs, `Location` headers) point here.
+pub const CURRENT: &str = "/v1";
+
+/// When the unversioned routes were deprecated in favour of `/v1`
+/// (2026-10-16, Unix seconds).
+const LEGACY_DEPRECATED_SINCE: u64 = 1_792_108_800;
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// How a deprecated version announces itself (RFC 9745 `Deprecation`,
+/// RFC 8594 `Sunset` and a `successor-version` link).
+#[derive(Debug, Clone, Copy)]
+pub struct Deprecation {
+    pub since: SystemTime,
+    /// When the version stops working, if that has been decided.
+    pub sunset: Option<SystemTime>,
+    /// Prefix of the version clients should move to.
+    pub successor: &'static str,
+}
+
+impl Deprecation {
+    /// The routes served without a version prefix, kept so clients from
+    /// before `/v1` keep working while they migrate.
+    pub fn legacy() -> Self {
+        Self {
+            since: UNIX_EPOCH + Duration::from_secs(LEGACY_DEPRECATED_SINCE),
+            sunset: None,
+            successor: CURRENT,
+        }
+    }
+}
+
+/// One version of the API: its routes (with their auth and limits already
+/// applied) and the prefix they are mounted under. `""` mounts at the root.
+pub struct ApiVersion {
+    prefix: &'static str,
+    routes: Router,
+    deprecation: Option<Deprecation>,
+}
+
+impl ApiVersion {
+    pub fn new(prefix: &'static str, routes: Router) -> Self {
+        Self {
+            prefix,
+            routes,
+            deprecation: None,
+        }
+    }
+
+    pub fn deprecated(mut self, deprecation: Deprecation) -> Self {
+        self.deprecation = Some(deprecation);
+        self
+    }
+}
+
+/// Mounts every version on `app`. Layers added to the result afterwards
+/// (state, metrics, logging, …) are shared by all versions; a future `/v2`
+/// only needs its own router and an entry here.
+pub fn mount(app: Router, versions: impl IntoIterator<Item = ApiVersion>) -> Router {
+    versions.into_iter().fold(app, |app, version| {
+        let routes = match version.deprecation {
+            Some(deprecation) => version.routes.layer(middleware::from_fn_with_state(
+                (version.prefix, deprecation),
+                deprecation_headers,
+            )),
+            None => version.routes,
+        };
+        if version.prefix.is_empty() {
+            app.merge(routes)
+        } else {
+            app.nest(version.prefix, routes)
+        }
+    })
+}
+
+async fn deprecation_headers(
+    State((prefix, deprecation)): State<(&'static str, Deprecation)>,
+    OriginalUri(uri): OriginalUri,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = uri.path();
+    let version = if prefix.is_empty() {
+        "unversioned"
+    } else {
+        prefix
+    };
+    debug!(%path, version, "deprecated API version used");
+    metrics::counter!("http_deprecated_requests_total", "version" => version).increment(1);
+
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    let since = deprecation
+        .since
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    headers.insert(
+        DEPRECATION,
+        HeaderValue::try_from(format!("@{since}")).expect("a number is a valid header value"),
+    );
+    if let Some(sunset) = deprecation.sunset
+        && let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(sunset))
+    {
+        headers.insert(SUNSET, value);
+    }
+    let successor = format!(
+        "<{}{}>; rel=\"successor-version\"",
+        deprecation.successor,
+        path.strip_prefix(prefix).unwrap_or(path)
+    );
+    if let Ok(value) = HeaderValue::from_str(&successor) {
+        headers.append(header::LINK, value);
+    }
+    response
+}
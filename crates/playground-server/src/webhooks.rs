@@ -0,0 +1,494 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{Json, extract::State, http::HeaderName};
+use hmac::{Hmac, Mac};
+use libsql::Row;
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::{
+    sync::{Notify, broadcast::error::RecvError, watch},
+    task::JoinSet,
+};
+use tracing::{Instrument, error, info, info_span, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{Admin, RequireRole},
+    config::WebhookConfig,
+    db::DbPool,
+    error::ApiError,
+    events::{AppEvent, EventBus},
+    filters::{FilterField, FilterQuery, SqlFilter},
+    pagination::{PageQuery, PageRequest, Paginated},
+    queue::{backoff_delay, poll_worker},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const WEBHOOK_ID_HEADER: HeaderName = HeaderName::from_static("x-webhook-id");
+const WEBHOOK_EVENT_HEADER: HeaderName = HeaderName::from_static("x-webhook-event");
+const WEBHOOK_TIMESTAMP_HEADER: HeaderName = HeaderName::from_static("x-webhook-timestamp");
+const WEBHOOK_SIGNATURE_HEADER: HeaderName = HeaderName::from_static("x-webhook-signature");
+
+/// Attempts per delivery before it is marked `failed`.
+const MAX_ATTEMPTS: u32 = 8;
+/// Delay before the first retry; doubles with every failed attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30 * 60);
+/// How long a subscriber gets to answer one attempt.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delivery workers; a slow subscriber only holds up one of them.
+const WORKERS: usize = 2;
+/// Longest subscriber error kept in the delivery log.
+const MAX_ERROR_LEN: usize = 500;
+
+/// A row of the `webhook_deliveries` table. `status` is one of `pending`,
+/// `sending`, `delivered` or `failed`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DeliveryRecord {
+    pub id: i64,
+    pub url: String,
+    pub event_id: i64,
+    pub event_kind: String,
+    pub status: String,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, if the subscriber answered.
+    pub response_status: Option<u16>,
+    /// Why the last attempt failed.
+    pub error: Option<String>,
+    /// Earliest time of the next attempt while `pending`.
+    pub next_attempt_at: String,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+}
+
+const DELIVERY_COLUMNS: &str = "id, url, event_id, event_kind, status, attempts, \
+     response_status, error, next_attempt_at, created_at, delivered_at";
+/// Columns `GET /admin/webhooks/deliveries?sort=` accepts.
+const DELIVERY_SORT_COLUMNS: &[&str] = &["id", "event_id", "status", "attempts", "created_at"];
+/// Filters `GET /admin/webhooks/deliveries` accepts.
+const DELIVERY_FILTERS: &[FilterField] = &[
+    FilterField::text("url", "url"),
+    FilterField::text("event_kind", "event_kind"),
+    FilterField::text("status", "status"),
+    FilterField::integer("event_id", "event_id"),
+    FilterField::timestamp("created", "created_at"),
+];
+
+/// What the worker needs to send one delivery.
+struct Claimed {
+    id: i64,
+    url: String,
+    event_kind: String,
+    payload: String,
+    attempts: u32,
+}
+
+/// Why an attempt failed, and what the subscriber answered if it did.
+struct AttemptError {
+    response_status: Option<u16>,
+    message: String,
+    /// Retrying cannot help (the subscriber was removed from the config), so
+    /// the delivery fails for good right away.
+    fatal: bool,
+}
+
+/// Outbound webhooks: application events POSTed to the subscribers in
+/// `[[webhooks]]`. Every matching event becomes a row in
+/// `webhook_deliveries` first, so deliveries survive restarts, and is then
+/// sent by a worker, retried with exponential backoff until it succeeds or
+/// runs out of attempts. Cheap to clone.
+///
+/// Requests carry the event as JSON ([`AppEvent`]) and these headers:
+///
+/// - `x-webhook-id`: the delivery id, the same on every retry;
+/// - `x-webhook-event`: the event kind;
+/// - `x-webhook-timestamp`: Unix seconds when the attempt was made;
+/// - `x-webhook-signature`: `sha256=` and the hex HMAC-SHA256 of
+///   `{timestamp}.{body}` keyed with the subscriber's secret. Covering the
+///   timestamp lets receivers reject replayed requests.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    pool: DbPool,
+    client: reqwest::Client,
+    subscribers: Arc<[WebhookConfig]>,
+    /// Wakes an idle worker as soon as a delivery is queued.
+    notify: Arc<Notify>,
+}
+
+impl WebhookDispatcher {
+    /// Checks every subscriber's URL and secret.
+    pub fn new(pool: DbPool, subscribers: Vec<WebhookConfig>) -> anyhow::Result<Self> {
+        for subscriber in &subscribers {
+            subscriber.validate()?;
+        }
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("simple-http-server/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            pool,
+            client,
+            subscribers: subscribers.into(),
+            notify: Arc::new(Notify::new()),
+        })
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    fn from_row(row: &Row) -> Result<DeliveryRecord, libsql::Error> {
+        Ok(DeliveryRecord {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            event_id: row.get(2)?,
+            event_kind: row.get(3)?,
+            status: row.get(4)?,
+            attempts: row.get(5)?,
+            response_status: row.get::<Option<u32>>(6)?.map(|status| status as u16),
+            error: row.get(7)?,
+            next_attempt_at: row.get(8)?,
+            created_at: row.get(9)?,
+            delivered_at: row.get(10)?,
+        })
+    }
+
+    /// Deliveries left `sending` by a crash or an expired drain go back to
+    /// `pending`. Call before [`Self::start`].
+    pub async fn recover(&self) -> Result<u64, libsql::Error> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE webhook_deliveries SET status = 'pending', updated_at = CURRENT_TIMESTAMP \
+             WHERE status = 'sending'",
+            (),
+        )
+        .await
+    }
+
+    /// Queues one delivery of `event` per interested subscriber.
+    async fn enqueue(&self, event: &AppEvent) -> Result<(), libsql::Error> {
+        let urls: Vec<&str> = self
+            .subscribers
+            .iter()
+            .filter(|subscriber| subscriber.wants(event.kind))
+            .map(|subscriber| subscriber.url.as_str())
+            .collect();
+        if urls.is_empty() {
+            return Ok(());
+        }
+        let payload = serde_json::to_string(event).unwrap_or_default();
+        let conn = self.pool.get().await?;
+        for url in urls {
+            conn.execute(
+                "INSERT INTO webhook_deliveries (url, event_id, event_kind, payload) \
+                 VALUES (?1, ?2, ?3, ?4)",
+                libsql::params![url, event.id as i64, event.kind, payload.as_str()],
+            )
+            .await?;
+            self.notify.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Marks the oldest due delivery `sending` and returns it. A single
+    /// statement, so two workers never claim the same one.
+    async fn claim(&self) -> Result<Option<Claimed>, libsql::Error> {
+        let conn = self.pool.get().await?;
+        let mut rows = conn
+            .query(
+                "UPDATE webhook_deliveries SET status = 'sending', attempts = attempts + 1, \
+                 updated_at = CURRENT_TIMESTAMP \
+                 WHERE id = (SELECT id FROM webhook_deliveries WHERE status = 'pending' \
+                 AND next_attempt_at <= CURRENT_TIMESTAMP ORDER BY id LIMIT 1) \
+                 RETURNING id, url, event_kind, payload, attempts",
+                (),
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(Claimed {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                event_kind: row.get(2)?,
+                payload: row.get(3)?,
+                attempts: row.get(4)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    async fn succeed(&self, id: i64, response_status: u16) -> Result<(), libsql::Error> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE webhook_deliveries SET status = 'delivered', response_status = ?2, \
+             error = NULL, delivered_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP \
+             WHERE id = ?1",
+            libsql::params![id, response_status],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Records a failed attempt: back to `pending` after a backoff delay, or
+    /// `failed` for good once the attempts ran out or the error is fatal.
+    /// `true` if it will retry.
+    async fn fail(&self, delivery: &Claimed, err: &AttemptError) -> Result<bool, libsql::Error> {
+        let retry = !err.fatal && delivery.attempts < MAX_ATTEMPTS;
+        let message: String = err.message.chars().take(MAX_ERROR_LEN).collect();
+        let conn = self.pool.get().await?;
+        if retry {
+            let delay = backoff_delay(RETRY_BASE_DELAY, RETRY_MAX_DELAY, delivery.attempts);
+            conn.execute(
+                "UPDATE webhook_deliveries SET status = 'pending', response_status = ?2, \
+                 error = ?3, next_attempt_at = datetime('now', ?4), \
+                 updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                libsql::params![
+                    delivery.id,
+                    err.response_status,
+                    message,
+                    format!("+{} seconds", delay.as_secs())
+                ],
+            )
+            .await?;
+        } else {
+            conn.execute(
+                "UPDATE webhook_deliveries SET status = 'failed', response_status = ?2, \
+                 error = ?3, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                libsql::params![delivery.id, err.response_status, message],
+            )
+            .await?;
+        }
+        Ok(retry)
+    }
+
+    /// One page of the deliveries matching `filter` in `order_by` order (an
+    /// already validated `ORDER BY` clause), plus how many match in total.
+    pub async fn list(
+        &self,
+        filter: &SqlFilter,
+        order_by: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<DeliveryRecord>, u64), libsql::Error> {
+        let conn = self.pool.get().await?;
+        let where_clause = filter.where_clause();
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT {DELIVERY_COLUMNS} FROM webhook_deliveries {where_clause} \
+                     ORDER BY {order_by} LIMIT ? OFFSET ?"
+                ),
+                filter.params([limit.into(), offset.into()]),
+            )
+            .await?;
+        let mut deliveries = Vec::new();
+        while let Some(row) = rows.next().await? {
+            deliveries.push(Self::from_row(&row)?);
+        }
+
+        let mut rows = conn
+            .query(
+                &format!("SELECT COUNT(*) FROM webhook_deliveries {where_clause}"),
+                filter.params([]),
+            )
+            .await?;
+        let total = match rows.next().await? {
+            Some(row) => row.get::<u64>(0)?,
+            None => 0,
+        };
+        Ok((deliveries, total))
+    }
+
+    /// Spawns the task that queues deliveries for `events` and the workers
+    /// that send them, all running until `shutdown` fires. A worker finishes
+    /// the attempt it is on before stopping, so awaiting the returned set
+    /// drains the in-flight deliveries. The workers run even without
+    /// subscribers, to settle deliveries queued by an earlier run.
+    pub fn start(&self, events: &EventBus, shutdown: watch::Receiver<()>) -> JoinSet<()> {
+        let mut set = JoinSet::new();
+        if !self.subscribers.is_empty() {
+            let dispatcher = self.clone();
+            let events = events.clone();
+            let shutdown = shutdown.clone();
+            set.spawn(
+                async move { dispatcher.listen(events, shutdown).await }
+                    .instrument(info_span!("webhook_listener")),
+            );
+        }
+        for worker in 0..WORKERS {
+            let dispatcher = self.clone();
+            let shutdown = shutdown.clone();
+            set.spawn(
+                async move { dispatcher.work(shutdown).await }
+                    .instrument(info_span!("webhook_worker", worker)),
+            );
+        }
+        set
+    }
+
+    async fn listen(self, events: EventBus, mut shutdown: watch::Receiver<()>) {
+        let mut receiver = events.subscribe();
+        let mut last_id = 0;
+        loop {
+            let event = tokio::select! {
+                event = receiver.recv() => event,
+                _ = shutdown.changed() => break,
+            };
+            let batch = match event {
+                Ok(event) => vec![event],
+                // Whatever is still in the replay buffer is not lost.
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "webhook listener lagged, replaying from history");
+                    events.since(last_id).into()
+                }
+                Err(RecvError::Closed) => break,
+            };
+            for event in batch {
+                if event.id <= last_id {
+                    continue;
+                }
+                last_id = event.id;
+                if let Err(err) = self.enqueue(&event).await {
+                    error!(error = %err, event_id = event.id, kind = event.kind, "failed to queue webhook deliveries");
+                }
+            }
+        }
+    }
+
+    async fn work(self, shutdown: watch::Receiver<()>) {
+        poll_worker(
+            "webhook delivery",
+            &self.notify,
+            shutdown,
+            || self.claim(),
+            |delivery| self.process(delivery),
+        )
+        .await;
+    }
+
+    async fn process(&self, delivery: Claimed) {
+        let span = info_span!(
+            "webhook_delivery",
+            delivery_id = delivery.id,
+            url = %delivery.url,
+            kind = %delivery.event_kind,
+            attempt = delivery.attempts,
+        );
+        async {
+            match self.send(&delivery).await {
+                Ok(status) => match self.succeed(delivery.id, status).await {
+                    Ok(()) => info!(status, "webhook delivered"),
+                    Err(err) => error!(error = %err, "failed to record webhook delivery"),
+                },
+                Err(err) => match self.fail(&delivery, &err).await {
+                    Ok(true) => warn!(error = %err.message, "webhook delivery failed; will retry"),
+                    Ok(false) => warn!(error = %err.message, "webhook delivery failed"),
+                    Err(db_err) => error!(error = %db_err, "failed to record webhook failure"),
+                },
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+
+    /// One attempt; any 2xx answer counts as delivered.
+    async fn send(&self, delivery: &Claimed) -> Result<u16, AttemptError> {
+        let Some(subscriber) = self.subscribers.iter().find(|s| s.url == delivery.url) else {
+            return Err(AttemptError {
+                response_status: None,
+                message: "webhook is no longer configured".to_owned(),
+                fatal: true,
+            });
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string();
+        let response = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_ID_HEADER, delivery.id.to_string())
+            .header(WEBHOOK_EVENT_HEADER, delivery.event_kind.as_str())
+            .header(WEBHOOK_TIMESTAMP_HEADER, timestamp.as_str())
+            .header(
+                WEBHOOK_SIGNATURE_HEADER,
+                signature(&subscriber.secret, &timestamp, &delivery.payload),
+            )
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .map_err(|err| AttemptError {
+                response_status: None,
+                message: err.to_string(),
+                fatal: false,
+            })?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(status.as_u16());
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(AttemptError {
+            response_status: Some(status.as_u16()),
+            message: format!("subscriber answered {status}: {}", body.trim()),
+            fatal: false,
+        })
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}`.
+fn signature(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    let digest = mac.finalize().into_bytes();
+    let mut signature = String::with_capacity(7 + digest.len() * 2);
+    signature.push_str("sha256=");
+    for byte in digest {
+        signature.push_str(&format!("{byte:02x}"));
+    }
+    signature
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/deliveries",
+    tag = "admin",
+    security(("bearer" = [])),
+    params(
+        PageQuery,
+        ("url" = Option<String>, Query, description = "Exact URL, `*` as a wildcard"),
+        ("event_kind" = Option<String>, Query, description = "Exact kind, `*` as a wildcard"),
+        ("status" = Option<String>, Query, description = "`pending`, `sending`, `delivered` or `failed`"),
+        ("event_id" = Option<i64>, Query),
+        ("created_after" = Option<String>, Query, description = "Date or date-time, inclusive"),
+        ("created_before" = Option<String>, Query, description = "Date or date-time, exclusive")
+    ),
+    responses(
+        (status = 200, body = Paginated<DeliveryRecord>),
+        (status = 400, description = "Invalid page, sort column or filter"),
+        (status = 401),
+        (status = 403, description = "Caller is not an admin")
+    )
+)]
+pub async fn list_deliveries(
+    _admin: RequireRole<Admin>,
+    State(webhooks): State<WebhookDispatcher>,
+    page: PageRequest,
+    filters: FilterQuery,
+) -> Result<Json<Paginated<DeliveryRecord>>, ApiError> {
+    // Newest first unless asked otherwise.
+    let order_by = page.order_by(DELIVERY_SORT_COLUMNS, "id DESC")?;
+    let filter = filters.to_sql(DELIVERY_FILTERS)?;
+    let (deliveries, total) = webhooks
+        .list(&filter, &order_by, page.limit(), page.offset())
+        .await
+        .map_err(|err| ApiError::internal("webhook delivery log failure", err))?;
+    Ok(Json(page.into_page(deliveries, total)))
+}
@@ -0,0 +1,58 @@
+//! Custo de codificar uma captura em cada formato do `--format` (e em cada
+//! nível do `--png-compression`), sem tocar no disco.
+//!
+//! `cargo bench -p screen-capture`
+
+use std::hint::black_box;
+use std::path::Path;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use screen_capture::output::{OutputFormat, PngOptions, encode, parse_compression};
+use screenshots::image::{Rgba, RgbaImage};
+
+/// Uma "tela" 1920x1080 com o que uma captura real tem: áreas lisas
+/// (fundo, janelas), um gradiente e um trecho de "texto" com detalhe fino.
+fn screen() -> RgbaImage {
+    RgbaImage::from_fn(1920, 1080, |x, y| match (x, y) {
+        (_, 0..40) => Rgba([32, 33, 36, 255]),
+        (0..300, _) => Rgba([(x / 2) as u8, (y / 6) as u8, 120, 255]),
+        (400..1800, 200..900) if (x / 3 + y / 5) % 7 == 0 => Rgba([20, 20, 20, 255]),
+        (400..1800, 200..900) => Rgba([250, 250, 250, 255]),
+        _ => Rgba([0, 90, 160, 255]),
+    })
+}
+
+fn encoding(c: &mut Criterion) {
+    let image = screen();
+    let mut formats = Vec::new();
+    for level in ["fast", "default", "best"] {
+        let png = PngOptions {
+            compression: parse_compression(level).expect("valid compression"),
+            optimize: None,
+        };
+        formats.push((format!("png-{level}"), OutputFormat::Png(png)));
+    }
+    formats.push(("jpg".to_owned(), OutputFormat::Jpeg));
+    formats.push(("bmp".to_owned(), OutputFormat::Bmp));
+
+    let mut group = c.benchmark_group("encode");
+    group.sample_size(20);
+    group.throughput(Throughput::Elements(
+        (image.width() * image.height()) as u64,
+    ));
+    for (name, format) in formats {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &format, |b, &format| {
+            // A cópia da imagem (o `encode` consome a captura) fica fora da
+            // medição.
+            b.iter_batched(
+                || image.clone(),
+                |image| encode(black_box(image), Path::new("bench"), format).expect("encode"),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encoding);
+criterion_main!(benches);
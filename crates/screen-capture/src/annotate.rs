@@ -0,0 +1,225 @@
+//! Anotações desenhadas na captura antes de ela ser gravada (`--annotate`),
+//! para que uma captura de relatório de bug não precise de um editor:
+//!
+//! - `rect:x,y,largura,altura[,cor]`: contorno de um retângulo;
+//! - `text:x,y,'texto'[,cor]`: texto com o canto superior esquerdo em
+//!   `x,y`, sobre uma faixa escura.
+//!
+//! As coordenadas são da área de trabalho, como as de `--redact` e
+//! `--region`. A cor é um nome (`red`, `green`, `blue`, `yellow`, `orange`,
+//! `magenta`, `cyan`, `white`, `black`) ou `#rrggbb`; sem ela, vermelho.
+
+use std::str::FromStr;
+
+use crate::{DisplayCapture, Region};
+
+use crate::watermark::{Font, blend};
+
+/// Espessura do contorno dos retângulos.
+const STROKE: u32 = 3;
+/// Altura do texto, em pixels.
+const TEXT_SIZE: u32 = 16;
+/// Espaço entre o texto e a borda da faixa atrás dele.
+const PADDING: u32 = 4;
+/// Opacidade da faixa atrás do texto.
+const BACKGROUND_ALPHA: f32 = 0.6;
+const DEFAULT_COLOR: [u8; 3] = [255, 0, 0];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Annotation {
+    Rect {
+        region: Region,
+        color: [u8; 3],
+    },
+    Text {
+        x: i32,
+        y: i32,
+        text: String,
+        color: [u8; 3],
+    },
+}
+
+impl FromStr for Annotation {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Anotação inválida: {value} (use rect:x,y,largura,altura[,cor] ou text:x,y,'texto'[,cor])"
+            )
+        };
+        let (kind, spec) = value.split_once(':').ok_or_else(invalid)?;
+        match kind {
+            "rect" => {
+                let parts: Vec<&str> = spec.split(',').map(str::trim).collect();
+                let (numbers, color) = match parts.as_slice() {
+                    [x, y, w, h] => ([*x, *y, *w, *h], None),
+                    [x, y, w, h, color] => ([*x, *y, *w, *h], Some(*color)),
+                    _ => return Err(invalid()),
+                };
+                let [x, y] = [numbers[0], numbers[1]].map(str::parse::<i32>);
+                let [width, height] = [numbers[2], numbers[3]].map(str::parse::<u32>);
+                let region = match (x, y, width, height) {
+                    (Ok(x), Ok(y), Ok(width), Ok(height)) if width > 0 && height > 0 => Region {
+                        x,
+                        y,
+                        width,
+                        height,
+                    },
+                    _ => return Err(invalid()),
+                };
+                Ok(Self::Rect {
+                    region,
+                    color: color.map_or(Ok(DEFAULT_COLOR), parse_color)?,
+                })
+            }
+            "text" => {
+                let mut parts = spec.splitn(3, ',');
+                let (Some(x), Some(y), Some(rest)) = (parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(invalid());
+                };
+                let (Ok(x), Ok(y)) = (x.trim().parse(), y.trim().parse()) else {
+                    return Err(invalid());
+                };
+                let rest = rest.trim();
+                // Entre aspas, o texto pode ter vírgulas e vir seguido da
+                // cor; sem aspas, é tudo o que sobrou.
+                let (text, color) = match rest.chars().next() {
+                    Some(quote @ ('\'' | '"')) => {
+                        let end = rest[1..].find(quote).ok_or_else(invalid)? + 1;
+                        let color = match rest[end + 1..].trim() {
+                            "" => None,
+                            after => Some(after.strip_prefix(',').ok_or_else(invalid)?.trim()),
+                        };
+                        (&rest[1..end], color)
+                    }
+                    _ => (rest, None),
+                };
+                if text.is_empty() {
+                    return Err(invalid());
+                }
+                Ok(Self::Text {
+                    x,
+                    y,
+                    text: text.to_owned(),
+                    color: color.map_or(Ok(DEFAULT_COLOR), parse_color)?,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn parse_color(value: &str) -> Result<[u8; 3], String> {
+    let named = match value.to_ascii_lowercase().as_str() {
+        "red" => Some([255, 0, 0]),
+        "green" => Some([0, 200, 0]),
+        "blue" => Some([0, 90, 255]),
+        "yellow" => Some([255, 220, 0]),
+        "orange" => Some([255, 140, 0]),
+        "magenta" => Some([255, 0, 255]),
+        "cyan" => Some([0, 220, 255]),
+        "white" => Some([255, 255, 255]),
+        "black" => Some([0, 0, 0]),
+        _ => None,
+    };
+    named
+        .or_else(|| {
+            let hex = value.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+            let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+            Some([channel(0)?, channel(2)?, channel(4)?])
+        })
+        .ok_or_else(|| format!("Cor inválida: {value} (use um nome como red ou #rrggbb)"))
+}
+
+/// Desenha as anotações em `capture`; o que cai fora dela é cortado.
+pub struct Annotator {
+    annotations: Vec<Annotation>,
+    font: Font,
+}
+
+impl Annotator {
+    pub fn new(annotations: Vec<Annotation>) -> Self {
+        Self {
+            annotations,
+            font: Font::Builtin {
+                scale: TEXT_SIZE / 8,
+            },
+        }
+    }
+
+    pub fn draw(&self, capture: &mut DisplayCapture) {
+        for annotation in &self.annotations {
+            match annotation {
+                Annotation::Rect { region, color } => {
+                    let (left, top) = (
+                        i64::from(region.x) - i64::from(capture.x),
+                        i64::from(region.y) - i64::from(capture.y),
+                    );
+                    let (width, height) = (i64::from(region.width), i64::from(region.height));
+                    let stroke = i64::from(STROKE).min(width).min(height);
+                    // As quatro bordas, por dentro do retângulo.
+                    for (x, y, w, h) in [
+                        (left, top, width, stroke),
+                        (left, top + height - stroke, width, stroke),
+                        (left, top, stroke, height),
+                        (left + width - stroke, top, stroke, height),
+                    ] {
+                        fill(capture, (x, y, w, h), |pixel| {
+                            blend(pixel, *color, 1.0);
+                        });
+                    }
+                }
+                Annotation::Text { x, y, text, color } => {
+                    let rendered = self.font.render(text);
+                    let (left, top) = (
+                        i64::from(*x) - i64::from(capture.x),
+                        i64::from(*y) - i64::from(capture.y),
+                    );
+                    let padding = i64::from(PADDING);
+                    let (text_width, text_height) =
+                        (i64::from(rendered.width), i64::from(rendered.height));
+                    let area = (
+                        left,
+                        top,
+                        text_width + 2 * padding,
+                        text_height + 2 * padding,
+                    );
+                    fill(capture, area, |pixel| {
+                        blend(pixel, [0, 0, 0], BACKGROUND_ALPHA);
+                    });
+                    let (origin_x, origin_y) = (left + padding, top + padding);
+                    let (width, height) = capture.image.dimensions();
+                    for ty in 0..text_height {
+                        for tx in 0..text_width {
+                            let (px, py) = (origin_x + tx, origin_y + ty);
+                            if !(0..i64::from(width)).contains(&px)
+                                || !(0..i64::from(height)).contains(&py)
+                            {
+                                continue;
+                            }
+                            let coverage = rendered.coverage[(ty * text_width + tx) as usize];
+                            let pixel = capture.image.get_pixel_mut(px as u32, py as u32);
+                            blend(&mut pixel.0, *color, coverage);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Aplica `paint` a cada pixel de `(x, y, largura, altura)`, em
+/// coordenadas da imagem, que cai dentro dela.
+fn fill(capture: &mut DisplayCapture, area: (i64, i64, i64, i64), paint: impl Fn(&mut [u8; 4])) {
+    let (x, y, w, h) = area;
+    let (width, height) = capture.image.dimensions();
+    let (left, right) = (x.max(0), (x + w).min(i64::from(width)));
+    let (top, bottom) = (y.max(0), (y + h).min(i64::from(height)));
+    for py in top..bottom {
+        for px in left..right {
+            paint(&mut capture.image.get_pixel_mut(px as u32, py as u32).0);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Detecção de capturas em branco: toda preta ou de uma cor só, o que a
+//! captura devolve com o monitor em repouso ou, no macOS, sem a permissão
+//! de gravação de tela.
+
+use std::str::FromStr;
+
+use crate::RgbaImage;
+
+/// Diferença em um canal até a qual o pixel conta como da mesma cor.
+const CHANNEL_TOLERANCE: u8 = 8;
+/// Só um pixel a cada `SAMPLE_STEP` em cada direção é comparado, como na
+/// detecção de mudança.
+const SAMPLE_STEP: u32 = 4;
+
+/// O que fazer com uma captura em branco (`--on-blank`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnBlank {
+    /// Grava mesmo assim, com um aviso.
+    #[default]
+    Warn,
+    /// Captura de novo algumas vezes; se continuar em branco, avisa e
+    /// grava.
+    Retry,
+    /// Não grava, e conta como falha daquele monitor.
+    Skip,
+}
+
+impl FromStr for OnBlank {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "warn" => Ok(Self::Warn),
+            "retry" => Ok(Self::Retry),
+            "skip" => Ok(Self::Skip),
+            other => Err(format!(
+                "Valor inválido para --on-blank: {other} (use warn, retry ou skip)"
+            )),
+        }
+    }
+}
+
+/// Se todos os pixels amostrados de `image` têm a mesma cor.
+pub fn is_blank(image: &RgbaImage) -> bool {
+    let Some(first) = image.pixels().next() else {
+        return true;
+    };
+    let (width, height) = image.dimensions();
+    (0..height).step_by(SAMPLE_STEP as usize).all(|y| {
+        (0..width).step_by(SAMPLE_STEP as usize).all(|x| {
+            let pixel = image.get_pixel(x, y);
+            (0..3).all(|c| pixel[c].abs_diff(first[c]) <= CHANNEL_TOLERANCE)
+        })
+    })
+}
@@ -0,0 +1,66 @@
+//! Detecção de mudança no modo intervalo: capturas quase iguais à anterior
+//! do mesmo monitor não são gravadas.
+
+use std::collections::HashMap;
+
+use crate::{DisplayCapture, RgbaImage};
+
+/// Diferença em um canal abaixo da qual o pixel conta como igual, para
+/// que ruído de compressão ou dithering não pareça mudança.
+const CHANNEL_TOLERANCE: u8 = 16;
+/// Só um pixel a cada `SAMPLE_STEP` em cada direção é comparado: o bastante
+/// para perceber uma janela mudando sem varrer 8 milhões de pixels.
+const SAMPLE_STEP: u32 = 4;
+
+/// A última captura gravada de cada monitor.
+pub struct ChangeDetector {
+    /// Porcentagem mínima de pixels diferentes (`--min-change`).
+    min_change: f64,
+    previous: HashMap<u32, RgbaImage>,
+}
+
+impl ChangeDetector {
+    pub fn new(min_change: f64) -> Self {
+        Self {
+            min_change,
+            previous: HashMap::new(),
+        }
+    }
+
+    /// Se `capture` difere o bastante da última guardada do mesmo monitor;
+    /// quando difere, passa a ser a nova referência. A primeira de cada
+    /// monitor, ou uma de outra resolução, sempre conta como nova.
+    pub fn is_new(&mut self, capture: &DisplayCapture) -> bool {
+        let changed = self
+            .previous
+            .get(&capture.display_id)
+            .is_none_or(|previous| changed_percent(previous, &capture.image) >= self.min_change);
+        if changed {
+            self.previous
+                .insert(capture.display_id, capture.image.clone());
+        }
+        changed
+    }
+}
+
+/// Porcentagem dos pixels amostrados que mudaram entre `a` e `b`.
+fn changed_percent(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    if a.dimensions() != b.dimensions() {
+        return 100.0;
+    }
+    let (width, height) = a.dimensions();
+    let (mut sampled, mut changed) = (0u64, 0u64);
+    for y in (0..height).step_by(SAMPLE_STEP as usize) {
+        for x in (0..width).step_by(SAMPLE_STEP as usize) {
+            let (pa, pb) = (a.get_pixel(x, y), b.get_pixel(x, y));
+            sampled += 1;
+            if (0..3).any(|c| pa[c].abs_diff(pb[c]) > CHANNEL_TOLERANCE) {
+                changed += 1;
+            }
+        }
+    }
+    if sampled == 0 {
+        return 0.0;
+    }
+    changed as f64 * 100.0 / sampled as f64
+}
@@ -0,0 +1,31 @@
+//! Cópia das capturas para a área de transferência do sistema (`arboard`).
+
+use std::borrow::Cow;
+
+use crate::RgbaImage;
+
+use crate::error::Result;
+
+/// A área de transferência aberta por esta execução.
+///
+/// No Linux o conteúdo pertence ao processo: ao sair, ele é entregue ao
+/// gerenciador de área de transferência do ambiente gráfico, se houver um;
+/// sem gerenciador, a imagem some junto com o processo.
+pub struct Clipboard(arboard::Clipboard);
+
+impl Clipboard {
+    pub fn open() -> Result<Self> {
+        Ok(Self(arboard::Clipboard::new()?))
+    }
+
+    /// Substitui o conteúdo da área de transferência por `image`.
+    pub fn copy(&mut self, image: &RgbaImage) -> Result<()> {
+        let (width, height) = image.dimensions();
+        self.0.set_image(arboard::ImageData {
+            width: width as usize,
+            height: height as usize,
+            bytes: Cow::Borrowed(image.as_raw()),
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+//! Subcomando `compare <dir-a> <dir-b>`: relatório HTML com as capturas de
+//! duas execuções lado a lado e os pixels que mudaram destacados, monitor a
+//! monitor, para conferir visualmente uma nova versão da interface.
+//!
+//! As capturas são pareadas pelo monitor do `.json` de `--sidecar` ou, sem
+//! ele, pelo nome do arquivo. Com várias do mesmo monitor num diretório
+//! (modo intervalo), vale a mais recente.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use maud::{DOCTYPE, Markup, html};
+use screenshots::image::{self, Rgba};
+use tracing::info;
+
+use crate::{
+    RgbaImage,
+    error::{Result, ScreenshotError, io},
+    metadata::{self, Metadata},
+};
+
+/// Diferença em um canal acima da qual o pixel conta como mudado, como na
+/// detecção de mudança.
+const CHANNEL_TOLERANCE: u8 = 16;
+/// Cor dos pixels mudados na imagem de diferença.
+const HIGHLIGHT: Rgba<u8> = Rgba([255, 0, 64, 255]);
+/// Extensões lidas como captura; as de `--format`.
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2rem; }
+th, td { text-align: left; padding: .4rem .6rem; border-bottom: 1px solid #eee; }
+.same { color: #2a7d2a; }
+.changed, .missing { color: #c0392b; font-weight: 600; }
+.pair { display: grid; grid-template-columns: repeat(3, 1fr); gap: 1rem; margin-bottom: 2.5rem; }
+.pair figure { margin: 0; }
+.pair img { width: 100%; border: 1px solid #ddd; }
+.pair figcaption { color: #666; font-size: .85rem; }
+";
+
+/// Opções de `compare`.
+pub struct Options {
+    pub dir_a: PathBuf,
+    pub dir_b: PathBuf,
+    /// Diretório do relatório (`index.html` e as imagens).
+    pub out: PathBuf,
+    /// Porcentagem de pixels mudados acima da qual o monitor conta como
+    /// diferente.
+    pub threshold: f64,
+}
+
+/// O resultado de um monitor.
+enum Outcome {
+    /// Dentro do limite; a porcentagem mudada.
+    Same(f64),
+    /// Acima do limite; a porcentagem mudada e a imagem de diferença.
+    Changed(f64, PathBuf),
+    /// Resoluções diferentes, sem como comparar pixel a pixel.
+    Resized((u32, u32), (u32, u32)),
+    /// Só um dos diretórios tem captura desse monitor.
+    OnlyIn(&'static str),
+}
+
+struct Entry {
+    key: String,
+    /// As cópias no diretório do relatório.
+    a: Option<PathBuf>,
+    b: Option<PathBuf>,
+    outcome: Outcome,
+}
+
+impl Entry {
+    fn differs(&self) -> bool {
+        !matches!(self.outcome, Outcome::Same(_))
+    }
+}
+
+/// Compara os diretórios de `options` e grava o relatório. Falha com
+/// [`ScreenshotError::Mismatch`] se algum monitor diferir.
+pub fn run(options: &Options) -> Result<()> {
+    let a = captures(&options.dir_a)?;
+    let b = captures(&options.dir_b)?;
+    std::fs::create_dir_all(&options.out).map_err(io(&options.out))?;
+
+    let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    let mut entries = Vec::new();
+    for key in keys {
+        let copy_a = a
+            .get(key)
+            .map(|path| copy(path, &options.out, key, "a"))
+            .transpose()?;
+        let copy_b = b
+            .get(key)
+            .map(|path| copy(path, &options.out, key, "b"))
+            .transpose()?;
+        let outcome = match (a.get(key), b.get(key)) {
+            (Some(path_a), Some(path_b)) => {
+                let diff = options.out.join(format!("{}-diff.png", file_key(key)));
+                compare(path_a, path_b, &diff, options.threshold)?
+            }
+            (Some(_), None) => Outcome::OnlyIn("A"),
+            _ => Outcome::OnlyIn("B"),
+        };
+        entries.push(Entry {
+            key: key.clone(),
+            a: copy_a,
+            b: copy_b,
+            outcome,
+        });
+    }
+
+    let index = options.out.join("index.html");
+    let page = report(options, &entries).into_string();
+    std::fs::write(&index, page).map_err(io(&index))?;
+    info!(path = %index.display(), "Relatório salvo");
+
+    match entries.iter().filter(|entry| entry.differs()).count() {
+        0 => Ok(()),
+        differ => Err(ScreenshotError::Mismatch {
+            differ,
+            total: entries.len(),
+        }),
+    }
+}
+
+/// A captura mais recente de cada monitor em `dir`.
+fn captures(dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let mut latest: BTreeMap<String, (SystemTime, PathBuf)> = BTreeMap::new();
+    for entry in std::fs::read_dir(dir).map_err(io(dir))? {
+        let path = entry.map_err(io(dir))?.path();
+        let is_image = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        if !is_image || !path.is_file() {
+            continue;
+        }
+        let modified = path
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map_err(io(&path))?;
+        let key = std::fs::read(metadata::sidecar_path(&path))
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Metadata>(&json).ok())
+            .map(|metadata| format!("monitor {}", metadata.display))
+            .unwrap_or_else(|| {
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into()
+            });
+        if latest.get(&key).is_none_or(|(seen, _)| modified > *seen) {
+            latest.insert(key, (modified, path));
+        }
+    }
+    Ok(latest
+        .into_iter()
+        .map(|(key, (_, path))| (key, path))
+        .collect())
+}
+
+/// `key` sem o que não pode ir num nome de arquivo.
+fn file_key(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Copia `path` para o relatório, para que ele continue válido depois que
+/// os diretórios comparados sumirem (ex.: artefato de CI).
+fn copy(path: &Path, out: &Path, key: &str, side: &str) -> Result<PathBuf> {
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    let target = out.join(format!("{}-{side}.{ext}", file_key(key)));
+    std::fs::copy(path, &target).map_err(io(&target))?;
+    Ok(target)
+}
+
+fn open(path: &Path) -> Result<RgbaImage> {
+    image::open(path)
+        .map(|image| image.to_rgba8())
+        .map_err(|source| ScreenshotError::Image {
+            path: path.to_owned(),
+            source,
+        })
+}
+
+/// Compara as duas capturas e, se diferirem além de `threshold`, grava em
+/// `diff` a de B esmaecida com os pixels mudados em destaque.
+fn compare(a: &Path, b: &Path, diff: &Path, threshold: f64) -> Result<Outcome> {
+    let (a, b) = (open(a)?, open(b)?);
+    if a.dimensions() != b.dimensions() {
+        return Ok(Outcome::Resized(a.dimensions(), b.dimensions()));
+    }
+    let mut highlighted = RgbaImage::new(b.width(), b.height());
+    let mut changed = 0u64;
+    for ((pa, pb), out) in a.pixels().zip(b.pixels()).zip(highlighted.pixels_mut()) {
+        *out = if (0..3).any(|c| pa[c].abs_diff(pb[c]) > CHANNEL_TOLERANCE) {
+            changed += 1;
+            HIGHLIGHT
+        } else {
+            // Esmaecida, para o destaque aparecer sem perder o contexto.
+            Rgba([pb[0] / 4 + 160, pb[1] / 4 + 160, pb[2] / 4 + 160, 255])
+        };
+    }
+    let pixels = u64::from(b.width()) * u64::from(b.height());
+    let percent = if pixels == 0 {
+        0.0
+    } else {
+        changed as f64 * 100.0 / pixels as f64
+    };
+    // Nenhum pixel mudado é sempre igual, mesmo com `--threshold 0`.
+    if changed == 0 || percent <= threshold {
+        return Ok(Outcome::Same(percent));
+    }
+    highlighted
+        .save(diff)
+        .map_err(|source| ScreenshotError::Image {
+            path: diff.to_owned(),
+            source,
+        })?;
+    Ok(Outcome::Changed(percent, diff.to_owned()))
+}
+
+/// O caminho de `path` relativo ao relatório, que fica no mesmo diretório.
+fn href(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn figure(caption: &str, path: Option<&Path>) -> Markup {
+    html! {
+        figure {
+            @if let Some(path) = path {
+                a href=(href(path)) { img src=(href(path)) alt=(caption) loading="lazy"; }
+            }
+            figcaption { (caption) }
+        }
+    }
+}
+
+fn report(options: &Options, entries: &[Entry]) -> Markup {
+    let differ = entries.iter().filter(|entry| entry.differs()).count();
+    html! {
+        (DOCTYPE)
+        html lang="pt-BR" {
+            head {
+                meta charset="utf-8";
+                title { "Comparação de capturas" }
+                style { (STYLE) }
+            }
+            body {
+                h1 { "Comparação de capturas" }
+                p {
+                    "A: " code { (options.dir_a.display()) } br;
+                    "B: " code { (options.dir_b.display()) } br;
+                    (differ) " de " (entries.len()) " monitores diferem"
+                    @if options.threshold > 0.0 { " (limite " (options.threshold) "%)" }
+                }
+                table {
+                    thead { tr { th { "Monitor" } th { "Resultado" } } }
+                    tbody {
+                        @for entry in entries {
+                            tr {
+                                td { a href={ "#" (file_key(&entry.key)) } { (entry.key) } }
+                                td { (outcome(&entry.outcome)) }
+                            }
+                        }
+                    }
+                }
+                @for entry in entries {
+                    h2 id=(file_key(&entry.key)) { (entry.key) }
+                    div.pair {
+                        (figure("A", entry.a.as_deref()))
+                        (figure("B", entry.b.as_deref()))
+                        @if let Outcome::Changed(_, diff) = &entry.outcome {
+                            (figure("Diferença", Some(diff)))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn outcome(outcome: &Outcome) -> Markup {
+    html! {
+        @match outcome {
+            Outcome::Same(percent) => span.same { "igual" @if *percent > 0.0 { (format!(" ({percent:.2}% mudaram)")) } },
+            Outcome::Changed(percent, _) => span.changed { (format!("{percent:.2}% dos pixels mudaram")) },
+            Outcome::Resized((wa, ha), (wb, hb)) => span.changed { (format!("resolução mudou: {wa}x{ha} -> {wb}x{hb}")) },
+            Outcome::OnlyIn(side) => span.missing { "só em " (side) },
+        }
+    }
+}
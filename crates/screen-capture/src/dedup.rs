@@ -0,0 +1,131 @@
+//! Deduplicação das capturas gravadas (`--dedup`): um índice com o hash
+//! perceptual de cada arquivo no diretório de saída, para que uma tela
+//! parada horas a fio não vire centenas de arquivos iguais.
+//!
+//! Ao contrário de `--min-change`, que só compara com a captura anterior
+//! do mesmo monitor, aqui vale todo o histórico, mesmo de execuções
+//! anteriores.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use screenshots::image::imageops::{self, FilterType};
+
+use crate::{
+    RgbaImage,
+    error::{Result, io},
+};
+
+/// Arquivo do índice, dentro do diretório de saída. Começa com ponto para
+/// não casar com o modelo de nome nem aparecer em listagens.
+const INDEX_FILE: &str = ".screenshots-dedup.json";
+/// Distância de Hamming padrão (em bits, de 64) até a qual duas capturas
+/// contam como iguais: cobre um relógio na barra de tarefas ou a marca
+/// d'água, não uma janela nova.
+pub const DEFAULT_DISTANCE: u32 = 4;
+
+/// O que fazer com uma captura igual a uma já gravada.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupMode {
+    /// Não grava; a captura aponta para o arquivo existente.
+    Skip,
+    /// Grava um hard link para o arquivo existente, com o nome novo: o
+    /// histórico continua completo sem ocupar espaço.
+    Link,
+}
+
+impl FromStr for DedupMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "skip" => Ok(Self::Skip),
+            "link" => Ok(Self::Link),
+            other => Err(format!(
+                "Valor inválido para --dedup: {other} (use skip ou link)"
+            )),
+        }
+    }
+}
+
+/// O índice, compartilhado pelas threads que gravam cada monitor.
+pub struct Dedup {
+    pub mode: DedupMode,
+    max_distance: u32,
+    dir: PathBuf,
+    /// Nome do arquivo (relativo a `dir`) → hash.
+    hashes: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Dedup {
+    /// Lê o índice de `dir`, se houver, esquecendo os arquivos que não
+    /// existem mais (apagados à mão ou por `--keep-last`).
+    pub fn open(dir: &Path, mode: DedupMode, max_distance: u32) -> Result<Self> {
+        let path = dir.join(INDEX_FILE);
+        let mut hashes: BTreeMap<String, u64> = match std::fs::read(&path) {
+            // Um índice corrompido só faz as capturas serem gravadas de
+            // novo; não vale parar por isso.
+            Ok(json) => serde_json::from_slice(&json).unwrap_or_default(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(io(path)(err)),
+        };
+        hashes.retain(|name, _| dir.join(name).is_file());
+        Ok(Self {
+            mode,
+            max_distance,
+            dir: dir.to_owned(),
+            hashes: Mutex::new(hashes),
+        })
+    }
+
+    /// O arquivo já gravado mais parecido com `hash`, se algum estiver
+    /// dentro da distância.
+    pub fn find(&self, hash: u64) -> Option<PathBuf> {
+        let hashes = self.hashes.lock().unwrap_or_else(|err| err.into_inner());
+        hashes
+            .iter()
+            .map(|(name, known)| ((known ^ hash).count_ones(), name))
+            .filter(|(distance, _)| *distance <= self.max_distance)
+            .min()
+            .map(|(_, name)| self.dir.join(name))
+            .filter(|path| path.is_file())
+    }
+
+    pub fn insert(&self, path: &Path, hash: u64) {
+        if let Some(name) = path.file_name() {
+            self.hashes
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .insert(name.to_string_lossy().into_owned(), hash);
+        }
+    }
+
+    /// Grava o índice em disco.
+    pub fn save(&self) -> Result<()> {
+        let path = self.dir.join(INDEX_FILE);
+        let hashes = self.hashes.lock().unwrap_or_else(|err| err.into_inner());
+        let json = serde_json::to_vec(&*hashes).expect("index is always serializable");
+        std::fs::write(&path, json).map_err(io(path))
+    }
+}
+
+/// dHash de 64 bits: a imagem em tons de cinza reduzida para 9x8, um bit
+/// por par de pixels vizinhos dizendo se o da esquerda é mais claro.
+/// Resiste a compressão, escala e pequenas mudanças.
+pub fn hash(image: &RgbaImage) -> u64 {
+    let small = imageops::grayscale(&imageops::resize(image, 9, 8, FilterType::Triangle));
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
@@ -0,0 +1,93 @@
+//! Erros do binário `screenshots` e como cada um vira um
+//! [`PlaygroundError`], que decide o código de saída; assim scripts sabem se
+//! nada, parte ou tudo foi capturado.
+
+use std::path::PathBuf;
+
+use playground_common::error::{self, ConfigError, PlaygroundError};
+use screenshots::image::ImageError;
+use thiserror::Error;
+
+use crate::CaptureError;
+
+pub type Result<T, E = ScreenshotError> = std::result::Result<T, E>;
+
+#[derive(Error, Debug)]
+pub enum ScreenshotError {
+    /// Argumentos inválidos; nada foi capturado.
+    #[error("{0}")]
+    Usage(String),
+    /// A configuração compartilhada (`playground.toml`, `PLAYGROUND_*`) é
+    /// inválida; nada foi capturado.
+    #[error("Configuração: {0}")]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Capture(#[from] CaptureError),
+    #[error("Erro ao gravar {}: {source}", path.display())]
+    Image {
+        path: PathBuf,
+        #[source]
+        source: ImageError,
+    },
+    #[error("Erro ao otimizar {}: {source}", path.display())]
+    Optimize {
+        path: PathBuf,
+        #[source]
+        source: oxipng::PngError,
+    },
+    #[error("Erro de E/S em {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("ffmpeg: {0}")]
+    Ffmpeg(String),
+    #[error("Área de transferência: {0}")]
+    Clipboard(#[from] arboard::Error),
+    /// A captura saiu de uma cor só (monitor em repouso, sem permissão) e
+    /// `--on-blank skip` pediu para não gravá-la.
+    #[error("Monitor {0} capturado em branco (em repouso ou sem permissão de captura?)")]
+    Blank(String),
+    #[error("Atalho global: {0}")]
+    Hotkey(String),
+    #[error("Webhook: {0}")]
+    Webhook(String),
+    #[error("Envio: {0}")]
+    Upload(String),
+    /// `compare` encontrou monitores diferentes entre as duas execuções;
+    /// o relatório diz quais.
+    #[error("{differ} de {total} monitores diferem")]
+    Mismatch { differ: usize, total: usize },
+    /// Alguns monitores foram gravados e outros não; os erros de cada um já
+    /// foram impressos.
+    #[error("{failed} de {total} capturas falharam")]
+    Partial { failed: usize, total: usize },
+}
+
+impl From<ScreenshotError> for PlaygroundError {
+    /// Argumentos e configuração inválidos saem com `2`, sucesso parcial com
+    /// `3` e a falta de monitor, área de transferência ou atalho com `4`.
+    fn from(err: ScreenshotError) -> Self {
+        match err {
+            ScreenshotError::Usage(message) => Self::Usage(message),
+            ScreenshotError::Config(err) => Self::Config(err),
+            ScreenshotError::Partial { failed, total } => Self::Partial { failed, total },
+            ScreenshotError::Capture(CaptureError::Displays(_) | CaptureError::Unsupported(_))
+            | ScreenshotError::Clipboard(_)
+            | ScreenshotError::Hotkey(_) => Self::unavailable(error::flatten(err)),
+            err => Self::Other(error::flatten(err)),
+        }
+    }
+}
+
+/// Atalho para [`ScreenshotError::Usage`].
+pub fn usage(message: impl Into<String>) -> ScreenshotError {
+    ScreenshotError::Usage(message.into())
+}
+
+/// Atalho para [`ScreenshotError::Io`] em `map_err`.
+pub fn io(path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> ScreenshotError {
+    let path = path.into();
+    move |source| ScreenshotError::Io { path, source }
+}
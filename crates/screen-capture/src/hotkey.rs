@@ -0,0 +1,245 @@
+//! Atalho de teclado global do modo `--daemon`, registrado no servidor X
+//! com `GrabKey`: o aviso chega mesmo com outra janela em foco.
+
+use std::{fmt, str::FromStr};
+
+use crate::error::{Result, ScreenshotError};
+
+/// Atalho de `--hotkey` quando não informado.
+pub const DEFAULT_HOTKEY: &str = "ctrl+alt+s";
+
+/// Teclas com nome, e o keysym X11 de cada uma. Letras e dígitos usam o
+/// próprio código ASCII (minúsculo), e `f1` a `f24` vêm em seguida a
+/// `XK_F1`.
+const NAMED_KEYS: &[(&str, u32)] = &[
+    ("print", 0xff61),
+    ("space", 0x0020),
+    ("escape", 0xff1b),
+    ("pause", 0xff13),
+    ("scroll_lock", 0xff14),
+    ("insert", 0xff63),
+    ("home", 0xff50),
+    ("end", 0xff57),
+];
+const XK_F1: u32 = 0xffbe;
+
+/// Um atalho como `ctrl+shift+s`: modificadores (`ctrl`, `shift`, `alt`,
+/// `super`) e uma tecla (letra, dígito, `f1` a `f24` ou uma das de
+/// [`NAMED_KEYS`]), sem diferença entre maiúsculas e minúsculas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotkey {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+    super_: bool,
+    key: String,
+    keysym: u32,
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.to_ascii_lowercase();
+        let mut parts: Vec<&str> = value.split('+').map(str::trim).collect();
+        let key = parts.pop().unwrap_or_default();
+        let mut hotkey = Self {
+            ctrl: false,
+            shift: false,
+            alt: false,
+            super_: false,
+            key: key.to_owned(),
+            keysym: keysym(key)
+                .ok_or_else(|| format!("Tecla desconhecida no atalho {value:?}: {key:?}"))?,
+        };
+        for modifier in parts {
+            match modifier {
+                "ctrl" | "control" => hotkey.ctrl = true,
+                "shift" => hotkey.shift = true,
+                "alt" => hotkey.alt = true,
+                "super" | "win" | "meta" => hotkey.super_ = true,
+                other => {
+                    return Err(format!(
+                        "Modificador desconhecido no atalho {value:?}: {other:?} \
+                         (use ctrl, shift, alt ou super)"
+                    ));
+                }
+            }
+        }
+        Ok(hotkey)
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "ctrl+"),
+            (self.shift, "shift+"),
+            (self.alt, "alt+"),
+            (self.super_, "super+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        f.write_str(&self.key)
+    }
+}
+
+fn keysym(key: &str) -> Option<u32> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next())
+        && c.is_ascii_alphanumeric()
+    {
+        return Some(c as u32);
+    }
+    if let Some(n) = key.strip_prefix('f').and_then(|n| n.parse::<u32>().ok())
+        && (1..=24).contains(&n)
+    {
+        return Some(XK_F1 + n - 1);
+    }
+    NAMED_KEYS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, keysym)| *keysym)
+}
+
+/// O atalho registrado; a captura dele acaba quando o valor é descartado
+/// (a conexão com o servidor X é fechada).
+pub struct HotkeyListener {
+    #[cfg(target_os = "linux")]
+    grab: x11::Grab,
+}
+
+impl HotkeyListener {
+    /// Registra `hotkey` para esta execução. Falha se outro programa já o
+    /// usa, ou fora de uma sessão X11.
+    #[cfg(target_os = "linux")]
+    pub fn grab(hotkey: &Hotkey) -> Result<Self> {
+        if std::env::var_os("XDG_SESSION_TYPE").is_some_and(|session| session == "wayland") {
+            return Err(ScreenshotError::Hotkey(
+                "atalhos globais só funcionam numa sessão X11".to_owned(),
+            ));
+        }
+        let grab = x11::Grab::new(hotkey)
+            .map_err(|err| ScreenshotError::Hotkey(format!("{hotkey}: {err}")))?;
+        Ok(Self { grab })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn grab(_hotkey: &Hotkey) -> Result<Self> {
+        Err(ScreenshotError::Hotkey(
+            "atalhos globais só funcionam no Linux (X11)".to_owned(),
+        ))
+    }
+
+    /// Bloqueia até o atalho ser pressionado.
+    #[cfg(target_os = "linux")]
+    pub fn wait(&mut self) -> Result<()> {
+        self.grab
+            .wait()
+            .map_err(|err| ScreenshotError::Hotkey(err.to_string()))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn wait(&mut self) -> Result<()> {
+        unreachable!("HotkeyListener::grab always fails off Linux")
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use xcb::{Connection, x};
+
+    use super::Hotkey;
+
+    /// Com a tecla segurada, o servidor repete o `KeyPress`; um novo só
+    /// conta depois deste intervalo, em milissegundos do servidor.
+    const REPEAT_GUARD_MS: u32 = 500;
+
+    pub struct Grab {
+        conn: Connection,
+        keycode: x::Keycode,
+        /// Horário do servidor do último `KeyPress` aceito.
+        last: Option<x::Timestamp>,
+    }
+
+    impl Grab {
+        pub fn new(hotkey: &Hotkey) -> Result<Self, Box<dyn std::error::Error>> {
+            let (conn, screen_num) = Connection::connect(None)?;
+            let setup = conn.get_setup();
+            let root = setup
+                .roots()
+                .nth(screen_num as usize)
+                .ok_or("X server without screens")?
+                .root();
+
+            // A tecla física cujo mapeamento contém o keysym pedido.
+            let (min, max) = (setup.min_keycode(), setup.max_keycode());
+            let cookie = conn.send_request(&x::GetKeyboardMapping {
+                first_keycode: min,
+                count: max - min + 1,
+            });
+            let mapping = conn.wait_for_reply(cookie)?;
+            let per_keycode = usize::from(mapping.keysyms_per_keycode()).max(1);
+            let index = mapping
+                .keysyms()
+                .iter()
+                .position(|&keysym| keysym == hotkey.keysym)
+                .ok_or("the key is not on this keyboard")?;
+            let keycode = min + (index / per_keycode) as u8;
+
+            let mut modifiers = x::ModMask::empty();
+            for (held, mask) in [
+                (hotkey.ctrl, x::ModMask::CONTROL),
+                (hotkey.shift, x::ModMask::SHIFT),
+                (hotkey.alt, x::ModMask::N1),
+                (hotkey.super_, x::ModMask::N4),
+            ] {
+                if held {
+                    modifiers |= mask;
+                }
+            }
+            // Caps Lock (LOCK) e Num Lock (N2, na maioria dos layouts) mudam o
+            // estado dos modificadores; o atalho vale com eles ligados ou não.
+            for locks in [
+                x::ModMask::empty(),
+                x::ModMask::LOCK,
+                x::ModMask::N2,
+                x::ModMask::LOCK | x::ModMask::N2,
+            ] {
+                let cookie = conn.send_request_checked(&x::GrabKey {
+                    owner_events: false,
+                    grab_window: root,
+                    modifiers: modifiers | locks,
+                    key: keycode,
+                    pointer_mode: x::GrabMode::Async,
+                    keyboard_mode: x::GrabMode::Async,
+                });
+                conn.check_request(cookie)
+                    .map_err(|_| "the hotkey is already taken by another program")?;
+            }
+            Ok(Self {
+                conn,
+                keycode,
+                last: None,
+            })
+        }
+
+        pub fn wait(&mut self) -> xcb::Result<()> {
+            loop {
+                if let xcb::Event::X(x::Event::KeyPress(event)) = self.conn.wait_for_event()?
+                    && event.detail() == self.keycode
+                {
+                    let repeated = self
+                        .last
+                        .is_some_and(|last| event.time().wrapping_sub(last) < REPEAT_GUARD_MS);
+                    self.last = Some(event.time());
+                    if !repeated {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,852 @@
+//! Captura de tela compartilhada entre o binário `screenshots` e o servidor
+//! HTTP.
+//!
+//! O crate `screenshots` devolve erros `anyhow`; aqui eles viram
+//! [`CaptureError`] para que cada chamador decida o que fazer (o binário
+//! imprime e sai, o servidor responde com um status HTTP).
+//!
+//! Os submódulos são o que o binário `screenshots` faz com cada captura;
+//! [`session::run`] junta tudo numa execução, e o binário só lê os
+//! argumentos.
+
+pub mod annotate;
+pub mod blank;
+pub mod change;
+pub mod clipboard;
+pub mod compare;
+pub mod dedup;
+pub mod error;
+pub mod hotkey;
+pub mod metadata;
+pub mod output;
+pub mod redact;
+pub mod report;
+pub mod retention;
+pub mod serve;
+pub mod session;
+pub mod timelapse;
+pub mod upload;
+pub mod watch;
+pub mod watermark;
+pub mod webhook;
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use screenshots::Screen;
+pub use screenshots::image::RgbaImage;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+/// Erros possíveis ao capturar ou salvar uma tela.
+pub enum CaptureError {
+    /// Não foi possível listar os monitores (ex.: sem servidor gráfico).
+    #[error("Failed to list displays: {0}")]
+    Displays(String),
+    /// A captura de um monitor específico falhou.
+    #[error("Failed to capture display {0}: {1}")]
+    Capture(u32, String),
+    /// Nenhum monitor corresponde ao [`DisplaySelector`] pedido.
+    #[error("No display matches {0}")]
+    NoSuchDisplay(String),
+    /// Nenhuma janela visível tem o título ou processo procurado.
+    #[error("No window matches {0:?}")]
+    NoSuchWindow(String),
+    /// A plataforma (ou a sessão gráfica) não permite a operação.
+    #[error("Unsupported: {0}")]
+    Unsupported(&'static str),
+    #[error("Failed to save image: {0}")]
+    Save(#[from] screenshots::image::ImageError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A imagem de um monitor, ainda em memória.
+pub struct DisplayCapture {
+    pub display_id: u32,
+    /// Posição do canto superior esquerdo da imagem na área de trabalho.
+    pub x: i32,
+    pub y: i32,
+    pub image: RgbaImage,
+    /// Fator de escala (HiDPI) do monitor: quantos pixels físicos cabem
+    /// num pixel lógico.
+    pub scale_factor: f32,
+    /// Se a imagem já foi reduzida para pixels lógicos por
+    /// [`DisplayCapture::downscale_to_logical`].
+    pub logical: bool,
+    /// Quanto a captura levou.
+    pub elapsed: Duration,
+}
+
+/// [`DisplayCapture::display_id`] de um panorama montado por [`stitch`],
+/// que não é de um monitor só.
+pub const ALL_DISPLAYS: u32 = u32::MAX;
+
+/// Filtro de log do `screenshots` quando nem `RUST_LOG` nem `log.level`
+/// definem um.
+pub const DEFAULT_LOG_FILTER: &str = "screen_capture=info";
+
+/// Um PNG já gravado em disco.
+#[derive(Debug, Clone)]
+pub struct SavedCapture {
+    pub display_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub path: PathBuf,
+}
+
+/// Um monitor conectado, como listado por [`list_displays`].
+#[derive(Debug, Clone)]
+pub struct Display {
+    pub id: u32,
+    /// Posição do canto superior esquerdo na área de trabalho.
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    pub is_primary: bool,
+}
+
+/// Qual monitor capturar: o principal, ou um número que é procurado
+/// primeiro entre os ids e, se nenhum bater, usado como índice (a partir de
+/// 0) na ordem de [`list_displays`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplaySelector {
+    Primary,
+    IdOrIndex(u32),
+}
+
+impl FromStr for DisplaySelector {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.eq_ignore_ascii_case("primary") {
+            return Ok(Self::Primary);
+        }
+        value
+            .parse()
+            .map(Self::IdOrIndex)
+            .map_err(|_| format!("invalid display {value:?}: expected an id, an index or primary"))
+    }
+}
+
+impl fmt::Display for DisplaySelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Primary => f.write_str("primary"),
+            Self::IdOrIndex(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+fn screens() -> Result<Vec<Screen>, CaptureError> {
+    Screen::all().map_err(|err| CaptureError::Displays(err.to_string()))
+}
+
+fn capture_screen(screen: &Screen) -> Result<DisplayCapture, CaptureError> {
+    let started = Instant::now();
+    let info = screen.display_info;
+    let image = screen
+        .capture()
+        .map_err(|err| CaptureError::Capture(info.id, err.to_string()))?;
+    Ok(DisplayCapture {
+        display_id: info.id,
+        x: info.x,
+        y: info.y,
+        image,
+        scale_factor: info.scale_factor,
+        logical: false,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Captura `screens` ao mesmo tempo, uma thread por monitor: três telas 4K
+/// em sequência levam o triplo.
+fn capture_parallel(screens: &[Screen]) -> Vec<Result<DisplayCapture, CaptureError>> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = screens
+            .iter()
+            .map(|screen| {
+                (
+                    screen.display_info.id,
+                    scope.spawn(|| capture_screen(screen)),
+                )
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|(display_id, handle)| {
+                handle.join().unwrap_or_else(|_| {
+                    Err(CaptureError::Capture(
+                        display_id,
+                        "capture panicked".to_owned(),
+                    ))
+                })
+            })
+            .collect()
+    })
+}
+
+/// Um retângulo em coordenadas da área de trabalho, escrito
+/// `x,y,LARGURAxALTURA` (`100,200,640x80`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid region {value:?}: expected x,y,WIDTHxHEIGHT");
+        let mut parts = value.split(',');
+        let (Some(x), Some(y), Some(size), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let region = Self {
+            x: x.trim().parse().map_err(|_| invalid())?,
+            y: y.trim().parse().map_err(|_| invalid())?,
+            width: width.trim().parse().map_err(|_| invalid())?,
+            height: height.trim().parse().map_err(|_| invalid())?,
+        };
+        if region.width == 0 || region.height == 0 {
+            return Err(invalid());
+        }
+        Ok(region)
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// Os monitores conectados num instante, e as capturas deles. Cada
+/// chamada captura a tela de novo, mas a lista de monitores é a de
+/// [`ScreenCapturer::new`]: para notar um monitor ligado ou desligado
+/// depois, crie outro.
+///
+/// As funções soltas deste módulo ([`capture_all`], [`capture_display`]...)
+/// criam um a cada chamada.
+pub struct ScreenCapturer {
+    screens: Vec<Screen>,
+}
+
+impl ScreenCapturer {
+    pub fn new() -> Result<Self, CaptureError> {
+        Ok(Self {
+            screens: screens()?,
+        })
+    }
+
+    /// Os monitores, na ordem em que o sistema os lista.
+    pub fn displays(&self) -> Vec<Display> {
+        self.screens
+            .iter()
+            .map(|screen| {
+                let info = screen.display_info;
+                Display {
+                    id: info.id,
+                    x: info.x,
+                    y: info.y,
+                    width: info.width,
+                    height: info.height,
+                    scale_factor: info.scale_factor,
+                    is_primary: info.is_primary,
+                }
+            })
+            .collect()
+    }
+
+    /// Captura todos os monitores; falha se qualquer um falhar.
+    pub fn capture_all(&self) -> Result<Vec<DisplayCapture>, CaptureError> {
+        self.capture_each().into_iter().collect()
+    }
+
+    /// Como [`Self::capture_all`], mas com o resultado de cada monitor
+    /// separado, para aproveitar os que funcionaram quando outro falha (ex.:
+    /// monitor em repouso).
+    pub fn capture_each(&self) -> Vec<Result<DisplayCapture, CaptureError>> {
+        capture_parallel(&self.screens)
+    }
+
+    /// Captura só o monitor escolhido por `selector`.
+    pub fn capture_display(
+        &self,
+        selector: DisplaySelector,
+    ) -> Result<DisplayCapture, CaptureError> {
+        let screen = match selector {
+            DisplaySelector::Primary => self.screens.iter().find(|s| s.display_info.is_primary),
+            DisplaySelector::IdOrIndex(n) => self
+                .screens
+                .iter()
+                .find(|s| s.display_info.id == n)
+                .or_else(|| self.screens.get(n as usize)),
+        };
+        capture_screen(screen.ok_or_else(|| CaptureError::NoSuchDisplay(selector.to_string()))?)
+    }
+
+    /// Captura `region`, mesmo que ela atravesse mais de um monitor: cada
+    /// um contribui com a parte que cobre, e as partes são juntadas como em
+    /// [`stitch`]. A imagem começa no canto da região coberto por algum
+    /// monitor; o `display_id` é o do monitor, ou [`ALL_DISPLAYS`] se forem
+    /// vários.
+    pub fn capture_region(&self, region: Region) -> Result<DisplayCapture, CaptureError> {
+        let started = Instant::now();
+        let mut parts = Vec::new();
+        for screen in &self.screens {
+            let info = screen.display_info;
+            let left = region.x.max(info.x);
+            let top = region.y.max(info.y);
+            let right = (i64::from(region.x) + i64::from(region.width))
+                .min(i64::from(info.x) + i64::from(info.width)) as i32;
+            let bottom = (i64::from(region.y) + i64::from(region.height))
+                .min(i64::from(info.y) + i64::from(info.height)) as i32;
+            if right <= left || bottom <= top {
+                continue;
+            }
+            let image = screen
+                .capture_area(
+                    left - info.x,
+                    top - info.y,
+                    (right - left) as u32,
+                    (bottom - top) as u32,
+                )
+                .map_err(|err| CaptureError::Capture(info.id, err.to_string()))?;
+            parts.push(DisplayCapture {
+                display_id: info.id,
+                x: left,
+                y: top,
+                image,
+                scale_factor: info.scale_factor,
+                logical: false,
+                elapsed: started.elapsed(),
+            });
+        }
+        if parts.len() == 1 {
+            return Ok(parts.remove(0));
+        }
+        let mut capture = stitch(&parts)
+            .ok_or_else(|| CaptureError::NoSuchDisplay(format!("region {region}")))?;
+        // Aqui os monitores foram capturados em sequência.
+        capture.elapsed = started.elapsed();
+        Ok(capture)
+    }
+}
+
+/// Os monitores conectados, na ordem em que o sistema os lista.
+pub fn list_displays() -> Result<Vec<Display>, CaptureError> {
+    Ok(ScreenCapturer::new()?.displays())
+}
+
+/// Captura todos os monitores conectados, na ordem em que o sistema os
+/// lista.
+pub fn capture_all() -> Result<Vec<DisplayCapture>, CaptureError> {
+    ScreenCapturer::new()?.capture_all()
+}
+
+/// Como [`capture_all`], mas com o resultado de cada monitor separado, para
+/// aproveitar os que funcionaram quando outro falha (ex.: monitor em
+/// repouso). Só falha por inteiro se nem a lista de monitores sair.
+pub fn capture_each() -> Result<Vec<Result<DisplayCapture, CaptureError>>, CaptureError> {
+    Ok(ScreenCapturer::new()?.capture_each())
+}
+
+/// Captura `region` da área de trabalho; veja
+/// [`ScreenCapturer::capture_region`].
+pub fn capture_region(region: Region) -> Result<DisplayCapture, CaptureError> {
+    ScreenCapturer::new()?.capture_region(region)
+}
+
+/// Junta as capturas numa imagem só, cada uma na sua posição da área de
+/// trabalho; o que nenhum monitor cobre fica transparente. `None` sem
+/// capturas.
+///
+/// As posições são as da área de trabalho e os tamanhos, os das imagens:
+/// com escalas diferentes entre os monitores, eles podem se sobrepor ou
+/// deixar vãos.
+pub fn stitch(captures: &[DisplayCapture]) -> Option<DisplayCapture> {
+    let left = captures.iter().map(|c| c.x).min()?;
+    let top = captures.iter().map(|c| c.y).min()?;
+    let right = captures
+        .iter()
+        .map(|c| i64::from(c.x) + i64::from(c.image.width()))
+        .max()?;
+    let bottom = captures
+        .iter()
+        .map(|c| i64::from(c.y) + i64::from(c.image.height()))
+        .max()?;
+    let mut image = RgbaImage::new(
+        (right - i64::from(left)) as u32,
+        (bottom - i64::from(top)) as u32,
+    );
+    for capture in captures {
+        screenshots::image::imageops::replace(
+            &mut image,
+            &capture.image,
+            i64::from(capture.x - left),
+            i64::from(capture.y - top),
+        );
+    }
+    Some(DisplayCapture {
+        display_id: ALL_DISPLAYS,
+        x: left,
+        y: top,
+        image,
+        // Com escalas diferentes, vale a maior: o panorama tem a resolução
+        // do monitor mais denso.
+        scale_factor: captures.iter().map(|c| c.scale_factor).fold(1.0, f32::max),
+        logical: captures.iter().all(|c| c.logical),
+        // Os monitores são capturados em paralelo.
+        elapsed: captures.iter().map(|c| c.elapsed).max()?,
+    })
+}
+
+/// Captura só o monitor escolhido por `selector`. O principal vem por
+/// [`primary_screen`], sem passar pela lista dos outros.
+pub fn capture_display(selector: DisplaySelector) -> Result<DisplayCapture, CaptureError> {
+    match selector {
+        DisplaySelector::Primary => capture_screen(&primary_screen()?),
+        DisplaySelector::IdOrIndex(_) => ScreenCapturer::new()?.capture_display(selector),
+    }
+}
+
+/// O monitor principal, pelo caminho mais curto de cada plataforma: no X11
+/// uma consulta só ao RandR, sem as de modo e rotação de cada saída que
+/// [`list_displays`] faz; no Windows e no macOS, o monitor que contém a
+/// origem, que é sempre o principal. No Wayland não há atalho.
+fn primary_screen() -> Result<Screen, CaptureError> {
+    #[cfg(target_os = "linux")]
+    {
+        if std::env::var_os("XDG_SESSION_TYPE").is_some_and(|session| session == "wayland") {
+            return screens()?
+                .into_iter()
+                .find(|s| s.display_info.is_primary)
+                .ok_or_else(|| CaptureError::NoSuchDisplay(DisplaySelector::Primary.to_string()));
+        }
+        x11::primary_display()
+            .map(|info| Screen::new(&info))
+            .map_err(|err| CaptureError::Displays(err.to_string()))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Screen::from_point(0, 0).map_err(|err| CaptureError::Displays(err.to_string()))
+    }
+}
+
+/// Uma janela de aplicativo visível, com a posição na área de trabalho.
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub title: String,
+    /// Nome do processo dono da janela, quando ele o informa.
+    pub process: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A parte de um monitor coberta por uma janela.
+pub struct WindowCapture {
+    pub window: Window,
+    pub capture: DisplayCapture,
+}
+
+/// Captura a janela mais acima cujo título ou nome de processo contém
+/// `query` (sem diferenciar maiúsculas). Sai o que está na tela naquela
+/// área, inclusive o que estiver por cima da janela; uma janela entre dois
+/// monitores é cortada no limite daquele que tem o centro dela.
+///
+/// Só funciona em sessões X11; nas demais devolve
+/// [`CaptureError::Unsupported`].
+pub fn capture_window(query: &str) -> Result<WindowCapture, CaptureError> {
+    let needle = query.to_lowercase();
+    let window = windows()?
+        .into_iter()
+        .find(|window| {
+            window.title.to_lowercase().contains(&needle)
+                || window
+                    .process
+                    .as_ref()
+                    .is_some_and(|process| process.to_lowercase().contains(&needle))
+        })
+        .ok_or_else(|| CaptureError::NoSuchWindow(query.to_owned()))?;
+
+    let center_x = window.x + (window.width / 2) as i32;
+    let center_y = window.y + (window.height / 2) as i32;
+    let screen = Screen::from_point(center_x, center_y)
+        .map_err(|err| CaptureError::Displays(err.to_string()))?;
+    let info = screen.display_info;
+    let left = window.x.max(info.x);
+    let top = window.y.max(info.y);
+    let right = (window.x + window.width as i32).min(info.x + info.width as i32);
+    let bottom = (window.y + window.height as i32).min(info.y + info.height as i32);
+    if right <= left || bottom <= top {
+        return Err(CaptureError::NoSuchWindow(query.to_owned()));
+    }
+    let started = Instant::now();
+    let image = screen
+        .capture_area(
+            left - info.x,
+            top - info.y,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        )
+        .map_err(|err| CaptureError::Capture(info.id, err.to_string()))?;
+    Ok(WindowCapture {
+        window,
+        capture: DisplayCapture {
+            display_id: info.id,
+            x: left,
+            y: top,
+            image,
+            scale_factor: info.scale_factor,
+            logical: false,
+            elapsed: started.elapsed(),
+        },
+    })
+}
+
+/// As janelas visíveis, da mais acima para a mais abaixo.
+#[cfg(target_os = "linux")]
+pub fn windows() -> Result<Vec<Window>, CaptureError> {
+    if std::env::var_os("XDG_SESSION_TYPE").is_some_and(|session| session == "wayland") {
+        return Err(CaptureError::Unsupported(
+            "window capture needs an X11 session",
+        ));
+    }
+    x11::windows().map_err(|err| CaptureError::Displays(err.to_string()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn windows() -> Result<Vec<Window>, CaptureError> {
+    Err(CaptureError::Unsupported(
+        "window capture is only available on Linux (X11)",
+    ))
+}
+
+/// O ponteiro do mouse, já com a transparência resolvida.
+pub struct Cursor {
+    /// Posição do canto superior esquerdo da imagem na área de trabalho
+    /// (a ponta da seta menos o ponto ativo).
+    pub x: i32,
+    pub y: i32,
+    pub image: RgbaImage,
+}
+
+/// A imagem e a posição atuais do ponteiro. As capturas de tela não o
+/// incluem; [`DisplayCapture::draw_cursor`] o desenha por cima.
+///
+/// Só funciona em sessões X11 (extensão XFixes).
+#[cfg(target_os = "linux")]
+pub fn cursor() -> Result<Cursor, CaptureError> {
+    if std::env::var_os("XDG_SESSION_TYPE").is_some_and(|session| session == "wayland") {
+        return Err(CaptureError::Unsupported(
+            "the cursor can only be captured in an X11 session",
+        ));
+    }
+    x11::cursor().map_err(|err| CaptureError::Displays(err.to_string()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn cursor() -> Result<Cursor, CaptureError> {
+    Err(CaptureError::Unsupported(
+        "the cursor can only be captured on Linux (X11)",
+    ))
+}
+
+impl DisplayCapture {
+    /// O id do monitor como texto, ou `all` para um panorama.
+    pub fn display_label(&self) -> String {
+        match self.display_id {
+            ALL_DISPLAYS => "all".to_owned(),
+            id => id.to_string(),
+        }
+    }
+
+    /// O tamanho da área capturada em pixels lógicos, o mesmo em qualquer
+    /// monitor para a mesma área da área de trabalho.
+    pub fn logical_size(&self) -> (u32, u32) {
+        if self.logical {
+            return self.image.dimensions();
+        }
+        self.scaled_size(1.0 / self.scale_factor.max(f32::EPSILON))
+    }
+
+    /// O tamanho da área capturada em pixels do monitor, mesmo depois de
+    /// [`DisplayCapture::downscale_to_logical`].
+    pub fn physical_size(&self) -> (u32, u32) {
+        if self.logical {
+            return self.scaled_size(self.scale_factor);
+        }
+        self.image.dimensions()
+    }
+
+    fn scaled_size(&self, factor: f32) -> (u32, u32) {
+        let (width, height) = self.image.dimensions();
+        (
+            (width as f32 * factor).round().max(1.0) as u32,
+            (height as f32 * factor).round().max(1.0) as u32,
+        )
+    }
+
+    /// Reduz a imagem para [`DisplayCapture::logical_size`], para que
+    /// capturas de monitores com escalas diferentes sejam comparáveis. Em
+    /// monitores de escala 1 (ou menor) a imagem fica como está.
+    pub fn downscale_to_logical(&mut self) {
+        if self.logical || self.scale_factor <= 1.0 {
+            return;
+        }
+        let (width, height) = self.logical_size();
+        self.logical = true;
+        self.image = screenshots::image::imageops::resize(
+            &self.image,
+            width,
+            height,
+            screenshots::image::imageops::FilterType::Lanczos3,
+        );
+    }
+
+    /// Desenha `cursor` na captura, se ele estiver sobre ela.
+    pub fn draw_cursor(&mut self, cursor: &Cursor) {
+        let (width, height) = self.image.dimensions();
+        for (cx, cy, pixel) in cursor.image.enumerate_pixels() {
+            let x = i64::from(cursor.x) + i64::from(cx) - i64::from(self.x);
+            let y = i64::from(cursor.y) + i64::from(cy) - i64::from(self.y);
+            if x < 0 || y < 0 || x >= i64::from(width) || y >= i64::from(height) {
+                continue;
+            }
+            let alpha = u32::from(pixel[3]);
+            let target = self.image.get_pixel_mut(x as u32, y as u32);
+            for channel in 0..3 {
+                let over = u32::from(pixel[channel]) * alpha;
+                let under = u32::from(target[channel]) * (255 - alpha);
+                target[channel] = ((over + under) / 255) as u8;
+            }
+        }
+    }
+}
+
+/// Janelas listadas pelo gerenciador de janelas (EWMH) de um servidor X, o
+/// ponteiro pela extensão XFixes e o monitor principal pela RandR.
+#[cfg(target_os = "linux")]
+mod x11 {
+    use screenshots::display_info::DisplayInfo;
+    use xcb::{Connection, Xid, randr, x, xfixes};
+
+    use super::{Cursor, RgbaImage};
+
+    /// O monitor marcado como principal (ou o primeiro, se nenhum for), com
+    /// as mesmas coordenadas e id que o `display_info` daria a ele.
+    pub fn primary_display() -> Result<DisplayInfo, Box<dyn std::error::Error>> {
+        let (conn, screen_num) =
+            Connection::connect_with_extensions(None, &[xcb::Extension::RandR], &[])?;
+        let root = conn
+            .get_setup()
+            .roots()
+            .nth(screen_num as usize)
+            .ok_or("X server without screens")?
+            .root();
+        let cookie = conn.send_request(&randr::GetMonitors {
+            window: root,
+            get_active: true,
+        });
+        let reply = conn.wait_for_reply(cookie)?;
+        let monitors: Vec<_> = reply.monitors().collect();
+        let monitor = monitors
+            .iter()
+            .find(|monitor| monitor.primary())
+            .or_else(|| monitors.first())
+            .ok_or("no active monitors")?;
+        let output = *monitor.outputs().first().ok_or("monitor without outputs")?;
+
+        // O `display_info` divide a geometria pela escala do `Xft.dpi`, e a
+        // captura multiplica de volta.
+        let resources = property(&conn, root, x::ATOM_RESOURCE_MANAGER, x::ATOM_STRING)?;
+        let scale_factor = String::from_utf8_lossy(resources.value::<u8>())
+            .lines()
+            .find_map(|line| line.strip_prefix("Xft.dpi:"))
+            .and_then(|dpi| dpi.trim().parse::<f32>().ok())
+            .map_or(1.0, |dpi| dpi / 96.0);
+        let scaled = |value: f32| value / scale_factor;
+        Ok(DisplayInfo {
+            id: output.resource_id(),
+            raw_handle: output,
+            x: scaled(monitor.x().into()) as i32,
+            y: scaled(monitor.y().into()) as i32,
+            width: scaled(monitor.width().into()) as u32,
+            height: scaled(monitor.height().into()) as u32,
+            rotation: 0.0,
+            scale_factor,
+            frequency: 0.0,
+            is_primary: true,
+        })
+    }
+
+    pub fn cursor() -> Result<Cursor, Box<dyn std::error::Error>> {
+        let (conn, _) = Connection::connect_with_extensions(None, &[xcb::Extension::XFixes], &[])?;
+        // O servidor só responde ao XFixes depois da negociação de versão.
+        let cookie = conn.send_request(&xfixes::QueryVersion {
+            client_major_version: 4,
+            client_minor_version: 0,
+        });
+        conn.wait_for_reply(cookie)?;
+        let cookie = conn.send_request(&xfixes::GetCursorImage {});
+        let reply = conn.wait_for_reply(cookie)?;
+
+        // ARGB com alfa pré-multiplicado, um `u32` por pixel.
+        let mut rgba = Vec::with_capacity(reply.cursor_image().len() * 4);
+        for &argb in reply.cursor_image() {
+            let [a, r, g, b] = argb.to_be_bytes();
+            let unmultiply = |c: u8| match a {
+                0 => 0,
+                a => (u32::from(c) * 255 / u32::from(a)).min(255) as u8,
+            };
+            rgba.extend_from_slice(&[unmultiply(r), unmultiply(g), unmultiply(b), a]);
+        }
+        let image = RgbaImage::from_raw(reply.width().into(), reply.height().into(), rgba)
+            .ok_or("cursor image with an unexpected size")?;
+        Ok(Cursor {
+            x: i32::from(reply.x()) - i32::from(reply.xhot()),
+            y: i32::from(reply.y()) - i32::from(reply.yhot()),
+            image,
+        })
+    }
+
+    use super::Window;
+
+    fn atom(conn: &Connection, name: &[u8]) -> xcb::Result<x::Atom> {
+        let cookie = conn.send_request(&x::InternAtom {
+            only_if_exists: false,
+            name,
+        });
+        Ok(conn.wait_for_reply(cookie)?.atom())
+    }
+
+    fn property(
+        conn: &Connection,
+        window: x::Window,
+        property: x::Atom,
+        r#type: x::Atom,
+    ) -> xcb::Result<x::GetPropertyReply> {
+        let cookie = conn.send_request(&x::GetProperty {
+            delete: false,
+            window,
+            property,
+            r#type,
+            long_offset: 0,
+            long_length: u32::MAX / 4,
+        });
+        conn.wait_for_reply(cookie)
+    }
+
+    pub fn windows() -> Result<Vec<Window>, Box<dyn std::error::Error>> {
+        let (conn, screen_num) = Connection::connect(None)?;
+        let root = conn
+            .get_setup()
+            .roots()
+            .nth(screen_num as usize)
+            .ok_or("X server without screens")?
+            .root();
+        let stacking = atom(&conn, b"_NET_CLIENT_LIST_STACKING")?;
+        let net_wm_name = atom(&conn, b"_NET_WM_NAME")?;
+        let utf8_string = atom(&conn, b"UTF8_STRING")?;
+        let net_wm_pid = atom(&conn, b"_NET_WM_PID")?;
+
+        let clients = property(&conn, root, stacking, x::ATOM_WINDOW)?;
+        let mut windows = Vec::new();
+        // A lista vai de baixo para cima.
+        for &window in clients.value::<x::Window>().iter().rev() {
+            let cookie = conn.send_request(&x::GetWindowAttributes { window });
+            if conn.wait_for_reply(cookie)?.map_state() != x::MapState::Viewable {
+                continue;
+            }
+            let mut title = property(&conn, window, net_wm_name, utf8_string)?
+                .value::<u8>()
+                .to_vec();
+            if title.is_empty() {
+                title = property(&conn, window, x::ATOM_WM_NAME, x::ATOM_STRING)?
+                    .value::<u8>()
+                    .to_vec();
+            }
+            let process = property(&conn, window, net_wm_pid, x::ATOM_CARDINAL)?
+                .value::<u32>()
+                .first()
+                .and_then(|pid| std::fs::read_to_string(format!("/proc/{pid}/comm")).ok())
+                .map(|comm| comm.trim_end().to_owned());
+
+            let cookie = conn.send_request(&x::GetGeometry {
+                drawable: x::Drawable::Window(window),
+            });
+            let geometry = conn.wait_for_reply(cookie)?;
+            let cookie = conn.send_request(&x::TranslateCoordinates {
+                src_window: window,
+                dst_window: root,
+                src_x: 0,
+                src_y: 0,
+            });
+            let position = conn.wait_for_reply(cookie)?;
+            windows.push(Window {
+                title: String::from_utf8_lossy(&title).into_owned(),
+                process,
+                x: position.dst_x().into(),
+                y: position.dst_y().into(),
+                width: geometry.width().into(),
+                height: geometry.height().into(),
+            });
+        }
+        Ok(windows)
+    }
+}
+
+/// Captura todos os monitores e grava cada um como
+/// `screen-<id>-<largura>x<altura>.png` em `out_dir` (criado se preciso).
+/// O `prefix` vai no começo do nome para que capturas repetidas não se
+/// sobrescrevam.
+pub fn capture_to_dir(out_dir: &Path, prefix: &str) -> Result<Vec<SavedCapture>, CaptureError> {
+    std::fs::create_dir_all(out_dir)?;
+
+    let captures = ScreenCapturer::new()?.capture_all()?;
+    // Uma thread por monitor também na codificação, a parte mais lenta.
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = captures
+            .into_iter()
+            .map(|capture| {
+                scope.spawn(move || {
+                    let (width, height) = capture.image.dimensions();
+                    let path = out_dir.join(format!(
+                        "{prefix}screen-{}-{width}x{height}.png",
+                        capture.display_id
+                    ));
+                    capture.image.save(&path)?;
+                    Ok(SavedCapture {
+                        display_id: capture.display_id,
+                        width,
+                        height,
+                        path,
+                    })
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,80 @@
+//! Arquivo `.json` gravado ao lado de cada captura com `--sidecar`: o
+//! monitor, a posição e os tamanhos físico e lógico, para comparar
+//! capturas de monitores com escalas diferentes.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DisplayCapture,
+    error::{Result, io},
+};
+
+/// O conteúdo do arquivo.
+#[derive(Serialize, Deserialize)]
+pub struct Metadata {
+    pub display: String,
+    /// Posição do canto superior esquerdo na área de trabalho.
+    pub x: i32,
+    pub y: i32,
+    /// Tamanho da imagem gravada.
+    pub width: u32,
+    pub height: u32,
+    pub physical_width: u32,
+    pub physical_height: u32,
+    pub logical_width: u32,
+    pub logical_height: u32,
+    pub scale_factor: f32,
+    /// Se a imagem foi reduzida para o tamanho lógico (`--logical`).
+    pub logical: bool,
+    pub captured_at: String,
+    /// Número da captura, a partir de 0.
+    pub shot: u32,
+    /// Onde o arquivo foi publicado com `--upload`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl Metadata {
+    pub fn new(capture: &DisplayCapture, at: SystemTime, shot: u32) -> Self {
+        let (width, height) = capture.image.dimensions();
+        let (physical_width, physical_height) = capture.physical_size();
+        let (logical_width, logical_height) = capture.logical_size();
+        Self {
+            display: capture.display_label(),
+            x: capture.x,
+            y: capture.y,
+            width,
+            height,
+            physical_width,
+            physical_height,
+            logical_width,
+            logical_height,
+            scale_factor: capture.scale_factor,
+            logical: capture.logical,
+            captured_at: humantime::format_rfc3339_millis(at).to_string(),
+            shot,
+            url: None,
+        }
+    }
+
+    /// Grava em [`sidecar_path`] de `image`.
+    pub fn write(&self, image: &Path) -> Result<()> {
+        let path = sidecar_path(image);
+        let json = serde_json::to_vec_pretty(self).expect("metadata is always serializable");
+        std::fs::write(&path, json).map_err(io(&path))
+    }
+}
+
+/// O arquivo de metadados de `image`: o mesmo nome com `.json` no fim
+/// (`screen-1.png.json`), para não colidir entre formatos.
+pub fn sidecar_path(image: &Path) -> PathBuf {
+    let mut name = OsString::from(image.as_os_str());
+    name.push(".json");
+    PathBuf::from(name)
+}
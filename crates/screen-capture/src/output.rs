@@ -0,0 +1,125 @@
+//! Codificação das capturas no formato escolhido com `--format`.
+
+use std::{io::Cursor, path::Path};
+
+use screenshots::image::{
+    DynamicImage, ImageEncoder, ImageFormat, RgbaImage,
+    codecs::png::{CompressionType, FilterType, PngEncoder},
+};
+
+use crate::{
+    DisplayCapture,
+    error::{Result, ScreenshotError, io, usage},
+};
+
+/// Formatos em que a captura pode ser gravada.
+#[derive(Clone, Copy)]
+pub enum OutputFormat {
+    Png(PngOptions),
+    Jpeg,
+    Bmp,
+}
+
+/// Quanto CPU gastar para deixar cada PNG menor; no modo intervalo a
+/// diferença se soma em centenas de arquivos.
+#[derive(Clone, Copy)]
+pub struct PngOptions {
+    /// Nível do deflate (`--png-compression fast|default|best`).
+    pub compression: CompressionType,
+    /// Passada extra do `oxipng` com este preset (0 a 6), que testa
+    /// filtros e reduções de cor sem perda.
+    pub optimize: Option<u8>,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self {
+            compression: CompressionType::Fast,
+            optimize: None,
+        }
+    }
+}
+
+pub fn parse_compression(value: &str) -> Result<CompressionType> {
+    match value.to_ascii_lowercase().as_str() {
+        "fast" => Ok(CompressionType::Fast),
+        "default" => Ok(CompressionType::Default),
+        "best" => Ok(CompressionType::Best),
+        other => Err(usage(format!(
+            "Compressão PNG inválida: {other} (use fast, default ou best)"
+        ))),
+    }
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "png" => Ok(Self::Png(PngOptions::default())),
+            "jpg" | "jpeg" => Ok(Self::Jpeg),
+            "bmp" => Ok(Self::Bmp),
+            other => Err(usage(format!(
+                "Formato não suportado: {other} (use png, jpg ou bmp)"
+            ))),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png(_) => "png",
+            Self::Jpeg => "jpg",
+            Self::Bmp => "bmp",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png(_) => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Bmp => "image/bmp",
+        }
+    }
+}
+
+/// Codifica a imagem no formato pedido, em memória; `path` só aparece nas
+/// mensagens de erro. O JPEG não tem canal alfa, então a imagem é
+/// convertida para RGB antes.
+pub fn encode(image: RgbaImage, path: &Path, format: OutputFormat) -> Result<Vec<u8>> {
+    let image_error = |source| ScreenshotError::Image {
+        path: path.to_owned(),
+        source,
+    };
+    let image = DynamicImage::ImageRgba8(image);
+    let mut bytes = Vec::new();
+    match format {
+        OutputFormat::Png(png) => {
+            PngEncoder::new_with_quality(&mut bytes, png.compression, FilterType::Adaptive)
+                .write_image(
+                    image.as_bytes(),
+                    image.width(),
+                    image.height(),
+                    image.color(),
+                )
+                .map_err(image_error)?;
+            if let Some(level) = png.optimize {
+                bytes = oxipng::optimize_from_memory(&bytes, &oxipng::Options::from_preset(level))
+                    .map_err(|source| ScreenshotError::Optimize {
+                        path: path.to_owned(),
+                        source,
+                    })?;
+            }
+        }
+        OutputFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg)
+            .map_err(image_error)?,
+        OutputFormat::Bmp => image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Bmp)
+            .map_err(image_error)?,
+    }
+    Ok(bytes)
+}
+
+/// Grava a captura no formato pedido (ver [`encode`]).
+pub fn save(capture: DisplayCapture, path: &Path, format: OutputFormat) -> Result<()> {
+    let bytes = encode(capture.image, path, format)?;
+    std::fs::write(path, bytes).map_err(io(path))
+}
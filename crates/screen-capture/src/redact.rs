@@ -0,0 +1,67 @@
+//! Áreas escondidas antes de a captura ir para qualquer lugar (`--redact`):
+//! gerenciadores de senha, dados pessoais e afins.
+
+use std::str::FromStr;
+
+use screenshots::image::{Rgba, imageops};
+
+use crate::{DisplayCapture, Region};
+
+/// Desvio do borrão gaussiano de `--redact-mode blur`: forte o bastante
+/// para texto de tamanho normal deixar de ser legível.
+const BLUR_SIGMA: f32 = 16.0;
+
+/// Como as áreas são escondidas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RedactMode {
+    /// Pintadas de preto: nada do original sobra.
+    #[default]
+    Black,
+    /// Borradas: a área continua reconhecível, o conteúdo não.
+    Blur,
+}
+
+impl FromStr for RedactMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "black" => Ok(Self::Black),
+            "blur" => Ok(Self::Blur),
+            other => Err(format!("Modo inválido: {other} (use black ou blur)")),
+        }
+    }
+}
+
+/// Esconde em `capture` a parte de cada uma das `regions` que cai nela.
+pub fn redact(capture: &mut DisplayCapture, regions: &[Region], mode: RedactMode) {
+    let (width, height) = capture.image.dimensions();
+    for region in regions {
+        // A área em coordenadas da imagem, cortada nas bordas dela.
+        let left = (i64::from(region.x) - i64::from(capture.x)).clamp(0, i64::from(width));
+        let top = (i64::from(region.y) - i64::from(capture.y)).clamp(0, i64::from(height));
+        let right = (i64::from(region.x) + i64::from(region.width) - i64::from(capture.x))
+            .clamp(0, i64::from(width));
+        let bottom = (i64::from(region.y) + i64::from(region.height) - i64::from(capture.y))
+            .clamp(0, i64::from(height));
+        if left >= right || top >= bottom {
+            continue;
+        }
+        let (x, y) = (left as u32, top as u32);
+        let (w, h) = ((right - left) as u32, (bottom - top) as u32);
+        match mode {
+            RedactMode::Black => {
+                for py in y..y + h {
+                    for px in x..x + w {
+                        capture.image.put_pixel(px, py, Rgba([0, 0, 0, 255]));
+                    }
+                }
+            }
+            RedactMode::Blur => {
+                let area = imageops::crop_imm(&capture.image, x, y, w, h).to_image();
+                let blurred = imageops::blur(&area, BLUR_SIGMA);
+                imageops::replace(&mut capture.image, &blurred, i64::from(x), i64::from(y));
+            }
+        }
+    }
+}
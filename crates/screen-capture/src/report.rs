@@ -0,0 +1,38 @@
+//! Tabela do resultado de cada monitor numa captura de vários, para que
+//! uma falha parcial (monitor em repouso, permissão negada) fique clara.
+
+use std::path::PathBuf;
+
+/// Uma linha da tabela.
+pub struct Row {
+    /// Id do monitor, `all` num panorama ou `?` quando a falha não diz.
+    pub display: String,
+    /// O arquivo gravado (`None` quando nada é gravado, como em
+    /// `--clipboard-only`) ou o erro.
+    pub outcome: Result<Option<PathBuf>, String>,
+}
+
+/// Imprime as linhas alinhadas em colunas, com a contagem no fim.
+pub fn print(rows: &[Row]) {
+    let width = rows
+        .iter()
+        .map(|row| row.display.len())
+        .chain(["Monitor".len()])
+        .max()
+        .unwrap_or_default();
+    println!("{:<width$}  {:<9}  Detalhe", "Monitor", "Resultado");
+    for row in rows {
+        let (status, detail) = match &row.outcome {
+            Ok(Some(path)) => ("ok", path.display().to_string()),
+            Ok(None) => ("ok", String::new()),
+            Err(err) => ("falhou", err.clone()),
+        };
+        println!("{:<width$}  {status:<9}  {detail}", row.display);
+    }
+    let failed = rows.iter().filter(|row| row.outcome.is_err()).count();
+    println!(
+        "{} de {} monitores capturados",
+        rows.len() - failed,
+        rows.len()
+    );
+}
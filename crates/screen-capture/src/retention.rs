@@ -0,0 +1,221 @@
+//! Limpeza das capturas antigas (`--keep-last` / `--keep-days`), para que
+//! sessões longas de timelapse ou do modo daemon não encham o disco.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    error::{Result, io, usage},
+    metadata,
+    output::OutputFormat,
+};
+
+/// Quais capturas manter. Só são consideradas as do diretório de saída cujo
+/// nome segue o modelo de `--name`, que por isso precisa começar com um
+/// trecho fixo (como o `screen-` do padrão): sem ele, `{display}.{ext}`
+/// casaria com qualquer PNG do diretório, inclusive os que não gravamos.
+#[derive(Clone, Copy, Default)]
+pub struct Retention {
+    /// Mantém só os `keep_last` arquivos mais novos.
+    pub keep_last: Option<usize>,
+    /// Remove os arquivos modificados há mais que isto.
+    pub max_age: Option<Duration>,
+}
+
+impl Retention {
+    pub fn is_enabled(&self) -> bool {
+        self.keep_last.is_some() || self.max_age.is_some()
+    }
+
+    /// Recusa um modelo de nome que não começa com um trecho fixo, com o
+    /// qual a limpeza não saberia quais arquivos são nossos.
+    pub fn check_template(&self, template: &str) -> Result<()> {
+        if self.is_enabled() && !template_has_prefix(template) {
+            return Err(usage(format!(
+                "--keep-last e --keep-days precisam de um modelo de nome que comece com um trecho fixo \
+                 (como \"screen-{{display}}-{{timestamp}}.{{ext}}\"), não \"{template}\""
+            )));
+        }
+        Ok(())
+    }
+
+    /// Remove de `dir` as capturas que passam dos limites, devolvendo
+    /// quantas foram removidas.
+    pub fn prune(&self, dir: &Path, template: &str, format: OutputFormat) -> Result<usize> {
+        self.check_template(template)?;
+        let pattern = Pattern::new(template, format);
+        let mut captures: Vec<(SystemTime, PathBuf)> = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(io(dir))? {
+            let entry = entry.map_err(io(dir))?;
+            let name = entry.file_name();
+            if !name.to_str().is_some_and(|name| pattern.matches(name)) {
+                continue;
+            }
+            let metadata = entry.metadata().map_err(io(entry.path()))?;
+            if metadata.is_file() {
+                let modified = metadata.modified().map_err(io(entry.path()))?;
+                captures.push((modified, entry.path()));
+            }
+        }
+        // Mais novas primeiro.
+        captures.sort_by(|a, b| b.cmp(a));
+
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for (index, (modified, path)) in captures.iter().enumerate() {
+            let over_count = self.keep_last.is_some_and(|keep| index >= keep);
+            let too_old = self.max_age.is_some_and(|max_age| {
+                now.duration_since(*modified).is_ok_and(|age| age > max_age)
+            });
+            if over_count || too_old {
+                std::fs::remove_file(path).map_err(io(path))?;
+                // O `.json` de `--sidecar`, se houver, vai junto.
+                let sidecar = metadata::sidecar_path(path);
+                match std::fs::remove_file(&sidecar) {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                        return Err(io(sidecar)(err));
+                    }
+                    _ => {}
+                }
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+fn template_has_prefix(template: &str) -> bool {
+    !template.is_empty() && !template.starts_with('{')
+}
+
+/// Os trechos fixos do modelo de nome; cada campo entre eles casa com
+/// qualquer texto não vazio, menos `{ext}`, que é a extensão do formato.
+struct Pattern {
+    literals: Vec<String>,
+}
+
+impl Pattern {
+    fn new(template: &str, format: OutputFormat) -> Self {
+        let template = template.replace("{ext}", format.extension());
+        let mut literals = Vec::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            literals.push(rest[..start].to_owned());
+            // O modelo já foi validado, então toda `{` tem a sua `}`.
+            let end = rest[start..]
+                .find('}')
+                .map_or(rest.len(), |end| start + end + 1);
+            rest = &rest[end..];
+        }
+        literals.push(rest.to_owned());
+        Self { literals }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let (first, rest) = self.literals.split_first().expect("at least one literal");
+        let Some(mut name) = name.strip_prefix(first.as_str()) else {
+            return false;
+        };
+        let Some((last, middle)) = rest.split_last() else {
+            // Modelo sem campos: o nome é exatamente o literal.
+            return name.is_empty();
+        };
+        for literal in middle {
+            // Cada campo ocupa pelo menos um caractere.
+            let skip = name.chars().next().map_or(0, char::len_utf8);
+            let Some(found) = name
+                .get(skip..)
+                .and_then(|after| after.find(literal.as_str()))
+            else {
+                return false;
+            };
+            name = &name[skip + found + literal.len()..];
+        }
+        name.len() > last.len() && name.ends_with(last.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(template: &str) -> Pattern {
+        Pattern::new(template, OutputFormat::Png(Default::default()))
+    }
+
+    #[test]
+    fn matches_names_rendered_from_the_template() {
+        let pattern = pattern("screen-{display}-{timestamp}.{ext}");
+        assert!(pattern.matches("screen-0-20260101-120000.png"));
+        assert!(pattern.matches("screen-HDMI-1-20260101-120000.png"));
+        assert!(!pattern.matches("screen-0-20260101-120000.jpg"));
+        assert!(!pattern.matches("shot-0-20260101-120000.png"));
+        assert!(!pattern.matches("vacation.png"));
+    }
+
+    #[test]
+    fn every_field_takes_at_least_one_character() {
+        let pattern = pattern("screen-{display}-{timestamp}.{ext}");
+        assert!(!pattern.matches("screen--.png"));
+        assert!(!pattern.matches("screen-0-.png"));
+        assert!(!pattern.matches("screen-.png"));
+    }
+
+    #[test]
+    fn a_template_without_fields_matches_only_itself() {
+        let pattern = pattern("latest.{ext}");
+        assert!(pattern.matches("latest.png"));
+        assert!(!pattern.matches("latest.png.bak"));
+        assert!(!pattern.matches("old-latest.png"));
+    }
+
+    #[test]
+    fn templates_without_a_fixed_prefix_are_refused() {
+        let retention = Retention {
+            keep_last: Some(1),
+            max_age: None,
+        };
+        assert!(retention.check_template("{display}.{ext}").is_err());
+        assert!(retention.check_template("").is_err());
+        assert!(retention.check_template("screen-{display}.{ext}").is_ok());
+        // Sem limpeza, qualquer modelo serve.
+        assert!(
+            Retention::default()
+                .check_template("{display}.{ext}")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn prune_leaves_files_it_did_not_write() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["vacation.png", "0.png", "screen-0-1.png", "screen-0-2.png"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        let retention = Retention {
+            keep_last: Some(1),
+            max_age: None,
+        };
+        assert!(
+            retention
+                .prune(
+                    dir.path(),
+                    "{display}.{ext}",
+                    OutputFormat::Png(Default::default())
+                )
+                .is_err()
+        );
+        let removed = retention
+            .prune(
+                dir.path(),
+                "screen-{display}-{shot}.{ext}",
+                OutputFormat::Png(Default::default()),
+            )
+            .unwrap();
+        assert_eq!(removed, 1);
+        assert!(dir.path().join("vacation.png").exists());
+        assert!(dir.path().join("0.png").exists());
+    }
+}
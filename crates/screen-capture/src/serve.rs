@@ -0,0 +1,378 @@
+//! Servidor HTTP embutido de `--serve` e `--stream`: `GET /latest.png`
+//! devolve a última captura, para painéis que mostram a tela quase ao vivo,
+//! e `GET /stream.mjpg` manda cada captura nova como um quadro MJPEG, que
+//! qualquer navegador mostra sem nada instalado.
+//!
+//! Um servidor mínimo sobre `std::net`, uma thread por conexão: as rotas
+//! são só de leitura e o binário não tem runtime assíncrono.
+
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+
+use screenshots::image::{
+    ColorType, DynamicImage, ImageEncoder,
+    codecs::{
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+    },
+};
+use tracing::warn;
+
+use crate::{
+    DisplayCapture, RgbaImage,
+    error::{Result, io, usage},
+};
+
+/// Tempo para o cliente mandar a requisição e ler a resposta.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Quanto da requisição (linha e cabeçalhos) é lido, no máximo.
+const MAX_REQUEST: u64 = 64 * 1024;
+
+/// `:8080` escuta em todas as interfaces, como `0.0.0.0:8080`.
+pub fn parse_addr(value: &str) -> Result<SocketAddr> {
+    let value = match value.strip_prefix(':') {
+        Some(port) => format!("0.0.0.0:{port}"),
+        None => value.to_owned(),
+    };
+    value
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| {
+            usage(format!(
+                "Endereço inválido: {value} (use :8080 ou 127.0.0.1:8080)"
+            ))
+        })
+}
+
+/// Qualidade dos quadros do MJPEG: o bastante para ler texto, com
+/// quadros de poucas centenas de KB.
+const JPEG_QUALITY: u8 = 75;
+/// Separador das partes de `multipart/x-mixed-replace`.
+const BOUNDARY: &str = "frame";
+
+/// Como um quadro é entregue: PNG em `/latest.png`, JPEG no stream.
+#[derive(Clone, Copy)]
+enum Encoding {
+    Png,
+    Jpeg,
+}
+
+/// A última captura de cada monitor, e os arquivos dela já pedidos.
+struct Frame {
+    image: Arc<RgbaImage>,
+    at: SystemTime,
+    png: Option<Arc<Vec<u8>>>,
+    jpeg: Option<Arc<Vec<u8>>>,
+}
+
+impl Frame {
+    fn cached(&mut self, encoding: Encoding) -> &mut Option<Arc<Vec<u8>>> {
+        match encoding {
+            Encoding::Png => &mut self.png,
+            Encoding::Jpeg => &mut self.jpeg,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Frames {
+    /// Pelo id do monitor; `/latest.png` é o de menor id.
+    by_display: BTreeMap<u32, Frame>,
+    /// Aumenta a cada [`Latest::publish`], para o stream saber que há
+    /// quadro novo.
+    generation: u64,
+}
+
+/// As capturas servidas; o laço de captura publica, as conexões leem.
+/// Clonar só copia o `Arc`.
+#[derive(Clone, Default)]
+pub struct Latest {
+    frames: Arc<(Mutex<Frames>, Condvar)>,
+}
+
+impl Latest {
+    /// Começa a servir em `addr`, numa thread que dura até o fim do
+    /// processo. Pode ser chamado mais de uma vez (`--serve` e
+    /// `--stream`), com as mesmas capturas em todos os endereços.
+    pub fn listen(&self, addr: SocketAddr) -> Result<()> {
+        let listener = TcpListener::bind(addr).map_err(io(addr.to_string()))?;
+        let served = self.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let latest = served.clone();
+                std::thread::spawn(move || {
+                    if let Err(err) = latest.answer(stream) {
+                        warn!(error = %err, "Falha no servidor embutido");
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Frames> {
+        self.frames.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Troca a imagem servida de cada monitor de `captures`.
+    pub fn publish<'a>(
+        &self,
+        captures: impl IntoIterator<Item = &'a DisplayCapture>,
+        at: SystemTime,
+    ) {
+        let mut frames = self.lock();
+        for capture in captures {
+            frames.by_display.insert(
+                capture.display_id,
+                Frame {
+                    image: Arc::new(capture.image.clone()),
+                    at,
+                    png: None,
+                    jpeg: None,
+                },
+            );
+        }
+        frames.generation += 1;
+        self.frames.1.notify_all();
+    }
+
+    /// A última captura do monitor `display` (ou do primeiro) em
+    /// `encoding`, codificada na primeira vez que é pedida e guardada até a
+    /// próxima captura.
+    fn encoded(
+        &self,
+        display: Option<u32>,
+        encoding: Encoding,
+    ) -> Option<(Arc<Vec<u8>>, SystemTime)> {
+        let (image, at) = {
+            let mut frames = self.lock();
+            let frame = match display {
+                Some(id) => frames.by_display.get_mut(&id)?,
+                None => frames.by_display.values_mut().next()?,
+            };
+            if let Some(bytes) = frame.cached(encoding) {
+                return Some((bytes.clone(), frame.at));
+            }
+            (frame.image.clone(), frame.at)
+        };
+        // Fora do lock: codificar uma tela grande leva dezenas de ms.
+        let bytes = Arc::new(encode(&image, encoding)?);
+        let mut frames = self.lock();
+        // Só guarda se a captura não foi trocada enquanto codificava.
+        if let Some(frame) = frames
+            .by_display
+            .values_mut()
+            .find(|f| Arc::ptr_eq(&f.image, &image))
+        {
+            *frame.cached(encoding) = Some(bytes.clone());
+        }
+        Some((bytes, at))
+    }
+
+    /// Espera uma captura mais nova que `seen`, devolvendo a geração dela.
+    fn next_generation(&self, seen: u64) -> u64 {
+        let frames = self.lock();
+        let frames = self
+            .frames
+            .1
+            .wait_while(frames, |frames| frames.generation <= seen)
+            .unwrap_or_else(|e| e.into_inner());
+        frames.generation
+    }
+
+    /// Manda um quadro JPEG a cada captura, até o cliente desconectar.
+    fn stream(&self, stream: &mut TcpStream, display: Option<u32>) -> std::io::Result<()> {
+        stream.write_all(
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={BOUNDARY}\r\n\
+                 Cache-Control: no-store\r\nConnection: close\r\n\r\n"
+            )
+            .as_bytes(),
+        )?;
+        // Um cliente que some é notado quando o próximo quadro não sai
+        // (erro ou o timeout de escrita de `answer`).
+        let mut seen = 0;
+        loop {
+            seen = self.next_generation(seen);
+            let Some((jpeg, _)) = self.encoded(display, Encoding::Jpeg) else {
+                continue;
+            };
+            stream.write_all(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+                    jpeg.len()
+                )
+                .as_bytes(),
+            )?;
+            stream.write_all(&jpeg)?;
+            stream.write_all(b"\r\n")?;
+            stream.flush()?;
+        }
+    }
+
+    fn answer(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?).take(MAX_REQUEST);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // O resto dos cabeçalhos não importa, mas é lido até a linha vazia.
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+        let path = target.split('?').next().unwrap_or(target);
+        match self.route(method, path) {
+            Route::Response(response) => response.write(&mut stream, method == "HEAD"),
+            Route::Stream(display) => self.stream(&mut stream, display),
+        }
+    }
+
+    fn route(&self, method: &str, path: &str) -> Route {
+        if method != "GET" && method != "HEAD" {
+            return Route::Response(
+                Response::text("405 Method Not Allowed", "use GET\n").header("Allow", "GET, HEAD"),
+            );
+        }
+        if path == "/" {
+            return Route::Response(Response {
+                status: "200 OK",
+                content_type: "text/html; charset=utf-8",
+                body: Arc::new(VIEWER.as_bytes().to_vec()),
+                headers: Vec::new(),
+            });
+        }
+        let Some((encoding, display)) = resource(path) else {
+            return Route::Response(Response::text(
+                "404 Not Found",
+                "use /latest.png, /latest/<monitor>.png, /stream.mjpg ou /stream/<monitor>.mjpg\n",
+            ));
+        };
+        if let Encoding::Jpeg = encoding {
+            return Route::Stream(display);
+        }
+        Route::Response(match self.encoded(display, Encoding::Png) {
+            Some((png, at)) => Response {
+                status: "200 OK",
+                content_type: "image/png",
+                body: png,
+                headers: Vec::new(),
+            }
+            .header("Cache-Control", "no-store")
+            .header("Last-Modified", httpdate::fmt_http_date(at)),
+            None if display.is_some() => Response::text("404 Not Found", "monitor sem captura\n"),
+            None => Response::text("503 Service Unavailable", "nenhuma captura ainda\n")
+                .header("Retry-After", "1"),
+        })
+    }
+}
+
+/// `/latest.png` e `/stream.mjpg` são do primeiro monitor;
+/// `/latest/<id>.png` e `/stream/<id>.mjpg`, de um deles.
+fn resource(path: &str) -> Option<(Encoding, Option<u32>)> {
+    match path {
+        "/latest.png" => return Some((Encoding::Png, None)),
+        "/stream.mjpg" => return Some((Encoding::Jpeg, None)),
+        _ => {}
+    }
+    let (encoding, id) = if let Some(rest) = path.strip_prefix("/latest/") {
+        (Encoding::Png, rest.strip_suffix(".png")?)
+    } else {
+        (
+            Encoding::Jpeg,
+            path.strip_prefix("/stream/")?.strip_suffix(".mjpg")?,
+        )
+    };
+    Some((encoding, Some(id.parse().ok()?)))
+}
+
+/// Página de `/`: o stream do primeiro monitor ocupando a janela.
+const VIEWER: &str = "<!doctype html><title>screenshots</title>\
+<style>body{margin:0;background:#111}img{display:block;max-width:100vw;max-height:100vh;margin:auto}</style>\
+<img src=\"/stream.mjpg\" alt=\"tela\">\n";
+
+enum Route {
+    Response(Response),
+    /// `/stream.mjpg`: fica aberto mandando quadros.
+    Stream(Option<u32>),
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Arc<Vec<u8>>,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl Response {
+    fn text(status: &'static str, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: Arc::new(body.as_bytes().to_vec()),
+            headers: Vec::new(),
+        }
+    }
+
+    fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Escreve a resposta; sem o corpo quando `head`.
+    fn write(&self, stream: &mut TcpStream, head: bool) -> std::io::Result<()> {
+        let mut out = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            self.content_type,
+            self.body.len()
+        );
+        for (name, value) in &self.headers {
+            out.push_str(&format!("{name}: {value}\r\n"));
+        }
+        out.push_str("\r\n");
+        stream.write_all(out.as_bytes())?;
+        if !head {
+            stream.write_all(&self.body)?;
+        }
+        stream.flush()
+    }
+}
+
+fn encode(image: &RgbaImage, encoding: Encoding) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match encoding {
+        Encoding::Png => {
+            PngEncoder::new_with_quality(&mut bytes, CompressionType::Fast, FilterType::Adaptive)
+                .write_image(
+                    image.as_raw(),
+                    image.width(),
+                    image.height(),
+                    ColorType::Rgba8,
+                )
+        }
+        Encoding::Jpeg => {
+            // O JPEG não tem alfa.
+            let rgb = DynamicImage::ImageRgba8(image.clone()).into_rgb8();
+            JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY).write_image(
+                rgb.as_raw(),
+                rgb.width(),
+                rgb.height(),
+                ColorType::Rgb8,
+            )
+        }
+    }
+    .ok()?;
+    Some(bytes)
+}
@@ -0,0 +1,701 @@
+//! Uma execução do binário `screenshots`: captura uma vez ou em laço e
+//! aplica a cada captura o que as [`Options`] pedem (tarja, marca d'água,
+//! anotações) antes de gravar, copiar, servir, enviar ou avisar.
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use tracing::{Span, info, info_span, warn};
+
+use crate::{
+    CaptureError, DisplayCapture, DisplaySelector, Region, annotate, blank, capture_display,
+    capture_each, capture_region, capture_window, change, clipboard, cursor, dedup,
+    error::{Result, ScreenshotError, io, usage},
+    hotkey, list_displays, metadata,
+    output::{OutputFormat, save},
+    redact, report, retention, serve, stitch, timelapse, upload, watch, watermark, webhook,
+};
+
+/// Modelo padrão do nome de cada arquivo.
+pub const DEFAULT_NAME: &str = "screen-{display}-{timestamp}.{ext}";
+
+/// O que [`run`] deve capturar e o que fazer com cada captura.
+pub struct Options {
+    /// Diretório de saída (criado se preciso); padrão `.tmp`.
+    pub out_dir: PathBuf,
+    /// Formato da imagem (`png`, `jpg`, `bmp`), com as opções do PNG.
+    pub format: OutputFormat,
+    /// Modelo do nome do arquivo; veja [`render_name`].
+    pub name: String,
+    /// Captura só este monitor em vez de todos.
+    pub display: Option<DisplaySelector>,
+    /// Captura só a janela cujo título ou processo contém este texto.
+    pub window: Option<String>,
+    /// Captura só este retângulo da área de trabalho, mesmo que ele
+    /// atravesse monitores.
+    pub region: Option<Region>,
+    /// Junta todos os monitores numa imagem só, na disposição da área de
+    /// trabalho, em vez de um arquivo por monitor.
+    pub stitch: bool,
+    /// Reduz cada captura para o tamanho lógico do monitor, para que
+    /// monitores HiDPI e comuns gerem imagens comparáveis.
+    pub logical: bool,
+    /// Grava um `.json` com os metadados ao lado de cada arquivo.
+    pub sidecar: bool,
+    /// Envia cada arquivo gravado para um bucket S3 (ou outro destino
+    /// registrado em [`upload`]).
+    pub upload: Option<Box<dyn upload::Uploader>>,
+    /// Índice de hashes perceptuais para não gravar duas vezes a mesma
+    /// tela.
+    pub dedup: Option<dedup::Dedup>,
+    /// Só lista os monitores, sem capturar nada.
+    pub list_displays: bool,
+    /// Quantas capturas fazer (modo intervalo quando maior que 1).
+    pub count: u32,
+    /// Espera entre uma captura e a seguinte.
+    pub interval: Duration,
+    /// Contagem regressiva antes da primeira captura (e, no modo daemon,
+    /// de cada uma), para dar tempo de abrir um menu ou trocar de janela.
+    pub delay: Option<Duration>,
+    /// No modo intervalo, só grava a captura de um monitor se pelo menos
+    /// esta porcentagem dos pixels mudou desde a última gravada.
+    pub min_change: Option<f64>,
+    /// Vídeo (`.mp4`) ou GIF montado com as capturas no fim da execução.
+    pub timelapse: Option<PathBuf>,
+    /// Quadros por segundo do timelapse.
+    pub fps: u32,
+    /// Se a captura vai para a área de transferência.
+    pub clipboard: ClipboardMode,
+    /// O que fazer com capturas de uma cor só.
+    pub on_blank: blank::OnBlank,
+    /// Desenha o ponteiro do mouse na captura.
+    pub cursor: bool,
+    /// Retângulos e textos desenhados em cada captura (`--annotate`).
+    pub annotate: Option<annotate::Annotator>,
+    /// Áreas da área de trabalho escondidas antes de a captura ser gravada,
+    /// copiada ou servida.
+    pub redact: Vec<Region>,
+    pub redact_mode: redact::RedactMode,
+    /// Escreve horário, máquina e monitor num canto de cada captura.
+    pub watermark: Option<watermark::Watermark>,
+    /// Modo daemon: fica rodando e captura a cada vez que este atalho é
+    /// pressionado, em vez de seguir `count` e `interval`.
+    pub daemon: Option<hotkey::Hotkey>,
+    /// Modo watch: captura ao iniciar e a cada mudança na configuração dos
+    /// monitores, até ser interrompido.
+    pub watch: bool,
+    /// Avisado a cada captura.
+    pub webhook: Option<webhook::Webhook>,
+    /// Serve a última captura em `GET /latest.png` neste endereço.
+    pub serve: Option<SocketAddr>,
+    /// Modo stream: captura a cada `interval`, sem gravar arquivos, até
+    /// ser interrompido, servindo as capturas como MJPEG neste endereço.
+    pub stream: Option<SocketAddr>,
+    /// Quais capturas antigas de `out_dir` remover.
+    pub retention: retention::Retention,
+}
+
+/// Novas tentativas de `--on-blank retry`, e a espera antes de cada uma
+/// (o bastante para um monitor acordar).
+const BLANK_RETRIES: u32 = 3;
+const BLANK_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Espera entre capturas no modo stream quando `--interval` não diz.
+pub const STREAM_INTERVAL: Duration = Duration::from_millis(500);
+
+impl Options {
+    /// Se as capturas viram arquivos em `out_dir`.
+    fn saves_files(&self) -> bool {
+        self.clipboard != ClipboardMode::Only && self.stream.is_none()
+    }
+}
+
+/// `--clipboard` copia e também grava o arquivo; `--clipboard-only` só
+/// copia.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardMode {
+    Off,
+    Also,
+    Only,
+}
+
+/// Valores usados por [`render_name`] para uma captura.
+#[derive(Default)]
+pub struct NameFields {
+    /// Id do monitor, ou `all` num panorama.
+    display: String,
+    width: u32,
+    height: u32,
+    physical_width: u32,
+    physical_height: u32,
+    logical_width: u32,
+    logical_height: u32,
+    scale_factor: f32,
+    timestamp: String,
+    /// Número da captura no modo intervalo, a partir de 0.
+    shot: u32,
+}
+
+/// Preenche o modelo do nome: `{display}` (id do monitor, ou `all` com
+/// `--stitch`), `{width}` e `{height}` (da imagem gravada),
+/// `{physical_width}`, `{physical_height}`, `{logical_width}` e
+/// `{logical_height}` (da área capturada, em pixels do monitor e lógicos),
+/// `{scale}` (fator de escala do monitor), `{timestamp}` (UTC,
+/// `20250101T120000Z`), `{shot}` (número da captura, com 5 dígitos) e
+/// `{ext}` (extensão do formato).
+pub fn render_name(template: &str, fields: &NameFields, format: OutputFormat) -> Result<String> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| usage(format!("Modelo de nome sem `}}`: {template}")))?;
+        match &rest[start + 1..start + end] {
+            "display" => name.push_str(&fields.display),
+            "width" => name.push_str(&fields.width.to_string()),
+            "height" => name.push_str(&fields.height.to_string()),
+            "physical_width" => name.push_str(&fields.physical_width.to_string()),
+            "physical_height" => name.push_str(&fields.physical_height.to_string()),
+            "logical_width" => name.push_str(&fields.logical_width.to_string()),
+            "logical_height" => name.push_str(&fields.logical_height.to_string()),
+            "scale" => name.push_str(&fields.scale_factor.to_string()),
+            "timestamp" => name.push_str(&fields.timestamp),
+            "shot" => name.push_str(&format!("{:05}", fields.shot)),
+            "ext" => name.push_str(format.extension()),
+            other => {
+                return Err(usage(format!(
+                    "Campo desconhecido no modelo de nome: {{{other}}} \
+                     (use display, width, height, physical_width, physical_height, \
+                     logical_width, logical_height, scale, timestamp, shot ou ext)"
+                )));
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(usage(format!(
+            "Nome de arquivo inválido gerado pelo modelo: {name:?}"
+        )));
+    }
+    Ok(name)
+}
+
+/// Modo `--list-displays`: índice, id, resolução e posição de cada monitor.
+fn print_displays() -> Result<()> {
+    for (index, display) in list_displays()?.iter().enumerate() {
+        println!(
+            "{index}: id {} {}x{} em ({}, {}) escala {}{}",
+            display.id,
+            display.width,
+            display.height,
+            display.x,
+            display.y,
+            display.scale_factor,
+            if display.is_primary {
+                " (principal)"
+            } else {
+                ""
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Grava uma captura com o nome do modelo (e os metadados, com
+/// `--sidecar`), devolvendo o monitor e o caminho.
+fn store(
+    capture: DisplayCapture,
+    options: &Options,
+    at: SystemTime,
+    timestamp: &str,
+    shot: u32,
+) -> Result<(u32, PathBuf)> {
+    let started = Instant::now();
+    let display_id = capture.display_id;
+    let elapsed = capture.elapsed;
+    let mut metadata = metadata::Metadata::new(&capture, at, shot);
+    let fields = NameFields {
+        display: metadata.display.clone(),
+        width: metadata.width,
+        height: metadata.height,
+        physical_width: metadata.physical_width,
+        physical_height: metadata.physical_height,
+        logical_width: metadata.logical_width,
+        logical_height: metadata.logical_height,
+        scale_factor: metadata.scale_factor,
+        timestamp: timestamp.to_owned(),
+        shot,
+    };
+    let path = options
+        .out_dir
+        .join(render_name(&options.name, &fields, options.format)?);
+    if let Some(dedup) = &options.dedup {
+        let hash = dedup::hash(&capture.image);
+        match (dedup.find(hash), dedup.mode) {
+            (Some(original), dedup::DedupMode::Skip) => {
+                info!(
+                    display = %metadata.display,
+                    original = %original.display(),
+                    "Captura igual a uma anterior; não gravada"
+                );
+                return Ok((display_id, original));
+            }
+            (Some(original), dedup::DedupMode::Link) => {
+                std::fs::hard_link(&original, &path).map_err(io(&path))?;
+                // O link também entra no índice: continua valendo se o
+                // original for apagado pela limpeza.
+                dedup.insert(&path, hash);
+                info!(
+                    display = %metadata.display,
+                    original = %original.display(),
+                    "Captura igual a uma anterior; gravada como link"
+                );
+            }
+            (None, _) => {
+                save(capture, &path, options.format)?;
+                dedup.insert(&path, hash);
+            }
+        }
+    } else {
+        save(capture, &path, options.format)?;
+    }
+    // Uma falha no envio conta como falha do monitor, mas o arquivo (e os
+    // metadados, sem a URL) ficam.
+    let uploaded = match &options.upload {
+        Some(uploader) => uploader.upload(&path, options.format).map(Some),
+        None => Ok(None),
+    };
+    if let Ok(url) = &uploaded {
+        metadata.url.clone_from(url);
+    }
+    if options.sidecar {
+        metadata.write(&path)?;
+    }
+    if let Some(url) = uploaded? {
+        info!(%url, "Captura enviada");
+    }
+    info!(
+        path = %path.display(),
+        capture_ms = elapsed.as_millis() as u64,
+        save_ms = started.elapsed().as_millis() as u64,
+        "Arquivo salvo"
+    );
+    Ok((display_id, path))
+}
+
+/// Uma captura do que `options` pede: a janela, o monitor, a região ou todos
+/// os monitores, um resultado por monitor.
+fn take(options: &Options) -> Vec<Result<DisplayCapture>> {
+    let captures = match (&options.window, options.display) {
+        (Some(query), _) => vec![capture_window(query).map(|found| {
+            info!(
+                title = %found.window.title,
+                process = found.window.process.as_deref(),
+                x = found.window.x,
+                y = found.window.y,
+                "Janela encontrada"
+            );
+            found.capture
+        })],
+        (None, Some(selector)) => vec![capture_display(selector)],
+        (None, None) if let Some(region) = options.region => vec![capture_region(region)],
+        // Sem a lista de monitores, a captura inteira conta como uma
+        // falha, e o modo daemon ou intervalo segue para a próxima.
+        (None, None) => capture_each().unwrap_or_else(|err| vec![Err(err)]),
+    };
+    captures
+        .into_iter()
+        .map(|capture| capture.map_err(Into::into))
+        .collect()
+}
+
+/// O monitor de um resultado de captura, para a tabela; `?` quando a falha
+/// não diz qual.
+fn result_label(capture: &Result<DisplayCapture>) -> String {
+    match capture {
+        Ok(capture) => capture.display_label(),
+        Err(ScreenshotError::Capture(CaptureError::Capture(id, _))) => id.to_string(),
+        Err(ScreenshotError::Blank(label)) => label.clone(),
+        Err(_) => "?".to_owned(),
+    }
+}
+
+/// A tabela sai quando há mais de um monitor ou alguma falha; com um só que
+/// deu certo, a linha de [`store`] basta.
+fn print_report(rows: &[report::Row]) {
+    if rows.len() > 1 || rows.iter().any(|row| row.outcome.is_err()) {
+        report::print(rows);
+    }
+}
+
+/// Espera `delay` mostrando quantos segundos faltam, na mesma linha.
+fn countdown(delay: Duration) {
+    let deadline = Instant::now() + delay;
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        print!("\rCapturando em {}s... ", left.as_secs_f64().ceil());
+        let _ = std::io::stdout().flush();
+        // Acorda na virada de cada segundo, ou no fim.
+        let fraction = Duration::from_nanos(left.as_nanos() as u64 % 1_000_000_000);
+        std::thread::sleep(if fraction.is_zero() {
+            Duration::from_secs(1).min(left)
+        } else {
+            fraction
+        });
+    }
+    println!("\rCapturando agora.    ");
+}
+
+/// Avisa o `--webhook`, se houver, do resultado de uma captura. Uma falha
+/// no aviso só é registrada.
+fn notify(options: &Options, shot: u32, at: SystemTime, saved: &[PathBuf], failed: usize) {
+    if let Some(webhook) = &options.webhook
+        && let Err(err) = webhook.notify(shot, at, saved, failed)
+    {
+        warn!(error = %err, "Falha ao avisar o webhook");
+    }
+}
+
+/// Aplica `--keep-last` e `--keep-days` ao diretório de saída. Uma falha
+/// na limpeza só é registrada: as capturas já foram gravadas.
+fn prune(options: &Options) {
+    if !options.retention.is_enabled() {
+        return;
+    }
+    match options
+        .retention
+        .prune(&options.out_dir, &options.name, options.format)
+    {
+        Ok(0) => {}
+        Ok(removed) => info!(
+            removed,
+            dir = %options.out_dir.display(),
+            "Capturas antigas removidas"
+        ),
+        Err(err) => warn!(error = %err, "Limpeza das capturas antigas falhou"),
+    }
+}
+
+/// Captura uma vez ou em laço (intervalo, daemon, watch ou stream),
+/// conforme `options`, e grava, copia, serve ou envia cada captura.
+pub fn run(options: &Options) -> Result<()> {
+    if options.list_displays {
+        return print_displays();
+    }
+    if options.saves_files() {
+        std::fs::create_dir_all(&options.out_dir).map_err(io(&options.out_dir))?;
+    }
+    // Aberta antes da primeira captura para falhar cedo, e mantida até o
+    // fim para que a última imagem copiada continue disponível.
+    let mut clipboard = match options.clipboard {
+        ClipboardMode::Off => None,
+        _ => Some(clipboard::Clipboard::open()?),
+    };
+
+    // Onde não há como ler o ponteiro, o aviso sai uma vez e as capturas
+    // seguem sem ele.
+    let mut draw_cursor = options.cursor;
+
+    let mut detector = options.min_change.map(change::ChangeDetector::new);
+
+    // `--serve` e `--stream` servem as mesmas capturas.
+    let latest = (options.serve.is_some() || options.stream.is_some()).then(serve::Latest::default);
+    if let (Some(latest), Some(addr)) = (&latest, options.serve) {
+        latest.listen(addr)?;
+        info!(url = %format!("http://{addr}/latest.png"), "Servindo a última captura");
+    }
+    if let (Some(latest), Some(addr)) = (&latest, options.stream) {
+        latest.listen(addr)?;
+        info!(url = %format!("http://{addr}/"), "Transmitindo (Ctrl+C para sair)");
+    }
+
+    let mut listener = match &options.daemon {
+        Some(hotkey) => {
+            let listener = hotkey::HotkeyListener::grab(hotkey)?;
+            info!(%hotkey, "Modo daemon: pressione o atalho para capturar (Ctrl+C para sair)");
+            Some(listener)
+        }
+        None => None,
+    };
+    let mut watcher = if options.watch {
+        let watcher = watch::LayoutWatcher::new()?;
+        info!("Modo watch: capturando a cada mudança nos monitores (Ctrl+C para sair)");
+        Some(watcher)
+    } else {
+        None
+    };
+    // Nos modos daemon, watch e stream, até ser interrompido.
+    let shots = if listener.is_some() || watcher.is_some() || options.stream.is_some() {
+        u32::MAX
+    } else {
+        options.count
+    };
+
+    // Arquivos gravados de cada monitor, na ordem, para o timelapse.
+    let mut frames: BTreeMap<u32, Vec<PathBuf>> = BTreeMap::new();
+    // Um monitor que falha não impede os outros de serem gravados.
+    let mut total = 0;
+    let mut failures = Vec::new();
+    for shot in 0..shots {
+        match (listener.as_mut(), watcher.as_mut()) {
+            (Some(listener), _) => listener.wait()?,
+            (None, Some(watcher)) if shot > 0 => {
+                for change in watcher.wait() {
+                    info!(%change, "Monitores mudaram");
+                }
+            }
+            (None, None) if shot > 0 => std::thread::sleep(options.interval),
+            _ => {}
+        }
+        // Os eventos desta captura (inclusive os das threads de gravação)
+        // saem com o número dela.
+        let _shot = info_span!("shot", shot = shot + 1).entered();
+        if let Some(delay) = options.delay
+            && (shot == 0 || listener.is_some())
+        {
+            countdown(delay);
+        }
+        // Mesmo instante para todos os monitores de uma captura.
+        let now = SystemTime::now();
+        let timestamp = humantime::format_rfc3339_seconds(now)
+            .to_string()
+            .replace(['-', ':'], "");
+
+        let started = Instant::now();
+        let mut captures = take(options);
+        if options.on_blank == blank::OnBlank::Retry {
+            for attempt in 1..=BLANK_RETRIES {
+                if !captures
+                    .iter()
+                    .flatten()
+                    .any(|capture| blank::is_blank(&capture.image))
+                {
+                    break;
+                }
+                info!(
+                    attempt,
+                    of = BLANK_RETRIES,
+                    "Captura em branco; tentando de novo"
+                );
+                std::thread::sleep(BLANK_RETRY_DELAY);
+                captures = take(options);
+            }
+        }
+        for capture in &mut captures {
+            if let Ok(taken) = capture
+                && blank::is_blank(&taken.image)
+            {
+                let label = taken.display_label();
+                if options.on_blank == blank::OnBlank::Skip {
+                    *capture = Err(ScreenshotError::Blank(label));
+                } else {
+                    warn!(
+                        display = %label,
+                        "Monitor capturado em branco (em repouso ou sem permissão de captura?)"
+                    );
+                }
+            }
+        }
+        // Antes de tudo que usa a imagem: o que foi escondido não chega ao
+        // disco, à área de transferência nem ao servidor embutido.
+        if !options.redact.is_empty() {
+            for capture in captures.iter_mut().flatten() {
+                redact::redact(capture, &options.redact, options.redact_mode);
+            }
+        }
+        if draw_cursor {
+            match cursor() {
+                Ok(cursor) => captures
+                    .iter_mut()
+                    .flatten()
+                    .for_each(|capture| capture.draw_cursor(&cursor)),
+                Err(err @ CaptureError::Unsupported(_)) => {
+                    warn!(error = %err, "Capturas sem o ponteiro");
+                    draw_cursor = false;
+                }
+                Err(err) => warn!(error = %err, "Captura sem o ponteiro"),
+            }
+        }
+        // Antes do panorama, que só fecha sem vãos com todos os monitores
+        // na mesma escala.
+        if options.logical {
+            for capture in captures.iter_mut().flatten() {
+                capture.downscale_to_logical();
+            }
+        }
+        if options.stitch {
+            let (ok, mut failed): (Vec<_>, Vec<_>) = captures.into_iter().partition(Result::is_ok);
+            let ok: Vec<_> = ok.into_iter().flatten().collect();
+            // Os monitores que falharam contam como falhas; o panorama sai
+            // com os outros.
+            failed.extend(stitch(&ok).map(Ok));
+            captures = failed;
+        }
+        if let Some(detector) = detector.as_mut() {
+            captures.retain(|capture| match capture {
+                Ok(capture) if !detector.is_new(capture) => {
+                    info!(
+                        display = %capture.display_label(),
+                        "Monitor sem mudanças; captura descartada"
+                    );
+                    false
+                }
+                _ => true,
+            });
+        }
+        // Depois da detecção de mudança, para que o relógio na imagem não
+        // conte como mudança.
+        if let Some(watermark) = &options.watermark {
+            for capture in captures.iter_mut().flatten() {
+                watermark.draw(capture, now);
+            }
+        }
+        if let Some(annotator) = &options.annotate {
+            for capture in captures.iter_mut().flatten() {
+                annotator.draw(capture);
+            }
+        }
+        if let Some(latest) = &latest {
+            latest.publish(captures.iter().flatten(), now);
+        }
+
+        // A área de transferência guarda uma imagem só: a do primeiro
+        // monitor que deu certo.
+        if let Some(clipboard) = clipboard.as_mut()
+            && let Some(Ok(first)) = captures.iter().find(|capture| capture.is_ok())
+        {
+            clipboard.copy(&first.image)?;
+            info!(
+                display = %first.display_label(),
+                only_first = captures.iter().flatten().count() > 1,
+                "Captura copiada para a área de transferência"
+            );
+        }
+        // Antes de as capturas irem para as threads; os resultados voltam
+        // na mesma ordem.
+        let labels: Vec<String> = captures.iter().map(result_label).collect();
+        if !options.saves_files() {
+            let failed_before = failures.len();
+            let mut rows = Vec::new();
+            for (capture, display) in captures.into_iter().zip(labels) {
+                total += 1;
+                let outcome = match capture {
+                    Ok(_) => Ok(None),
+                    Err(err) => {
+                        let message = err.to_string();
+                        failures.push(err);
+                        Err(message)
+                    }
+                };
+                rows.push(report::Row { display, outcome });
+            }
+            // No modo stream a tabela sairia a cada quadro; só as falhas
+            // são registradas.
+            if options.stream.is_none() {
+                print_report(&rows);
+            } else {
+                for row in &rows {
+                    if let Err(err) = &row.outcome {
+                        warn!(display = %row.display, error = %err, "Falha na captura");
+                    }
+                }
+            }
+            notify(options, shot, now, &[], failures.len() - failed_before);
+            continue;
+        }
+
+        // Codificar uma tela grande em PNG leva mais que capturá-la; cada
+        // monitor é gravado na sua thread.
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = captures
+                .into_iter()
+                .map(|capture| {
+                    let timestamp = &timestamp;
+                    let options = &options;
+                    let span = Span::current();
+                    scope.spawn(move || {
+                        span.in_scope(|| store(capture?, options, now, timestamp, shot))
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        let failed_before = failures.len();
+        let mut saved = Vec::new();
+        let mut rows = Vec::new();
+        for (result, display) in results.into_iter().zip(labels) {
+            total += 1;
+            let outcome = match result {
+                Ok((display, path)) => {
+                    frames.entry(display).or_default().push(path.clone());
+                    saved.push(path.clone());
+                    Ok(Some(path))
+                }
+                Err(err) => {
+                    let message = err.to_string();
+                    failures.push(err);
+                    Err(message)
+                }
+            };
+            rows.push(report::Row { display, outcome });
+        }
+        print_report(&rows);
+        if let Some(dedup) = &options.dedup {
+            dedup.save()?;
+        }
+        notify(options, shot, now, &saved, failures.len() - failed_before);
+        // Os modos daemon e watch não têm fim de execução; limpa a cada
+        // captura.
+        if options.daemon.is_some() || options.watch {
+            prune(options);
+        }
+        info!(
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "Captura concluída"
+        );
+    }
+
+    if let Some(out) = &options.timelapse {
+        let several = frames.len() > 1;
+        for (display, paths) in &frames {
+            // Um arquivo por monitor quando mais de um foi capturado.
+            let out = if several {
+                timelapse::per_display(out, *display)
+            } else {
+                out.clone()
+            };
+            timelapse::encode(paths, &out, options.fps)?;
+            info!(
+                path = %out.display(),
+                frames = paths.len(),
+                "Timelapse salvo"
+            );
+        }
+    }
+    // Depois do timelapse, que ainda precisa dos quadros.
+    prune(options);
+
+    match failures.len() {
+        0 => Ok(()),
+        // Nada foi gravado: o erro em si diz mais que a contagem.
+        failed if failed == total => Err(failures.swap_remove(0)),
+        failed => Err(ScreenshotError::Partial { failed, total }),
+    }
+}
@@ -0,0 +1,119 @@
+//! Montagem das capturas do modo intervalo em um único arquivo: GIF direto
+//! pelo crate `image`, MP4 pelo `ffmpeg` instalado no sistema.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use screenshots::image::{
+    self, Delay, Frame,
+    codecs::gif::{GifEncoder, Repeat},
+};
+
+use crate::error::{Result, ScreenshotError, io, usage};
+
+/// Velocidade da quantização de cores do GIF (1 = melhor, 30 = mais
+/// rápido); telas inteiras são grandes, então fica no meio-termo.
+const GIF_SPEED: i32 = 10;
+
+/// Formato do timelapse, escolhido pela extensão do arquivo.
+pub enum Container {
+    Gif,
+    Mp4,
+}
+
+impl Container {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("gif") => Ok(Self::Gif),
+            Some("mp4") => Ok(Self::Mp4),
+            _ => Err(usage(format!(
+                "Timelapse precisa terminar em .mp4 ou .gif: {}",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// `dia.mp4` vira `dia-<display>.mp4`.
+pub fn per_display(path: &Path, display: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}-{display}.{ext}"))
+}
+
+/// Junta `frames` (na ordem) em `out`, a `fps` quadros por segundo.
+pub fn encode(frames: &[PathBuf], out: &Path, fps: u32) -> Result<()> {
+    match Container::from_path(out)? {
+        Container::Gif => encode_gif(frames, out, fps),
+        Container::Mp4 => encode_mp4(frames, out, fps),
+    }
+}
+
+fn encode_gif(frames: &[PathBuf], out: &Path, fps: u32) -> Result<()> {
+    let image_error = |path: &Path| {
+        let path = path.to_owned();
+        move |source| ScreenshotError::Image { path, source }
+    };
+    let file = File::create(out).map_err(io(out))?;
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), GIF_SPEED);
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(image_error(out))?;
+    let delay = Delay::from_numer_denom_ms(1000, fps);
+    for path in frames {
+        let image = image::open(path).map_err(image_error(path))?.to_rgba8();
+        encoder
+            .encode_frame(Frame::from_parts(image, 0, 0, delay))
+            .map_err(image_error(out))?;
+    }
+    Ok(())
+}
+
+/// Passa os arquivos, como estão, pela entrada padrão do `ffmpeg`
+/// (`image2pipe`), que detecta o formato das imagens sozinho.
+fn encode_mp4(frames: &[PathBuf], out: &Path, fps: u32) -> Result<()> {
+    let mut ffmpeg = Command::new("ffmpeg")
+        .args(["-loglevel", "error", "-y", "-f", "image2pipe", "-framerate"])
+        .arg(fps.to_string())
+        .args(["-i", "-", "-c:v", "libx264", "-pix_fmt", "yuv420p"])
+        // O H.264 exige largura e altura pares.
+        .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+        .arg(out)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            ScreenshotError::Ffmpeg(format!(
+                "não foi possível executá-lo (ele está instalado?): {err}"
+            ))
+        })?;
+
+    let mut stdin = ffmpeg
+        .stdin
+        .take()
+        .ok_or_else(|| ScreenshotError::Ffmpeg("sem entrada padrão".to_owned()))?;
+    for path in frames {
+        let bytes = std::fs::read(path).map_err(io(path))?;
+        if stdin.write_all(&bytes).is_err() {
+            // O ffmpeg saiu antes; o erro dele aparece no `wait` abaixo.
+            break;
+        }
+    }
+    drop(stdin);
+
+    let status = ffmpeg
+        .wait()
+        .map_err(|err| ScreenshotError::Ffmpeg(err.to_string()))?;
+    if !status.success() {
+        return Err(ScreenshotError::Ffmpeg(format!("terminou com {status}")));
+    }
+    Ok(())
+}
@@ -0,0 +1,275 @@
+//! Envio de cada captura gravada para fora da máquina com `--upload <url>`,
+//! para máquinas sem tela mandarem as capturas para fora.
+//!
+//! O destino é escolhido pelo esquema da URL num registro de fábricas
+//! ([`register_uploader`]); este módulo registra `s3://`, para um bucket S3
+//! ou compatível (MinIO, R2, ...): um `PUT` assinado com AWS Signature V4,
+//! sem o SDK, porque o binário não tem runtime assíncrono e só precisa
+//! dessa operação.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, LazyLock, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Result, ScreenshotError, io, usage},
+    output::OutputFormat,
+};
+
+/// Credenciais e região, nas variáveis de ambiente de sempre da AWS.
+const ACCESS_KEY_ENV: &str = "AWS_ACCESS_KEY_ID";
+const SECRET_KEY_ENV: &str = "AWS_SECRET_ACCESS_KEY";
+const SESSION_TOKEN_ENV: &str = "AWS_SESSION_TOKEN";
+const REGION_ENV: &str = "AWS_REGION";
+/// Endereço de um serviço compatível; sem ele, o S3 da região.
+const ENDPOINT_ENV: &str = "S3_ENDPOINT";
+const DEFAULT_REGION: &str = "us-east-1";
+/// Uma captura 4K em PNG passa de 10 MB; o limite é folgado.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Um destino das capturas gravadas.
+pub trait Uploader: Send + Sync {
+    /// Envia o arquivo em `path` e devolve a URL onde ele ficou.
+    fn upload(&self, path: &Path, format: OutputFormat) -> Result<String>;
+}
+
+/// Monta o destino a partir da URL completa do `--upload`.
+type UploaderFactory = Arc<dyn Fn(&str) -> Result<Box<dyn Uploader>> + Send + Sync>;
+
+/// Fábricas por esquema, já com o `s3` deste módulo.
+static UPLOADERS: LazyLock<RwLock<BTreeMap<String, UploaderFactory>>> = LazyLock::new(|| {
+    let mut uploaders: BTreeMap<String, UploaderFactory> = BTreeMap::new();
+    uploaders.insert(
+        "s3".into(),
+        Arc::new(|target| Ok(Box::new(S3Uploader::new(target)?))),
+    );
+    RwLock::new(uploaders)
+});
+
+/// Registra (ou substitui) o destino das URLs `scheme://…`.
+pub fn register_uploader<F>(scheme: &str, factory: F)
+where
+    F: Fn(&str) -> Result<Box<dyn Uploader>> + Send + Sync + 'static,
+{
+    UPLOADERS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(scheme.to_owned(), Arc::new(factory));
+}
+
+/// Esquemas registrados, em ordem alfabética.
+pub fn uploader_schemes() -> Vec<String> {
+    UPLOADERS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .keys()
+        .cloned()
+        .collect()
+}
+
+/// Monta o destino de `target` com a fábrica do esquema dela.
+pub fn create_uploader(target: &str) -> Result<Box<dyn Uploader>> {
+    let factory = target.split_once("://").and_then(|(scheme, _)| {
+        UPLOADERS
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(scheme)
+            .cloned()
+    });
+    match factory {
+        Some(factory) => factory(target),
+        None => Err(usage(format!(
+            "--upload inválido: {target} (esquemas conhecidos: {})",
+            uploader_schemes()
+                .iter()
+                .map(|scheme| format!("{scheme}://"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// Bucket S3 para onde as capturas vão. Os objetos são endereçados no
+/// estilo de caminho (`endpoint/bucket/chave`), o que todo serviço
+/// compatível aceita.
+pub struct S3Uploader {
+    endpoint: reqwest::Url,
+    bucket: String,
+    /// Prefixo das chaves, vazio ou terminado em `/`.
+    prefix: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    client: reqwest::blocking::Client,
+}
+
+impl S3Uploader {
+    /// Interpreta `target` (`s3://bucket` ou `s3://bucket/prefixo`) e lê as
+    /// credenciais do ambiente.
+    pub fn new(target: &str) -> Result<Self> {
+        let (bucket, prefix) = target
+            .strip_prefix("s3://")
+            .map(|rest| rest.split_once('/').unwrap_or((rest, "")))
+            .filter(|(bucket, _)| !bucket.is_empty())
+            .ok_or_else(|| {
+                usage(format!(
+                    "--upload inválido: {target} (use s3://bucket ou s3://bucket/prefixo)"
+                ))
+            })?;
+        let prefix = match prefix.trim_matches('/') {
+            "" => String::new(),
+            prefix => format!("{prefix}/"),
+        };
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let credential = |name: &str| {
+            env(name).ok_or_else(|| usage(format!("--upload precisa das credenciais em {name}")))
+        };
+        let region = env(REGION_ENV).unwrap_or_else(|| DEFAULT_REGION.to_owned());
+        let endpoint =
+            env(ENDPOINT_ENV).unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com"));
+        let endpoint = reqwest::Url::parse(&endpoint)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some())
+            .ok_or_else(|| {
+                usage(format!(
+                    "{ENDPOINT_ENV} inválido: {endpoint} (use uma URL http ou https)"
+                ))
+            })?;
+        // Para URLs https o rustls precisa de um provedor de criptografia.
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let client = reqwest::blocking::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("screenshots/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|err| ScreenshotError::Upload(err.to_string()))?;
+        Ok(Self {
+            endpoint,
+            bucket: bucket.to_owned(),
+            prefix,
+            region,
+            access_key: credential(ACCESS_KEY_ENV)?,
+            secret_key: credential(SECRET_KEY_ENV)?,
+            session_token: env(SESSION_TOKEN_ENV),
+            client,
+        })
+    }
+}
+
+impl Uploader for S3Uploader {
+    /// Envia o arquivo em `path` com o nome dele como chave (depois do
+    /// prefixo), devolvendo a URL do objeto.
+    fn upload(&self, path: &Path, format: OutputFormat) -> Result<String> {
+        let body = std::fs::read(path).map_err(io(path))?;
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let object_path = format!(
+            "{}/{}/{}",
+            self.endpoint.path().trim_end_matches('/'),
+            uri_encode(&self.bucket),
+            uri_encode(&format!("{}{name}", self.prefix))
+        );
+        let mut url = self.endpoint.clone();
+        url.set_path(&object_path);
+
+        // `20250101T120000Z`, e só a data para o escopo.
+        let amz_date = humantime::format_rfc3339_seconds(SystemTime::now())
+            .to_string()
+            .replace(['-', ':'], "");
+        let date = &amz_date[..8];
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_owned(),
+        };
+        let payload_hash = hex(&Sha256::digest(&body));
+
+        let mut headers = vec![
+            ("content-type", format.mime_type().to_owned()),
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+            .collect();
+        let canonical_request = format!(
+            "PUT\n{}\n\n{canonical_headers}\n{signed_headers}\n{payload_hash}",
+            url.path()
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut key = hmac(format!("AWS4{}", self.secret_key).as_bytes(), date);
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            key = hmac(&key, part);
+        }
+        let signature = hex(&hmac(&key, &string_to_sign));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key
+        );
+
+        let mut request = self
+            .client
+            .put(url.clone())
+            .header(reqwest::header::AUTHORIZATION, authorization);
+        // `host` o reqwest põe sozinho, com o mesmo valor.
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        let response = request
+            .body(body)
+            .send()
+            .map_err(|err| ScreenshotError::Upload(err.to_string()))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ScreenshotError::Upload(format!("{url} respondeu {status}")));
+        }
+        Ok(url.to_string())
+    }
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Codifica o caminho como a assinatura do S3 espera: tudo menos letras,
+/// dígitos, `-_.~` e as barras entre os trechos.
+fn uri_encode(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
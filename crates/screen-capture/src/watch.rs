@@ -0,0 +1,116 @@
+//! Modo watch: uma captura a cada mudança na configuração dos monitores
+//! (um conectado ou desconectado, outra resolução, posição ou escala),
+//! para depurar problemas de disposição com vários monitores.
+
+use std::{thread, time::Duration};
+
+use crate::{Display, list_displays};
+
+use crate::error::Result;
+
+/// De quanto em quanto tempo a lista de monitores é consultada.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Espera depois de uma mudança até a lista parar de mudar: ao conectar um
+/// monitor, o sistema costuma reorganizar os outros em vários passos.
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// A última configuração vista.
+pub struct LayoutWatcher {
+    displays: Vec<Display>,
+}
+
+impl LayoutWatcher {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            displays: list_displays()?,
+        })
+    }
+
+    /// Bloqueia até a configuração mudar e ficar estável, devolvendo a
+    /// descrição de cada mudança. Falhas ao listar os monitores (comuns no
+    /// meio de uma reconfiguração) só adiam a consulta.
+    pub fn wait(&mut self) -> Vec<String> {
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let Ok(mut current) = list_displays() else {
+                continue;
+            };
+            if same_layout(&current, &self.displays) {
+                continue;
+            }
+            loop {
+                thread::sleep(SETTLE_DELAY);
+                match list_displays() {
+                    Ok(settled) if same_layout(&settled, &current) => break,
+                    Ok(settled) => current = settled,
+                    Err(_) => {}
+                }
+            }
+            let changes = describe(&self.displays, &current);
+            self.displays = current;
+            return changes;
+        }
+    }
+}
+
+/// Os campos que contam como configuração; o `f32` da escala vai como bits
+/// porque só interessa se mudou.
+fn key(display: &Display) -> (i32, i32, u32, u32, u32, bool) {
+    (
+        display.x,
+        display.y,
+        display.width,
+        display.height,
+        display.scale_factor.to_bits(),
+        display.is_primary,
+    )
+}
+
+fn same_layout(a: &[Display], b: &[Display]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| a.id == b.id && key(a) == key(b))
+}
+
+fn summary(display: &Display) -> String {
+    format!(
+        "{}x{} em ({}, {}) escala {}{}",
+        display.width,
+        display.height,
+        display.x,
+        display.y,
+        display.scale_factor,
+        if display.is_primary {
+            " (principal)"
+        } else {
+            ""
+        }
+    )
+}
+
+fn describe(before: &[Display], after: &[Display]) -> Vec<String> {
+    let mut changes = Vec::new();
+    for old in before {
+        match after.iter().find(|new| new.id == old.id) {
+            None => changes.push(format!("Monitor {} desconectado", old.id)),
+            Some(new) if key(new) != key(old) => changes.push(format!(
+                "Monitor {}: {} -> {}",
+                old.id,
+                summary(old),
+                summary(new)
+            )),
+            Some(_) => {}
+        }
+    }
+    for new in after {
+        if !before.iter().any(|old| old.id == new.id) {
+            changes.push(format!("Monitor {} conectado: {}", new.id, summary(new)));
+        }
+    }
+    if changes.is_empty() {
+        // Mesmos monitores, outra ordem.
+        changes.push("Ordem dos monitores mudou".to_owned());
+    }
+    changes
+}
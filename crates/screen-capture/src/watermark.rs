@@ -0,0 +1,229 @@
+//! Marca d'água das capturas (`--watermark`): horário, máquina e monitor
+//! escritos num canto da imagem, para capturas que servem de registro.
+
+use std::{path::Path, str::FromStr, time::SystemTime};
+
+use ab_glyph::{Font as _, FontVec, PxScale, ScaleFont as _, point};
+use font8x8::{BASIC_FONTS, LATIN_FONTS, UnicodeFonts as _};
+
+use crate::{
+    DisplayCapture,
+    error::{Result, io, usage},
+};
+
+/// Altura do texto, em pixels, quando `--watermark-size` não diz.
+pub const DEFAULT_SIZE: u32 = 16;
+/// Espaço entre o texto e a borda da faixa, e entre a faixa e a da imagem.
+const MARGIN: u32 = 6;
+/// Opacidade da faixa escura atrás do texto, para que ele seja legível
+/// sobre qualquer fundo.
+const BACKGROUND_ALPHA: f32 = 0.6;
+
+/// Canto da imagem onde a marca vai.
+#[derive(Clone, Copy, Default)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl FromStr for Corner {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "top-left" => Ok(Self::TopLeft),
+            "top-right" => Ok(Self::TopRight),
+            "bottom-left" => Ok(Self::BottomLeft),
+            "bottom-right" => Ok(Self::BottomRight),
+            other => Err(format!(
+                "Posição inválida: {other} (use top-left, top-right, bottom-left ou bottom-right)"
+            )),
+        }
+    }
+}
+
+/// A fonte do texto: a embutida (8x8, ampliada por um fator inteiro) ou um
+/// arquivo TrueType/OpenType.
+pub enum Font {
+    Builtin { scale: u32 },
+    File { font: FontVec, size: f32 },
+}
+
+impl Font {
+    /// `path` é o caminho de um `.ttf`/`.otf`; sem ele, usa a fonte
+    /// embutida. `size` é a altura do texto em pixels.
+    pub fn new(path: Option<&Path>, size: u32) -> Result<Self> {
+        Ok(match path {
+            Some(path) => {
+                let data = std::fs::read(path).map_err(io(path))?;
+                let font = FontVec::try_from_vec(data)
+                    .map_err(|_| usage(format!("Fonte inválida: {}", path.display())))?;
+                Self::File {
+                    font,
+                    size: size as f32,
+                }
+            }
+            None => Self::Builtin {
+                scale: (size / 8).max(1),
+            },
+        })
+    }
+
+    pub fn render(&self, text: &str) -> Rendered {
+        match self {
+            Self::Builtin { scale } => render_builtin(text, *scale),
+            Self::File { font, size } => render_file(text, font, *size),
+        }
+    }
+}
+
+/// O texto, com a máscara de cobertura (0 a 1) de cada pixel.
+pub struct Rendered {
+    pub width: u32,
+    pub height: u32,
+    pub coverage: Vec<f32>,
+}
+
+pub struct Watermark {
+    font: Font,
+    corner: Corner,
+    hostname: String,
+}
+
+impl Watermark {
+    /// `font` e `size` como em [`Font::new`].
+    pub fn new(font: Option<&Path>, size: u32, corner: Corner) -> Result<Self> {
+        Ok(Self {
+            font: Font::new(font, size)?,
+            corner,
+            hostname: gethostname::gethostname().to_string_lossy().into_owned(),
+        })
+    }
+
+    /// Escreve `time` (UTC), o nome da máquina e o monitor no canto
+    /// escolhido de `capture`. Se o texto não couber, nada é desenhado.
+    pub fn draw(&self, capture: &mut DisplayCapture, time: SystemTime) {
+        let text = format!(
+            "{}  {}  monitor {}",
+            humantime::format_rfc3339_seconds(time),
+            self.hostname,
+            capture.display_label()
+        );
+        let rendered = self.font.render(&text);
+        let image = &mut capture.image;
+        let (width, height) = image.dimensions();
+        let (box_width, box_height) = (rendered.width + 2 * MARGIN, rendered.height + 2 * MARGIN);
+        if box_width + 2 * MARGIN > width || box_height + 2 * MARGIN > height {
+            return;
+        }
+        let left = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => MARGIN,
+            Corner::TopRight | Corner::BottomRight => width - MARGIN - box_width,
+        };
+        let top = match self.corner {
+            Corner::TopLeft | Corner::TopRight => MARGIN,
+            Corner::BottomLeft | Corner::BottomRight => height - MARGIN - box_height,
+        };
+
+        for y in 0..box_height {
+            for x in 0..box_width {
+                let pixel = image.get_pixel_mut(left + x, top + y);
+                blend(&mut pixel.0, [0, 0, 0], BACKGROUND_ALPHA);
+                let (tx, ty) = (x.wrapping_sub(MARGIN), y.wrapping_sub(MARGIN));
+                if tx < rendered.width && ty < rendered.height {
+                    let coverage = rendered.coverage[(ty * rendered.width + tx) as usize];
+                    blend(&mut pixel.0, [255, 255, 255], coverage);
+                }
+            }
+        }
+    }
+}
+
+fn render_builtin(text: &str, scale: u32) -> Rendered {
+    let glyphs: Vec<[u8; 8]> = text
+        .chars()
+        .map(|c| {
+            BASIC_FONTS
+                .get(c)
+                .or_else(|| LATIN_FONTS.get(c))
+                .or_else(|| BASIC_FONTS.get('?'))
+                .unwrap_or_default()
+        })
+        .collect();
+    let (width, height) = (glyphs.len() as u32 * 8 * scale, 8 * scale);
+    let mut coverage = vec![0.0; (width * height) as usize];
+    for (index, glyph) in glyphs.iter().enumerate() {
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..8 {
+                // O bit menos significativo é a coluna da esquerda.
+                if bits & (1 << column) == 0 {
+                    continue;
+                }
+                let x0 = (index as u32 * 8 + column) * scale;
+                let y0 = row as u32 * scale;
+                for y in y0..y0 + scale {
+                    let start = (y * width + x0) as usize;
+                    coverage[start..start + scale as usize].fill(1.0);
+                }
+            }
+        }
+    }
+    Rendered {
+        width,
+        height,
+        coverage,
+    }
+}
+
+fn render_file(text: &str, font: &FontVec, size: f32) -> Rendered {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let height = (scaled.ascent() - scaled.descent()).ceil() as u32;
+    let mut glyphs = Vec::new();
+    let mut caret = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(scaled.scale, point(caret, scaled.ascent())));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    let width = caret.ceil() as u32;
+    let mut coverage = vec![0.0; (width * height) as usize];
+    for glyph in glyphs {
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|x, y, value| {
+            let (x, y) = (
+                bounds.min.x as i32 + x as i32,
+                bounds.min.y as i32 + y as i32,
+            );
+            if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+                let cell = &mut coverage[(y as u32 * width + x as u32) as usize];
+                *cell = f32::max(*cell, value);
+            }
+        });
+    }
+    Rendered {
+        width,
+        height,
+        coverage,
+    }
+}
+
+/// Mistura `color` sobre o pixel com opacidade `alpha`.
+pub fn blend(pixel: &mut [u8; 4], color: [u8; 3], alpha: f32) {
+    if alpha <= 0.0 {
+        return;
+    }
+    for (channel, value) in pixel.iter_mut().zip(color) {
+        *channel = (*channel as f32 * (1.0 - alpha) + value as f32 * alpha).round() as u8;
+    }
+}
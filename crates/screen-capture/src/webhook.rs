@@ -0,0 +1,121 @@
+//! Aviso por webhook a cada captura (`--webhook`), no mesmo formato dos
+//! webhooks do `simple-http-server`, para que um receptor sirva aos dois.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+
+use crate::error::{Result, ScreenshotError, usage};
+
+/// Variável de ambiente com o segredo da assinatura; fora da linha de
+/// comando para não aparecer no `ps`.
+pub const SECRET_ENV: &str = "SCREENSHOTS_WEBHOOK_SECRET";
+/// Tipo do evento enviado, no cabeçalho `x-webhook-event` e no corpo.
+const EVENT_KIND: &str = "screenshot.captured";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Onde os avisos são entregues. Cada aviso é tentado uma vez só: uma
+/// falha é impressa e as capturas seguem.
+pub struct Webhook {
+    url: reqwest::Url,
+    secret: String,
+    client: reqwest::blocking::Client,
+}
+
+impl Webhook {
+    /// Lê o segredo de [`SECRET_ENV`].
+    pub fn new(url: &str) -> Result<Self> {
+        let url = reqwest::Url::parse(url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| {
+                usage(format!(
+                    "--webhook inválido: {url} (use uma URL http ou https)"
+                ))
+            })?;
+        let secret = std::env::var(SECRET_ENV)
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .ok_or_else(|| usage(format!("--webhook precisa do segredo em {SECRET_ENV}")))?;
+        // Para URLs https o rustls precisa de um provedor de criptografia.
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let client = reqwest::blocking::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("screenshots/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|err| ScreenshotError::Webhook(err.to_string()))?;
+        Ok(Self {
+            url,
+            secret,
+            client,
+        })
+    }
+
+    /// Avisa que a captura `shot` gravou `files` (e que `failed` monitores
+    /// falharam). O corpo segue o `AppEvent` do servidor, com `id` sendo o
+    /// número da captura a partir de 1.
+    pub fn notify(
+        &self,
+        shot: u32,
+        at: SystemTime,
+        files: &[PathBuf],
+        failed: usize,
+    ) -> Result<()> {
+        let body = json!({
+            "id": shot + 1,
+            "kind": EVENT_KIND,
+            "at": humantime::format_rfc3339_millis(at).to_string(),
+            "data": {
+                "files": files,
+                "failed": failed,
+            },
+        })
+        .to_string();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string();
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("x-webhook-id", uuid::Uuid::new_v4().to_string())
+            .header("x-webhook-event", EVENT_KIND)
+            .header("x-webhook-timestamp", timestamp.as_str())
+            .header(
+                "x-webhook-signature",
+                signature(&self.secret, &timestamp, &body),
+            )
+            .body(body)
+            .send()
+            .map_err(|err| ScreenshotError::Webhook(err.to_string()))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ScreenshotError::Webhook(format!(
+                "{} respondeu {status}",
+                self.url
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `sha256=` e o HMAC-SHA256 em hexadecimal de `{timestamp}.{body}`.
+fn signature(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    let mut signature = String::from("sha256=");
+    for byte in mac.finalize().into_bytes() {
+        signature.push_str(&format!("{byte:02x}"));
+    }
+    signature
+}
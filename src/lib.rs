@@ -1,2 +0,0 @@
-#[path = "lib/migrate_to_latest.rs"]
-pub mod migrate_to_latest;